@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use wasmer::*;
+
+/// A module with many small functions, each of the first 4096 with a
+/// signature of its own.
+fn large_module_wat(functions: usize) -> String {
+    let mut wat = String::from("(module\n");
+    for i in 0..functions {
+        // The bits of the index pick the types of the 12 parameters.
+        let params: String = (0..12)
+            .map(|bit| if i >> bit & 1 == 0 { " i32" } else { " i64" })
+            .collect();
+        wat.push_str(&format!(
+            "  (func (export \"f{}\") (param{}) (result i64) i64.const {})\n",
+            i, params, i
+        ));
+    }
+    wat.push(')');
+    wat
+}
+
+fn run_large_module_loading(store: &Store, compiler_name: &str, c: &mut Criterion) {
+    let module = Module::new(store, large_module_wat(20_000)).unwrap();
+    let serialized = module.serialize().unwrap();
+    // Every iteration deserializes into a fresh engine, so that all the
+    // signatures of the module are registered and their code laid out
+    // again.
+    c.bench_function(
+        &format!("deserialize large module {}", compiler_name),
+        |b| {
+            b.iter_batched(
+                || Store::new(EngineBuilder::headless()),
+                |store| {
+                    let module = unsafe { Module::deserialize(&store, &serialized) }.unwrap();
+                    black_box(module);
+                    store
+                },
+                BatchSize::SmallInput,
+            )
+        },
+    );
+}
+
+fn run_loading_benchmarks(_c: &mut Criterion) {
+    #[cfg(feature = "llvm")]
+    {
+        let store = Store::new(wasmer_compiler_llvm::LLVM::new());
+        run_large_module_loading(&store, "llvm", _c);
+    }
+    #[cfg(feature = "cranelift")]
+    {
+        let store = Store::new(wasmer_compiler_cranelift::Cranelift::new());
+        run_large_module_loading(&store, "cranelift", _c);
+    }
+    #[cfg(feature = "singlepass")]
+    {
+        let store = Store::new(wasmer_compiler_singlepass::Singlepass::new());
+        run_large_module_loading(&store, "singlepass", _c);
+    }
+}
+
+criterion_group!(benches, run_loading_benchmarks);
+criterion_main!(benches);
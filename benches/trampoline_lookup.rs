@@ -0,0 +1,75 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use wasmer::*;
+use wasmer_compiler::ArtifactCreate;
+
+/// A module using many distinct signatures.
+fn many_signatures_wat(signatures: usize) -> String {
+    let mut wat = String::from("(module\n");
+    for i in 0..signatures {
+        let params = " i32".repeat(i % 16);
+        let results = " i64".repeat(i / 16 + 1);
+        wat.push_str(&format!(
+            "  (func (export \"f{}\") (param{}) (result{}) unreachable)\n",
+            i, params, results
+        ));
+    }
+    wat.push(')');
+    wat
+}
+
+fn run_trampoline_lookup(store: &Store, compiler_name: &str, c: &mut Criterion) {
+    let engine = store.engine();
+    let wasm = wat2wasm(many_signatures_wat(64).as_bytes()).unwrap();
+    let artifact = engine.compile(&wasm, store.tunables()).unwrap();
+    engine.warm_signatures_from(&artifact);
+    let signatures = artifact
+        .create_module_info()
+        .signatures
+        .values()
+        .map(|func_type| engine.register_signature(func_type))
+        .collect::<Vec<_>>();
+
+    c.bench_function(
+        &format!("lookup trampolines one by one {}", compiler_name),
+        |b| {
+            b.iter(|| {
+                for sig in &signatures {
+                    black_box(engine.function_call_trampoline(*sig));
+                }
+            })
+        },
+    );
+    c.bench_function(
+        &format!("lookup trampolines in a snapshot {}", compiler_name),
+        |b| {
+            b.iter(|| {
+                let table = engine.trampoline_table();
+                for sig in &signatures {
+                    black_box(table.get(sig));
+                }
+            })
+        },
+    );
+}
+
+fn run_lookup_benchmarks(_c: &mut Criterion) {
+    #[cfg(feature = "llvm")]
+    {
+        let store = Store::new(wasmer_compiler_llvm::LLVM::new());
+        run_trampoline_lookup(&store, "llvm", _c);
+    }
+    #[cfg(feature = "cranelift")]
+    {
+        let store = Store::new(wasmer_compiler_cranelift::Cranelift::new());
+        run_trampoline_lookup(&store, "cranelift", _c);
+    }
+    #[cfg(feature = "singlepass")]
+    {
+        let store = Store::new(wasmer_compiler_singlepass::Singlepass::new());
+        run_trampoline_lookup(&store, "singlepass", _c);
+    }
+}
+
+criterion_group!(benches, run_lookup_benchmarks);
+criterion_main!(benches);
@@ -1,8 +1,10 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::ptr::NonNull;
 
+use wasmer_types::RawValue;
 use wasmer_vm::{StoreHandle, VMExternObj, VMExternRef};
 
-use super::store::{AsStoreMut, AsStoreRef};
+use super::store::{AsStoreMut, AsStoreRef, LocalStore};
 
 #[derive(Debug, Clone)]
 #[repr(transparent)]
@@ -22,17 +24,124 @@ impl ExternRef {
         }
     }
 
+    /// Make a new extern reference to a value that may not be `Send +
+    /// Sync`, such as an `Rc`, which is retrieved with
+    /// [`ExternRef::downcast_local`].
+    ///
+    /// The store is a [`LocalStore`], so the value never leaves the thread
+    /// it was created on.
+    pub fn new_local<T>(store: &mut LocalStore, value: T) -> Self
+    where
+        T: Any + 'static + Sized,
+    {
+        Self {
+            handle: StoreHandle::new(store.objects_mut(), VMExternObj::new_local(value)),
+        }
+    }
+
+    /// Try to downcast to the given value, which was made with
+    /// [`ExternRef::new_local`].
+    ///
+    /// This returns `None` on another thread than the one the value was
+    /// created on, which may happen once the objects of its store were
+    /// swapped into a `Store` sent to another thread.
+    pub fn downcast_local<'a, T>(&self, store: &'a impl AsStoreRef) -> Option<&'a T>
+    where
+        T: Any + 'static + Sized,
+    {
+        self.handle
+            .get(store.as_store_ref().objects())
+            .downcast_local_ref::<T>()
+    }
+
+    /// Try to downcast to the given value mutably, which was made with
+    /// [`ExternRef::new_local`], see [`ExternRef::downcast_local`].
+    pub fn downcast_local_mut<'a, T>(&self, store: &'a mut impl AsStoreMut) -> Option<&'a mut T>
+    where
+        T: Any + 'static + Sized,
+    {
+        self.handle
+            .get_mut(store.objects_mut())
+            .downcast_local_mut::<T>()
+    }
+
     /// Try to downcast to the given value.
+    ///
+    /// Values made with [`ExternRef::new_local`] are only retrieved with
+    /// [`ExternRef::downcast_local`].
     pub fn downcast<'a, T>(&self, store: &'a impl AsStoreRef) -> Option<&'a T>
     where
         T: Any + Send + Sync + 'static + Sized,
     {
         self.handle
             .get(store.as_store_ref().objects())
-            .as_ref()
             .downcast_ref::<T>()
     }
 
+    /// Returns the `TypeId` of the value, to find out which type to
+    /// downcast it to.
+    pub fn type_id(&self, store: &impl AsStoreRef) -> TypeId {
+        self.handle.get(store.as_store_ref().objects()).type_id()
+    }
+
+    /// Checks whether the value is a `T`, that is whether
+    /// [`ExternRef::downcast`] to `T` succeeds, or
+    /// [`ExternRef::downcast_local`] for values made with
+    /// [`ExternRef::new_local`].
+    pub fn is<T>(&self, store: &impl AsStoreRef) -> bool
+    where
+        T: Any + 'static + Sized,
+    {
+        self.handle.get(store.as_store_ref().objects()).is::<T>()
+    }
+
+    /// Try to downcast to the given value, mutably.
+    ///
+    /// The store stays borrowed for as long as the returned reference is
+    /// alive, so it can't alias with the one from [`ExternRef::downcast`].
+    pub fn downcast_mut<'a, T>(&self, store: &'a mut impl AsStoreMut) -> Option<&'a mut T>
+    where
+        T: Any + Send + Sync + 'static + Sized,
+    {
+        self.handle.get_mut(store.objects_mut()).downcast_mut::<T>()
+    }
+
+    /// Checks whether both references point to the same object of the
+    /// same store, regardless of the value of the object.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+
+    /// Converts the reference into an opaque pointer, to be turned back
+    /// into a reference with [`ExternRef::from_raw`], for instance after
+    /// going through foreign code.
+    ///
+    /// The pointer doesn't point to the object: it is only meaningful to
+    /// the store the reference comes from. Objects are owned by their
+    /// store, so the object stays alive as long as the store does, whether
+    /// or not the pointer is turned back into a reference.
+    pub fn into_raw(self) -> NonNull<()> {
+        let raw = unsafe { self.vm_externref().into_raw().externref };
+        NonNull::new(raw as *mut ()).expect("extern refs are never null")
+    }
+
+    /// Turns a pointer returned by [`ExternRef::into_raw`] back into a
+    /// reference to the same object.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must come from [`ExternRef::into_raw`] on a reference of
+    /// `store`.
+    pub unsafe fn from_raw(store: &impl AsStoreRef, raw: NonNull<()>) -> Self {
+        let vm_externref = VMExternRef::from_raw(RawValue {
+            externref: raw.as_ptr() as usize,
+        })
+        .unwrap();
+        Self {
+            handle: StoreHandle::from_internal(store.as_store_ref().objects().id(), vm_externref.0),
+        }
+    }
+
     pub(crate) fn vm_externref(&self) -> VMExternRef {
         VMExternRef(self.handle.internal_handle())
     }
@@ -46,6 +155,18 @@ impl ExternRef {
         }
     }
 
+    /// Clones the reference for use with `store`, or returns `None` if
+    /// the object doesn't belong to `store`.
+    ///
+    /// Unlike `clone`, this makes using a reference with another store a
+    /// recoverable error, rather than a panic when the object is accessed.
+    pub fn try_clone_into(&self, store: &impl AsStoreRef) -> Option<Self> {
+        if !self.is_from_store(store) {
+            return None;
+        }
+        Some(self.clone())
+    }
+
     /// Checks whether this `ExternRef` can be used with the given context.
     ///
     /// Primitive (`i32`, `i64`, etc) and null funcref/externref values are not
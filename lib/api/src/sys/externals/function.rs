@@ -11,7 +11,8 @@ pub use inner::{FromToNativeWasmType, HostFunction, WasmTypeList, WithEnv, Witho
 use std::cell::UnsafeCell;
 use std::cmp::max;
 use std::ffi::c_void;
-use wasmer_types::RawValue;
+use std::sync::Arc;
+use wasmer_types::{CallingConvention, RawValue};
 use wasmer_vm::{
     on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline, InternalStoreHandle,
     MaybeInstanceOwned, StoreHandle, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext,
@@ -148,7 +149,8 @@ impl Function {
             address: std::ptr::null(),
             ctx: DynamicFunction { func: wrapper },
         });
-        host_data.address = host_data.ctx.func_body_ptr();
+        let host_calling_convention = store.as_store_ref().engine().host_calling_convention();
+        host_data.address = host_data.ctx.func_body_ptr(host_calling_convention);
 
         // We don't yet have the address with the Wasm ABI signature.
         // The engine linker will replace the address with one pointing to a
@@ -172,7 +174,7 @@ impl Function {
         let vm_function = VMFunction {
             anyfunc: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(anyfunc))),
             kind: VMFunctionKind::Dynamic,
-            signature: function_type,
+            signature: Arc::new(function_type),
             host_data,
         };
         Self {
@@ -231,7 +233,7 @@ impl Function {
         let vm_function = VMFunction {
             anyfunc: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(anyfunc))),
             kind: VMFunctionKind::Static,
-            signature: function_type,
+            signature: Arc::new(function_type),
             host_data,
         };
         Self {
@@ -316,7 +318,7 @@ impl Function {
         let vm_function = VMFunction {
             anyfunc: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(anyfunc))),
             kind: VMFunctionKind::Static,
-            signature: function_type,
+            signature: Arc::new(function_type),
             host_data,
         };
         Self {
@@ -343,10 +345,7 @@ impl Function {
     /// assert_eq!(f.ty(&mut store).results(), vec![Type::I32]);
     /// ```
     pub fn ty(&self, store: &impl AsStoreRef) -> FunctionType {
-        self.handle
-            .get(store.as_store_ref().objects())
-            .signature
-            .clone()
+        (*self.handle.get(store.as_store_ref().objects()).signature).clone()
     }
 
     #[cfg(feature = "compiler")]
@@ -402,6 +401,7 @@ impl Function {
         }
 
         // Call the trampoline.
+        let _execution = store.as_store_ref().engine().enter_execution();
         let vm_function = self.handle.get(store.as_store_ref().objects());
         if let Err(error) = unsafe {
             wasmer_call_trampoline(
@@ -412,7 +412,9 @@ impl Function {
                 values_vec.as_mut_ptr() as *mut u8,
             )
         } {
-            return Err(RuntimeError::from_trap(error));
+            let error = RuntimeError::from_trap(error);
+            store.as_store_ref().engine().notify_trap(&error);
+            return Err(error);
         }
 
         // Load the return values out of `values_vec`.
@@ -504,14 +506,23 @@ impl Function {
         store: &mut impl AsStoreMut,
         params: &[Value],
     ) -> Result<Box<[Value]>, RuntimeError> {
-        let trampoline = unsafe {
-            self.handle
+        let (type_index, call_trampoline) = unsafe {
+            let anyfunc = self
+                .handle
                 .get(store.as_store_ref().objects())
                 .anyfunc
                 .as_ptr()
-                .as_ref()
-                .call_trampoline
+                .as_ref();
+            (anyfunc.type_index, anyfunc.call_trampoline)
         };
+        // The trampolines known to the engine, for example imported from a
+        // table shared with another process, take precedence over the one
+        // of the function.
+        let trampoline = store
+            .as_store_ref()
+            .engine()
+            .function_call_trampoline(type_index)
+            .unwrap_or(call_trampoline);
         let mut results = vec![Value::null(); self.result_arity(store)];
         self.call_wasm(store, trampoline, params, &mut results)?;
         Ok(results.into_boxed_slice())
@@ -530,7 +541,7 @@ impl Function {
         let signature = store
             .as_store_ref()
             .engine()
-            .lookup_signature(funcref.0.as_ref().type_index)
+            .lookup_signature_arc(funcref.0.as_ref().type_index)
             .expect("Signature not found in store");
         let vm_function = VMFunction {
             anyfunc: MaybeInstanceOwned::Instance(funcref.0),
@@ -739,8 +750,38 @@ where
         }
     }
 
-    fn func_body_ptr(&self) -> *const VMFunctionBody {
-        Self::func_wrapper as *const VMFunctionBody
+    // The dynamic function trampolines may call the wrapper with another
+    // calling convention than the C one, see
+    // `Engine::with_host_calling_convention`.
+    #[cfg(target_arch = "x86_64")]
+    unsafe extern "sysv64" fn func_wrapper_sysv64(
+        this: &mut VMDynamicFunctionContext<Self>,
+        values_vec: *mut RawValue,
+    ) {
+        Self::func_wrapper(this, values_vec)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe extern "win64" fn func_wrapper_win64(
+        this: &mut VMDynamicFunctionContext<Self>,
+        values_vec: *mut RawValue,
+    ) {
+        Self::func_wrapper(this, values_vec)
+    }
+
+    fn func_body_ptr(
+        &self,
+        calling_convention: Option<CallingConvention>,
+    ) -> *const VMFunctionBody {
+        match calling_convention {
+            #[cfg(target_arch = "x86_64")]
+            Some(CallingConvention::SystemV) => Self::func_wrapper_sysv64 as *const VMFunctionBody,
+            #[cfg(target_arch = "x86_64")]
+            Some(CallingConvention::WindowsFastcall) => {
+                Self::func_wrapper_win64 as *const VMFunctionBody
+            }
+            _ => Self::func_wrapper as *const VMFunctionBody,
+        }
     }
 
     fn call_trampoline_address(&self) -> VMTrampoline {
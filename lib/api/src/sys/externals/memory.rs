@@ -83,6 +83,18 @@ impl Memory {
         self.handle.get(store.as_store_ref().objects()).ty()
     }
 
+    /// Returns the size in bytes of the guard region following this memory.
+    ///
+    /// Out-of-bounds accesses landing in the guard region trap through a
+    /// fault instead of an explicit bounds check. The size depends on the
+    /// [`Tunables`](crate::Tunables) used to create the memory.
+    pub fn guard_size(&self, store: &impl AsStoreRef) -> usize {
+        self.handle
+            .get(store.as_store_ref().objects())
+            .style()
+            .offset_guard_size() as usize
+    }
+
     /// Creates a view into the memory that then allows for
     /// read and write
     pub fn view<'a>(&'a self, store: &impl AsStoreRef) -> MemoryView<'a> {
@@ -1,11 +1,14 @@
 use crate::sys::exports::Exports;
-use crate::sys::externals::Extern;
+use crate::sys::externals::{Extern, Global, Memory, Table};
 use crate::sys::imports::Imports;
 use crate::sys::module::Module;
-use crate::sys::{LinkError, RuntimeError};
+use crate::sys::{CompileError, LinkError, RuntimeError, Value};
 use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
-use wasmer_vm::{InstanceHandle, StoreHandle};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{ExportIndex, GlobalIndex, MemoryIndex, TableIndex};
+use wasmer_vm::{InstanceHandle, StoreHandle, VMExtern};
 
 use super::store::AsStoreMut;
 
@@ -21,10 +24,19 @@ use super::store::AsStoreMut;
 pub struct Instance {
     _handle: StoreHandle<InstanceHandle>,
     module: Module,
+    snapshot: Option<Arc<InstanceSnapshot>>,
     /// The exports for an instance.
     pub exports: Exports,
 }
 
+/// The state of the memories, mutable globals and tables defined by an
+/// instance, as it was right after instantiation.
+struct InstanceSnapshot {
+    memories: Vec<(MemoryIndex, Memory, Vec<u8>)>,
+    globals: Vec<(Global, Value)>,
+    tables: Vec<(TableIndex, Table, Vec<Value>)>,
+}
+
 #[cfg(test)]
 mod send_test {
     use super::*;
@@ -62,10 +74,43 @@ pub enum InstantiationError {
     #[error("missing required CPU features: {0:?}")]
     CpuFeature(String),
 
+    /// The module was compiled for another target than the host, whose
+    /// triple is given.
+    #[error("module compiled for another target: {0}")]
+    ForeignTarget(String),
+
     /// Import from a different Store.
     /// This error occurs when an import from a different store is used.
     #[error("cannot mix imports from different stores")]
     DifferentStores,
+
+    /// The module could not be compiled.
+    /// This error only occurs when compiling and instantiating in one
+    /// step, see [`Instance::compile_and_instantiate`].
+    #[error(transparent)]
+    Compile(CompileError),
+}
+
+/// An error while resetting an instance with [`Instance::reset`].
+///
+/// Every check is done before any state is restored, so the instance is
+/// left untouched when this happens.
+#[derive(Error, Debug)]
+pub enum ResetError {
+    /// The instance wasn't created with [`Instance::new_resettable`], so
+    /// there is no state to reset it to.
+    #[error("the instance has no snapshot to reset to")]
+    NoSnapshot,
+
+    /// A memory of the instance has grown since instantiation, and memories
+    /// can't shrink.
+    #[error("memory {0} has grown since instantiation")]
+    MemoryGrown(u32),
+
+    /// A table of the instance has grown since instantiation, and tables
+    /// can't shrink.
+    #[error("table {0} has grown since instantiation")]
+    TableGrown(u32),
 }
 
 impl From<wasmer_compiler::InstantiationError> for InstantiationError {
@@ -74,6 +119,7 @@ impl From<wasmer_compiler::InstantiationError> for InstantiationError {
             wasmer_compiler::InstantiationError::Link(e) => Self::Link(e),
             wasmer_compiler::InstantiationError::Start(e) => Self::Start(e),
             wasmer_compiler::InstantiationError::CpuFeature(e) => Self::CpuFeature(e),
+            wasmer_compiler::InstantiationError::ForeignTarget(e) => Self::ForeignTarget(e),
         }
     }
 }
@@ -132,12 +178,48 @@ impl Instance {
         let instance = Self {
             _handle: StoreHandle::new(store.objects_mut(), handle),
             module: module.clone(),
+            snapshot: None,
             exports,
         };
 
         Ok(instance)
     }
 
+    #[cfg(feature = "compiler")]
+    /// Creates a new `Instance` like [`Instance::new`], and takes a snapshot
+    /// of its memories, mutable globals and tables right after
+    /// instantiation, so that [`Instance::reset`] can bring it back to this
+    /// state.
+    ///
+    /// The snapshot holds a copy of every memory defined by the module.
+    /// Imported memories, globals and tables are not part of it, as they
+    /// are shared with other instances.
+    pub fn new_resettable(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        imports: &Imports,
+    ) -> Result<Self, InstantiationError> {
+        let mut instance = Self::new(store, module, imports)?;
+        instance.snapshot = Some(Arc::new(instance.take_snapshot(store)));
+        Ok(instance)
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Compiles a WebAssembly binary with the engine of the store and
+    /// instantiates it right away.
+    ///
+    /// This is the same as calling [`Module::new`] and then
+    /// [`Instance::new`], with compilation errors reported as
+    /// [`InstantiationError::Compile`].
+    pub fn compile_and_instantiate(
+        store: &mut impl AsStoreMut,
+        binary: impl AsRef<[u8]>,
+        imports: &Imports,
+    ) -> Result<Self, InstantiationError> {
+        let module = Module::new(&*store, binary).map_err(InstantiationError::Compile)?;
+        Self::new(store, &module, imports)
+    }
+
     #[cfg(feature = "compiler")]
     /// Creates a new `Instance` from a WebAssembly [`Module`] and a
     /// vector of imports.
@@ -169,6 +251,7 @@ impl Instance {
         let instance = Self {
             _handle: StoreHandle::new(store.objects_mut(), handle),
             module: module.clone(),
+            snapshot: None,
             exports,
         };
 
@@ -179,6 +262,140 @@ impl Instance {
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    /// Reads the current value of the global at `index` in the index space
+    /// of the module, whether it is exported or not.
+    ///
+    /// Returns `None` if the module has no such global.
+    pub fn read_global(&self, store: &mut impl AsStoreMut, index: GlobalIndex) -> Option<Value> {
+        let global = self.global(store, index)?;
+        Some(global.get(store))
+    }
+
+    /// Sets the value of the mutable global at `index` in the index space
+    /// of the module, whether it is exported or not.
+    ///
+    /// This fails if the module has no such global, if the global is
+    /// immutable, or if `value` doesn't have the type of the global.
+    pub fn write_global(
+        &self,
+        store: &mut impl AsStoreMut,
+        index: GlobalIndex,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        let global = self.global(store, index).ok_or_else(|| {
+            RuntimeError::new(format!("The module has no global {}", index.index()))
+        })?;
+        global.set(store, value)
+    }
+
+    /// Restores the memories, mutable globals and tables defined by the
+    /// module to the state they had right after instantiation.
+    ///
+    /// This is cheaper than instantiating the module again, but only works
+    /// for instances created with [`Instance::new_resettable`], and fails
+    /// if a memory or a table has grown since then.
+    ///
+    /// Passive data and element segments dropped since instantiation, with
+    /// `data.drop` or `elem.drop`, stay dropped.
+    pub fn reset(&mut self, store: &mut impl AsStoreMut) -> Result<(), ResetError> {
+        let snapshot = self.snapshot.clone().ok_or(ResetError::NoSnapshot)?;
+        for (index, memory, data) in &snapshot.memories {
+            if memory.view(store).data_size() != data.len() as u64 {
+                return Err(ResetError::MemoryGrown(index.as_u32()));
+            }
+        }
+        for (index, table, elements) in &snapshot.tables {
+            if table.size(store) as usize != elements.len() {
+                return Err(ResetError::TableGrown(index.as_u32()));
+            }
+        }
+        for (_, memory, data) in &snapshot.memories {
+            memory
+                .view(store)
+                .write(0, data)
+                .expect("the size of the memory was checked");
+        }
+        // The values were read from these globals and tables, which are
+        // mutable and big enough, so writing them back can't fail.
+        for (global, value) in &snapshot.globals {
+            global
+                .set(store, value.clone())
+                .expect("the value was read from the global");
+        }
+        for (_, table, elements) in &snapshot.tables {
+            for (i, element) in elements.iter().enumerate() {
+                table
+                    .set(store, i as u32, element.clone())
+                    .expect("the size of the table was checked");
+            }
+        }
+        Ok(())
+    }
+
+    fn take_snapshot(&self, store: &mut impl AsStoreMut) -> InstanceSnapshot {
+        let handle = self._handle.get_mut(store.objects_mut());
+        let module = handle.module().clone();
+        let memories = (module.num_imported_memories..module.memories.len())
+            .map(MemoryIndex::new)
+            .filter_map(|index| {
+                let handle = self._handle.get_mut(store.objects_mut());
+                match handle.lookup_by_declaration(ExportIndex::Memory(index)) {
+                    VMExtern::Memory(memory) => {
+                        Some((index, Memory::from_vm_extern(store, memory)))
+                    }
+                    _ => None,
+                }
+            })
+            .map(|(index, memory)| {
+                let view = memory.view(store);
+                let mut data = vec![0; view.data_size() as usize];
+                view.read(0, &mut data).expect("reading the whole memory");
+                (index, memory, data)
+            })
+            .collect();
+        let globals = (module.num_imported_globals..module.globals.len())
+            .map(GlobalIndex::new)
+            .filter(|index| module.globals[*index].mutability.is_mutable())
+            .filter_map(|index| self.global(store, index))
+            .map(|global| {
+                let value = global.get(store);
+                (global, value)
+            })
+            .collect();
+        let tables = (module.num_imported_tables..module.tables.len())
+            .map(TableIndex::new)
+            .filter_map(|index| {
+                let handle = self._handle.get_mut(store.objects_mut());
+                match handle.lookup_by_declaration(ExportIndex::Table(index)) {
+                    VMExtern::Table(table) => Some((index, Table::from_vm_extern(store, table))),
+                    _ => None,
+                }
+            })
+            .map(|(index, table)| {
+                let elements = (0..table.size(store))
+                    .map(|i| table.get(store, i).expect("element within the table"))
+                    .collect();
+                (index, table, elements)
+            })
+            .collect();
+        InstanceSnapshot {
+            memories,
+            globals,
+            tables,
+        }
+    }
+
+    fn global(&self, store: &mut impl AsStoreMut, index: GlobalIndex) -> Option<Global> {
+        let handle = self._handle.get_mut(store.objects_mut());
+        if index.index() >= handle.module_ref().globals.len() {
+            return None;
+        }
+        match handle.lookup_by_declaration(ExportIndex::Global(index)) {
+            VMExtern::Global(global) => Some(Global::from_vm_extern(store, global)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for Instance {
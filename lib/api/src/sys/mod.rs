@@ -21,7 +21,7 @@ pub use crate::sys::externals::{
 };
 pub use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
 pub use crate::sys::imports::Imports;
-pub use crate::sys::instance::{Instance, InstantiationError};
+pub use crate::sys::instance::{Instance, InstantiationError, ResetError};
 pub use crate::sys::mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
 pub use crate::sys::module::Module;
 pub use crate::sys::native::TypedFunction;
@@ -29,7 +29,7 @@ pub use crate::sys::native_type::NativeWasmTypeInto;
 pub use crate::sys::store::{AsStoreMut, AsStoreRef, StoreMut, StoreRef};
 
 pub use crate::sys::ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
-pub use crate::sys::store::Store;
+pub use crate::sys::store::{LocalStore, Store};
 pub use crate::sys::tunables::BaseTunables;
 pub use crate::sys::value::Value;
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
@@ -42,22 +42,25 @@ pub use wasmer_derive::ValueType;
 pub use wasmer_types::is_wasm;
 pub use wasmer_types::{
     CpuFeature, ExportType, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
-    Mutability, TableType, Target, Type,
+    Mutability, TableType, TagType, Target, Type,
 };
 
 pub use wasmer_types::{
-    Bytes, CompileError, DeserializeError, ExportIndex, GlobalInit, LocalFunctionIndex,
-    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    ArtifactHash, ArtifactMetadata, Bytes, CompileError, CompileHints, CompileOptions,
+    CustomSectionInfo, DeserializeError, DeserializeForwardPolicy, DeterminismReport, ExportIndex,
+    GlobalIndex, GlobalInit, LocalFunctionIndex, MiddlewareError, OptLevel, Pages,
+    ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult, WASM_MAX_PAGES,
+    WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 
 // TODO: should those be moved into wasmer::vm as well?
-pub use wasmer_vm::{raise_user_trap, MemoryError};
+pub use wasmer_vm::{raise_user_trap, MemoryError, RoundingMode};
 pub mod vm {
     //! The `vm` module re-exports wasmer-vm types.
 
     pub use wasmer_vm::{
-        MemoryError, MemoryStyle, TableStyle, VMExtern, VMMemory, VMMemoryDefinition, VMTable,
+        InstanceHandle, MemoryError, MemoryStyle, StoreObjects, TableGrowObserver,
+        TableLimitExceeded, TableStyle, Trap, VMExtern, VMMemory, VMMemoryDefinition, VMTable,
         VMTableDefinition,
     };
 }
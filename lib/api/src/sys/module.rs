@@ -1,4 +1,5 @@
 use crate::sys::InstantiationError;
+use crate::sys::LinkError;
 use crate::AsStoreMut;
 use crate::AsStoreRef;
 use std::fmt;
@@ -228,6 +229,33 @@ impl Module {
         self.artifact.serialize_to_file(path.as_ref())
     }
 
+    /// Serializes a module along with an image of its code, which
+    /// [`Module::deserialize_mmap`] can execute straight from the file.
+    ///
+    /// See [`Artifact::serialize_mappable`].
+    pub fn serialize_mappable(&self) -> Result<Vec<u8>, SerializeError> {
+        self.artifact.serialize_mappable()
+    }
+
+    /// Serializes a module into `writer`, in the same binary representation
+    /// as [`Module::serialize`], without holding it all in memory.
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// # let module = Module::from_file(&store, "path/to/foo.wasm")?;
+    /// let mut file = std::fs::File::create("path/to/foo.wasmu")?;
+    /// module.serialize_to(&mut file)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn serialize_to<W: io::Write>(&self, writer: &mut W) -> Result<(), SerializeError> {
+        self.artifact.serialize_to(writer)
+    }
+
     #[cfg(feature = "compiler")]
     /// Deserializes a serialized Module binary into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
@@ -289,6 +317,26 @@ impl Module {
         Ok(Self::from_artifact(artifact))
     }
 
+    #[cfg(feature = "compiler")]
+    /// Deserializes a Module located in a `Path`, executing its code
+    /// straight from the mapping of the file if it was serialized with
+    /// [`Module::serialize_mappable`].
+    ///
+    /// # Safety
+    ///
+    /// Please check [`Module::deserialize`]. Moreover, the file must not be
+    /// modified for as long as the engine of the store is alive.
+    pub unsafe fn deserialize_mmap(
+        store: &impl AsStoreRef,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, DeserializeError> {
+        let artifact = store
+            .as_store_ref()
+            .engine()
+            .deserialize_mmap(path.as_ref())?;
+        Ok(Self::from_artifact(artifact))
+    }
+
     fn from_artifact(artifact: Arc<Artifact>) -> Self {
         Self {
             module_info: Arc::new(artifact.create_module_info()),
@@ -308,6 +356,11 @@ impl Module {
                 return Err(InstantiationError::DifferentStores);
             }
         }
+        if store.as_store_ref().engine().is_draining() {
+            return Err(InstantiationError::Link(LinkError::Resource(
+                "The Engine is draining and doesn't accept new instantiations.".to_string(),
+            )));
+        }
         let mut store_mut = store.as_store_mut();
         let (tunables, objects) = store_mut.tunables_and_objects_mut();
         unsafe {
@@ -97,6 +97,7 @@ macro_rules! impl_native_traits {
                     }
                     rets_list.as_mut()
                 };
+                let _execution = store.as_store_ref().engine().enter_execution();
                 unsafe {
                     wasmer_vm::wasmer_call_trampoline(
                         store.as_store_ref().signal_handler(),
@@ -105,7 +106,12 @@ macro_rules! impl_native_traits {
                         anyfunc.func_ptr,
                         args_rets.as_mut_ptr() as *mut u8,
                     )
-                }?;
+                }
+                .map_err(|trap| {
+                    let error = RuntimeError::from_trap(trap);
+                    store.as_store_ref().engine().notify_trap(&error);
+                    error
+                })?;
                 let num_rets = rets_list.len();
                 if !using_rets_array && num_rets > 0 {
                     let src_pointer = params_list.as_ptr();
@@ -1,5 +1,6 @@
 use crate::sys::tunables::BaseTunables;
 use std::fmt;
+use std::marker::PhantomData;
 use std::sync::{Arc, RwLock};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::{Engine, EngineBuilder, Tunables};
@@ -206,6 +207,77 @@ impl fmt::Debug for Store {
     }
 }
 
+/// A [`Store`] that stays on the thread it was created on, so that it can
+/// hold values that aren't `Send + Sync`, see [`ExternRef::new_local`].
+///
+/// It is neither `Send` nor `Sync`, so it can't be moved or shared across
+/// threads:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<wasmer::LocalStore>();
+/// ```
+///
+/// Its objects can still reach other threads, through the handles
+/// borrowing it or once swapped into another store with
+/// [`AsStoreMut::objects_mut`]. Their values then aren't handed out on
+/// other threads, and dropping them on another thread leaks them.
+///
+/// [`ExternRef::new_local`]: crate::ExternRef::new_local
+pub struct LocalStore {
+    store: Store,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LocalStore {
+    #[cfg(feature = "compiler")]
+    /// Creates a new `LocalStore` with a specific [`Engine`].
+    pub fn new(engine: impl Into<Engine>) -> Self {
+        Self::from(Store::new(engine))
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Returns the [`Tunables`].
+    pub fn tunables(&self) -> &dyn Tunables {
+        self.store.tunables()
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Returns the [`Engine`].
+    pub fn engine(&self) -> &Engine {
+        self.store.engine()
+    }
+}
+
+impl From<Store> for LocalStore {
+    fn from(store: Store) -> Self {
+        Self {
+            store,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl AsStoreRef for LocalStore {
+    fn as_store_ref(&self) -> StoreRef<'_> {
+        self.store.as_store_ref()
+    }
+}
+impl AsStoreMut for LocalStore {
+    fn as_store_mut(&mut self) -> StoreMut<'_> {
+        self.store.as_store_mut()
+    }
+    fn objects_mut(&mut self) -> &mut StoreObjects {
+        self.store.objects_mut()
+    }
+}
+
+impl fmt::Debug for LocalStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LocalStore").finish()
+    }
+}
+
 /// A temporary handle to a [`Store`].
 pub struct StoreRef<'a> {
     pub(crate) inner: &'a StoreInner,
@@ -25,6 +25,10 @@ pub struct BaseTunables {
 
     /// The size in bytes of the offset guard for dynamic heaps.
     pub dynamic_memory_offset_guard_size: u64,
+
+    /// The number of pages of memories created at instantiation to commit
+    /// up front.
+    pub initial_committed_pages: u32,
 }
 
 impl BaseTunables {
@@ -59,6 +63,7 @@ impl BaseTunables {
             static_memory_bound,
             static_memory_offset_guard_size,
             dynamic_memory_offset_guard_size,
+            initial_committed_pages: 0,
         }
     }
 }
@@ -112,6 +117,12 @@ impl Tunables for BaseTunables {
         VMMemory::from_definition(ty, style, vm_definition_location)
     }
 
+    /// The number of pages of memories created at instantiation to commit
+    /// up front.
+    fn initial_committed_pages(&self) -> u32 {
+        self.initial_committed_pages
+    }
+
     /// Create a table owned by the host given a [`TableType`] and a [`TableStyle`].
     fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
         VMTable::new(ty, style)
@@ -142,6 +153,7 @@ mod tests {
             static_memory_bound: Pages(2048),
             static_memory_offset_guard_size: 128,
             dynamic_memory_offset_guard_size: 256,
+            initial_committed_pages: 0,
         };
 
         // No maximum
@@ -151,6 +151,23 @@ mod sys {
         Ok(())
     }
 
+    #[test]
+    fn memory_guard_size() -> Result<()> {
+        let mut tunables = BaseTunables::for_target(&Target::default());
+        tunables.static_memory_offset_guard_size = 0x1_0000;
+        let engine = Store::default().engine().cloned();
+        let mut store = Store::new_with_tunables(engine, tunables);
+
+        let memory = Memory::new(&mut store, MemoryType::new(Pages(1), Some(Pages(2)), false))?;
+        assert_eq!(memory.guard_size(&store), 0x1_0000);
+
+        let module = Module::new(&store, "(module (memory (export \"mem\") 1 2))")?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let memory = instance.exports.get_memory("mem")?;
+        assert_eq!(memory.guard_size(&store), 0x1_0000);
+        Ok(())
+    }
+
     #[test]
     fn memory_grow() -> Result<()> {
         let mut store = Store::default();
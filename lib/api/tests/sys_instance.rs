@@ -73,4 +73,205 @@ mod sys {
 
         Ok(())
     }
+
+    #[test]
+    fn compile_and_instantiate_in_one_step() -> Result<()> {
+        let mut store = Store::default();
+        let wat = r#"(module
+            (import "host" "base" (global i32))
+            (func (export "add") (param i32) (result i32)
+                local.get 0
+                global.get 0
+                i32.add))"#;
+        let base = Global::new(&mut store, Value::I32(40));
+        let imports = imports! { "host" => { "base" => base } };
+
+        let fused = Instance::compile_and_instantiate(&mut store, wat, &imports)?;
+        let module = Module::new(&store, wat)?;
+        let two_step = Instance::new(&mut store, &module, &imports)?;
+        let fused_add: TypedFunction<i32, i32> =
+            fused.exports.get_typed_function(&mut store, "add")?;
+        let two_step_add: TypedFunction<i32, i32> =
+            two_step.exports.get_typed_function(&mut store, "add")?;
+        assert_eq!(fused_add.call(&mut store, 2)?, 42);
+        assert_eq!(
+            fused_add.call(&mut store, 2)?,
+            two_step_add.call(&mut store, 2)?
+        );
+        assert_eq!(fused.module().exports().count(), 1);
+
+        // Compile errors are reported apart from instantiation errors.
+        assert!(matches!(
+            Instance::compile_and_instantiate(&mut store, "(module (func (result i32)))", &imports),
+            Err(InstantiationError::Compile(_))
+        ));
+        assert!(matches!(
+            Instance::compile_and_instantiate(&mut store, wat, &Imports::new()),
+            Err(InstantiationError::Link(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn read_and_write_globals_by_index() -> Result<()> {
+        let mut store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                (import "host" "base" (global i32))
+                (global f64 (f64.const 1.5))
+                (global (mut i64) (i64.const 7)))"#,
+        )?;
+        let base = Global::new(&mut store, Value::I32(40));
+        let imports = imports! { "host" => { "base" => base } };
+        let instance = Instance::new(&mut store, &module, &imports)?;
+
+        let imported = GlobalIndex::from_u32(0);
+        let constant = GlobalIndex::from_u32(1);
+        let counter = GlobalIndex::from_u32(2);
+        assert_eq!(
+            instance.read_global(&mut store, imported),
+            Some(Value::I32(40))
+        );
+        assert_eq!(
+            instance.read_global(&mut store, constant),
+            Some(Value::F64(1.5))
+        );
+        assert_eq!(
+            instance.read_global(&mut store, counter),
+            Some(Value::I64(7))
+        );
+        assert_eq!(
+            instance.read_global(&mut store, GlobalIndex::from_u32(3)),
+            None
+        );
+
+        instance.write_global(&mut store, counter, Value::I64(42))?;
+        assert_eq!(
+            instance.read_global(&mut store, counter),
+            Some(Value::I64(42))
+        );
+
+        // Writes of the wrong type and to immutable globals are rejected.
+        assert!(instance
+            .write_global(&mut store, counter, Value::I32(1))
+            .is_err());
+        assert!(instance
+            .write_global(&mut store, constant, Value::F64(2.5))
+            .is_err());
+        assert_eq!(
+            instance.read_global(&mut store, counter),
+            Some(Value::I64(42))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reset_restores_the_state_after_instantiation() -> Result<()> {
+        let mut store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "hello")
+                (global $counter (export "counter") (mut i32) (i32.const 1))
+                (table (export "table") 1 funcref)
+                (func $bump (export "bump")
+                    (i32.store8 (i32.const 0) (i32.const 0x4a))
+                    (i32.store (i32.const 100) (i32.const 7))
+                    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+                    (table.set (i32.const 0) (ref.func $bump)))
+                (func (export "grow") (drop (memory.grow (i32.const 1))))
+                (elem declare func $bump))"#,
+        )?;
+        let mut instance = Instance::new_resettable(&mut store, &module, &imports! {})?;
+        let bump = instance.exports.get_function("bump")?.clone();
+        let memory = instance.exports.get_memory("memory")?.clone();
+        let counter = instance.exports.get_global("counter")?.clone();
+        let table = instance.exports.get_table("table")?.clone();
+
+        let read = |store: &Store, offset: u64| {
+            let mut buf = [0; 5];
+            memory.view(store).read(offset, &mut buf).unwrap();
+            buf
+        };
+        bump.call(&mut store, &[])?;
+        bump.call(&mut store, &[])?;
+        assert_eq!(&read(&store, 0), b"Jello");
+        assert_eq!(read(&store, 100), [7, 0, 0, 0, 0]);
+        assert_eq!(counter.get(&mut store), Value::I32(3));
+        assert!(table
+            .get(&mut store, 0)
+            .unwrap()
+            .funcref()
+            .unwrap()
+            .is_some());
+
+        instance.reset(&mut store)?;
+        assert_eq!(&read(&store, 0), b"hello");
+        assert_eq!(read(&store, 100), [0; 5]);
+        assert_eq!(counter.get(&mut store), Value::I32(1));
+        assert!(table
+            .get(&mut store, 0)
+            .unwrap()
+            .funcref()
+            .unwrap()
+            .is_none());
+
+        // Memories can't shrink back to their initial size.
+        let grow = instance.exports.get_function("grow")?.clone();
+        grow.call(&mut store, &[])?;
+        assert!(matches!(
+            instance.reset(&mut store),
+            Err(ResetError::MemoryGrown(0))
+        ));
+
+        // Instances need a snapshot to be reset.
+        let mut instance = Instance::new(&mut store, &module, &imports! {})?;
+        assert!(matches!(
+            instance.reset(&mut store),
+            Err(ResetError::NoSnapshot)
+        ));
+        Ok(())
+    }
+
+    /// Returns whether the page holding `address` is resident, according to
+    /// `/proc/self/pagemap`.
+    #[cfg(target_os = "linux")]
+    fn is_resident(address: usize) -> std::io::Result<bool> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        const PAGE_SIZE: usize = 4096;
+        let mut pagemap = std::fs::File::open("/proc/self/pagemap")?;
+        pagemap.seek(SeekFrom::Start((address / PAGE_SIZE * 8) as u64))?;
+        let mut entry = [0u8; 8];
+        pagemap.read_exact(&mut entry)?;
+        Ok(u64::from_le_bytes(entry) & (1 << 63) != 0)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn initial_committed_pages_are_resident() -> Result<()> {
+        let wat = r#"(module (memory (export "memory") 4))"#;
+        let resident_pages = |committed_pages: u32| -> Result<Vec<bool>> {
+            let engine = Store::default().engine().clone();
+            let mut tunables = BaseTunables::for_target(engine.target());
+            tunables.initial_committed_pages = committed_pages;
+            let mut store = Store::new_with_tunables(engine, tunables);
+            let module = Module::new(&store, wat)?;
+            let instance = Instance::new(&mut store, &module, &imports! {})?;
+            let memory = instance.exports.get_memory("memory")?;
+            let base = memory.view(&store).data_ptr() as usize;
+            let resident = (0..4)
+                .map(|page| is_resident(base + page * WASM_PAGE_SIZE))
+                .collect::<std::io::Result<_>>()?;
+            Ok(resident)
+        };
+
+        // The other pages may be resident too if the kernel backs the memory
+        // with huge pages.
+        assert_eq!(resident_pages(2)?[..2], [true, true]);
+        assert_eq!(resident_pages(0)?, vec![false; 4]);
+        Ok(())
+    }
 }
@@ -242,6 +242,217 @@ mod sys {
         Ok(())
     }
 
+    #[test]
+    fn extern_ref_ptr_eq() -> Result<()> {
+        let mut store = Store::default();
+        let wat = r#"(module
+        (func (export "identity") (param $er externref) (result externref)
+              (local.get $er))
+    )"#;
+        let module = Module::new(&store, wat)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let identity: TypedFunction<Option<ExternRef>, Option<ExternRef>> =
+            instance.exports.get_typed_function(&store, "identity")?;
+
+        let er = ExternRef::new(&mut store, 3u32);
+        assert!(er.ptr_eq(&er.clone()));
+        let returned = identity.call(&mut store, Some(er.clone()))?.unwrap();
+        assert!(returned.ptr_eq(&er));
+
+        // Equal values are still distinct objects.
+        let other = ExternRef::new(&mut store, 3u32);
+        assert!(!other.ptr_eq(&er));
+
+        // Objects of other stores are distinct even at the same position.
+        let mut other_store = Store::default();
+        let foreign = ExternRef::new(&mut other_store, 3u32);
+        assert!(!foreign.ptr_eq(&er));
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_type_introspection() -> Result<()> {
+        use std::any::TypeId;
+
+        let mut store = Store::default();
+        let er = ExternRef::new(&mut store, 3u32);
+        assert!(er.is::<u32>(&store));
+        assert!(!er.is::<u64>(&store));
+        assert!(!er.is::<String>(&store));
+        assert_eq!(er.type_id(&store), TypeId::of::<u32>());
+        // `is` and `downcast` agree.
+        assert!(er.downcast::<u64>(&store).is_none());
+
+        let wat = r#"(module
+        (func (export "identity") (param externref) (result externref)
+              local.get 0))"#;
+        let module = Module::new(&store, wat)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let identity: TypedFunction<Option<ExternRef>, Option<ExternRef>> =
+            instance.exports.get_typed_function(&store, "identity")?;
+        let er = ExternRef::new(&mut store, "hello".to_string());
+        let returned = identity.call(&mut store, Some(er))?.unwrap();
+        assert_eq!(returned.type_id(&store), TypeId::of::<String>());
+        assert!(returned.is::<String>(&store));
+        assert_eq!(returned.downcast::<String>(&store).unwrap(), "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_try_clone_into_checks_the_store() -> Result<()> {
+        let mut store = Store::default();
+        let other_store = Store::default();
+        let er = ExternRef::new(&mut store, 5u32);
+
+        let clone = er.try_clone_into(&store).unwrap();
+        assert!(clone.ptr_eq(&er));
+        assert_eq!(clone.downcast::<u32>(&store), Some(&5));
+
+        assert!(er.try_clone_into(&other_store).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_local_values() -> Result<()> {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut store = LocalStore::from(Store::default());
+        let wat = r#"(module
+        (func (export "identity") (param externref) (result externref)
+              local.get 0))"#;
+        let module = Module::new(&store, wat)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let identity: TypedFunction<Option<ExternRef>, Option<ExternRef>> =
+            instance.exports.get_typed_function(&store, "identity")?;
+
+        let log = Rc::new(RefCell::new(vec![1]));
+        let er = ExternRef::new_local(&mut store, log.clone());
+        let returned = identity.call(&mut store, Some(er))?.unwrap();
+        assert!(returned.is::<Rc<RefCell<Vec<i32>>>>(&store));
+        returned
+            .downcast_local::<Rc<RefCell<Vec<i32>>>>(&store)
+            .unwrap()
+            .borrow_mut()
+            .push(2);
+        assert_eq!(*log.borrow(), vec![1, 2]);
+        assert!(returned.downcast_local::<Rc<u32>>(&store).is_none());
+
+        let number = ExternRef::new_local(&mut store, 7u32);
+        *number.downcast_local_mut::<u32>(&mut store).unwrap() += 1;
+        assert_eq!(number.downcast_local::<u32>(&store), Some(&8));
+        // Local values are only retrieved as such.
+        assert!(number.downcast::<u32>(&store).is_none());
+        assert!(ExternRef::new(&mut store, 7u32)
+            .downcast_local::<u32>(&store)
+            .is_none());
+
+        // The value is dropped along with the store.
+        drop(store);
+        assert_eq!(Rc::strong_count(&log), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_local_values_stay_on_their_thread() -> Result<()> {
+        use std::rc::Rc;
+
+        let mut local_store = LocalStore::from(Store::default());
+        let value = Rc::new(5u32);
+        let er = ExternRef::new_local(&mut local_store, value.clone());
+
+        // Move the objects into a store that can be sent to another thread.
+        let mut store = Store::default();
+        std::mem::swap(local_store.objects_mut(), store.objects_mut());
+        let er_on_thread = er.clone();
+        let mut store = std::thread::spawn(move || {
+            assert!(er_on_thread.is::<Rc<u32>>(&store));
+            assert!(er_on_thread.downcast_local::<Rc<u32>>(&store).is_none());
+            store
+        })
+        .join()
+        .unwrap();
+        assert_eq!(**er.downcast_local::<Rc<u32>>(&store).unwrap(), 5);
+
+        // Dropping the value on another thread leaks it.
+        std::thread::spawn(move || drop(store)).join().unwrap();
+        assert_eq!(Rc::strong_count(&value), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_raw_round_trip() -> Result<()> {
+        use std::ptr::NonNull;
+
+        let mut store = Store::default();
+        let wat = r#"(module
+        (func $read (import "env" "read") (param i64) (result i32))
+        (func (export "run") (param $raw i64) (result i32)
+              (call $read (local.get $raw)))
+    )"#;
+        let module = Module::new(&store, wat)?;
+        let env = FunctionEnv::new(&mut store, ());
+        let imports = imports! {
+            "env" => {
+                "read" => Function::new_typed_with_env(&mut store, &env, |env: FunctionEnvMut<()>, raw: i64| -> u32 {
+                    let raw = NonNull::new(raw as usize as *mut ()).unwrap();
+                    let er = unsafe { ExternRef::from_raw(&env, raw) };
+                    *er.downcast::<u32>(&env).unwrap()
+                }),
+            },
+        };
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        let run: TypedFunction<i64, u32> = instance.exports.get_typed_function(&store, "run")?;
+
+        let er = ExternRef::new(&mut store, 42u32);
+        let raw = er.clone().into_raw();
+        assert_eq!(run.call(&mut store, raw.as_ptr() as usize as i64)?, 42);
+
+        let back = unsafe { ExternRef::from_raw(&store, raw) };
+        assert!(back.ptr_eq(&er));
+        assert_eq!(back.downcast::<u32>(&store), Some(&42));
+        Ok(())
+    }
+
+    #[test]
+    fn extern_ref_downcast_mut() -> Result<()> {
+        let mut store = Store::default();
+        let wat = r#"(module
+        (func $bump (import "env" "bump") (param externref) (result externref))
+        (func (export "run") (param $er externref) (result externref)
+              (call $bump (local.get $er)))
+    )"#;
+        let module = Module::new(&store, wat)?;
+        let env = FunctionEnv::new(&mut store, ());
+        let imports = imports! {
+            "env" => {
+                "bump" => Function::new_typed_with_env(&mut store, &env, |mut env: FunctionEnvMut<()>, er: Option<ExternRef>| -> Option<ExternRef> {
+                    *er.as_ref().unwrap().downcast_mut::<u32>(&mut env).unwrap() += 1;
+                    er
+                }),
+            },
+        };
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        let f: TypedFunction<Option<ExternRef>, Option<ExternRef>> =
+            instance.exports.get_typed_function(&store, "run")?;
+
+        let er = ExternRef::new(&mut store, 3u32);
+        *er.downcast_mut::<u32>(&mut store).unwrap() *= 10;
+        let returned = f.call(&mut store, Some(er.clone()))?.unwrap();
+        assert_eq!(returned.downcast::<u32>(&store), Some(&31));
+
+        // The mutation done by the host function is visible from every
+        // reference to the object.
+        *returned.downcast_mut::<u32>(&mut store).unwrap() += 1;
+        assert_eq!(er.downcast::<u32>(&store), Some(&32));
+        f.call(&mut store, Some(er.clone()))?;
+        assert_eq!(er.downcast::<u32>(&store), Some(&33));
+
+        assert!(er.downcast_mut::<u64>(&mut store).is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn refs_in_globals() -> Result<()> {
         let mut store = Store::default();
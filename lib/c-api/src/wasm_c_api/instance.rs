@@ -80,11 +80,23 @@ pub unsafe extern "C" fn wasm_instance_new(
             return None;
         }
 
+        Err(e @ InstantiationError::ForeignTarget(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
         Err(e @ InstantiationError::DifferentStores) => {
             crate::error::update_last_error(e);
 
             return None;
         }
+
+        Err(e @ InstantiationError::Compile(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
     };
 
     Some(Box::new(wasm_instance_t {
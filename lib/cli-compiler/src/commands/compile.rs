@@ -6,7 +6,8 @@ use std::path::{Path, PathBuf};
 use wasmer_compiler::{ArtifactBuild, ArtifactCreate, ModuleEnvironment};
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
-    CompileError, CpuFeature, MemoryIndex, MemoryStyle, TableIndex, TableStyle, Target, Triple,
+    CompileError, CompileHints, CpuFeature, MemoryIndex, MemoryStyle, TableIndex, TableStyle,
+    Target, Triple,
 };
 
 #[derive(Debug, Parser)]
@@ -96,11 +97,12 @@ impl Compile {
             .map(|table_type| tunables.table_style(table_type))
             .collect();
         let artifact = ArtifactBuild::new(
-            &mut engine.inner_mut(),
+            &engine.inner().compile_settings()?,
             &wasm_bytes,
             &target,
             memory_styles,
             table_styles,
+            &CompileHints::default(),
         )?;
         artifact.serialize_to_file(self.output.as_ref())?;
         eprintln!(
@@ -13,24 +13,25 @@ use crate::translator::{
     signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator,
 };
 use cranelift_codegen::ir::ExternalName;
+use cranelift_codegen::isa::CallConv;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{ir, MachReloc};
-use cranelift_codegen::{Context, MachTrap};
+use cranelift_codegen::{Context, MachStackMap, MachTrap};
 #[cfg(feature = "unwind")]
 use gimli::write::{Address, EhFrame, FrameTable};
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
 use wasmer_compiler::{
-    Compiler, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader, ModuleMiddleware,
-    ModuleMiddlewareChain, ModuleTranslationState,
+    map_on_executor, CompileExecutor, Compiler, FunctionBinaryReader, FunctionBodyData,
+    MiddlewareBinaryReader, ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
-    CallingConvention, Compilation, CompileError, CompileModuleInfo, CompiledFunction,
-    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, Dwarf, FunctionBody, FunctionIndex,
-    LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex, SignatureIndex,
-    Target, TrapCode, TrapInformation,
+    CallingConvention, Compilation, CompileError, CompileHints, CompileModuleInfo,
+    CompiledFunction, CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, Dwarf, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex,
+    SignatureIndex, StackMap, Target, TrapCode, TrapInformation,
 };
 
 /// A compiler that compiles a WebAssembly module with Cranelift, translating the Wasm to Cranelift IR,
@@ -49,23 +50,31 @@ impl CraneliftCompiler {
     pub fn config(&self) -> &Cranelift {
         &self.config
     }
-}
 
-impl Compiler for CraneliftCompiler {
-    /// Get the middlewares for this compiler
-    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
-        &self.config.middlewares
-    }
-
-    /// Compile the module using Cranelift, producing a compilation result with
-    /// associated relocations.
-    fn compile_module(
+    /// Compiles the module with the optimization level of the hints, if any,
+    /// compiling the functions on `executor` if any.
+    fn compile_functions_on(
         &self,
         target: &Target,
         compile_info: &CompileModuleInfo,
         module_translation_state: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        hints: &CompileHints,
+        executor: Option<&dyn CompileExecutor>,
     ) -> Result<Compilation, CompileError> {
+        if let Some(opt_level) = hints.opt_level {
+            let mut config = self.config.clone();
+            config.opt_level(opt_level.into());
+            return Self::new(config).compile_functions_on(
+                target,
+                compile_info,
+                module_translation_state,
+                function_body_inputs,
+                &CompileHints::default(),
+                executor,
+            );
+        }
+
         let isa = self
             .config()
             .isa(target)
@@ -106,207 +115,137 @@ impl Compiler for CraneliftCompiler {
 
         let mut custom_sections = PrimaryMap::new();
 
-        #[cfg(not(feature = "rayon"))]
-        let mut func_translator = FuncTranslator::new();
-        #[cfg(not(feature = "rayon"))]
-        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .into_iter()
-            .map(|(i, input)| {
-                let func_index = module.func_index(i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    &memory_styles,
-                    &table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(
-                    self.config
-                        .middlewares
-                        .generate_function_middleware_chain(i),
-                );
-
-                func_translator.translate(
+        let compile_function = |func_translator: &mut FuncTranslator,
+                                i: LocalFunctionIndex,
+                                input: &FunctionBodyData<'_>|
+         -> Result<_, CompileError> {
+            let func_index = module.func_index(i);
+            let mut context = Context::new();
+            let mut func_env = FuncEnvironment::new(
+                isa.frontend_config(),
+                module,
+                &signatures,
+                memory_styles,
+                table_styles,
+            );
+            context.func.name = get_function_name(func_index);
+            context.func.signature = signatures[module.functions[func_index]].clone();
+            // if generate_debug_info {
+            //     context.func.collect_debug_info();
+            // }
+            let mut reader =
+                MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+            reader.set_middleware_chain(
+                self.config
+                    .middlewares
+                    .generate_function_middleware_chain(i),
+            );
+
+            func_translator
+                .translate(
                     module_translation_state,
                     &mut reader,
                     &mut context.func,
                     &mut func_env,
                     i,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                context
-                    .compile_and_emit(&*isa, &mut code_buf)
-                    .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-                let result = context.mach_compile_result.as_ref().unwrap();
-                let func_relocs = result
-                    .buffer
-                    .relocs()
-                    .into_iter()
-                    .map(|r| mach_reloc_to_reloc(module, r))
-                    .collect::<Vec<_>>();
-
-                let traps = result
-                    .buffer
-                    .traps()
-                    .into_iter()
-                    .map(mach_trap_to_trap)
-                    .collect::<Vec<_>>();
-
-                let (unwind_info, fde) = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::Fde(fde) => {
-                        if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
-                        } else {
-                            (None, None)
-                        }
+                )
+                .map_err(|error| CompileError::from(error).in_function(i))?;
+
+            let mut code_buf: Vec<u8> = Vec::new();
+            context
+                .compile_and_emit(&isa, &mut code_buf)
+                .map_err(|error| {
+                    CompileError::Codegen(pretty_error(&context.func, error)).in_function(i)
+                })?;
+
+            let result = context.mach_compile_result.as_ref().unwrap();
+            let func_relocs = result
+                .buffer
+                .relocs()
+                .iter()
+                .map(|r| mach_reloc_to_reloc(module, r))
+                .collect::<Vec<_>>();
+
+            let traps = result
+                .buffer
+                .traps()
+                .iter()
+                .map(mach_trap_to_trap)
+                .collect::<Vec<_>>();
+
+            let stack_maps = mach_stack_maps_to_stack_maps(result.buffer.stack_maps());
+
+            let (unwind_info, fde) = match compiled_function_unwind_info(&isa, &context)? {
+                #[cfg(feature = "unwind")]
+                CraneliftUnwindInfo::Fde(fde) => {
+                    if dwarf_frametable.is_some() {
+                        let fde = fde.to_fde(Address::Symbol {
+                            // The symbol is the kind of relocation.
+                            // "0" is used for functions
+                            symbol: WriterRelocate::FUNCTION_SYMBOL,
+                            // We use the addend as a way to specify the
+                            // function index
+                            addend: i.index() as _,
+                        });
+                        // The unwind information is inserted into the dwarf section
+                        (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
+                    } else {
+                        (None, None)
                     }
-                    #[cfg(feature = "unwind")]
-                    other => (other.maybe_into_to_windows_unwind(), None),
-
-                    // This is a bit hacky, but necessary since gimli is not
-                    // available when the "unwind" feature is disabled.
-                    #[cfg(not(feature = "unwind"))]
-                    other => (other.maybe_into_to_windows_unwind(), None::<()>),
-                };
-
-                let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
-
-                Ok((
-                    CompiledFunction {
-                        body: FunctionBody {
-                            body: code_buf,
-                            unwind_info,
-                        },
-                        relocations: func_relocs,
-                        frame_info: CompiledFunctionFrameInfo { address_map, traps },
+                }
+                #[cfg(feature = "unwind")]
+                other => (other.maybe_into_to_windows_unwind(), None),
+
+                // This is a bit hacky, but necessary since gimli is not
+                // available when the "unwind" feature is disabled.
+                #[cfg(not(feature = "unwind"))]
+                other => (other.maybe_into_to_windows_unwind(), None::<()>),
+            };
+
+            let range = reader.range();
+            let address_map = get_function_address_map(&context, range, code_buf.len());
+
+            Ok((
+                CompiledFunction {
+                    body: FunctionBody {
+                        body: code_buf,
+                        unwind_info,
                     },
-                    fde,
-                ))
-            })
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .unzip();
-        #[cfg(feature = "rayon")]
-        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .par_iter()
-            .map_init(FuncTranslator::new, |func_translator, (i, input)| {
-                let func_index = module.func_index(*i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    memory_styles,
-                    table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(
-                    self.config
-                        .middlewares
-                        .generate_function_middleware_chain(*i),
-                );
-
-                func_translator.translate(
-                    module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
-                    *i,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                context
-                    .compile_and_emit(&*isa, &mut code_buf)
-                    .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-                let result = context.mach_compile_result.as_ref().unwrap();
-                let func_relocs = result
-                    .buffer
-                    .relocs()
-                    .iter()
-                    .map(|r| mach_reloc_to_reloc(module, r))
-                    .collect::<Vec<_>>();
-
-                let traps = result
-                    .buffer
-                    .traps()
-                    .iter()
-                    .map(mach_trap_to_trap)
-                    .collect::<Vec<_>>();
-
-                let (unwind_info, fde) = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::Fde(fde) => {
-                        if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
-                        } else {
-                            (None, None)
-                        }
-                    }
-                    #[cfg(feature = "unwind")]
-                    other => (other.maybe_into_to_windows_unwind(), None),
-
-                    // This is a bit hacky, but necessary since gimli is not
-                    // available when the "unwind" feature is disabled.
-                    #[cfg(not(feature = "unwind"))]
-                    other => (other.maybe_into_to_windows_unwind(), None::<()>),
-                };
-
-                let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
-
-                Ok((
-                    CompiledFunction {
-                        body: FunctionBody {
-                            body: code_buf,
-                            unwind_info,
-                        },
-                        relocations: func_relocs,
-                        frame_info: CompiledFunctionFrameInfo { address_map, traps },
+                    relocations: func_relocs,
+                    frame_info: CompiledFunctionFrameInfo {
+                        address_map,
+                        traps,
+                        stack_maps,
                     },
-                    fde,
-                ))
-            })
+                },
+                fde,
+            ))
+        };
+        let function_body_inputs = function_body_inputs
+            .iter()
+            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>();
+        let compiled_functions = match executor {
+            Some(executor) => map_on_executor(executor, function_body_inputs, |(i, input)| {
+                compile_function(&mut FuncTranslator::new(), i, input)
+            }),
+            #[cfg(not(feature = "rayon"))]
+            None => {
+                let mut func_translator = FuncTranslator::new();
+                function_body_inputs
+                    .into_iter()
+                    .map(|(i, input)| compile_function(&mut func_translator, i, input))
+                    .collect::<Vec<_>>()
+            }
+            #[cfg(feature = "rayon")]
+            None => function_body_inputs
+                .par_iter()
+                .map_init(FuncTranslator::new, |func_translator, (i, input)| {
+                    compile_function(func_translator, *i, *input)
+                })
+                .collect::<Vec<_>>(),
+        };
+        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = compiled_functions
+            .into_iter()
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .unzip();
@@ -356,6 +295,12 @@ impl Compiler for CraneliftCompiler {
 
         use wasmer_types::VMOffsets;
         let offsets = VMOffsets::new_for_trampolines(frontend_config.pointer_bytes());
+        let host_call_conv = match target.host_calling_convention() {
+            Some(CallingConvention::SystemV) => CallConv::SystemV,
+            Some(CallingConvention::WindowsFastcall) => CallConv::WindowsFastcall,
+            Some(CallingConvention::AppleAarch64) => CallConv::AppleAarch64,
+            _ => frontend_config.default_call_conv,
+        };
         // dynamic function trampolines (only for imported functions)
         #[cfg(not(feature = "rayon"))]
         let mut cx = FunctionBuilderContext::new();
@@ -364,7 +309,15 @@ impl Compiler for CraneliftCompiler {
             .imported_function_types()
             .collect::<Vec<_>>()
             .into_iter()
-            .map(|func_type| make_trampoline_dynamic_function(&*isa, &offsets, &mut cx, &func_type))
+            .map(|func_type| {
+                make_trampoline_dynamic_function(
+                    &*isa,
+                    &offsets,
+                    &mut cx,
+                    &func_type,
+                    host_call_conv,
+                )
+            })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
@@ -374,7 +327,7 @@ impl Compiler for CraneliftCompiler {
             .collect::<Vec<_>>()
             .par_iter()
             .map_init(FunctionBuilderContext::new, |cx, func_type| {
-                make_trampoline_dynamic_function(&*isa, &offsets, cx, func_type)
+                make_trampoline_dynamic_function(&*isa, &offsets, cx, func_type, host_call_conv)
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
@@ -390,6 +343,82 @@ impl Compiler for CraneliftCompiler {
     }
 }
 
+impl Compiler for CraneliftCompiler {
+    /// Get the middlewares for this compiler
+    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+        &self.config.middlewares
+    }
+
+    fn version_tag(&self) -> String {
+        self.config.version_tag()
+    }
+
+    /// Cranelift always records which stack slots hold references at
+    /// safepoints.
+    fn emits_stack_maps(&self) -> bool {
+        true
+    }
+
+    /// Compiles the module with the optimization level of the hints, if any,
+    /// instead of the configured one.
+    fn compile_module_with_hints(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        hints: &CompileHints,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(
+            target,
+            compile_info,
+            module_translation_state,
+            function_body_inputs,
+            hints,
+            None,
+        )
+    }
+
+    /// Compiles every function of the module as a task of `executor`.
+    fn compile_module_on(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        hints: &CompileHints,
+        executor: &dyn CompileExecutor,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(
+            target,
+            compile_info,
+            module_translation_state,
+            function_body_inputs,
+            hints,
+            Some(executor),
+        )
+    }
+
+    /// Compile the module using Cranelift, producing a compilation result with
+    /// associated relocations.
+    fn compile_module(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(
+            target,
+            compile_info,
+            module_translation_state,
+            function_body_inputs,
+            &CompileHints::default(),
+            None,
+        )
+    }
+}
+
 fn mach_reloc_to_reloc(module: &ModuleInfo, reloc: &MachReloc) -> Relocation {
     let &MachReloc {
         offset,
@@ -425,6 +454,28 @@ fn mach_trap_to_trap(trap: &MachTrap) -> TrapInformation {
     }
 }
 
+fn mach_stack_maps_to_stack_maps(stack_maps: &[MachStackMap]) -> Vec<StackMap> {
+    let mut stack_maps = stack_maps
+        .iter()
+        .map(|stack_map| {
+            let mapped_words = stack_map.stack_map.mapped_words();
+            let live_slots = (0..mapped_words)
+                .filter(|slot| {
+                    let bits = stack_map.stack_map.as_slice()[*slot as usize / 32].0;
+                    bits & (1 << (slot % 32)) != 0
+                })
+                .collect();
+            StackMap {
+                code_offset: stack_map.offset_end,
+                mapped_words,
+                live_slots,
+            }
+        })
+        .collect::<Vec<_>>();
+    stack_maps.sort_unstable_by_key(|stack_map| stack_map.code_offset);
+    stack_maps
+}
+
 /// Translates the Cranelift IR TrapCode into generic Trap Code
 fn translate_ir_trapcode(trap: ir::TrapCode) -> TrapCode {
     match trap {
@@ -4,7 +4,7 @@ use cranelift_codegen::settings::{self, Configurable};
 use cranelift_codegen::CodegenResult;
 use std::sync::Arc;
 use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
-use wasmer_types::{Architecture, CpuFeature, Target};
+use wasmer_types::{Architecture, CpuFeature, OptLevel, Target};
 
 // Runtime Environment
 
@@ -22,6 +22,16 @@ pub enum CraneliftOptLevel {
     SpeedAndSize,
 }
 
+impl From<OptLevel> for CraneliftOptLevel {
+    fn from(opt_level: OptLevel) -> Self {
+        match opt_level {
+            OptLevel::None => Self::None,
+            OptLevel::Speed => Self::Speed,
+            OptLevel::SpeedAndSize => Self::SpeedAndSize,
+        }
+    }
+}
+
 /// Global configuration options used to create an
 /// `wasmer_engine::Engine` and customize its behavior.
 ///
@@ -179,6 +189,18 @@ impl Cranelift {
 
         settings::Flags::new(flags)
     }
+
+    /// Identifies this version of Cranelift and the settings changing the
+    /// code it generates.
+    pub(crate) fn version_tag(&self) -> String {
+        format!(
+            "cranelift-{} opt_level={:?} nan_canonicalization={} pic={}",
+            env!("CARGO_PKG_VERSION"),
+            self.opt_level,
+            self.enable_nan_canonicalization,
+            self.enable_pic,
+        )
+    }
 }
 
 impl CompilerConfig for Cranelift {
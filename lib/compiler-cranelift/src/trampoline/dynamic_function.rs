@@ -8,7 +8,7 @@ use cranelift_codegen::ir;
 use cranelift_codegen::ir::{
     ExternalName, Function, InstBuilder, MemFlags, StackSlotData, StackSlotKind,
 };
-use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::isa::{CallConv, TargetIsa};
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::Context;
 use std::cmp;
@@ -18,16 +18,20 @@ use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use wasmer_types::{CompileError, FunctionBody, FunctionType, VMOffsets};
 
 /// Create a trampoline for invoking a WebAssembly function.
+///
+/// The host function is called with the `host_call_conv` calling
+/// convention.
 pub fn make_trampoline_dynamic_function(
     isa: &dyn TargetIsa,
     offsets: &VMOffsets,
     fn_builder_ctx: &mut FunctionBuilderContext,
     func_type: &FunctionType,
+    host_call_conv: CallConv,
 ) -> Result<FunctionBody, CompileError> {
     let pointer_type = isa.pointer_type();
     let frontend_config = isa.frontend_config();
     let signature = signature_to_cranelift_ir(func_type, frontend_config);
-    let mut stub_sig = ir::Signature::new(frontend_config.default_call_conv);
+    let mut stub_sig = ir::Signature::new(host_call_conv);
     // Add the caller `vmctx` parameter.
     stub_sig.params.push(ir::AbiParam::special(
         pointer_type,
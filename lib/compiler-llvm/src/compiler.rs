@@ -10,12 +10,15 @@ use inkwell::DLLStorageClass;
 use rayon::iter::ParallelBridge;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, FunctionBodyData, ModuleMiddleware, ModuleTranslationState};
+use wasmer_compiler::{
+    map_on_executor, CompileExecutor, Compiler, FunctionBodyData, ModuleMiddleware,
+    ModuleTranslationState,
+};
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
-    Compilation, CompileError, CompileModuleInfo, CustomSection, CustomSectionProtection, Dwarf,
-    FunctionIndex, LocalFunctionIndex, RelocationTarget, SectionBody, SectionIndex, SignatureIndex,
-    Symbol, SymbolRegistry, Target,
+    Compilation, CompileError, CompileHints, CompileModuleInfo, CustomSection,
+    CustomSectionProtection, Dwarf, FunctionIndex, LocalFunctionIndex, RelocationTarget,
+    SectionBody, SectionIndex, SignatureIndex, Symbol, SymbolRegistry, Target,
 };
 
 //use std::sync::Mutex;
@@ -36,6 +39,181 @@ impl LLVMCompiler {
     fn config(&self) -> &LLVM {
         &self.config
     }
+
+    /// Compiles the module, compiling the functions on `executor` if any.
+    fn compile_functions_on<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        executor: Option<&dyn CompileExecutor>,
+    ) -> Result<Compilation, CompileError> {
+        if target.host_calling_convention() != target.triple().default_calling_convention().ok() {
+            return Err(CompileError::UnsupportedTarget(
+                "LLVM only calls host functions with the default calling convention".to_string(),
+            ));
+        }
+        //let data = Arc::new(Mutex::new(0));
+        let memory_styles = &compile_info.memory_styles;
+        let table_styles = &compile_info.table_styles;
+
+        let module = &compile_info.module;
+
+        // TODO: merge constants in sections.
+
+        let mut module_custom_sections = PrimaryMap::new();
+        let mut frame_section_bytes = vec![];
+        let mut frame_section_relocations = vec![];
+        let new_func_translator = || {
+            let target_machine = self.config().target_machine(target);
+            FuncTranslator::new(target_machine)
+        };
+        let compile_function = |func_translator: &mut FuncTranslator,
+                                i: &LocalFunctionIndex,
+                                input: &FunctionBodyData<'_>| {
+            // TODO: remove (to serialize)
+            //let _data = data.lock().unwrap();
+            func_translator
+                .translate(
+                    module,
+                    module_translation,
+                    i,
+                    input,
+                    self.config(),
+                    memory_styles,
+                    table_styles,
+                    &ShortNames {},
+                )
+                .map_err(|error| error.in_function(*i))
+        };
+        let function_body_inputs = function_body_inputs
+            .iter()
+            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>();
+        let compiled_functions = match executor {
+            Some(executor) => map_on_executor(executor, function_body_inputs, |(i, input)| {
+                compile_function(&mut new_func_translator(), &i, input)
+            }),
+            None => function_body_inputs
+                .par_iter()
+                .map_init(new_func_translator, |func_translator, (i, input)| {
+                    compile_function(func_translator, i, *input)
+                })
+                .collect::<Vec<_>>(),
+        };
+        let functions = compiled_functions
+            .into_iter()
+            .collect::<Result<Vec<_>, CompileError>>()?
+            .into_iter()
+            .map(|mut compiled_function| {
+                let first_section = module_custom_sections.len() as u32;
+                for (section_index, custom_section) in compiled_function.custom_sections.iter() {
+                    // TODO: remove this call to clone()
+                    let mut custom_section = custom_section.clone();
+                    for mut reloc in &mut custom_section.relocations {
+                        if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
+                            reloc.reloc_target = RelocationTarget::CustomSection(
+                                SectionIndex::from_u32(first_section + index.as_u32()),
+                            )
+                        }
+                    }
+                    if compiled_function
+                        .eh_frame_section_indices
+                        .contains(&section_index)
+                    {
+                        let offset = frame_section_bytes.len() as u32;
+                        for mut reloc in &mut custom_section.relocations {
+                            reloc.offset += offset;
+                        }
+                        frame_section_bytes.extend_from_slice(custom_section.bytes.as_slice());
+                        frame_section_relocations.extend(custom_section.relocations);
+                        // TODO: we do this to keep the count right, remove it.
+                        module_custom_sections.push(CustomSection {
+                            protection: CustomSectionProtection::Read,
+                            bytes: SectionBody::new_with_vec(vec![]),
+                            relocations: vec![],
+                        });
+                    } else {
+                        module_custom_sections.push(custom_section);
+                    }
+                }
+                for mut reloc in &mut compiled_function.compiled_function.relocations {
+                    if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
+                        reloc.reloc_target = RelocationTarget::CustomSection(
+                            SectionIndex::from_u32(first_section + index.as_u32()),
+                        )
+                    }
+                }
+                compiled_function.compiled_function
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+        let dwarf = if !frame_section_bytes.is_empty() {
+            let dwarf = Some(Dwarf::new(SectionIndex::from_u32(
+                module_custom_sections.len() as u32,
+            )));
+            // Terminating zero-length CIE.
+            frame_section_bytes.extend(vec![
+                0x00, 0x00, 0x00, 0x00, // Length
+                0x00, 0x00, 0x00, 0x00, // CIE ID
+                0x10, // Version (must be 1)
+                0x00, // Augmentation data
+                0x00, // Code alignment factor
+                0x00, // Data alignment factor
+                0x00, // Return address register
+                0x00, 0x00, 0x00, // Padding to a multiple of 4 bytes
+            ]);
+            module_custom_sections.push(CustomSection {
+                protection: CustomSectionProtection::Read,
+                bytes: SectionBody::new_with_vec(frame_section_bytes),
+                relocations: frame_section_relocations,
+            });
+            dwarf
+        } else {
+            None
+        };
+
+        let function_call_trampolines = module
+            .signatures
+            .values()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map_init(
+                || {
+                    let target_machine = self.config().target_machine(target);
+                    FuncTrampoline::new(target_machine)
+                },
+                |func_trampoline, sig| func_trampoline.trampoline(sig, self.config(), ""),
+            )
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<Result<PrimaryMap<_, _>, CompileError>>()?;
+
+        let dynamic_function_trampolines = module
+            .imported_function_types()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map_init(
+                || {
+                    let target_machine = self.config().target_machine(target);
+                    FuncTrampoline::new(target_machine)
+                },
+                |func_trampoline, func_type| {
+                    func_trampoline.dynamic_trampoline(func_type, self.config(), "")
+                },
+            )
+            .collect::<Result<Vec<_>, CompileError>>()?
+            .into_iter()
+            .collect::<PrimaryMap<_, _>>();
+
+        Ok(Compilation::new(
+            functions,
+            module_custom_sections,
+            function_call_trampolines,
+            dynamic_function_trampolines,
+            dwarf,
+        ))
+    }
 }
 
 struct ShortNames {}
@@ -192,6 +370,10 @@ impl Compiler for LLVMCompiler {
         &self.config.middlewares
     }
 
+    fn version_tag(&self) -> String {
+        self.config.version_tag()
+    }
+
     fn experimental_native_compile_module<'data, 'module>(
         &self,
         target: &Target,
@@ -222,150 +404,31 @@ impl Compiler for LLVMCompiler {
         module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError> {
-        //let data = Arc::new(Mutex::new(0));
-        let memory_styles = &compile_info.memory_styles;
-        let table_styles = &compile_info.table_styles;
-
-        let module = &compile_info.module;
-
-        // TODO: merge constants in sections.
-
-        let mut module_custom_sections = PrimaryMap::new();
-        let mut frame_section_bytes = vec![];
-        let mut frame_section_relocations = vec![];
-        let functions = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .par_iter()
-            .map_init(
-                || {
-                    let target_machine = self.config().target_machine(target);
-                    FuncTranslator::new(target_machine)
-                },
-                |func_translator, (i, input)| {
-                    // TODO: remove (to serialize)
-                    //let _data = data.lock().unwrap();
-                    func_translator.translate(
-                        module,
-                        module_translation,
-                        i,
-                        input,
-                        self.config(),
-                        memory_styles,
-                        table_styles,
-                        &ShortNames {},
-                    )
-                },
-            )
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .map(|mut compiled_function| {
-                let first_section = module_custom_sections.len() as u32;
-                for (section_index, custom_section) in compiled_function.custom_sections.iter() {
-                    // TODO: remove this call to clone()
-                    let mut custom_section = custom_section.clone();
-                    for mut reloc in &mut custom_section.relocations {
-                        if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
-                            reloc.reloc_target = RelocationTarget::CustomSection(
-                                SectionIndex::from_u32(first_section + index.as_u32()),
-                            )
-                        }
-                    }
-                    if compiled_function
-                        .eh_frame_section_indices
-                        .contains(&section_index)
-                    {
-                        let offset = frame_section_bytes.len() as u32;
-                        for mut reloc in &mut custom_section.relocations {
-                            reloc.offset += offset;
-                        }
-                        frame_section_bytes.extend_from_slice(custom_section.bytes.as_slice());
-                        frame_section_relocations.extend(custom_section.relocations);
-                        // TODO: we do this to keep the count right, remove it.
-                        module_custom_sections.push(CustomSection {
-                            protection: CustomSectionProtection::Read,
-                            bytes: SectionBody::new_with_vec(vec![]),
-                            relocations: vec![],
-                        });
-                    } else {
-                        module_custom_sections.push(custom_section);
-                    }
-                }
-                for mut reloc in &mut compiled_function.compiled_function.relocations {
-                    if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
-                        reloc.reloc_target = RelocationTarget::CustomSection(
-                            SectionIndex::from_u32(first_section + index.as_u32()),
-                        )
-                    }
-                }
-                compiled_function.compiled_function
-            })
-            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
-
-        let dwarf = if !frame_section_bytes.is_empty() {
-            let dwarf = Some(Dwarf::new(SectionIndex::from_u32(
-                module_custom_sections.len() as u32,
-            )));
-            // Terminating zero-length CIE.
-            frame_section_bytes.extend(vec![
-                0x00, 0x00, 0x00, 0x00, // Length
-                0x00, 0x00, 0x00, 0x00, // CIE ID
-                0x10, // Version (must be 1)
-                0x00, // Augmentation data
-                0x00, // Code alignment factor
-                0x00, // Data alignment factor
-                0x00, // Return address register
-                0x00, 0x00, 0x00, // Padding to a multiple of 4 bytes
-            ]);
-            module_custom_sections.push(CustomSection {
-                protection: CustomSectionProtection::Read,
-                bytes: SectionBody::new_with_vec(frame_section_bytes),
-                relocations: frame_section_relocations,
-            });
-            dwarf
-        } else {
-            None
-        };
-
-        let function_call_trampolines = module
-            .signatures
-            .values()
-            .collect::<Vec<_>>()
-            .par_iter()
-            .map_init(
-                || {
-                    let target_machine = self.config().target_machine(target);
-                    FuncTrampoline::new(target_machine)
-                },
-                |func_trampoline, sig| func_trampoline.trampoline(sig, self.config(), ""),
-            )
-            .collect::<Vec<_>>()
-            .into_iter()
-            .collect::<Result<PrimaryMap<_, _>, CompileError>>()?;
-
-        let dynamic_function_trampolines = module
-            .imported_function_types()
-            .collect::<Vec<_>>()
-            .par_iter()
-            .map_init(
-                || {
-                    let target_machine = self.config().target_machine(target);
-                    FuncTrampoline::new(target_machine)
-                },
-                |func_trampoline, func_type| {
-                    func_trampoline.dynamic_trampoline(func_type, self.config(), "")
-                },
-            )
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .collect::<PrimaryMap<_, _>>();
+        self.compile_functions_on(
+            target,
+            compile_info,
+            module_translation,
+            function_body_inputs,
+            None,
+        )
+    }
 
-        Ok(Compilation::new(
-            functions,
-            module_custom_sections,
-            function_call_trampolines,
-            dynamic_function_trampolines,
-            dwarf,
-        ))
+    /// Compiles every function of the module as a task of `executor`.
+    fn compile_module_on<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        _hints: &CompileHints,
+        executor: &dyn CompileExecutor,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(
+            target,
+            compile_info,
+            module_translation,
+            function_body_inputs,
+            Some(executor),
+        )
     }
 }
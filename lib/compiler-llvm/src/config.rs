@@ -188,6 +188,18 @@ impl LLVM {
             )
             .unwrap()
     }
+
+    /// Identifies this version of the LLVM compiler and the settings
+    /// changing the code it generates.
+    pub(crate) fn version_tag(&self) -> String {
+        format!(
+            "llvm-{} opt_level={:?} nan_canonicalization={} pic={}",
+            env!("CARGO_PKG_VERSION"),
+            self.opt_level,
+            self.enable_nan_canonicalization,
+            self.is_pic,
+        )
+    }
 }
 
 impl CompilerConfig for LLVM {
@@ -357,6 +357,7 @@ where
             frame_info: CompiledFunctionFrameInfo {
                 address_map,
                 traps: vec![],
+                stack_maps: vec![],
             },
         },
         custom_sections,
@@ -5974,7 +5974,11 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             CompiledFunction {
                 body: FunctionBody { body, unwind_info },
                 relocations: self.relocations.clone(),
-                frame_info: CompiledFunctionFrameInfo { traps, address_map },
+                frame_info: CompiledFunctionFrameInfo {
+                    traps,
+                    address_map,
+                    stack_maps: vec![],
+                },
             },
             fde,
         ))
@@ -20,8 +20,9 @@ use gimli::write::{EhFrame, FrameTable};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::sync::Arc;
 use wasmer_compiler::{
-    Compiler, CompilerConfig, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader,
-    ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    map_on_executor, CompileExecutor, CompileHints, Compiler, CompilerConfig, FunctionBinaryReader,
+    FunctionBodyData, MiddlewareBinaryReader, ModuleMiddleware, ModuleMiddlewareChain,
+    ModuleTranslationState,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
@@ -53,22 +54,14 @@ impl SinglepassCompiler {
     fn config(&self) -> &Singlepass {
         &self.config
     }
-}
-
-impl Compiler for SinglepassCompiler {
-    /// Get the middlewares for this compiler
-    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
-        &self.config.middlewares
-    }
 
-    /// Compile the module using Singlepass, producing a compilation result with
-    /// associated relocations.
-    fn compile_module(
+    /// Compiles the module, compiling the functions on `executor` if any.
+    fn compile_functions_on(
         &self,
         target: &Target,
         compile_info: &CompileModuleInfo,
-        _module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        executor: Option<&dyn CompileExecutor>,
     ) -> Result<Compilation, CompileError> {
         match target.triple().architecture {
             Architecture::X86_64 => {}
@@ -104,6 +97,12 @@ impl Compiler for SinglepassCompiler {
                 ))
             }
         };
+        if target.host_calling_convention() != Some(calling_convention) {
+            return Err(CompileError::UnsupportedTarget(
+                "Singlepass only calls host functions with the default calling convention"
+                    .to_string(),
+            ));
+        }
 
         // Generate the frametable
         #[cfg(feature = "unwind")]
@@ -148,11 +147,8 @@ impl Compiler for SinglepassCompiler {
             .collect::<Vec<_>>()
             .into_iter()
             .collect();
-        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .into_par_iter_if_rayon()
-            .map(|(i, input)| {
+        let compile_function =
+            |(i, input): (LocalFunctionIndex, &FunctionBodyData<'_>)| -> Result<_, CompileError> {
                 let middleware_chain = self
                     .config
                     .middlewares
@@ -185,14 +181,18 @@ impl Compiler for SinglepassCompiler {
                             machine,
                             calling_convention,
                         )
-                        .map_err(to_compile_error)?;
+                        .map_err(|error| to_compile_error(error).in_function(i))?;
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
+                            generator
+                                .feed_operator(op)
+                                .map_err(|error| to_compile_error(error).in_function(i))?;
                         }
 
-                        generator.finalize(input).map_err(to_compile_error)
+                        generator
+                            .finalize(input)
+                            .map_err(|error| to_compile_error(error).in_function(i))
                     }
                     Architecture::Aarch64(_) => {
                         let machine = MachineARM64::new();
@@ -207,18 +207,34 @@ impl Compiler for SinglepassCompiler {
                             machine,
                             calling_convention,
                         )
-                        .map_err(to_compile_error)?;
+                        .map_err(|error| to_compile_error(error).in_function(i))?;
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
+                            generator
+                                .feed_operator(op)
+                                .map_err(|error| to_compile_error(error).in_function(i))?;
                         }
 
-                        generator.finalize(input).map_err(to_compile_error)
+                        generator
+                            .finalize(input)
+                            .map_err(|error| to_compile_error(error).in_function(i))
                     }
                     _ => unimplemented!(),
                 }
-            })
+            };
+        let function_body_inputs = function_body_inputs
+            .iter()
+            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>();
+        let compiled_functions = match executor {
+            Some(executor) => map_on_executor(executor, function_body_inputs, compile_function),
+            None => function_body_inputs
+                .into_par_iter_if_rayon()
+                .map(compile_function)
+                .collect::<Vec<_>>(),
+        };
+        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = compiled_functions
+            .into_iter()
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .unzip();
@@ -278,6 +294,46 @@ impl Compiler for SinglepassCompiler {
     }
 }
 
+impl Compiler for SinglepassCompiler {
+    /// Get the middlewares for this compiler
+    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+        &self.config.middlewares
+    }
+
+    fn version_tag(&self) -> String {
+        format!(
+            "singlepass-{} nan_canonicalization={}",
+            env!("CARGO_PKG_VERSION"),
+            self.config.enable_nan_canonicalization,
+        )
+    }
+
+    /// Compile the module using Singlepass, producing a compilation result with
+    /// associated relocations.
+    fn compile_module(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(target, compile_info, function_body_inputs, None)
+    }
+
+    /// Compiles every function of the module as a task of `executor`.
+    fn compile_module_on(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        _hints: &CompileHints,
+        executor: &dyn CompileExecutor,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_functions_on(target, compile_info, function_body_inputs, Some(executor))
+    }
+}
+
 trait ToCompileError {
     fn to_compile_error(self) -> CompileError;
 }
@@ -3,25 +3,41 @@
 
 #[cfg(feature = "compiler")]
 use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
+#[cfg(feature = "compiler")]
+use crate::translator::{missing_proposals, scan_proposals, used_proposals};
 use crate::ArtifactCreate;
+#[cfg(feature = "compiler")]
+use crate::CompileSettings;
+#[cfg(not(feature = "compiler"))]
+#[cfg(not(target_arch = "wasm32"))]
 use crate::EngineInner;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::{
+    FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader, ModuleTranslationState,
+};
 use crate::{ModuleEnvironment, ModuleMiddlewareChain};
 use enumset::EnumSet;
+use std::io::Write;
 use std::mem;
 use wasmer_types::entity::PrimaryMap;
-#[cfg(feature = "compiler")]
-use wasmer_types::CompileModuleInfo;
 use wasmer_types::MetadataHeader;
 use wasmer_types::SerializeError;
 use wasmer_types::{
-    CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
-    MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
-    TableIndex, TableStyle, Target,
+    ArtifactHash, CompileError, CompileHints, CompileOptions, CpuFeature, CustomSection,
+    DeterminismReport, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex, MemoryStyle,
+    ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex, TableIndex,
+    TableStyle, Target,
 };
+#[cfg(feature = "compiler")]
+use wasmer_types::{CompileModuleInfo, CompiledFunction};
 use wasmer_types::{
     CompiledFunctionFrameInfo, FunctionBody, SerializableCompilation, SerializableModule,
 };
+#[cfg(feature = "compiler")]
+use wasmer_types::{Type, WasmResult};
+#[cfg(feature = "compiler")]
+use wasmparser::Operator;
 
 /// A compiled wasm module, ready to be instantiated.
 pub struct ArtifactBuild {
@@ -40,18 +56,49 @@ impl ArtifactBuild {
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
     #[cfg(feature = "compiler")]
     pub fn new(
-        inner_engine: &mut EngineInner,
+        settings: &CompileSettings,
         data: &[u8],
         target: &Target,
         memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: PrimaryMap<TableIndex, TableStyle>,
+        hints: &CompileHints,
     ) -> Result<Self, CompileError> {
         let environ = ModuleEnvironment::new();
-        let features = inner_engine.features().clone();
+        let features = settings.features().clone();
+
+        // Report a proposal the module needs but the engine doesn't enable
+        // up front, rather than as an obscure failure while compiling.
+        let needed_proposals = scan_proposals(data).map_err(CompileError::Wasm)?;
+        if let Some(feature) = missing_proposals(&needed_proposals, &features)
+            .first()
+            .copied()
+        {
+            return Err(CompileError::DisabledFeature { feature });
+        }
 
         let translation = environ.translate(data).map_err(CompileError::Wasm)?;
 
-        let compiler = inner_engine.compiler()?;
+        if let Some(max_locals) = settings.max_locals_per_function() {
+            check_locals_limit(
+                &translation.module,
+                &translation.function_body_inputs,
+                max_locals,
+            )?;
+        }
+
+        let compiler = settings.compiler();
+        let stack_maps = settings.stack_maps();
+        if stack_maps && !compiler.emits_stack_maps() {
+            return Err(CompileError::UnsupportedFeature(
+                "recording stack maps".to_string(),
+            ));
+        }
+        let compile_target = Self::compile_target(settings, target);
+        let hash =
+            Self::compile_hash(settings, data, target, &memory_styles, &table_styles, hints)?;
+        let used_proposals = used_proposals(&needed_proposals, &features);
+        let determinism =
+            determinism_report(&translation.module, &translation.function_body_inputs)?;
 
         // We try to apply the middleware first
         let mut module = translation.module;
@@ -66,15 +113,26 @@ impl ArtifactBuild {
         };
 
         // Compile the Module
-        let compilation = compiler.compile_module(
-            target,
-            &compile_info,
-            // SAFETY: Calling `unwrap` is correct since
-            // `environ.translate()` above will write some data into
-            // `module_translation_state`.
-            translation.module_translation_state.as_ref().unwrap(),
-            translation.function_body_inputs,
-        )?;
+        // SAFETY: Calling `unwrap` is correct since `environ.translate()`
+        // above will write some data into `module_translation_state`.
+        let module_translation_state = translation.module_translation_state.as_ref().unwrap();
+        let mut compilation = match settings.compile_executor() {
+            Some(executor) => compiler.compile_module_on(
+                &compile_target,
+                &compile_info,
+                module_translation_state,
+                translation.function_body_inputs,
+                hints,
+                &**executor,
+            )?,
+            None => compiler.compile_module_with_hints(
+                &compile_target,
+                &compile_info,
+                module_translation_state,
+                translation.function_body_inputs,
+                hints,
+            )?,
+        };
         let function_call_trampolines = compilation.get_function_call_trampolines();
         let dynamic_function_trampolines = compilation.get_dynamic_function_trampolines();
 
@@ -85,8 +143,6 @@ impl ArtifactBuild {
             .collect::<Vec<_>>()
             .into_boxed_slice();
 
-        let frame_infos = compilation.get_frame_info();
-
         // Synthesize a custom section to hold the libcall trampolines.
         let mut custom_sections = compilation.get_custom_sections();
         let mut custom_section_relocations = compilation.get_custom_section_relocations();
@@ -95,10 +151,25 @@ impl ArtifactBuild {
         let libcall_trampolines = custom_sections.push(libcall_trampolines_section);
         let libcall_trampoline_len = libcall_trampoline_len(target) as u32;
 
+        // Move the function bodies out of the compilation rather than
+        // copying them, so that they are only held once.
+        let functions = compilation.take_functions();
+        let mut function_bodies = PrimaryMap::with_capacity(functions.len());
+        let mut function_relocations = PrimaryMap::with_capacity(functions.len());
+        let mut function_frame_info = PrimaryMap::with_capacity(functions.len());
+        for (_, mut function) in functions {
+            if !stack_maps {
+                function.frame_info.stack_maps = Vec::new();
+            }
+            function_bodies.push(function.body);
+            function_relocations.push(function.relocations);
+            function_frame_info.push(function.frame_info);
+        }
+
         let serializable_compilation = SerializableCompilation {
-            function_bodies: compilation.get_function_bodies(),
-            function_relocations: compilation.get_relocations(),
-            function_frame_info: frame_infos,
+            function_bodies,
+            function_relocations,
+            function_frame_info,
             function_call_trampolines,
             dynamic_function_trampolines,
             custom_sections,
@@ -112,6 +183,11 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: target.cpu_features().as_u64(),
+            used_proposals,
+            compile_options: settings.compile_options(),
+            determinism,
+            target_triple: target.triple().to_string(),
+            hash,
         };
         Ok(Self { serializable })
     }
@@ -125,6 +201,7 @@ impl ArtifactBuild {
         _target: &Target,
         _memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
         _table_styles: PrimaryMap<TableIndex, TableStyle>,
+        _hints: &CompileHints,
     ) -> Result<Self, CompileError> {
         Err(CompileError::Codegen(
             "Compilation is not enabled in the engine".to_string(),
@@ -185,6 +262,268 @@ impl ArtifactBuild {
     pub fn get_frame_info_ref(&self) -> &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo> {
         &self.serializable.compilation.function_frame_info
     }
+
+    /// Get ModuleInfo ref
+    pub fn get_module_info_ref(&self) -> &ModuleInfo {
+        &self.serializable.compile_info.module
+    }
+
+    /// Get the enabled proposals actually used by the module
+    pub fn used_proposals(&self) -> &Features {
+        self.serializable.used_proposals()
+    }
+
+    /// Get the options of the engine the module was compiled with
+    pub fn compile_options(&self) -> CompileOptions {
+        self.serializable.compile_options()
+    }
+
+    /// Get the sources of nondeterminism used by the module
+    pub fn determinism_report(&self) -> DeterminismReport {
+        self.serializable.determinism_report()
+    }
+
+    /// Get the triple of the target the module was compiled for
+    pub fn target_triple(&self) -> &str {
+        self.serializable.target_triple()
+    }
+
+    /// Hashes what determines the code `ArtifactBuild::new` compiles for
+    /// `data` with the same arguments, without compiling it.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn compile_hash(
+        settings: &CompileSettings,
+        data: &[u8],
+        target: &Target,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        hints: &CompileHints,
+    ) -> Result<ArtifactHash, CompileError> {
+        Ok(artifact_hash(
+            data,
+            &settings.compiler().version_tag(),
+            // Debug representations are stable for a given version, which
+            // the version tag of the compiler covers.
+            &format!(
+                "{:?}",
+                (
+                    &Self::compile_target(settings, target),
+                    settings.features(),
+                    memory_styles,
+                    table_styles,
+                    hints,
+                    settings.compile_options(),
+                    settings.stack_maps(),
+                    settings.max_locals_per_function(),
+                )
+            ),
+        ))
+    }
+
+    /// The target to compile for `target` with the calling convention of
+    /// the engine.
+    #[cfg(feature = "compiler")]
+    fn compile_target(settings: &CompileSettings, target: &Target) -> Target {
+        match settings.host_calling_convention() {
+            Some(calling_convention) => target
+                .clone()
+                .with_host_calling_convention(calling_convention),
+            None => target.clone(),
+        }
+    }
+
+    /// Compiles `body` as the body of the local function `index` of
+    /// `module`, with the features and styles the module was compiled
+    /// with.
+    ///
+    /// Returns the function with the custom sections it may refer to, the
+    /// last of which holds the libcall trampolines.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn compile_function(
+        settings: &CompileSettings,
+        artifact: &dyn ArtifactCreate,
+        module: &ModuleInfo,
+        index: LocalFunctionIndex,
+        body: &[u8],
+        target: &Target,
+        hints: &CompileHints,
+    ) -> Result<(CompiledFunction, PrimaryMap<SectionIndex, CustomSection>), CompileError> {
+        // The other functions are compiled as a lone `unreachable`, which
+        // is valid whatever their signature and cheap to compile.
+        const PLACEHOLDER_BODY: &[u8] = &[0x00, 0x00, 0x0b];
+
+        let num_local_functions = module.functions.len() - module.num_imported_functions;
+        if index.index() >= num_local_functions {
+            return Err(CompileError::Codegen(format!(
+                "The module has no local function {}.",
+                index.index()
+            )));
+        }
+        let function_body_inputs = (0..num_local_functions)
+            .map(|other| FunctionBodyData {
+                data: if other == index.index() {
+                    body
+                } else {
+                    PLACEHOLDER_BODY
+                },
+                module_offset: 0,
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+        if let Some(max_locals) = settings.max_locals_per_function() {
+            check_locals_limit(module, &function_body_inputs, max_locals)?;
+        }
+
+        let compile_info = CompileModuleInfo {
+            module: module.clone(),
+            features: artifact.features().clone(),
+            memory_styles: artifact.memory_styles().clone(),
+            table_styles: artifact.table_styles().clone(),
+        };
+        let mut compilation = settings.compiler().compile_module_with_hints(
+            &Self::compile_target(settings, target),
+            &compile_info,
+            &ModuleTranslationState::from_module_info(module),
+            function_body_inputs,
+            hints,
+        )?;
+        let mut custom_sections = compilation.get_custom_sections();
+        custom_sections.push(make_libcall_trampolines(target));
+        let (_, function) = compilation
+            .take_functions()
+            .into_iter()
+            .nth(index.index())
+            .unwrap();
+        Ok((function, custom_sections))
+    }
+
+    /// Get the hash of what determined the code of the module
+    pub fn hash(&self) -> ArtifactHash {
+        self.serializable.hash()
+    }
+}
+
+/// Hashes a binary with the description of how it is compiled, each
+/// prefixed with its length so that they can't run into each other.
+#[cfg(any(feature = "compiler", feature = "static-artifact-load"))]
+pub(crate) fn artifact_hash(data: &[u8], version_tag: &str, options: &str) -> ArtifactHash {
+    let mut hasher = blake3::Hasher::new();
+    for part in [data, version_tag.as_bytes(), options.as_bytes()] {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    ArtifactHash::new(hasher.finalize().into())
+}
+
+/// Finds the sources of nondeterminism used by a module.
+///
+/// A module can only use floats or vectors if some type, local or
+/// instruction producing one mentions them: every other instruction on
+/// them needs one as an operand.
+#[cfg(feature = "compiler")]
+fn determinism_report(
+    module: &ModuleInfo,
+    function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+) -> Result<DeterminismReport, CompileError> {
+    let mut report = DeterminismReport {
+        threads: module.memories.values().any(|memory| memory.shared),
+        ..Default::default()
+    };
+    let types = module
+        .signatures
+        .values()
+        .flat_map(|signature| signature.params().iter().chain(signature.results()))
+        .chain(module.globals.values().map(|global| &global.ty));
+    for ty in types {
+        report.floats |= matches!(ty, Type::F32 | Type::F64);
+        report.simd |= matches!(ty, Type::V128);
+    }
+
+    for (index, body) in function_body_inputs.iter() {
+        if report.floats && report.simd {
+            break;
+        }
+        scan_function_body(body, &mut report)
+            .map_err(|error| CompileError::from(error).in_function(index))?;
+    }
+    Ok(report)
+}
+
+/// Records the sources of nondeterminism used by a function into `report`.
+#[cfg(feature = "compiler")]
+fn scan_function_body(body: &FunctionBodyData, report: &mut DeterminismReport) -> WasmResult<()> {
+    let mut reader = MiddlewareBinaryReader::new_with_offset(body.data, body.module_offset);
+    for _ in 0..reader.read_local_count()? {
+        let (_, ty) = reader.read_local_decl()?;
+        report.floats |= matches!(ty, wasmparser::Type::F32 | wasmparser::Type::F64);
+        report.simd |= matches!(ty, wasmparser::Type::V128);
+    }
+    while !reader.eof() {
+        match reader.read_operator()? {
+            Operator::F32Const { .. }
+            | Operator::F64Const { .. }
+            | Operator::F32Load { .. }
+            | Operator::F64Load { .. }
+            | Operator::F32ConvertI32S
+            | Operator::F32ConvertI32U
+            | Operator::F32ConvertI64S
+            | Operator::F32ConvertI64U
+            | Operator::F64ConvertI32S
+            | Operator::F64ConvertI32U
+            | Operator::F64ConvertI64S
+            | Operator::F64ConvertI64U
+            | Operator::F32ReinterpretI32
+            | Operator::F64ReinterpretI64
+            | Operator::F32x4ExtractLane { .. }
+            | Operator::F64x2ExtractLane { .. } => report.floats = true,
+            Operator::V128Const { .. }
+            | Operator::V128Load { .. }
+            | Operator::V128Load8x8S { .. }
+            | Operator::V128Load8x8U { .. }
+            | Operator::V128Load16x4S { .. }
+            | Operator::V128Load16x4U { .. }
+            | Operator::V128Load32x2S { .. }
+            | Operator::V128Load32x2U { .. }
+            | Operator::V128Load8Splat { .. }
+            | Operator::V128Load16Splat { .. }
+            | Operator::V128Load32Splat { .. }
+            | Operator::V128Load64Splat { .. }
+            | Operator::V128Load32Zero { .. }
+            | Operator::V128Load64Zero { .. }
+            | Operator::I8x16Splat
+            | Operator::I16x8Splat
+            | Operator::I32x4Splat
+            | Operator::I64x2Splat
+            | Operator::F32x4Splat
+            | Operator::F64x2Splat => report.simd = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no function declares more than `max_locals` locals.
+#[cfg(feature = "compiler")]
+fn check_locals_limit(
+    module: &ModuleInfo,
+    function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+    max_locals: u32,
+) -> Result<(), CompileError> {
+    for (local_index, body) in function_body_inputs.iter() {
+        let mut reader = MiddlewareBinaryReader::new_with_offset(body.data, body.module_offset);
+        let mut count: u32 = 0;
+        let in_function = |error| CompileError::from(error).in_function(local_index);
+        for _ in 0..reader.read_local_count().map_err(in_function)? {
+            let (declared, _ty) = reader.read_local_decl().map_err(in_function)?;
+            count = count.saturating_add(declared);
+        }
+        if count > max_locals {
+            return Err(CompileError::TooManyLocals {
+                function: module.func_index(local_index),
+                count,
+            });
+        }
+    }
+    Ok(())
 }
 
 impl ArtifactCreate for ArtifactBuild {
@@ -213,7 +552,52 @@ impl ArtifactCreate for ArtifactBuild {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
-        let serialized_data = self.serializable.serialize()?;
+        Self::serialize_module(&self.serializable)
+    }
+
+    fn serialize_to(&self, writer: &mut dyn Write) -> Result<(), SerializeError> {
+        Self::serialize_module_to(&self.serializable, writer)
+    }
+}
+
+impl ArtifactBuild {
+    /// Serializes the artifact with the contents of its custom section
+    /// `name` replaced by `data`, adding the section if it is missing.
+    pub fn serialize_with_custom_section(
+        &self,
+        name: &str,
+        data: Box<[u8]>,
+    ) -> Result<Vec<u8>, SerializeError> {
+        Self::serialize_module(&self.with_custom_section(name, data))
+    }
+
+    /// Like [`ArtifactBuild::serialize_with_custom_section`], but writes
+    /// the serialized artifact into `writer`.
+    pub fn serialize_with_custom_section_to(
+        &self,
+        name: &str,
+        data: Box<[u8]>,
+        writer: &mut dyn Write,
+    ) -> Result<(), SerializeError> {
+        Self::serialize_module_to(&self.with_custom_section(name, data), writer)
+    }
+
+    fn with_custom_section(&self, name: &str, data: Box<[u8]>) -> SerializableModule {
+        let mut serializable = self.serializable.clone();
+        let module = &mut serializable.compile_info.module;
+        match module.custom_sections.get(name).copied() {
+            Some(index) => module.custom_sections_data[index] = data,
+            None => {
+                let index = module.custom_sections_data.push(data);
+                module.custom_section_names.push(name.to_string());
+                module.custom_sections.insert(name.to_string(), index);
+            }
+        }
+        serializable
+    }
+
+    fn serialize_module(serializable: &SerializableModule) -> Result<Vec<u8>, SerializeError> {
+        let serialized_data = serializable.serialize()?;
         assert!(mem::align_of::<SerializableModule>() <= MetadataHeader::ALIGN);
 
         let mut metadata_binary = vec![];
@@ -222,4 +606,17 @@ impl ArtifactCreate for ArtifactBuild {
         metadata_binary.extend(serialized_data);
         Ok(metadata_binary)
     }
+
+    fn serialize_module_to(
+        serializable: &SerializableModule,
+        writer: &mut dyn Write,
+    ) -> Result<(), SerializeError> {
+        assert!(mem::align_of::<SerializableModule>() <= MetadataHeader::ALIGN);
+        // The header holds the length of the metadata, so it has to be
+        // computed before streaming the metadata itself.
+        let len = serializable.serialized_len()?;
+        writer.write_all(Self::MAGIC_HEADER)?;
+        writer.write_all(&MetadataHeader::new(len).into_bytes())?;
+        serializable.serialize_to(writer)
+    }
 }
@@ -3,5 +3,7 @@
 mod artifact_builder;
 mod trampoline;
 
+#[cfg(any(feature = "compiler", feature = "static-artifact-load"))]
+pub(crate) use self::artifact_builder::artifact_hash;
 pub use self::artifact_builder::ArtifactBuild;
 pub use self::trampoline::*;
@@ -2,6 +2,7 @@
 //! compilers will need to implement.
 
 use crate::lib::std::boxed::Box;
+use crate::lib::std::string::String;
 use crate::lib::std::sync::Arc;
 use crate::translator::ModuleMiddleware;
 use crate::FunctionBodyData;
@@ -12,7 +13,7 @@ use wasmer_types::compilation::symbols::SymbolRegistry;
 use wasmer_types::compilation::target::Target;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::error::CompileError;
-use wasmer_types::{Features, LocalFunctionIndex};
+use wasmer_types::{CompileHints, Features, LocalFunctionIndex};
 use wasmparser::{Validator, WasmFeatures};
 
 /// The compiler configuration options.
@@ -76,8 +77,47 @@ where
     }
 }
 
+/// Runs the tasks a compiler splits the compilation of a module into, such
+/// as compiling every function, on threads chosen by the embedder.
+///
+/// See `Engine::set_compile_executor`.
+pub trait CompileExecutor: Send + Sync {
+    /// Runs all the `tasks`, returning once every one of them completed.
+    ///
+    /// The tasks are independent: they may run in any order, and
+    /// concurrently, including on the calling thread.
+    fn scope<'a>(&self, tasks: Vec<Box<dyn FnOnce() + Send + 'a>>);
+}
+
+/// Applies `f` to every item as a task of `executor`, returning the results
+/// in the order of the items.
+///
+/// # Panics
+///
+/// Panics if the executor returns without running every task.
+pub fn map_on_executor<T: Send, U: Send>(
+    executor: &dyn CompileExecutor,
+    items: Vec<T>,
+    f: impl Fn(T) -> U + Sync,
+) -> Vec<U> {
+    let mut results = items.iter().map(|_| None).collect::<Vec<Option<U>>>();
+    let f = &f;
+    let tasks = results
+        .iter_mut()
+        .zip(items)
+        .map(|(result, item)| {
+            Box::new(move || *result = Some(f(item))) as Box<dyn FnOnce() + Send + '_>
+        })
+        .collect();
+    executor.scope(tasks);
+    results
+        .into_iter()
+        .map(|result| result.expect("the compile executor didn't run every task"))
+        .collect()
+}
+
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
-pub trait Compiler: Send {
+pub trait Compiler: Send + Sync {
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -112,6 +152,27 @@ pub trait Compiler: Send {
         Ok(())
     }
 
+    /// Whether this compiler records the stack maps of the functions it
+    /// compiles, see [`CompiledFunctionFrameInfo::stack_maps`](wasmer_types::CompiledFunctionFrameInfo::stack_maps).
+    ///
+    /// This is required by [`Engine::with_stack_maps`](crate::Engine::with_stack_maps).
+    fn emits_stack_maps(&self) -> bool {
+        false
+    }
+
+    /// Identifies the compiler, with its version and the settings that
+    /// change the code it generates, which goes into [`ArtifactHash`].
+    ///
+    /// Middlewares aren't identified, so engines with different middlewares
+    /// must not share cached artifacts.
+    ///
+    /// Defaults to the name of the type of the compiler, which tells
+    /// compilers apart but not their versions or settings, so compilers
+    /// should override it.
+    fn version_tag(&self) -> String {
+        String::from(core::any::type_name::<Self>())
+    }
+
     /// Compiles a parsed module.
     ///
     /// It returns the [`Compilation`] or a [`CompileError`].
@@ -124,6 +185,48 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Compiles a parsed module, following the given hints where the
+    /// compiler supports them.
+    ///
+    /// By default the hints are ignored and the module is compiled with
+    /// [`Compiler::compile_module`].
+    fn compile_module_with_hints<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        // The list of function bodies
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        _hints: &CompileHints,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module(target, module, module_translation, function_body_inputs)
+    }
+
+    /// Compiles a parsed module like
+    /// [`Compiler::compile_module_with_hints`], running the work it splits
+    /// the compilation into on `executor`.
+    ///
+    /// By default the executor isn't used, and the module is compiled as
+    /// with [`Compiler::compile_module_with_hints`].
+    fn compile_module_on<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        // The list of function bodies
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        hints: &CompileHints,
+        _executor: &dyn CompileExecutor,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module_with_hints(
+            target,
+            module,
+            module_translation,
+            function_body_inputs,
+            hints,
+        )
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -0,0 +1,130 @@
+//! Tracking of the guest executions running on an `Engine`, so that it can
+//! be drained before shutting down, and of the floating-point state they
+//! run with.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use wasmer_vm::{restore_float_control, set_rounding_mode, RoundingMode, SavedFloatControl};
+
+/// The value of `EngineActivity::rounding_mode` when no mode is set.
+const NO_ROUNDING_MODE: u8 = u8::MAX;
+
+/// The guest executions of an `Engine`, whether it is draining, and the
+/// floating-point rounding mode they run with.
+pub(crate) struct EngineActivity {
+    draining: AtomicBool,
+    rounding_mode: AtomicU8,
+    /// The executions in flight. Entering and leaving only touch this
+    /// counter, the lock is only taken to wake those waiting for it to
+    /// reach zero.
+    executions: AtomicUsize,
+    idle_wakers: Mutex<Vec<Waker>>,
+    idle: Condvar,
+}
+
+impl Default for EngineActivity {
+    fn default() -> Self {
+        Self {
+            draining: AtomicBool::new(false),
+            rounding_mode: AtomicU8::new(NO_ROUNDING_MODE),
+            executions: AtomicUsize::new(0),
+            idle_wakers: Mutex::default(),
+            idle: Condvar::new(),
+        }
+    }
+}
+
+impl EngineActivity {
+    pub(crate) fn begin_drain(&self) {
+        self.draining.store(true, SeqCst);
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(SeqCst)
+    }
+
+    pub(crate) fn set_rounding_mode(&self, mode: RoundingMode) {
+        self.rounding_mode.store(mode as u8, SeqCst);
+    }
+
+    fn rounding_mode(&self) -> Option<RoundingMode> {
+        match self.rounding_mode.load(SeqCst) {
+            NO_ROUNDING_MODE => None,
+            mode if mode == RoundingMode::ToNearest as u8 => Some(RoundingMode::ToNearest),
+            mode if mode == RoundingMode::TowardZero as u8 => Some(RoundingMode::TowardZero),
+            mode if mode == RoundingMode::Upward as u8 => Some(RoundingMode::Upward),
+            _ => Some(RoundingMode::Downward),
+        }
+    }
+
+    pub(crate) fn enter(self: &Arc<Self>) -> ExecutionGuard {
+        self.executions.fetch_add(1, SeqCst);
+        ExecutionGuard {
+            activity: self.clone(),
+            saved_float_control: self.rounding_mode().and_then(set_rounding_mode),
+        }
+    }
+
+    pub(crate) fn wait_idle(&self) {
+        let idle_wakers = self.idle_wakers.lock().unwrap();
+        let _idle_wakers = self
+            .idle
+            .wait_while(idle_wakers, |_| self.executions.load(SeqCst) > 0)
+            .unwrap();
+    }
+
+    fn leave(&self) {
+        if self.executions.fetch_sub(1, SeqCst) == 1 {
+            // Those waiting check the counter under the lock, so taking it
+            // after the counter reached zero can't miss any of them.
+            let mut idle_wakers = self.idle_wakers.lock().unwrap();
+            self.idle.notify_all();
+            for waker in idle_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Marks a guest execution as in flight on an `Engine` for as long as it
+/// is alive, and applies the rounding mode of the `Engine` meanwhile.
+///
+/// See [`Engine::enter_execution`](crate::Engine::enter_execution).
+pub struct ExecutionGuard {
+    activity: Arc<EngineActivity>,
+    saved_float_control: Option<SavedFloatControl>,
+}
+
+impl Drop for ExecutionGuard {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved_float_control.take() {
+            restore_float_control(saved);
+        }
+        self.activity.leave();
+    }
+}
+
+/// A future that resolves once no guest execution is in flight on an
+/// `Engine`.
+///
+/// See [`Engine::await_idle`](crate::Engine::await_idle).
+pub struct IdleFuture {
+    pub(crate) activity: Arc<EngineActivity>,
+}
+
+impl Future for IdleFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut idle_wakers = self.activity.idle_wakers.lock().unwrap();
+        if self.activity.executions.load(SeqCst) == 0 {
+            Poll::Ready(())
+        } else {
+            idle_wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
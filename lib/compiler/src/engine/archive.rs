@@ -0,0 +1,72 @@
+//! The format of archives holding several serialized artifacts.
+//!
+//! An archive starts with [`ARCHIVE_MAGIC_HEADER`] and the number of
+//! artifacts, followed by each serialized artifact prefixed with its
+//! length. Numbers are 64-bit little-endian.
+
+use std::convert::{TryFrom, TryInto};
+use wasmer_types::DeserializeError;
+
+/// Header signature for archives of artifacts.
+pub(crate) const ARCHIVE_MAGIC_HEADER: &[u8; 16] = b"wasmer-archive\0\0";
+
+/// Writes the given serialized artifacts into an archive.
+pub(crate) fn write_archive(artifacts: &[Vec<u8>]) -> Vec<u8> {
+    let len = artifacts
+        .iter()
+        .map(|artifact| 8 + artifact.len())
+        .sum::<usize>();
+    let mut archive = Vec::with_capacity(ARCHIVE_MAGIC_HEADER.len() + 8 + len);
+    archive.extend_from_slice(ARCHIVE_MAGIC_HEADER);
+    archive.extend_from_slice(&(artifacts.len() as u64).to_le_bytes());
+    for artifact in artifacts {
+        archive.extend_from_slice(&(artifact.len() as u64).to_le_bytes());
+        archive.extend_from_slice(artifact);
+    }
+    archive
+}
+
+/// Splits an archive into the serialized artifacts it holds.
+pub(crate) fn read_archive(bytes: &[u8]) -> Result<Vec<&[u8]>, DeserializeError> {
+    let mut rest = bytes
+        .strip_prefix(&ARCHIVE_MAGIC_HEADER[..])
+        .ok_or_else(|| {
+            DeserializeError::Incompatible("The provided bytes are not an archive".to_string())
+        })?;
+    let count = read_u64(&mut rest)?;
+    let mut artifacts = Vec::new();
+    for _ in 0..count {
+        let len = read_u64(&mut rest)?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= rest.len())
+            .ok_or_else(|| {
+                DeserializeError::CorruptedBinary(format!(
+                    "An artifact of {} bytes overflows the archive",
+                    len
+                ))
+            })?;
+        let (artifact, remaining) = rest.split_at(len);
+        artifacts.push(artifact);
+        rest = remaining;
+    }
+    if !rest.is_empty() {
+        return Err(DeserializeError::CorruptedBinary(format!(
+            "The archive has {} trailing bytes",
+            rest.len()
+        )));
+    }
+    Ok(artifacts)
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Result<u64, DeserializeError> {
+    if bytes.len() < 8 {
+        return Err(DeserializeError::InvalidByteLength {
+            expected: 8,
+            got: bytes.len(),
+        });
+    }
+    let (value, rest) = bytes.split_at(8);
+    *bytes = rest;
+    Ok(u64::from_le_bytes(value.try_into().unwrap()))
+}
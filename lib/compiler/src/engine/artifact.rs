@@ -1,9 +1,16 @@
 //! Define `Artifact`, based on `ArtifactBuild`
 //! to allow compiling and instantiating to be done as separate steps.
 
+use crate::engine::inner::{CodeBytesCharge, SignatureRegistration};
 use crate::engine::link::link_module;
+use crate::engine::mapped_image::{
+    append_image, build_image, find_image, map_image, IMAGE_ALIGNMENT,
+};
+use crate::translator::{DylinkInfo, DYLINK_SECTION_NAME, LEGACY_DYLINK_SECTION_NAME};
 use crate::ArtifactBuild;
 use crate::ArtifactCreate;
+#[cfg(feature = "compiler")]
+use crate::CompileSettings;
 use crate::Features;
 use crate::ModuleEnvironment;
 use crate::{
@@ -12,10 +19,16 @@ use crate::{
 };
 #[cfg(feature = "static-artifact-create")]
 use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
-use crate::{Engine, EngineInner};
+use crate::{Engine, EngineId, EngineInner};
 use enumset::EnumSet;
+use memmap2::Mmap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use std::mem;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 #[cfg(feature = "static-artifact-create")]
@@ -23,18 +36,24 @@ use wasmer_object::{emit_compilation, emit_data, get_object_for_target, Object};
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use wasmer_types::compilation::symbols::ModuleMetadata;
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
+#[cfg(feature = "static-artifact-create")]
+use wasmer_types::CompileModuleInfo;
 use wasmer_types::MetadataHeader;
 #[cfg(feature = "static-artifact-load")]
 use wasmer_types::SerializableCompilation;
 use wasmer_types::{
-    CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, ModuleInfo, OwnedDataInitializer, SerializableModule, SerializeError,
-    SignatureIndex, TableIndex,
+    ArtifactHash, ArtifactMetadata, CompileError, CompileOptions, CpuFeature, DataInitializer,
+    DeserializeError, DeterminismReport, ExportIndex, FunctionIndex, FunctionType, GlobalType,
+    ImportIndex, LibCall, LocalFunctionIndex, MemoryIndex, ModuleInfo, OwnedDataInitializer,
+    RelocationTarget, SectionIndex, SerializableModule, SerializeError, SignatureIndex, StackMap,
+    TableIndex, TagType, TrapInformation, Type,
 };
-#[cfg(feature = "static-artifact-create")]
-use wasmer_types::{CompileModuleInfo, Target};
+use wasmer_types::{Target, Triple};
+use wasmer_vm::{catch_traps, Trap, TrapCode, VMContext, VMFunctionBody};
 use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
 use wasmer_vm::{InstanceAllocator, InstanceHandle, StoreObjects, TrapHandlerFn, VMExtern};
+use wasmer_vm::{SectionBodyPtr, TableGrowLimits};
+use wasmparser::BinaryReader;
 
 /// A compiled wasm module, ready to be instantiated.
 pub struct Artifact {
@@ -43,9 +62,27 @@ pub struct Artifact {
     finished_function_call_trampolines: BoxedSlice<SignatureIndex, VMTrampoline>,
     finished_dynamic_function_trampolines: BoxedSlice<FunctionIndex, FunctionBodyPtr>,
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
+    /// The registrations of `signatures`, shared with the instances of the
+    /// artifact, if it has code to run.
+    signature_registration: Option<Arc<SignatureRegistration>>,
+    /// The bytes of code of the artifact counted towards the limit of the
+    /// engine, given back when the artifact is dropped.
+    code_bytes_charge: CodeBytesCharge,
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// The engine this artifact was created by.
+    engine_id: EngineId,
+    /// The limits on table growth of the engine, given to every instance.
+    table_grow_limits: Arc<TableGrowLimits>,
+    /// The source map URL set with `set_source_map_url`, replacing the
+    /// one of the module.
+    source_map_url: Mutex<Option<String>>,
+    /// Whether the code runs straight from the file of the artifact.
+    code_mapped: bool,
+    /// Whether the code was loaded into the engine. Artifacts compiled for
+    /// another target than the host only keep their code to serialize it.
+    runnable: bool,
 }
 
 #[cfg(feature = "static-artifact-create")]
@@ -54,6 +91,21 @@ pub type PrefixerFn = Box<dyn Fn(&[u8]) -> String + Send>;
 #[cfg(feature = "static-artifact-create")]
 const WASMER_METADATA_SYMBOL: &[u8] = b"WASMER_METADATA";
 
+/// The name of the custom section holding the URL of the source map of a
+/// module.
+const SOURCE_MAPPING_URL_SECTION_NAME: &str = "sourceMappingURL";
+
+/// The magic number WebAssembly binaries start with.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Encodes a string the way WebAssembly does, prefixed with its length.
+fn encode_string(s: &str) -> Box<[u8]> {
+    let mut encoded = Vec::with_capacity(s.len() + 5);
+    leb128::write::unsigned(&mut encoded, s.len() as u64).unwrap();
+    encoded.extend_from_slice(s.as_bytes());
+    encoded.into_boxed_slice()
+}
+
 impl Artifact {
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
     #[cfg(feature = "compiler")]
@@ -61,47 +113,183 @@ impl Artifact {
         engine: &Engine,
         data: &[u8],
         tunables: &dyn Tunables,
+        target: &Target,
     ) -> Result<Self, CompileError> {
         let environ = ModuleEnvironment::new();
+        // Only lock the engine to allocate and publish the code, so that
+        // compilations don't wait for each other or block the engine.
+        let settings = engine.inner().compile_settings()?;
+        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        let (memory_styles, table_styles) = Self::styles(&settings, &translation.module, tunables);
+
+        let artifact = ArtifactBuild::new(
+            &settings,
+            data,
+            target,
+            memory_styles,
+            table_styles,
+            &tunables.compile_hints(),
+        )?;
+
         let mut inner_engine = engine.inner_mut();
+        let engine_id = inner_engine.engine_id().copy();
+        if !Self::is_host_target(target) {
+            return Ok(Self::serialize_only(&inner_engine, artifact, &engine_id));
+        }
+        Self::from_parts(&mut inner_engine, artifact, &engine_id)
+    }
+
+    /// Whether code compiled for `target` can run on the host.
+    #[cfg(feature = "compiler")]
+    fn is_host_target(target: &Target) -> bool {
+        let host = Triple::host();
+        let triple = target.triple();
+        triple.architecture == host.architecture && triple.operating_system == host.operating_system
+    }
+
+    /// Completes an `Artifact` whose code was compiled for another target.
+    ///
+    /// Its code can't run here, so it is neither allocated, linked nor
+    /// published, and the artifact can only be serialized.
+    #[cfg(feature = "compiler")]
+    fn serialize_only(
+        engine_inner: &EngineInner,
+        artifact: ArtifactBuild,
+        engine_id: &EngineId,
+    ) -> Self {
+        Self {
+            artifact,
+            finished_functions: PrimaryMap::new().into_boxed_slice(),
+            finished_function_call_trampolines: PrimaryMap::new().into_boxed_slice(),
+            finished_dynamic_function_trampolines: PrimaryMap::new().into_boxed_slice(),
+            signatures: PrimaryMap::new().into_boxed_slice(),
+            signature_registration: None,
+            code_bytes_charge: engine_inner.no_code_bytes_charge(),
+            frame_info_registration: Some(Mutex::new(None)),
+            finished_function_lengths: PrimaryMap::new().into_boxed_slice(),
+            engine_id: engine_id.copy(),
+            table_grow_limits: engine_inner.table_grow_limits().clone(),
+            source_map_url: Mutex::new(None),
+            code_mapped: false,
+            runnable: false,
+        }
+    }
+
+    /// Hashes what determines the code compiled for `data`, like
+    /// [`ArtifactCreate::hash`] does for the compiled artifact, without
+    /// compiling it.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn compile_hash(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+        target: &Target,
+    ) -> Result<ArtifactHash, CompileError> {
+        let environ = ModuleEnvironment::new();
+        let settings = engine.inner().compile_settings()?;
         let translation = environ.translate(data).map_err(CompileError::Wasm)?;
-        let module = translation.module;
-        let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
+        let (memory_styles, table_styles) = Self::styles(&settings, &translation.module, tunables);
+        ArtifactBuild::compile_hash(
+            &settings,
+            data,
+            target,
+            &memory_styles,
+            &table_styles,
+            &tunables.compile_hints(),
+        )
+    }
+
+    /// The styles of the memories and tables of `module`.
+    #[cfg(feature = "compiler")]
+    fn styles(
+        settings: &CompileSettings,
+        module: &ModuleInfo,
+        tunables: &dyn Tunables,
+    ) -> (
+        PrimaryMap<MemoryIndex, MemoryStyle>,
+        PrimaryMap<TableIndex, TableStyle>,
+    ) {
+        let bounds_check_elimination = settings.bounds_check_elimination();
+        let memory_styles = module
             .memories
             .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .map(|memory_type| {
+                if bounds_check_elimination {
+                    tunables.memory_style(memory_type)
+                } else {
+                    // Without guard pages, every access is explicitly checked.
+                    MemoryStyle::Dynamic {
+                        offset_guard_size: 0,
+                    }
+                }
+            })
             .collect();
-        let table_styles: PrimaryMap<TableIndex, TableStyle> = module
+        let table_styles = module
             .tables
             .values()
             .map(|table_type| tunables.table_style(table_type))
             .collect();
-
-        let artifact = ArtifactBuild::new(
-            &mut inner_engine,
-            data,
-            engine.target(),
-            memory_styles,
-            table_styles,
-        )?;
-
-        Self::from_parts(&mut inner_engine, artifact)
+        (memory_styles, table_styles)
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
     #[cfg(not(feature = "compiler"))]
-    pub fn new(_engine: &Engine, _data: &[u8]) -> Result<Self, CompileError> {
+    pub fn new(_engine: &Engine, _data: &[u8], _target: &Target) -> Result<Self, CompileError> {
         Err(CompileError::Codegen(
             "Compilation is not enabled in the engine".to_string(),
         ))
     }
 
+    /// Reads the metadata header of a serialized artifact, which tells the
+    /// format version of the artifact and the oldest version able to load
+    /// it, without deserializing the artifact.
+    pub fn peek_artifact_header(bytes: &[u8]) -> Result<MetadataHeader, DeserializeError> {
+        if !ArtifactBuild::is_deserializable(bytes) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are not wasmer-universal".to_string(),
+            ));
+        }
+        MetadataHeader::peek(&bytes[ArtifactBuild::MAGIC_HEADER.len()..])
+    }
+
+    /// Reads the description of a serialized artifact, such as its module
+    /// information and target, without deserializing or mapping its code.
+    ///
+    /// This is meant for tooling inspecting cached artifacts, which doesn't
+    /// need an [`Engine`], and doesn't check that the artifact could run on
+    /// one.
+    ///
+    /// # Safety
+    /// See [`Artifact::deserialize`].
+    pub unsafe fn deserialize_metadata(bytes: &[u8]) -> Result<ArtifactMetadata, DeserializeError> {
+        if !ArtifactBuild::is_deserializable(bytes) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are not wasmer-universal".to_string(),
+            ));
+        }
+        let bytes = Self::get_byte_slice(bytes, ArtifactBuild::MAGIC_HEADER.len(), bytes.len())?;
+        let metadata_len = MetadataHeader::parse(bytes)?;
+        let metadata_slice = Self::get_byte_slice(bytes, MetadataHeader::LEN, bytes.len())?;
+        let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
+        SerializableModule::deserialize_metadata(metadata_slice)
+    }
+
     /// Deserialize a ArtifactBuild
     ///
+    /// The artifact keeps its code in relocatable form next to the copy
+    /// that runs, so it can be serialized again, for example after setting
+    /// its source map URL.
+    ///
     /// # Safety
     /// This function is unsafe because rkyv reads directly without validating
     /// the data.
     pub unsafe fn deserialize(engine: &Engine, bytes: &[u8]) -> Result<Self, DeserializeError> {
+        if bytes.starts_with(WASM_MAGIC) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are a WebAssembly binary, which must be compiled rather than deserialized"
+                    .to_string(),
+            ));
+        }
         if !ArtifactBuild::is_deserializable(bytes) {
             let static_artifact = Self::deserialize_object(engine, bytes);
             match static_artifact {
@@ -117,32 +305,137 @@ impl Artifact {
             ));
         }
 
+        let artifact = Self::deserialize_build(engine, bytes)?;
+        let mut inner_engine = engine.inner_mut();
+        let engine_id = inner_engine.engine_id().copy();
+        Self::from_parts(&mut inner_engine, artifact, &engine_id)
+            .map_err(DeserializeError::Compiler)
+    }
+
+    /// Deserializes a `ArtifactBuild`, checking that it can run on the
+    /// engine.
+    ///
+    /// # Safety
+    /// See [`Artifact::deserialize`].
+    unsafe fn deserialize_build(
+        engine: &Engine,
+        bytes: &[u8],
+    ) -> Result<ArtifactBuild, DeserializeError> {
         let bytes = Self::get_byte_slice(bytes, ArtifactBuild::MAGIC_HEADER.len(), bytes.len())?;
 
-        let metadata_len = MetadataHeader::parse(bytes)?;
+        let forward_policy = engine.inner().deserialize_forward_policy();
+        let metadata_len = MetadataHeader::parse_with_policy(bytes, forward_policy)?;
         let metadata_slice = Self::get_byte_slice(bytes, MetadataHeader::LEN, bytes.len())?;
         let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
 
         let serializable = SerializableModule::deserialize(metadata_slice)?;
-        let artifact = ArtifactBuild::from_serializable(serializable);
+        let engine_options = engine.inner().compile_options();
+        if let Some(conflict) = serializable
+            .compile_options()
+            .conflict_with(&engine_options)
+        {
+            return Err(DeserializeError::Incompatible(conflict.to_string()));
+        }
+        let triple = Triple::from_str(serializable.target_triple()).map_err(|error| {
+            DeserializeError::CorruptedBinary(format!("Invalid target triple: {}", error))
+        })?;
+        let engine_triple = engine.target().triple();
+        if triple.architecture != engine_triple.architecture
+            || triple.operating_system != engine_triple.operating_system
+        {
+            return Err(DeserializeError::Incompatible(format!(
+                "The artifact was compiled for {}, but the engine targets {}",
+                triple, engine_triple
+            )));
+        }
+        Ok(ArtifactBuild::from_serializable(serializable))
+    }
+
+    /// Deserialize a ArtifactBuild from a file, executing its code straight
+    /// from the mapping of the file when it was serialized with
+    /// [`Artifact::serialize_mappable`].
+    ///
+    /// This saves allocating memory for the code and copying it, and lets
+    /// processes loading the same file share its code. The code is copied as
+    /// with [`Artifact::deserialize`] if the file has no image of the code,
+    /// if the engine has a relocation hook, or if the pages of the platform
+    /// are larger than the alignment of the image.
+    ///
+    /// # Safety
+    ///
+    /// See [`Artifact::deserialize`]. Moreover, the file must not be
+    /// modified for as long as the engine is alive.
+    pub unsafe fn deserialize_mmap(engine: &Engine, path: &Path) -> Result<Self, DeserializeError> {
+        let file = File::open(path)?;
+        // Empty files can't be mapped on every platform.
+        if file.metadata()?.len() == 0 {
+            return Self::deserialize(engine, &[]);
+        }
+        let bytes = Mmap::map(&file)?;
+        if !ArtifactBuild::is_deserializable(&bytes) {
+            return Self::deserialize(engine, &bytes);
+        }
+        let location = match find_image(&bytes)? {
+            Some(location) => location,
+            None => return Self::deserialize(engine, &bytes),
+        };
+        // A relocation hook must see every relocation, including those of
+        // the image, which were applied when it was built.
+        if engine.inner().relocation_hook().is_some() || region::page::size() > IMAGE_ALIGNMENT {
+            return Self::deserialize(engine, &bytes[..location.offset()]);
+        }
+        let artifact = Self::deserialize_build(engine, &bytes[..location.offset()])?;
         let mut inner_engine = engine.inner_mut();
-        Self::from_parts(&mut inner_engine, artifact).map_err(DeserializeError::Compiler)
+        let code_bytes_charge = inner_engine
+            .charge_code_bytes(location.len())
+            .map_err(DeserializeError::Compiler)?;
+        inner_engine.install_trap_handlers();
+
+        let image = map_image(&file, &location, &artifact)?;
+        let module_info = artifact.create_module_info();
+        let mut code = image.code;
+        code.publish_eh_frame(Self::eh_frame(&artifact, &image.sections))
+            .map_err(|e| {
+                DeserializeError::Compiler(CompileError::Resource(format!(
+                    "Error while publishing the unwind code: {}",
+                    e
+                )))
+            })?;
+        inner_engine.adopt_mapped_code(&module_info, code, &image.functions);
+        let mut artifact = Self::from_allocated(
+            &inner_engine,
+            artifact,
+            inner_engine.engine_id(),
+            &module_info,
+            image.functions,
+            image.call_trampolines,
+            image.dynamic_trampolines,
+            code_bytes_charge,
+        );
+        artifact.code_mapped = true;
+        Ok(artifact)
     }
 
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
         artifact: ArtifactBuild,
+        engine_id: &EngineId,
     ) -> Result<Self, CompileError> {
+        // Code is about to exist, so faults in it must be turned into traps.
+        engine_inner.install_trap_handlers();
+
         let module_info = artifact.create_module_info();
         let (
             finished_functions,
             finished_function_call_trampolines,
             finished_dynamic_function_trampolines,
             custom_sections,
+            code_bytes_charge,
         ) = engine_inner.allocate(
             &module_info,
             artifact.get_function_bodies_ref(),
+            &artifact.get_function_relocations(),
             artifact.get_function_call_trampolines_ref(),
             artifact.get_dynamic_function_trampolines_ref(),
             artifact.get_custom_sections_ref(),
@@ -156,35 +449,69 @@ impl Artifact {
             artifact.get_custom_section_relocations_ref(),
             artifact.get_libcall_trampolines(),
             artifact.get_libcall_trampoline_len(),
+            engine_inner.relocation_hook(),
         );
 
-        // Compute indices into the shared signature table.
-        let signatures = {
-            let signature_registry = engine_inner.signatures();
-            module_info
-                .signatures
-                .values()
-                .map(|sig| signature_registry.register(sig))
-                .collect::<PrimaryMap<_, _>>()
-        };
-
-        let eh_frame = match artifact.get_debug_ref() {
-            Some(debug) => {
-                let eh_frame_section_size = artifact.get_custom_sections_ref()[debug.eh_frame]
-                    .bytes
-                    .len();
-                let eh_frame_section_pointer = custom_sections[debug.eh_frame];
-                Some(unsafe {
-                    std::slice::from_raw_parts(*eh_frame_section_pointer, eh_frame_section_size)
-                })
-            }
-            None => None,
-        };
+        #[cfg(feature = "debug-info")]
+        engine_inner.set_debug_line(
+            &module_info,
+            &finished_functions,
+            artifact.get_frame_info_ref(),
+        );
 
         // Make all code compiled thus far executable.
         engine_inner.publish_compiled_code();
 
-        engine_inner.publish_eh_frame(eh_frame)?;
+        engine_inner.publish_eh_frame(Self::eh_frame(&artifact, &custom_sections))?;
+
+        Ok(Self::from_allocated(
+            engine_inner,
+            artifact,
+            engine_id,
+            &module_info,
+            finished_functions,
+            finished_function_call_trampolines,
+            finished_dynamic_function_trampolines,
+            code_bytes_charge,
+        ))
+    }
+
+    /// The DWARF unwind information of the code of `artifact`, whose custom
+    /// sections are at `custom_sections`.
+    fn eh_frame<'a>(
+        artifact: &ArtifactBuild,
+        custom_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    ) -> Option<&'a [u8]> {
+        let debug = artifact.get_debug_ref().as_ref()?;
+        let eh_frame_section_size = artifact.get_custom_sections_ref()[debug.eh_frame]
+            .bytes
+            .len();
+        let eh_frame_section_pointer = custom_sections[debug.eh_frame];
+        Some(unsafe {
+            std::slice::from_raw_parts(*eh_frame_section_pointer, eh_frame_section_size)
+        })
+    }
+
+    /// Completes an `Artifact` whose code is ready to run.
+    #[allow(clippy::too_many_arguments)]
+    fn from_allocated(
+        engine_inner: &EngineInner,
+        artifact: ArtifactBuild,
+        engine_id: &EngineId,
+        module_info: &ModuleInfo,
+        finished_functions: PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+        finished_function_call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline>,
+        finished_dynamic_function_trampolines: PrimaryMap<FunctionIndex, FunctionBodyPtr>,
+        code_bytes_charge: CodeBytesCharge,
+    ) -> Self {
+        // Compute indices into the shared signature table.
+        let signature_registration =
+            Arc::new(engine_inner.register_signatures(module_info.signatures.values()));
+        let signatures = signature_registration
+            .indices()
+            .iter()
+            .copied()
+            .collect::<PrimaryMap<_, _>>();
 
         let finished_function_lengths = finished_functions
             .values()
@@ -202,15 +529,22 @@ impl Artifact {
             finished_dynamic_function_trampolines.into_boxed_slice();
         let signatures = signatures.into_boxed_slice();
 
-        Ok(Self {
+        Self {
             artifact,
             finished_functions,
             finished_function_call_trampolines,
             finished_dynamic_function_trampolines,
             signatures,
+            signature_registration: Some(signature_registration),
+            code_bytes_charge,
             frame_info_registration: Some(Mutex::new(None)),
             finished_function_lengths,
-        })
+            engine_id: engine_id.copy(),
+            table_grow_limits: engine_inner.table_grow_limits().clone(),
+            source_map_url: Mutex::new(None),
+            code_mapped: false,
+            runnable: true,
+        }
     }
 
     /// Check if the provided bytes look like a serialized `ArtifactBuild`.
@@ -245,7 +579,25 @@ impl ArtifactCreate for Artifact {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
-        self.artifact.serialize()
+        self.check_serializable()?;
+        match self.source_map_url.lock().unwrap().as_deref() {
+            Some(url) => self
+                .artifact
+                .serialize_with_custom_section(SOURCE_MAPPING_URL_SECTION_NAME, encode_string(url)),
+            None => self.artifact.serialize(),
+        }
+    }
+
+    fn serialize_to(&self, writer: &mut dyn Write) -> Result<(), SerializeError> {
+        self.check_serializable()?;
+        match self.source_map_url.lock().unwrap().as_deref() {
+            Some(url) => self.artifact.serialize_with_custom_section_to(
+                SOURCE_MAPPING_URL_SECTION_NAME,
+                encode_string(url),
+                writer,
+            ),
+            None => self.artifact.serialize_to(writer),
+        }
     }
 }
 
@@ -254,6 +606,9 @@ impl Artifact {
     ///
     /// This is required to ensure that any traps can be properly symbolicated.
     pub fn register_frame_info(&self) {
+        if !self.runnable {
+            return;
+        }
         if let Some(frame_info_registration) = self.frame_info_registration.as_ref() {
             let mut info = frame_info_registration.lock().unwrap();
 
@@ -299,11 +654,453 @@ impl Artifact {
         &self.finished_dynamic_function_trampolines
     }
 
+    /// Returns the size in bytes of the machine code of a local function.
+    ///
+    /// This is `0` for artifacts loaded from static objects, which don't
+    /// record the sizes of their functions.
+    pub fn function_code_size(&self, index: LocalFunctionIndex) -> usize {
+        self.finished_function_lengths[index]
+    }
+
+    /// Returns the local functions in the order their code is laid out in
+    /// memory.
+    ///
+    /// Compiled functions are laid out in ascending `LocalFunctionIndex`
+    /// order, so this is the index order unless the functions come from a
+    /// static object with another layout. Functions sharing a body, see
+    /// [`Engine::with_function_dedup`], are ordered by index.
+    pub fn function_layout_order(&self) -> Vec<LocalFunctionIndex> {
+        let mut order = self.finished_functions.keys().collect::<Vec<_>>();
+        order.sort_by_key(|index| (self.finished_functions[*index].0 as usize, *index));
+        order
+    }
+
+    /// Returns the stack map of the safepoint at `pc`, which tells the stack
+    /// slots holding live references there. For calls, `pc` is the return
+    /// address.
+    ///
+    /// This is `None` if `pc` isn't a safepoint of a function of this
+    /// artifact, or if it was compiled without
+    /// [`Engine::with_stack_maps`].
+    pub fn stack_map(&self, pc: usize) -> Option<StackMap> {
+        let (index, start) = self
+            .finished_functions
+            .iter()
+            .map(|(index, function)| (index, function.0 as usize))
+            .find(|(index, start)| {
+                (*start..start + self.finished_function_lengths[*index]).contains(&pc)
+            })?;
+        let offset = u32::try_from(pc - start).ok()?;
+        let stack_maps = &self.artifact.get_frame_info_ref()[index].stack_maps;
+        let position = stack_maps
+            .binary_search_by_key(&offset, |stack_map| stack_map.code_offset)
+            .ok()?;
+        Some(stack_maps[position].clone())
+    }
+
     /// Returns the associated VM signatures for this `Artifact`.
     pub fn signatures(&self) -> &BoxedSlice<SignatureIndex, VMSharedSignatureIndex> {
         &self.signatures
     }
 
+    /// Returns the identifier of the engine that created this `Artifact`,
+    /// which is shared by its clones, see [`Engine::owns_artifact`].
+    pub fn engine_id(&self) -> &EngineId {
+        &self.engine_id
+    }
+
+    /// Returns how many functions of this `Artifact` share the code of the
+    /// given function, including itself.
+    ///
+    /// This is greater than one only when identical bodies were
+    /// deduplicated, see [`Engine::with_function_dedup`].
+    pub fn shared_code_refcount(&self, index: LocalFunctionIndex) -> usize {
+        let body = self.finished_functions[index].0;
+        self.finished_functions
+            .values()
+            .filter(|other| other.0 == body)
+            .count()
+    }
+
+    /// Returns the runtime functions referenced by the relocations of the
+    /// compiled code, without duplicates.
+    ///
+    /// The trampolines to every libcall that the engine adds to each
+    /// artifact are not taken into account. Note that some runtime
+    /// functions, such as the one implementing `memory.grow`, are VM
+    /// builtins reached through the `VMContext` rather than libcalls, and
+    /// are always provided.
+    pub fn required_libcalls(&self) -> Vec<LibCall> {
+        let libcall_trampolines = self.artifact.get_libcall_trampolines();
+        let function_relocations = self.artifact.get_function_relocations();
+        let section_relocations = self
+            .artifact
+            .get_custom_section_relocations_ref()
+            .iter()
+            .filter(|(index, _)| *index != libcall_trampolines)
+            .flat_map(|(_, relocations)| relocations.iter());
+        let mut libcalls = Vec::new();
+        for relocation in function_relocations
+            .values()
+            .flatten()
+            .chain(section_relocations)
+        {
+            if let RelocationTarget::LibCall(libcall) = relocation.reloc_target {
+                if !libcalls.contains(&libcall) {
+                    libcalls.push(libcall);
+                }
+            }
+        }
+        libcalls
+    }
+
+    /// Calls the local function `index` of `instance`, which must have the
+    /// signature `(i32) -> i32`.
+    ///
+    /// Unlike calls through the function call trampolines, the argument
+    /// and the result are passed directly in registers rather than through
+    /// an array of values. A function with another signature is rejected
+    /// with a [`TrapCode::BadSignature`] trap.
+    ///
+    /// # Safety
+    ///
+    /// `instance` must have been instantiated from this artifact, and
+    /// `trap_handler` is used as in [`Self::finish_instantiation`].
+    pub unsafe fn call_i32_i32(
+        &self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        instance: &InstanceHandle,
+        index: LocalFunctionIndex,
+        arg: i32,
+    ) -> Result<i32, Trap> {
+        let body = self.direct_call_target(index, &[Type::I32], Type::I32)?;
+        let vmctx = instance.vmctx_ptr();
+        catch_traps(trap_handler, || {
+            std::mem::transmute::<_, extern "C" fn(*mut VMContext, i32) -> i32>(body)(vmctx, arg)
+        })
+    }
+
+    /// Calls the local function `index` of `instance`, which must have the
+    /// signature `(i32, i32) -> i32`.
+    ///
+    /// See [`Self::call_i32_i32`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::call_i32_i32`].
+    pub unsafe fn call_i32_i32_i32(
+        &self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        instance: &InstanceHandle,
+        index: LocalFunctionIndex,
+        arg1: i32,
+        arg2: i32,
+    ) -> Result<i32, Trap> {
+        let body = self.direct_call_target(index, &[Type::I32, Type::I32], Type::I32)?;
+        let vmctx = instance.vmctx_ptr();
+        catch_traps(trap_handler, || {
+            std::mem::transmute::<_, extern "C" fn(*mut VMContext, i32, i32) -> i32>(body)(
+                vmctx, arg1, arg2,
+            )
+        })
+    }
+
+    /// Calls the local function `index` of `instance`, which must have the
+    /// signature `(i64) -> i64`.
+    ///
+    /// See [`Self::call_i32_i32`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::call_i32_i32`].
+    pub unsafe fn call_i64_i64(
+        &self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        instance: &InstanceHandle,
+        index: LocalFunctionIndex,
+        arg: i64,
+    ) -> Result<i64, Trap> {
+        let body = self.direct_call_target(index, &[Type::I64], Type::I64)?;
+        let vmctx = instance.vmctx_ptr();
+        catch_traps(trap_handler, || {
+            std::mem::transmute::<_, extern "C" fn(*mut VMContext, i64) -> i64>(body)(vmctx, arg)
+        })
+    }
+
+    /// Calls the local function `index` of `instance`, which must have the
+    /// signature `(f64) -> f64`.
+    ///
+    /// See [`Self::call_i32_i32`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::call_i32_i32`].
+    pub unsafe fn call_f64_f64(
+        &self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        instance: &InstanceHandle,
+        index: LocalFunctionIndex,
+        arg: f64,
+    ) -> Result<f64, Trap> {
+        let body = self.direct_call_target(index, &[Type::F64], Type::F64)?;
+        let vmctx = instance.vmctx_ptr();
+        catch_traps(trap_handler, || {
+            std::mem::transmute::<_, extern "C" fn(*mut VMContext, f64) -> f64>(body)(vmctx, arg)
+        })
+    }
+
+    /// Returns the body of the local function `index`, after checking that
+    /// it has the given signature.
+    fn direct_call_target(
+        &self,
+        index: LocalFunctionIndex,
+        params: &[Type],
+        result: Type,
+    ) -> Result<*const VMFunctionBody, Trap> {
+        let module = self.artifact.get_module_info_ref();
+        let signature = &module.signatures[module.functions[module.func_index(index)]];
+        if signature.params() != params || signature.results() != [result] {
+            return Err(Trap::lib(TrapCode::BadSignature));
+        }
+        Ok(self.finished_functions[index].0)
+    }
+
+    /// Returns the distinct signatures of the functions imported and
+    /// exported by the module, imports first, in the order they appear.
+    ///
+    /// This is what glue code between the module and its host has to
+    /// support.
+    pub fn interface_signatures(&self) -> Vec<FunctionType> {
+        let module = self.artifact.get_module_info_ref();
+        let imported = module.imports.values().filter_map(|import| match import {
+            ImportIndex::Function(index) => Some(*index),
+            _ => None,
+        });
+        let exported = module.exports.values().filter_map(|export| match export {
+            ExportIndex::Function(index) => Some(*index),
+            _ => None,
+        });
+        let mut signatures = Vec::new();
+        for index in imported.chain(exported) {
+            let signature = &module.signatures[module.functions[index]];
+            if !signatures.contains(signature) {
+                signatures.push(signature.clone());
+            }
+        }
+        signatures
+    }
+
+    /// Returns the trap sites of the given function: the instructions that
+    /// may trap, such as explicit bounds checks.
+    pub fn trap_sites(&self, index: LocalFunctionIndex) -> &[TrapInformation] {
+        self.artifact
+            .get_frame_info_ref()
+            .get(index)
+            .map_or(&[][..], |frame_info| frame_info.traps.as_slice())
+    }
+
+    /// Returns the name given to a function by the module's name section,
+    /// if any.
+    pub fn function_name(&self, index: FunctionIndex) -> Option<String> {
+        self.artifact
+            .get_module_info_ref()
+            .function_names
+            .get(&index)
+            .cloned()
+    }
+
+    /// Returns what this module requires to be linked dynamically, if it
+    /// was built as a dynamic library.
+    ///
+    /// This is read from the `dylink.0` custom section, or from the legacy
+    /// `dylink` one. `None` is returned if there is no such section, or if
+    /// it is malformed.
+    pub fn dylink_info(&self) -> Option<DylinkInfo> {
+        let module = self.artifact.get_module_info_ref();
+        if let Some(data) = module.custom_sections(DYLINK_SECTION_NAME).next() {
+            return DylinkInfo::parse(&data).ok();
+        }
+        let data = module.custom_sections(LEGACY_DYLINK_SECTION_NAME).next()?;
+        DylinkInfo::parse_legacy(&data).ok()
+    }
+
+    /// Returns the URL of the source map of the module, from which
+    /// debuggers can fetch the original sources.
+    ///
+    /// This is the URL set with [`Artifact::set_source_map_url`] if any, or
+    /// else is read from the `sourceMappingURL` custom section. `None` is
+    /// returned if there is no such section, or if it is malformed.
+    pub fn source_map_url(&self) -> Option<String> {
+        if let Some(url) = self.source_map_url.lock().unwrap().clone() {
+            return Some(url);
+        }
+        let data = self
+            .artifact
+            .get_module_info_ref()
+            .custom_sections(SOURCE_MAPPING_URL_SECTION_NAME)
+            .last()?;
+        let mut reader = BinaryReader::new(&data);
+        let url = reader.read_string().ok()?.to_string();
+        if !reader.eof() {
+            return None;
+        }
+        Some(url)
+    }
+
+    /// Sets the URL of the source map of the module, which is written in
+    /// the `sourceMappingURL` custom section when the artifact is
+    /// serialized.
+    pub fn set_source_map_url(&self, url: &str) {
+        *self.source_map_url.lock().unwrap() = Some(url.to_string());
+    }
+
+    /// Static artifacts are linked into the process, so the relocatable
+    /// form of their code is lost and they can't be serialized again.
+    fn check_serializable(&self) -> Result<(), SerializeError> {
+        if self.frame_info_registration.is_none() {
+            return Err(SerializeError::Generic(
+                "Artifacts loaded from static objects can't be serialized".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serializes the artifact followed by an image of its code, laid out
+    /// the way it runs, so that [`Artifact::deserialize_mmap`] can execute
+    /// it straight from the file.
+    ///
+    /// The image is left out, which makes this the same as
+    /// [`ArtifactCreate::serialize`], if some of the code must be patched
+    /// by every process loading it, that is if some of its relocations
+    /// aren't position-independent.
+    pub fn serialize_mappable(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut serialized = self.serialize()?;
+        if let Some((image, layout)) = build_image(&self.artifact) {
+            append_image(&mut serialized, &image, &layout);
+        }
+        Ok(serialized)
+    }
+
+    /// Whether the code of the artifact runs straight from its file, see
+    /// [`Artifact::deserialize_mmap`].
+    pub fn is_code_mapped(&self) -> bool {
+        self.code_mapped
+    }
+
+    /// Whether the code of the artifact was loaded into its engine, so that
+    /// it can be instantiated. Artifacts compiled for another target than
+    /// the host with [`Engine::compile_for`] can only be serialized.
+    pub fn is_runnable(&self) -> bool {
+        self.runnable
+    }
+
+    /// Makes the artifact hold `charge`, for code allocated on its behalf
+    /// after it was created, until it is dropped.
+    pub(crate) fn charge_code_bytes(&self, charge: CodeBytesCharge) {
+        self.code_bytes_charge.absorb(charge);
+    }
+
+    /// Returns the exported globals of the module with their types, which
+    /// tell whether they are mutable, in export order.
+    pub fn exported_globals(&self) -> Vec<(String, GlobalType)> {
+        let module = self.artifact.get_module_info_ref();
+        module
+            .exports
+            .iter()
+            .filter_map(|(name, index)| match index {
+                ExportIndex::Global(global) => Some((name.clone(), module.globals[*global])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the names and contents of the custom sections of the module,
+    /// in the order they are declared in, including several sections with
+    /// the same name.
+    pub fn custom_sections(&self) -> Vec<(String, &[u8])> {
+        let module = self.artifact.get_module_info_ref();
+        module
+            .custom_section_names
+            .iter()
+            .map(|(index, name)| (name.clone(), &*module.custom_sections_data[index]))
+            .collect()
+    }
+
+    /// Returns the types of the exception tags declared by the module, in
+    /// order.
+    pub fn tags(&self) -> Vec<TagType> {
+        let module = self.artifact.get_module_info_ref();
+        module
+            .tags
+            .iter()
+            .map(|sig_index| TagType::new(module.signatures[*sig_index].params()))
+            .collect()
+    }
+
+    /// The number of data segments declared by the data count section of
+    /// the module, or `None` if it has no such section.
+    pub fn data_count(&self) -> Option<u32> {
+        self.artifact.get_module_info_ref().data_count
+    }
+
+    /// Resolves the export with the given name to its index.
+    ///
+    /// The lookup uses the name map built when the module was compiled,
+    /// so it doesn't scan the exports.
+    pub fn export_index(&self, name: &str) -> Option<ExportIndex> {
+        self.artifact
+            .get_module_info_ref()
+            .exports
+            .get(name)
+            .copied()
+    }
+
+    /// Returns the WebAssembly proposals that are both enabled in
+    /// [`features`](ArtifactCreate::features) and actually used by the
+    /// module.
+    ///
+    /// For example, a module compiled with SIMD enabled that contains no
+    /// SIMD instructions reports `simd` as `false` here.
+    pub fn used_proposals(&self) -> &Features {
+        self.artifact.used_proposals()
+    }
+
+    /// Returns the sources of nondeterminism the module uses, as found
+    /// while parsing it.
+    ///
+    /// A module for which [`DeterminismReport::is_deterministic`] holds
+    /// runs the same on every host and in every run, as long as its
+    /// imports do too.
+    pub fn determinism_report(&self) -> DeterminismReport {
+        self.artifact.determinism_report()
+    }
+
+    /// Returns the triple of the target the module was compiled for, which
+    /// is the target of the engine unless it was compiled with
+    /// [`Engine::compile_for`].
+    pub fn target_triple(&self) -> &str {
+        self.artifact.target_triple()
+    }
+
+    /// Returns a hash of the binary the module was compiled from, of the
+    /// target, of the enabled features, and of the options of the engine
+    /// and the compiler, which is kept when serializing the artifact.
+    ///
+    /// It is the same for any engine configured the same way, so hosts can
+    /// use it as key to cache artifacts. Artifacts loaded from static
+    /// objects hash their metadata instead.
+    pub fn hash(&self) -> ArtifactHash {
+        self.artifact.hash()
+    }
+
+    /// Returns the options of the engine the module was compiled with.
+    ///
+    /// [`Artifact::deserialize`] rejects artifacts compiled with options
+    /// that conflict with those of the engine loading them.
+    pub fn compile_options(&self) -> CompileOptions {
+        self.artifact.compile_options()
+    }
+
     /// Do preinstantiation logic that is executed before instantiating
     pub fn preinstantiate(&self) -> Result<(), InstantiationError> {
         Ok(())
@@ -320,6 +1117,12 @@ impl Artifact {
         imports: &[VMExtern],
         context: &mut StoreObjects,
     ) -> Result<InstanceHandle, InstantiationError> {
+        if !self.runnable {
+            return Err(InstantiationError::ForeignTarget(
+                self.artifact.target_triple().to_string(),
+            ));
+        }
+
         // Validate the CPU features this module was compiled with against the
         // host CPU features.
         let host_cpu_features = CpuFeature::for_host();
@@ -373,7 +1176,7 @@ impl Artifact {
 
         self.register_frame_info();
 
-        let handle = InstanceHandle::new(
+        let mut handle = InstanceHandle::new(
             allocator,
             module,
             context,
@@ -386,6 +1189,10 @@ impl Artifact {
             self.signatures().clone(),
         )
         .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))?;
+        handle.set_table_grow_limits(self.table_grow_limits.clone());
+        if let Some(registration) = &self.signature_registration {
+            handle.set_keep_alive(registration.clone());
+        }
         Ok(handle)
     }
 
@@ -617,8 +1424,8 @@ impl Artifact {
         let mut finished_functions: PrimaryMap<LocalFunctionIndex, FunctionBodyPtr> =
             PrimaryMap::new();
 
+        engine.inner_mut().install_trap_handlers();
         let engine_inner = engine.inner();
-        let signature_registry = engine_inner.signatures();
 
         // read finished functions in order now...
         for _i in 0..num_finished_functions {
@@ -633,15 +1440,14 @@ impl Artifact {
         }
 
         // We register all the signatures
-        let signatures = {
-            metadata
-                .compile_info
-                .module
-                .signatures
-                .values()
-                .map(|sig| signature_registry.register(sig))
-                .collect::<PrimaryMap<_, _>>()
-        };
+        let signature_registration = Arc::new(
+            engine_inner.register_signatures(metadata.compile_info.module.signatures.values()),
+        );
+        let signatures = signature_registration
+            .indices()
+            .iter()
+            .copied()
+            .collect::<PrimaryMap<_, _>>();
 
         // read trampolines in order
         let mut finished_function_call_trampolines = PrimaryMap::new();
@@ -679,11 +1485,32 @@ impl Artifact {
             finished_dynamic_function_trampolines.push(fp);
         }
 
+        // Static objects don't record which proposals are used, so
+        // conservatively report all the enabled ones.
+        let used_proposals = metadata.compile_info.features.clone();
+        // Nor do they record the options they were compiled with, which are
+        // assumed to be those of the engine loading them.
+        let compile_options = engine_inner.compile_options();
+        // Nor the sources of nondeterminism they use, so conservatively
+        // report all of them.
+        let determinism = DeterminismReport {
+            floats: true,
+            threads: true,
+            simd: true,
+        };
         let artifact = ArtifactBuild::from_serializable(SerializableModule {
             compilation: SerializableCompilation::default(),
             compile_info: metadata.compile_info,
             data_initializers: metadata.data_initializers,
             cpu_features: metadata.cpu_features,
+            used_proposals,
+            compile_options,
+            determinism,
+            // They are loaded by the process they were linked into.
+            target_triple: engine.target().triple().to_string(),
+            // Nor do they keep the binary they were compiled from, so hash
+            // their metadata instead.
+            hash: crate::artifact_builders::artifact_hash(metadata_slice, "static", ""),
         });
 
         let finished_function_lengths = finished_functions
@@ -700,8 +1527,15 @@ impl Artifact {
             finished_dynamic_function_trampolines: finished_dynamic_function_trampolines
                 .into_boxed_slice(),
             signatures: signatures.into_boxed_slice(),
+            signature_registration: Some(signature_registration),
+            code_bytes_charge: engine_inner.no_code_bytes_charge(),
             finished_function_lengths,
             frame_info_registration: None,
+            engine_id: engine_inner.engine_id().copy(),
+            table_grow_limits: engine_inner.table_grow_limits().clone(),
+            source_map_url: Mutex::new(None),
+            code_mapped: false,
+            runnable: true,
         })
     }
 }
@@ -1,8 +1,20 @@
+use super::error::EngineBuilderError;
 use super::Engine;
+#[cfg(feature = "compiler")]
+use crate::CompileExecutor;
 use crate::CompilerConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::HugePages;
+#[cfg(feature = "compiler")]
+use std::sync::Arc;
 use wasmer_types::{Features, Target};
 
 /// The Builder contents of `Engine`
+///
+/// The default builder has no compiler, uses the host as target and leaves
+/// the features to the compiler. Headless engines only report the features
+/// they are given, with [`Engine::features`].
+#[derive(Default)]
 pub struct EngineBuilder {
     /// The compiler
     compiler_config: Option<Box<dyn CompilerConfig>>,
@@ -10,6 +22,15 @@ pub struct EngineBuilder {
     target: Option<Target>,
     /// The features to compile the Wasm module with
     features: Option<Features>,
+    /// The preference for backing the code memory with huge pages
+    #[cfg(not(target_arch = "wasm32"))]
+    huge_pages: Option<HugePages>,
+    /// The maximum number of bytes of code the engine allocates
+    #[cfg(not(target_arch = "wasm32"))]
+    max_code_bytes: Option<usize>,
+    /// The executor the compiler runs its work on
+    #[cfg(feature = "compiler")]
+    compile_executor: Option<Arc<dyn CompileExecutor>>,
 }
 
 impl EngineBuilder {
@@ -22,6 +43,12 @@ impl EngineBuilder {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            huge_pages: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            max_code_bytes: None,
+            #[cfg(feature = "compiler")]
+            compile_executor: None,
         }
     }
 
@@ -31,9 +58,24 @@ impl EngineBuilder {
             compiler_config: None,
             target: None,
             features: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            huge_pages: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            max_code_bytes: None,
+            #[cfg(feature = "compiler")]
+            compile_executor: None,
         }
     }
 
+    /// Set the compiler
+    pub fn set_compiler_config<T>(mut self, compiler_config: T) -> Self
+    where
+        T: Into<Box<dyn CompilerConfig>>,
+    {
+        self.compiler_config = Some(compiler_config.into());
+        self
+    }
+
     /// Set the target
     pub fn set_target(mut self, target: Option<Target>) -> Self {
         self.target = target;
@@ -46,24 +88,91 @@ impl EngineBuilder {
         self
     }
 
+    /// Set the preference for backing the code memory with huge pages,
+    /// see [`Engine::set_huge_pages`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_huge_pages(mut self, huge_pages: Option<HugePages>) -> Self {
+        self.huge_pages = huge_pages;
+        self
+    }
+
+    /// Set the maximum number of bytes of code the engine allocates, see
+    /// [`Engine::set_max_code_bytes`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_max_code_bytes(mut self, max_code_bytes: Option<usize>) -> Self {
+        self.max_code_bytes = max_code_bytes;
+        self
+    }
+
+    /// Set the executor the compiler runs its work on, see
+    /// [`Engine::set_compile_executor`]
+    #[cfg(feature = "compiler")]
+    pub fn set_compile_executor(mut self, executor: Option<Arc<dyn CompileExecutor>>) -> Self {
+        self.compile_executor = executor;
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
         let target = self.target.unwrap_or_default();
-        if let Some(compiler_config) = self.compiler_config {
+        let engine = if let Some(compiler_config) = self.compiler_config {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
             Engine::new(compiler_config, target, features)
         } else {
-            Engine::headless()
+            let engine = Engine::headless();
+            if let Some(features) = self.features {
+                engine.inner_mut().set_features(features);
+            }
+            engine
+        };
+        engine.set_compile_executor(self.compile_executor);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            engine.set_huge_pages(self.huge_pages);
+            engine.set_max_code_bytes(self.max_code_bytes);
         }
+        engine
     }
 
     /// Build the `Engine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> Engine {
-        Engine::headless()
+        let engine = Engine::headless();
+        if let Some(features) = self.features {
+            engine.inner_mut().set_features(features);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            engine.set_huge_pages(self.huge_pages);
+            engine.set_max_code_bytes(self.max_code_bytes);
+        }
+        engine
+    }
+
+    /// Build a headless `Engine`, failing if a compiler was set, rather
+    /// than compiling with it as [`EngineBuilder::engine`] would.
+    pub fn headless_engine(self) -> Result<Engine, EngineBuilderError> {
+        if self.compiler_config.is_some() {
+            return Err(EngineBuilderError::CompilerInHeadlessEngine);
+        }
+        let engine = Engine::headless();
+        if let Some(features) = self.features {
+            engine.inner_mut().set_features(features);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            engine.set_huge_pages(self.huge_pages);
+            engine.set_max_code_bytes(self.max_code_bytes);
+        }
+        Ok(engine)
+    }
+
+    /// The compiler
+    pub fn compiler_config(&self) -> Option<&dyn CompilerConfig> {
+        self.compiler_config.as_deref()
     }
 
     /// The Wasm features
@@ -75,4 +184,22 @@ impl EngineBuilder {
     pub fn target(&self) -> Option<&Target> {
         self.target.as_ref()
     }
+
+    /// The executor the compiler runs its work on
+    #[cfg(feature = "compiler")]
+    pub fn compile_executor(&self) -> Option<&Arc<dyn CompileExecutor>> {
+        self.compile_executor.as_ref()
+    }
+
+    /// The preference for backing the code memory with huge pages
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn huge_pages(&self) -> Option<&HugePages> {
+        self.huge_pages.as_ref()
+    }
+
+    /// The maximum number of bytes of code the engine allocates
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_code_bytes(&self) -> Option<usize> {
+        self.max_code_bytes
+    }
 }
@@ -2,9 +2,14 @@
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
 //! Memory management for executable code.
-use super::unwind::UnwindRegistry;
-use wasmer_types::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody};
-use wasmer_vm::{Mmap, VMFunctionBody};
+#[cfg(feature = "gdb-jit")]
+use super::gdb_jit::{GdbJitRegistration, GdbJitSymbol};
+use super::unwind::{UnwindEntry, UnwindRegistry};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+use wasmer_types::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody, FunctionType};
+use wasmer_vm::{Mmap, VMFunctionBody, HUGE_PAGE_SIZE};
 
 /// The optimal alignment for functions.
 ///
@@ -17,11 +22,313 @@ const ARCH_FUNCTION_ALIGNMENT: usize = 16;
 ///
 const DATA_SECTION_ALIGNMENT: usize = 64;
 
+/// Statistics about the memory used by the compiled code of an `Engine`,
+/// broken down by category.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CodeMemoryStats {
+    /// Bytes used by the compiled function bodies.
+    pub function_code_bytes: usize,
+    /// Bytes used by the function call trampolines.
+    pub call_trampoline_bytes: usize,
+    /// Bytes used by the dynamic function trampolines.
+    pub dynamic_trampoline_bytes: usize,
+    /// Bytes used by the custom sections, both executable and data.
+    pub custom_section_bytes: usize,
+    /// Bytes mapped to hold the code, padding included.
+    pub reserved_bytes: usize,
+    /// Bytes of code published, that is made executable.
+    pub published_bytes: usize,
+    /// Bytes of code executed straight from the files of artifacts, rather
+    /// than copied, see `Artifact::deserialize_mmap`.
+    pub mapped_bytes: usize,
+    /// The number of distinct function bodies allocated. Functions sharing
+    /// their body with another one are only counted once.
+    pub function_bodies: usize,
+}
+
+/// The permissions of a region of the memory holding the compiled code of
+/// an `Engine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionPermission {
+    /// The address of the start of the region.
+    pub start: usize,
+    /// The length of the region, in bytes.
+    pub len: usize,
+    /// Whether the region can be read.
+    pub readable: bool,
+    /// Whether the region can be written.
+    pub writable: bool,
+    /// Whether the region can be executed.
+    pub executable: bool,
+}
+
+/// Function call trampolines published by another process in a region of
+/// executable memory shared with this one, such as a shared-memory code
+/// cache.
+///
+/// See [`Engine::import_trampoline_table`](crate::Engine::import_trampoline_table).
+#[derive(Debug, Clone)]
+pub struct SharedTrampolineTable {
+    base: usize,
+    len: usize,
+    trampolines: Vec<(FunctionType, usize, usize)>,
+}
+
+impl SharedTrampolineTable {
+    /// Creates an empty table of the trampolines in the `len` bytes of
+    /// shared memory starting at `base`.
+    ///
+    /// # Safety
+    ///
+    /// The region must be mapped as executable in this process for as long
+    /// as any engine the table is imported into is alive, and every
+    /// trampoline added to the table must be code identical to what an
+    /// engine of this process would generate for its signature.
+    pub unsafe fn new(base: *const u8, len: usize) -> Self {
+        Self {
+            base: base as usize,
+            len,
+            trampolines: Vec::new(),
+        }
+    }
+
+    /// Adds the trampoline for `signature`, whose `len` bytes are found
+    /// `offset` bytes into the shared region.
+    pub fn add(&mut self, signature: FunctionType, offset: usize, len: usize) {
+        self.trampolines.push((signature, offset, len));
+    }
+
+    /// The address of the start of the shared region.
+    pub fn base(&self) -> *const u8 {
+        self.base as *const u8
+    }
+
+    /// The length of the shared region, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the shared region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The trampolines in the table, with the offset and length of each in
+    /// the shared region.
+    pub fn trampolines(&self) -> &[(FunctionType, usize, usize)] {
+        &self.trampolines
+    }
+}
+
+/// A preference for backing the executable code of an `Engine` with huge
+/// pages, which reduces the TLB misses of large modules.
+///
+/// It only applies to code memory of at least `min_size` bytes, mapped on
+/// Linux at no requested address and outside of a [`CodeMemoryPool`]. When
+/// huge pages aren't available, normal pages are used instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HugePages {
+    /// Whether to map the code from the huge pages reserved by the system
+    /// (`MAP_HUGETLB`), rather than advising the kernel to use transparent
+    /// huge pages.
+    pub explicit: bool,
+    /// The minimum size of the code memory, in bytes, to use huge pages for.
+    pub min_size: usize,
+}
+
+impl HugePages {
+    /// Prefers transparent huge pages, for code memory of at least a huge
+    /// page.
+    pub fn transparent() -> Self {
+        Self {
+            explicit: false,
+            min_size: HUGE_PAGE_SIZE,
+        }
+    }
+
+    /// Prefers reserved huge pages, for code memory of at least a huge
+    /// page.
+    ///
+    /// The executable code and the data sections are then separated on a
+    /// huge page boundary, so that they can be protected differently.
+    pub fn explicit() -> Self {
+        Self {
+            explicit: true,
+            min_size: HUGE_PAGE_SIZE,
+        }
+    }
+
+    /// Only uses huge pages for code memory of at least `min_size` bytes.
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+}
+
+impl Default for HugePages {
+    fn default() -> Self {
+        Self::transparent()
+    }
+}
+
+/// A pool of the memory mappings of dropped [`CodeMemory`]s, recycled for
+/// the code of new modules instead of being unmapped.
+///
+/// Mappings are sized in classes of a power of two pages, and are reused
+/// for code of the same class. A mapping is made read-write and zeroed
+/// when it is returned to the pool, so that stale code can never run
+/// again. The pool can be shared by several engines, see
+/// [`Engine::new_with_pool`](crate::Engine::new_with_pool).
+pub struct CodeMemoryPool {
+    free: Mutex<HashMap<usize, Vec<Mmap>>>,
+    max_cached: usize,
+    cached: AtomicUsize,
+    mappings: AtomicUsize,
+}
+
+impl CodeMemoryPool {
+    /// Creates a pool keeping at most `max_cached` unused mappings, the
+    /// others being unmapped.
+    pub fn new(max_cached: usize) -> Self {
+        Self {
+            free: Mutex::new(HashMap::new()),
+            max_cached,
+            cached: AtomicUsize::new(0),
+            mappings: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of mappings the pool created so far.
+    pub fn mappings(&self) -> usize {
+        self.mappings.load(SeqCst)
+    }
+
+    /// The number of unused mappings currently kept by the pool.
+    pub fn cached(&self) -> usize {
+        self.cached.load(SeqCst)
+    }
+
+    fn size_class(len: usize) -> usize {
+        let page_size = region::page::size();
+        round_up(len, page_size).next_power_of_two()
+    }
+
+    /// Takes a read-write mapping of at least `len` bytes, reusing an
+    /// unused one when possible.
+    fn take(&self, len: usize) -> Result<Mmap, String> {
+        if len == 0 {
+            return Ok(Mmap::new());
+        }
+        let class = Self::size_class(len);
+        if let Some(mmap) = self
+            .free
+            .lock()
+            .unwrap()
+            .get_mut(&class)
+            .and_then(|free| free.pop())
+        {
+            self.cached.fetch_sub(1, SeqCst);
+            return Ok(mmap);
+        }
+        let mmap = Mmap::with_at_least(class)?;
+        self.mappings.fetch_add(1, SeqCst);
+        Ok(mmap)
+    }
+
+    /// Returns a mapping taken from this pool, unmapping it if the pool is
+    /// full.
+    fn give_back(&self, mut mmap: Mmap) {
+        if mmap.is_empty() {
+            return unmap(mmap);
+        }
+        // Reserve a place in the pool first, so that mappings given back
+        // concurrently can't take it too.
+        let reserved = self
+            .cached
+            .fetch_update(SeqCst, SeqCst, |cached| {
+                (cached < self.max_cached).then(|| cached + 1)
+            })
+            .is_ok();
+        if !reserved {
+            return unmap(mmap);
+        }
+        // Nothing may execute the code left in the mapping anymore.
+        if !wipe(&mut mmap) {
+            self.cached.fetch_sub(1, SeqCst);
+            return;
+        }
+        self.free
+            .lock()
+            .unwrap()
+            .entry(mmap.len())
+            .or_default()
+            .push(mmap);
+    }
+}
+
+/// Makes `mmap` read-write and zeroes it, code and data alike, returning
+/// whether it succeeded.
+fn wipe(mmap: &mut Mmap) -> bool {
+    if mmap.is_empty() {
+        return true;
+    }
+    let reprotected = unsafe {
+        region::protect(
+            mmap.as_mut_ptr(),
+            mmap.len(),
+            region::Protection::READ_WRITE,
+        )
+    };
+    if reprotected.is_err() {
+        return false;
+    }
+    mmap.as_mut_slice().fill(0);
+    true
+}
+
+/// Reads a byte of every page of the `len` bytes at `ptr`, so that the
+/// pages are resident once it returns.
+pub(crate) fn touch_pages(ptr: *const u8, len: usize) {
+    let page_size = region::page::size();
+    for offset in (0..len).step_by(page_size) {
+        unsafe { std::ptr::read_volatile(ptr.add(offset)) };
+    }
+}
+
+/// Unmaps `mmap`, zeroing it first with the `secure-wipe` feature so that
+/// its contents don't linger in physical memory until it is reused.
+fn unmap(mut mmap: Mmap) {
+    #[cfg(feature = "secure-wipe")]
+    wipe(&mut mmap);
+    drop(mmap);
+}
+
 /// Memory manager for executable code.
+///
+/// Dropping it deregisters the unwind information of its functions, then
+/// unmaps its memory, or returns it to its pool.
 pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
     mmap: Mmap,
     start_of_nonexecutable_pages: usize,
+    base_address: Option<usize>,
+    /// The pool the mapping comes from and is returned to, if any.
+    pool: Option<Arc<CodeMemoryPool>>,
+    /// The preference for huge pages, if any.
+    huge_pages: Option<HugePages>,
+    /// Whether the mapping is backed by huge pages, as far as known.
+    huge_page_backed: bool,
+    /// Whether code was allocated in the mapping, which can't be replaced
+    /// anymore then.
+    allocated: bool,
+    /// The functions to describe to the debugger once published.
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit_symbols: Vec<GdbJitSymbol>,
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit_registration: Option<GdbJitRegistration>,
+    /// The `.debug_line` section of the code, if built.
+    #[cfg(feature = "debug-info")]
+    debug_line: Vec<u8>,
 }
 
 impl CodeMemory {
@@ -31,7 +338,120 @@ impl CodeMemory {
             unwind_registry: UnwindRegistry::new(),
             mmap: Mmap::new(),
             start_of_nonexecutable_pages: 0,
+            base_address: None,
+            pool: None,
+            huge_pages: None,
+            huge_page_backed: false,
+            allocated: false,
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit_symbols: Vec::new(),
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit_registration: None,
+            #[cfg(feature = "debug-info")]
+            debug_line: Vec::new(),
+        }
+    }
+
+    /// Create a new `CodeMemory` instance whose memory is mapped exactly at
+    /// `address`, which must be page-aligned.
+    pub fn new_at(address: usize) -> Self {
+        let mut code_memory = Self::new();
+        code_memory.base_address = Some(address);
+        code_memory
+    }
+
+    /// Create a new `CodeMemory` instance whose memory comes from `pool`,
+    /// and is returned to it when dropped.
+    pub fn with_pool(pool: Arc<CodeMemoryPool>) -> Self {
+        let mut code_memory = Self::new();
+        code_memory.pool = Some(pool);
+        code_memory
+    }
+
+    /// Create a new `CodeMemory` instance whose memory is backed by huge
+    /// pages according to `huge_pages`, when possible.
+    pub fn with_huge_pages(huge_pages: HugePages) -> Self {
+        let mut code_memory = Self::new();
+        code_memory.huge_pages = Some(huge_pages);
+        code_memory
+    }
+
+    /// Whether the memory of this `CodeMemory` was mapped with huge pages.
+    ///
+    /// With transparent huge pages, this only means that the kernel was
+    /// advised to use them.
+    pub fn uses_huge_pages(&self) -> bool {
+        self.huge_page_backed
+    }
+
+    /// Checks that a page can be mapped at `address`, which must be
+    /// page-aligned. The page is unmapped right away.
+    pub fn probe_address(address: usize) -> Result<(), String> {
+        Mmap::with_at_least_at(address, region::page::size()).map(drop)
+    }
+
+    /// The number of bytes mapped by this `CodeMemory`.
+    pub fn mapped_len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// The number of bytes of executable code at the start of the mapping,
+    /// which [`CodeMemory::publish`] makes executable.
+    pub fn executable_len(&self) -> usize {
+        self.start_of_nonexecutable_pages
+    }
+
+    /// Faults in every page of the mapping, code and data alike.
+    pub fn warm(&self) {
+        touch_pages(self.mmap.as_ptr(), self.mmap.len());
+    }
+
+    /// Returns the current permissions of the memory mapped by this
+    /// `CodeMemory`, region by region.
+    ///
+    /// If they can't be queried, the whole mapping is reported as readable,
+    /// writable and executable, so that permission checks fail closed.
+    pub fn region_permissions(&self) -> Vec<RegionPermission> {
+        if self.mmap.is_empty() {
+            return vec![];
         }
+        let mapping_start = self.mmap.as_ptr() as usize;
+        let mapping_end = mapping_start + self.mmap.len();
+        let unknown = RegionPermission {
+            start: mapping_start,
+            len: self.mmap.len(),
+            readable: true,
+            writable: true,
+            executable: true,
+        };
+        let regions = match region::query_range(self.mmap.as_ptr(), self.mmap.len()) {
+            Ok(regions) => regions,
+            Err(_) => return vec![unknown],
+        };
+        regions
+            .map(|region| {
+                // Adjacent mappings with the same permissions may be
+                // reported as a single region, so clamp it to this one.
+                let region = region?;
+                let range = region.as_range();
+                let start = range.start.max(mapping_start);
+                let end = range.end.min(mapping_end);
+                let protection = region.protection();
+                Ok(RegionPermission {
+                    start,
+                    len: end - start,
+                    readable: protection.contains(region::Protection::READ),
+                    writable: protection.contains(region::Protection::WRITE),
+                    executable: protection.contains(region::Protection::EXECUTE),
+                })
+            })
+            .collect::<Result<Vec<_>, region::Error>>()
+            .unwrap_or_else(|_| vec![unknown])
+    }
+
+    /// The functions of this `CodeMemory` registered with the unwinder.
+    pub fn unwind_entries(&self) -> &[UnwindEntry] {
+        self.unwind_registry.entries()
     }
 
     /// Mutably get the UnwindRegistry.
@@ -51,7 +471,9 @@ impl CodeMemory {
         let mut data_section_result = vec![];
         let mut executable_section_result = vec![];
 
-        let page_size = region::page::size();
+        if self.allocated {
+            return Err("the code memory is already allocated".to_string());
+        }
 
         // 1. Calculate the total size, that is:
         // - function body size, including all trampolines
@@ -63,23 +485,24 @@ impl CodeMemory {
         // - data section body size
         // -- padding between data sections
 
-        let total_len = round_up(
-            functions.iter().fold(0, |acc, func| {
-                round_up(
-                    acc + Self::function_allocation_size(func),
-                    ARCH_FUNCTION_ALIGNMENT,
-                )
-            }) + executable_sections.iter().fold(0, |acc, exec| {
-                round_up(acc + exec.bytes.len(), ARCH_FUNCTION_ALIGNMENT)
-            }),
-            page_size,
-        ) + data_sections.iter().fold(0, |acc, data| {
+        let code_len = functions.iter().fold(0, |acc, func| {
+            round_up(
+                acc + Self::function_allocation_size(func),
+                ARCH_FUNCTION_ALIGNMENT,
+            )
+        }) + executable_sections.iter().fold(0, |acc, exec| {
+            round_up(acc + exec.bytes.len(), ARCH_FUNCTION_ALIGNMENT)
+        });
+        let data_len = data_sections.iter().fold(0, |acc, data| {
             round_up(acc + data.bytes.len(), DATA_SECTION_ALIGNMENT)
         });
 
-        // 2. Allocate the pages. Mark them all read-write.
+        // 2. Allocate the pages, unless they were reserved already. Mark
+        // them all read-write.
 
-        self.mmap = Mmap::with_at_least(total_len)?;
+        self.reserve_pages(code_len, data_len)?;
+        self.allocated = true;
+        let page_size = self.protection_granularity();
 
         // 3. Determine where the pointers to each function, executable section
         // or data section are. Copy the functions. Collect the addresses of each and return them.
@@ -136,16 +559,138 @@ impl CodeMemory {
         ))
     }
 
+    /// Maps at least `total_bytes` of read-write memory up front for the
+    /// code and data of [`CodeMemory::allocate`], which then only maps
+    /// memory again if they don't fit.
+    ///
+    /// This fails once code was allocated, so that a reservation never
+    /// unmaps code that may be running.
+    pub fn reserve(&mut self, total_bytes: usize) -> Result<(), String> {
+        if self.allocated {
+            return Err("the code memory is already allocated".to_string());
+        }
+        self.reserve_pages(total_bytes, 0)
+    }
+
+    /// Maps memory for `code_len` bytes of executable code followed by
+    /// `data_len` bytes of data, unless the current mapping fits them.
+    fn reserve_pages(&mut self, code_len: usize, data_len: usize) -> Result<(), String> {
+        if round_up(code_len, self.protection_granularity()) + data_len <= self.mmap.len() {
+            return Ok(());
+        }
+        // The previous reservation is released first, since the new one may
+        // need its addresses.
+        let previous = std::mem::replace(&mut self.mmap, Mmap::new());
+        self.release(previous);
+        self.huge_page_backed = false;
+
+        let total_len = round_up(code_len, region::page::size()) + data_len;
+        self.mmap = match (self.base_address, &self.pool) {
+            (Some(address), _) => Mmap::with_at_least_at(address, total_len)?,
+            (None, Some(pool)) => pool.take(total_len)?,
+            (None, None) => match self.map_huge_pages(code_len, data_len) {
+                Some(mmap) => {
+                    self.huge_page_backed = true;
+                    mmap
+                }
+                None => Mmap::with_at_least(total_len)?,
+            },
+        };
+        Ok(())
+    }
+
+    /// The granularity at which the protection of the mapping can change,
+    /// which separates the executable code from the data.
+    fn protection_granularity(&self) -> usize {
+        match self.huge_pages {
+            // Reserved huge pages can only be protected a whole page at a
+            // time.
+            Some(huge_pages) if huge_pages.explicit && self.huge_page_backed => HUGE_PAGE_SIZE,
+            _ => region::page::size(),
+        }
+    }
+
+    /// Maps memory backed by huge pages for `code_len` bytes of executable
+    /// code followed by `data_len` bytes of data, if preferred and
+    /// possible.
+    #[cfg(target_os = "linux")]
+    fn map_huge_pages(&self, code_len: usize, data_len: usize) -> Option<Mmap> {
+        let huge_pages = self.huge_pages?;
+        let page_size = if huge_pages.explicit {
+            HUGE_PAGE_SIZE
+        } else {
+            region::page::size()
+        };
+        let total_len = round_up(code_len, page_size) + data_len;
+        if total_len == 0 || total_len < huge_pages.min_size {
+            return None;
+        }
+        Mmap::with_at_least_huge(total_len, huge_pages.explicit).ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn map_huge_pages(&self, _code_len: usize, _data_len: usize) -> Option<Mmap> {
+        None
+    }
+
+    /// Returns `mmap` to the pool, or unmaps it if there is none.
+    fn release(&self, mmap: Mmap) {
+        match &self.pool {
+            Some(pool) => pool.give_back(mmap),
+            None => unmap(mmap),
+        }
+    }
+
+    /// Sets the functions to register with the GDB JIT interface when the
+    /// code is published.
+    #[cfg(feature = "gdb-jit")]
+    pub(crate) fn set_gdb_jit_symbols(&mut self, symbols: Vec<GdbJitSymbol>) {
+        self.gdb_jit_symbols = symbols;
+    }
+
+    /// Sets the `.debug_line` section of the code, which is described to
+    /// the debugger along with the functions when the code is published.
+    #[cfg(feature = "debug-info")]
+    pub(crate) fn set_debug_line(&mut self, debug_line: Vec<u8>) {
+        self.debug_line = debug_line;
+    }
+
+    /// The `.debug_line` section of the code, empty if none was built.
+    #[cfg(feature = "debug-info")]
+    pub(crate) fn debug_line(&self) -> &[u8] {
+        &self.debug_line
+    }
+
     /// Apply the page permissions.
     pub fn publish(&mut self) {
+        #[cfg(feature = "gdb-jit")]
+        if !self.gdb_jit_symbols.is_empty() && self.gdb_jit_registration.is_none() {
+            #[cfg(feature = "debug-info")]
+            let debug_line = self.debug_line.as_slice();
+            #[cfg(not(feature = "debug-info"))]
+            let debug_line = &[][..];
+            // This is only a debugging aid, so code that can't be described
+            // to the debugger still runs.
+            self.gdb_jit_registration = GdbJitRegistration::register(
+                &std::mem::take(&mut self.gdb_jit_symbols),
+                debug_line,
+            )
+            .ok();
+        }
         if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
             return;
         }
         assert!(self.mmap.len() >= self.start_of_nonexecutable_pages);
+        // The data sections start on the next protection boundary, if any.
+        let executable_len = round_up(
+            self.start_of_nonexecutable_pages,
+            self.protection_granularity(),
+        )
+        .min(self.mmap.len());
         unsafe {
             region::protect(
                 self.mmap.as_mut_ptr(),
-                self.start_of_nonexecutable_pages,
+                executable_len,
                 region::Protection::READ_EXECUTE,
             )
         }
@@ -214,11 +759,123 @@ fn round_up(size: usize, multiple: usize) -> usize {
     (size + (multiple - 1)) & !(multiple - 1)
 }
 
+impl Drop for CodeMemory {
+    fn drop(&mut self) {
+        // The unwinder and the debugger may still point into the mapping,
+        // so they must forget about the functions before their memory goes
+        // away.
+        #[cfg(feature = "gdb-jit")]
+        drop(self.gdb_jit_registration.take());
+        self.unwind_registry.deregister();
+        let mmap = std::mem::replace(&mut self.mmap, Mmap::new());
+        self.release(mmap);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::CodeMemory;
+    #[cfg(target_os = "linux")]
+    use super::HugePages;
+    use super::{CodeMemory, CodeMemoryPool};
+    use std::sync::Arc;
+    use wasmer_types::FunctionBody;
+    #[cfg(target_os = "linux")]
+    use wasmer_vm::HUGE_PAGE_SIZE;
+
     fn _assert() {
         fn _assert_send_sync<T: Send + Sync>() {}
         _assert_send_sync::<CodeMemory>();
     }
+
+    #[test]
+    fn allocate_uses_the_reserved_memory() {
+        let body = FunctionBody {
+            body: vec![0xc3; 3 * region::page::size()],
+            unwind_info: None,
+        };
+        let mut code_memory = CodeMemory::new();
+        code_memory.reserve(1 << 20).unwrap();
+        let reserved = (code_memory.mmap.as_ptr(), code_memory.mapped_len());
+        code_memory.allocate(&[&body], &[], &[]).unwrap();
+        assert_eq!(
+            (code_memory.mmap.as_ptr(), code_memory.mapped_len()),
+            reserved
+        );
+
+        // Published code is never unmapped by a reservation.
+        code_memory.publish();
+        assert!(code_memory.reserve(2 << 20).is_err());
+        assert_eq!(code_memory.mmap.as_ptr(), reserved.0);
+    }
+
+    #[test]
+    fn allocate_maps_more_than_a_small_reservation() {
+        let body = FunctionBody {
+            body: vec![0xc3; 3 * region::page::size()],
+            unwind_info: None,
+        };
+        let mut code_memory = CodeMemory::new();
+        code_memory.reserve(1).unwrap();
+        code_memory.allocate(&[&body], &[], &[]).unwrap();
+        assert!(code_memory.mapped_len() >= body.body.len());
+    }
+
+    #[test]
+    fn recycled_mappings_no_longer_hold_the_code() {
+        let body = FunctionBody {
+            body: vec![0xc3; 64],
+            unwind_info: None,
+        };
+        let pool = Arc::new(CodeMemoryPool::new(1));
+        let mut code_memory = CodeMemory::with_pool(pool.clone());
+        code_memory.allocate(&[&body], &[], &[]).unwrap();
+        code_memory.publish();
+        assert!(code_memory.mmap.as_slice().contains(&0xc3));
+        let (ptr, len) = (code_memory.mmap.as_ptr(), code_memory.mapped_len());
+        drop(code_memory);
+
+        assert_eq!(pool.cached(), 1);
+        let mmap = pool.take(len).unwrap();
+        assert_eq!(mmap.as_ptr(), ptr);
+        assert!(mmap.as_slice().iter().all(|byte| *byte == 0));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn huge_pages_fall_back_to_normal_pages() {
+        let body = FunctionBody {
+            body: vec![0xc3; HUGE_PAGE_SIZE + 1],
+            unwind_info: None,
+        };
+        for huge_pages in [HugePages::transparent(), HugePages::explicit()] {
+            let mut code_memory = CodeMemory::with_huge_pages(huge_pages);
+            code_memory.allocate(&[&body], &[], &[]).unwrap();
+            // Huge pages may not be available, but the code is mapped
+            // either way.
+            assert!(code_memory.mapped_len() > HUGE_PAGE_SIZE);
+            if code_memory.uses_huge_pages() {
+                assert_eq!(code_memory.mapped_len() % HUGE_PAGE_SIZE, 0);
+            }
+            code_memory.publish();
+            let executable = code_memory
+                .region_permissions()
+                .into_iter()
+                .find(|region| region.executable)
+                .unwrap();
+            assert!(executable.len > HUGE_PAGE_SIZE);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn huge_pages_only_apply_above_the_threshold() {
+        let body = FunctionBody {
+            body: vec![0xc3; 64],
+            unwind_info: None,
+        };
+        let mut code_memory = CodeMemory::with_huge_pages(HugePages::transparent());
+        code_memory.allocate(&[&body], &[], &[]).unwrap();
+        assert!(!code_memory.uses_huge_pages());
+        assert!(code_memory.mapped_len() < HUGE_PAGE_SIZE);
+    }
 }
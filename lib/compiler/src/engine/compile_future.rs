@@ -0,0 +1,166 @@
+//! Compilation of modules off the calling thread, for async hosts.
+
+use crate::{Artifact, Engine, Tunables};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use wasmer_types::CompileError;
+
+lazy_static::lazy_static! {
+    /// The threads compiling the modules of every engine.
+    static ref COMPILE_POOL: CompilePool = CompilePool::default();
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of at most one thread per core running compilations, which are
+/// queued while every thread is busy.
+///
+/// The threads are started as compilations are queued, and are kept for
+/// the lifetime of the process.
+#[derive(Default)]
+struct CompilePool {
+    queue: Mutex<PoolQueue>,
+    queued: Condvar,
+}
+
+#[derive(Default)]
+struct PoolQueue {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+impl CompilePool {
+    /// Queues `job`, starting a thread to run it if the idle threads can't
+    /// take every queued job and the pool isn't full.
+    ///
+    /// Fails if the pool has no thread and none could be started, in which
+    /// case `job` is dropped.
+    fn execute(&'static self, job: Job) -> io::Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        queue.jobs.push_back(job);
+        let max_threads = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        if queue.jobs.len() > queue.idle && queue.threads < max_threads {
+            let spawned = std::thread::Builder::new()
+                .name("wasmer-compile".to_string())
+                .spawn(move || self.work());
+            match spawned {
+                Ok(_) => queue.threads += 1,
+                // The threads already running take the job eventually.
+                Err(_) if queue.threads > 0 => {}
+                Err(error) => {
+                    queue.jobs.pop_back();
+                    return Err(error);
+                }
+            }
+        }
+        self.queued.notify_one();
+        Ok(())
+    }
+
+    /// Runs the queued jobs, waiting for more when there are none.
+    fn work(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            match queue.jobs.pop_front() {
+                Some(job) => {
+                    drop(queue);
+                    job();
+                    queue = self.queue.lock().unwrap();
+                }
+                None => {
+                    queue.idle += 1;
+                    queue = self.queued.wait(queue).unwrap();
+                    queue.idle -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// The state shared by a [`CompileFuture`] and the thread compiling.
+#[derive(Default)]
+struct CompileState {
+    cancelled: AtomicBool,
+    outcome: Mutex<Outcome>,
+}
+
+#[derive(Default)]
+struct Outcome {
+    result: Option<Result<Arc<Artifact>, CompileError>>,
+    waker: Option<Waker>,
+}
+
+/// A future resolving to an artifact compiled on another thread.
+///
+/// The compilations of every engine share a pool of at most one thread
+/// per core, and wait for a thread to be free when they are all busy.
+/// Dropping the future before the compilation started cancels it. The compilers
+/// can't be interrupted, so a compilation already running finishes and its
+/// artifact is discarded.
+///
+/// See [`Engine::compile_async`].
+pub struct CompileFuture {
+    state: Arc<CompileState>,
+}
+
+impl CompileFuture {
+    pub(crate) fn spawn(
+        engine: Engine,
+        binary: Vec<u8>,
+        tunables: Arc<dyn Tunables + Send + Sync>,
+    ) -> Self {
+        let state = Arc::new(CompileState::default());
+        let worker_state = state.clone();
+        let queued = COMPILE_POOL.execute(Box::new(move || {
+            if worker_state.cancelled.load(SeqCst) {
+                return;
+            }
+            let result = engine.compile(&binary, &*tunables);
+            worker_state.resolve(result);
+        }));
+        if let Err(error) = queued {
+            state.resolve(Err(CompileError::Resource(format!(
+                "failed to spawn a compilation thread: {}",
+                error
+            ))));
+        }
+        Self { state }
+    }
+}
+
+impl CompileState {
+    fn resolve(&self, result: Result<Arc<Artifact>, CompileError>) {
+        let mut outcome = self.outcome.lock().unwrap();
+        outcome.result = Some(result);
+        if let Some(waker) = outcome.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for CompileFuture {
+    type Output = Result<Arc<Artifact>, CompileError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut outcome = self.state.outcome.lock().unwrap();
+        match outcome.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                outcome.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for CompileFuture {
+    fn drop(&mut self) {
+        self.state.cancelled.store(true, SeqCst);
+    }
+}
@@ -0,0 +1,332 @@
+//! Generation of a DWARF [`.debug_line`] section mapping the compiled code
+//! back to the offsets of the WebAssembly instructions it was compiled from,
+//! for debuggers and profilers.
+//!
+//! The line program describes a single file named after the module, whose
+//! "lines" are the byte offsets of the instructions in the wasm binary.
+//!
+//! [`.debug_line`]: https://dwarfstd.org/doc/DWARF4.pdf
+
+use super::trap::FunctionExtent;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{CompiledFunctionFrameInfo, LocalFunctionIndex, ModuleInfo, SourceLoc};
+
+const VERSION: u16 = 4;
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; OPCODE_BASE as usize - 1] =
+    [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+/// Builds the `.debug_line` section of the functions of a module, with one
+/// sequence per function body.
+///
+/// Every function gets an address range, even when the compiler recorded no
+/// wasm offsets for its code, or the module has no name section: such code
+/// is attributed to line 0, which DWARF reserves for unknown locations.
+pub(crate) fn line_program(
+    module: &ModuleInfo,
+    functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    frame_info: &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+) -> Vec<u8> {
+    let file_name = format!("{}.wasm", module.name.as_deref().unwrap_or("wasm"));
+
+    let mut section = Vec::new();
+    // The unit and header lengths are filled in once known.
+    section.extend_from_slice(&0u32.to_ne_bytes());
+    section.extend_from_slice(&VERSION.to_ne_bytes());
+    section.extend_from_slice(&0u32.to_ne_bytes());
+    let header_start = section.len();
+    section.extend_from_slice(&[1, 1, 1, LINE_BASE as u8, LINE_RANGE, OPCODE_BASE]);
+    section.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+    // No include directories.
+    section.push(0);
+    section.extend_from_slice(file_name.as_bytes());
+    // The terminator of the name, then no directory, time and size.
+    section.extend_from_slice(&[0, 0, 0, 0]);
+    // The end of the file names.
+    section.push(0);
+    let header_len = (section.len() - header_start) as u32;
+    section[header_start - 4..header_start].copy_from_slice(&header_len.to_ne_bytes());
+
+    // Deduplicated functions share their body, which is only described once.
+    let mut described = HashSet::new();
+    for (index, extent) in functions.iter() {
+        if extent.length == 0 || !described.insert(extent.ptr.0 as usize) {
+            continue;
+        }
+        let rows = function_rows(extent.length, frame_info.get(index));
+        write_sequence(&mut section, extent.ptr.0 as usize, extent.length, &rows);
+    }
+
+    let unit_len = (section.len() - 4) as u32;
+    section[..4].copy_from_slice(&unit_len.to_ne_bytes());
+    section
+}
+
+/// The rows of the line table of a function, as code offsets and the wasm
+/// offsets they were compiled from, in increasing code offset order and
+/// starting at offset 0.
+fn function_rows(
+    length: usize,
+    frame_info: Option<&CompiledFunctionFrameInfo>,
+) -> Vec<(usize, u32)> {
+    let known = |srcloc: SourceLoc| {
+        if srcloc.is_default() {
+            0
+        } else {
+            srcloc.bits()
+        }
+    };
+    let instructions = frame_info.map_or(&[][..], |info| &info.address_map.instructions[..]);
+    let mut rows: Vec<(usize, u32)> = Vec::new();
+    for instruction in instructions {
+        if instruction.srcloc.is_default() || instruction.code_offset >= length {
+            continue;
+        }
+        let line = instruction.srcloc.bits();
+        match rows.last() {
+            Some(&(offset, last_line))
+                if instruction.code_offset <= offset || line == last_line => {}
+            _ => rows.push((instruction.code_offset, line)),
+        }
+    }
+    // Code before the first instruction is attributed to the start of the
+    // function.
+    if rows.first().map_or(true, |row| row.0 != 0) {
+        let start = frame_info.map_or(0, |info| known(info.address_map.start_srcloc));
+        rows.insert(0, (0, start));
+    }
+    rows
+}
+
+fn write_sequence(section: &mut Vec<u8>, address: usize, length: usize, rows: &[(usize, u32)]) {
+    let address_bytes = address.to_ne_bytes();
+    section.push(0);
+    leb128::write::unsigned(section, 1 + address_bytes.len() as u64).unwrap();
+    section.push(DW_LNE_SET_ADDRESS);
+    section.extend_from_slice(&address_bytes);
+
+    // Lines start at 1 in every sequence.
+    let (mut offset, mut line) = (0, 1i64);
+    for &(row_offset, row_line) in rows {
+        if row_offset != offset {
+            section.push(DW_LNS_ADVANCE_PC);
+            leb128::write::unsigned(section, (row_offset - offset) as u64).unwrap();
+        }
+        if i64::from(row_line) != line {
+            section.push(DW_LNS_ADVANCE_LINE);
+            leb128::write::signed(section, i64::from(row_line) - line).unwrap();
+        }
+        section.push(DW_LNS_COPY);
+        offset = row_offset;
+        line = i64::from(row_line);
+    }
+    section.push(DW_LNS_ADVANCE_PC);
+    leb128::write::unsigned(section, (length - offset) as u64).unwrap();
+    section.extend_from_slice(&[0, 1, DW_LNE_END_SEQUENCE]);
+}
+
+/// Resolves `address` to the wasm offset it was compiled from, by running
+/// the line programs of a `.debug_line` section.
+///
+/// Returns `None` if no sequence covers `address`, or if the row covering
+/// it has no known location.
+pub(crate) fn lookup(mut section: &[u8], address: usize) -> Option<u32> {
+    while !section.is_empty() {
+        let unit_len = read_u32(&mut section)? as usize;
+        if unit_len > section.len() {
+            return None;
+        }
+        let (unit, rest) = section.split_at(unit_len);
+        section = rest;
+        if let Some(line) = lookup_in_unit(unit, address)? {
+            return u32::try_from(line).ok().filter(|line| *line != 0);
+        }
+    }
+    None
+}
+
+/// Returns `Some(None)` if no row of the unit with a valid line covers
+/// `address`, and `None` if the unit is malformed.
+fn lookup_in_unit(mut unit: &[u8], address: usize) -> Option<Option<u64>> {
+    let version = read_bytes(&mut unit, 2).map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let header_len = read_u32(&mut unit)? as usize;
+    let mut program = unit.get(header_len..)?;
+    let header = &mut unit;
+    let min_instruction_len = u64::from(read_u8(header)?);
+    if version >= 4 {
+        // The maximum number of operations per instruction.
+        read_u8(header)?;
+    }
+    // Whether rows are statements by default.
+    read_u8(header)?;
+    let line_base = read_u8(header)? as i8;
+    let line_range = read_u8(header)?;
+    let opcode_base = read_u8(header)?;
+    if line_range == 0 || opcode_base == 0 {
+        return None;
+    }
+    let opcode_lengths = read_bytes(header, opcode_base as usize - 1)?;
+
+    let pointer_bytes = std::mem::size_of::<usize>();
+    let (mut row_address, mut line) = (0u64, 1i64);
+    let mut previous: Option<(u64, i64)> = None;
+    let target = address as u64;
+    // Emits a row, returning the line of the previous row if it covers the
+    // target address.
+    let mut emit = |row_address: u64, line: i64, end_sequence: bool| {
+        let covering = previous
+            .filter(|(start, _)| (*start..row_address).contains(&target))
+            .map(|(_, line)| line);
+        previous = if end_sequence {
+            None
+        } else {
+            Some((row_address, line))
+        };
+        covering
+    };
+    while !program.is_empty() {
+        let opcode = read_u8(&mut program)?;
+        let covering = if opcode >= opcode_base {
+            let adjusted = opcode - opcode_base;
+            row_address += u64::from(adjusted / line_range) * min_instruction_len;
+            line += i64::from(line_base) + i64::from(adjusted % line_range);
+            emit(row_address, line, false)
+        } else {
+            match opcode {
+                0 => {
+                    let len = leb128::read::unsigned(&mut program).ok()? as usize;
+                    let mut instruction = read_bytes(&mut program, len)?;
+                    match read_u8(&mut instruction)? {
+                        DW_LNE_END_SEQUENCE => {
+                            let covering = emit(row_address, line, true);
+                            row_address = 0;
+                            line = 1;
+                            covering
+                        }
+                        DW_LNE_SET_ADDRESS if instruction.len() == pointer_bytes => {
+                            let mut bytes = [0; std::mem::size_of::<usize>()];
+                            bytes.copy_from_slice(instruction);
+                            row_address = usize::from_ne_bytes(bytes) as u64;
+                            None
+                        }
+                        _ => None,
+                    }
+                }
+                DW_LNS_COPY => emit(row_address, line, false),
+                DW_LNS_ADVANCE_PC => {
+                    row_address += leb128::read::unsigned(&mut program).ok()? * min_instruction_len;
+                    None
+                }
+                DW_LNS_ADVANCE_LINE => {
+                    line += leb128::read::signed(&mut program).ok()?;
+                    None
+                }
+                DW_LNS_CONST_ADD_PC => {
+                    row_address +=
+                        u64::from((255 - opcode_base) / line_range) * min_instruction_len;
+                    None
+                }
+                DW_LNS_FIXED_ADVANCE_PC => {
+                    let bytes = read_bytes(&mut program, 2)?;
+                    row_address += u64::from(u16::from_ne_bytes([bytes[0], bytes[1]]));
+                    None
+                }
+                _ => {
+                    // Skips the LEB128 operands of the other standard
+                    // opcodes.
+                    for _ in 0..opcode_lengths[opcode as usize - 1] {
+                        leb128::read::unsigned(&mut program).ok()?;
+                    }
+                    None
+                }
+            }
+        };
+        if let Some(line) = covering {
+            return Some(u64::try_from(line).ok());
+        }
+    }
+    Some(None)
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Some(bytes)
+}
+
+fn read_u8(data: &mut &[u8]) -> Option<u8> {
+    read_bytes(data, 1).map(|bytes| bytes[0])
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    read_bytes(data, 4).map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::{FunctionAddressMap, InstructionAddressMap};
+    use wasmer_vm::FunctionBodyPtr;
+
+    fn instruction(wasm_offset: u32, code_offset: usize, code_len: usize) -> InstructionAddressMap {
+        InstructionAddressMap {
+            srcloc: SourceLoc::new(wasm_offset),
+            code_offset,
+            code_len,
+        }
+    }
+
+    fn function(address: usize, length: usize) -> FunctionExtent {
+        FunctionExtent {
+            ptr: FunctionBodyPtr(address as *const _),
+            length,
+        }
+    }
+
+    #[test]
+    fn lookup_resolves_the_rows_of_every_function() {
+        let mut functions = PrimaryMap::new();
+        functions.push(function(0x1000, 0x40));
+        functions.push(function(0x2000, 0x10));
+        let mut frame_info = PrimaryMap::new();
+        frame_info.push(CompiledFunctionFrameInfo {
+            address_map: FunctionAddressMap {
+                instructions: vec![instruction(0x30, 0x8, 0x8), instruction(0x200, 0x10, 0x30)],
+                start_srcloc: SourceLoc::new(0x2a),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        // The code of the second function isn't mapped to instructions.
+        frame_info.push(CompiledFunctionFrameInfo::default());
+        let section = line_program(&ModuleInfo::new(), &functions, &frame_info);
+
+        assert_eq!(lookup(&section, 0x1000), Some(0x2a));
+        assert_eq!(lookup(&section, 0x1008), Some(0x30));
+        assert_eq!(lookup(&section, 0x100f), Some(0x30));
+        assert_eq!(lookup(&section, 0x103f), Some(0x200));
+        assert_eq!(lookup(&section, 0x1040), None);
+        assert_eq!(lookup(&section, 0x1fff), None);
+        // The second function has an address range, without a location.
+        assert_eq!(lookup(&section, 0x2000), None);
+        assert_eq!(lookup(&section, 0xfff), None);
+    }
+}
@@ -27,6 +27,31 @@ pub enum LinkError {
     Resource(String),
 }
 
+/// An error while configuring the memory that holds compiled code.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CodeMemoryError {
+    /// The requested address is not aligned to a page boundary.
+    #[error("The address {0:#x} is not page-aligned")]
+    UnalignedAddress(usize),
+
+    /// The requested address can not be mapped, for example because it is
+    /// already in use.
+    #[error("The address {0:#x} is not available: {1}")]
+    UnavailableAddress(usize, String),
+
+    /// A shared trampoline is outside of the shared region it should be in.
+    #[error("The trampoline at offset {0:#x} is outside of the shared region")]
+    OutsideSharedRegion(usize),
+}
+
+/// An error while building an `Engine` from an `EngineBuilder`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EngineBuilderError {
+    /// A compiler was set for an engine built as headless.
+    #[error("A headless engine can't have a compiler")]
+    CompilerInHeadlessEngine,
+}
+
 /// An error while instantiating a module.
 ///
 /// This is not a common WebAssembly error, however
@@ -45,6 +70,11 @@ pub enum InstantiationError {
     #[error("module compiled with CPU feature that is missing from host")]
     CpuFeature(String),
 
+    /// The module was compiled for another target than the host, whose
+    /// triple is given, so it can only be serialized.
+    #[error("module compiled for another target: {0}")]
+    ForeignTarget(String),
+
     /// A runtime error occured while invoking the start function
     #[cfg(not(target_arch = "wasm32"))]
     #[error(transparent)]
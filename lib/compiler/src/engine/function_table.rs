@@ -0,0 +1,123 @@
+//! A table of the functions compiled by an engine, sorted by address, to
+//! find the function an address in compiled code belongs to.
+
+use wasmer_types::FunctionIndex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FunctionRange {
+    start: usize,
+    len: usize,
+    index: FunctionIndex,
+}
+
+impl FunctionRange {
+    fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+/// The address ranges of compiled functions, sorted and never overlapping.
+#[derive(Debug, Default)]
+pub(crate) struct FunctionTable {
+    ranges: Vec<FunctionRange>,
+}
+
+impl FunctionTable {
+    /// Adds the `(start, len, index)` ranges of functions.
+    ///
+    /// Ranges can't overlap, so an empty range, or one overlapping a range
+    /// added before it, is skipped. Deduplicated functions share a body,
+    /// which is owned by the first of them added.
+    pub(crate) fn insert(
+        &mut self,
+        functions: impl IntoIterator<Item = (usize, usize, FunctionIndex)>,
+    ) {
+        let mut added = functions
+            .into_iter()
+            .filter(|(_, len, _)| *len > 0)
+            .map(|(start, len, index)| FunctionRange { start, len, index })
+            .collect::<Vec<_>>();
+        // The sort is stable, so the first of the ranges starting at the
+        // same address is kept.
+        added.sort_by_key(|range| range.start);
+        let mut accepted: Vec<FunctionRange> = Vec::with_capacity(added.len());
+        for range in added {
+            let overlaps_added = accepted
+                .last()
+                .map_or(false, |last| range.start < last.end());
+            if !overlaps_added && !self.overlaps(&range) {
+                accepted.push(range);
+            }
+        }
+        self.ranges.extend(accepted);
+        // Both parts are sorted already, which the sort takes advantage of.
+        self.ranges.sort_by_key(|range| range.start);
+    }
+
+    /// Whether `range` overlaps a range of the table.
+    fn overlaps(&self, range: &FunctionRange) -> bool {
+        let position = self
+            .ranges
+            .partition_point(|existing| existing.start < range.end());
+        self.ranges[..position]
+            .last()
+            .map_or(false, |existing| existing.end() > range.start)
+    }
+
+    /// The function holding `pc`, and the offset of `pc` in it.
+    pub(crate) fn lookup(&self, pc: usize) -> Option<(FunctionIndex, usize)> {
+        let position = self.ranges.partition_point(|range| range.start <= pc);
+        let range = self.ranges[..position].last()?;
+        let offset = pc - range.start;
+        if offset < range.len {
+            Some((range.index, offset))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::entity::EntityRef;
+
+    fn index(index: usize) -> FunctionIndex {
+        FunctionIndex::new(index)
+    }
+
+    #[test]
+    fn lookup_resolves_interior_addresses() {
+        let mut table = FunctionTable::default();
+        table.insert(vec![(0x2000, 0x10, index(1)), (0x1000, 0x20, index(0))]);
+        table.insert(vec![(0x3000, 0x08, index(2))]);
+
+        assert_eq!(table.lookup(0x0fff), None);
+        assert_eq!(table.lookup(0x1000), Some((index(0), 0)));
+        assert_eq!(table.lookup(0x101f), Some((index(0), 0x1f)));
+        assert_eq!(table.lookup(0x1020), None);
+        assert_eq!(table.lookup(0x2008), Some((index(1), 8)));
+        assert_eq!(table.lookup(0x3007), Some((index(2), 7)));
+        assert_eq!(table.lookup(0x3008), None);
+    }
+
+    #[test]
+    fn overlapping_ranges_keep_the_first_added() {
+        let mut table = FunctionTable::default();
+        table.insert(vec![
+            (0x1000, 0x10, index(0)),
+            // A deduplicated function sharing the body of the first one.
+            (0x1000, 0x10, index(1)),
+            (0x1008, 0x10, index(2)),
+            (0x1010, 0, index(3)),
+        ]);
+        assert_eq!(table.lookup(0x1008), Some((index(0), 8)));
+        assert_eq!(table.lookup(0x1010), None);
+
+        // Ranges starting before an existing one don't replace it either.
+        table.insert(vec![(0x0ff8, 0x10, index(4)), (0x1010, 0x10, index(5))]);
+        assert_eq!(table.lookup(0x0ff8), None);
+        assert_eq!(table.lookup(0x1000), Some((index(0), 0)));
+        assert_eq!(table.lookup(0x1010), Some((index(5), 0)));
+    }
+}
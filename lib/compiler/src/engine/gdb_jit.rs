@@ -0,0 +1,266 @@
+//! Registration of compiled functions with the [GDB JIT interface], so that
+//! debuggers can resolve the names of the functions of a module.
+//!
+//! Every published region of code is described to the debugger by an
+//! in-memory ELF object holding one absolute symbol per function, and the
+//! `.debug_line` section of the code with the `debug-info` feature.
+//!
+//! [GDB JIT interface]: https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html
+
+use super::trap::FunctionExtent;
+use object::write::{Object, Symbol, SymbolSection};
+use object::{
+    Architecture, BinaryFormat, Endianness, SectionKind, SymbolFlags, SymbolKind, SymbolScope,
+};
+use std::ptr;
+use std::sync::Mutex;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{LocalFunctionIndex, ModuleInfo};
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+/// The descriptor the debugger reads the registered entries from, laid out
+/// as the interface requires.
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+/// The list of entries known to the debugger.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The function the debugger breaks on to learn about changes to
+/// [`__jit_debug_descriptor`].
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // Keep the calls from being optimized away.
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the changes to the descriptor.
+    static ref DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A compiled function to describe to the debugger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GdbJitSymbol {
+    pub(crate) name: String,
+    pub(crate) address: usize,
+    pub(crate) len: usize,
+}
+
+/// Names every function of a module after the module and the function,
+/// falling back to their indices when the name section doesn't name them.
+pub(crate) fn function_symbols(
+    module: &ModuleInfo,
+    functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+) -> Vec<GdbJitSymbol> {
+    let module_name = module.name.as_deref().unwrap_or("wasm");
+    functions
+        .iter()
+        .map(|(local_index, extent)| {
+            let index = module.func_index(local_index);
+            let name = match module.function_names.get(&index) {
+                Some(name) => format!("{}::{}", module_name, name),
+                None => format!("{}::function[{}]", module_name, index.index()),
+            };
+            GdbJitSymbol {
+                name,
+                address: extent.ptr.0 as usize,
+                len: extent.length,
+            }
+        })
+        .collect()
+}
+
+/// The registration of a region of code with the debugger, which is
+/// removed when dropped.
+pub(crate) struct GdbJitRegistration {
+    entry: *mut JitCodeEntry,
+    // Read by the debugger through `entry`.
+    _symfile: Box<[u8]>,
+}
+
+// The entry is only accessed while holding `DESCRIPTOR_LOCK`.
+unsafe impl Send for GdbJitRegistration {}
+unsafe impl Sync for GdbJitRegistration {}
+
+impl GdbJitRegistration {
+    /// Describes the given functions to the debugger, with the line table
+    /// of their code if `debug_line` isn't empty.
+    pub(crate) fn register(symbols: &[GdbJitSymbol], debug_line: &[u8]) -> Result<Self, String> {
+        let symfile = symbol_file(symbols, debug_line)?.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: symfile.as_ptr(),
+            symfile_size: symfile.len() as u64,
+        }));
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = &mut *ptr::addr_of_mut!(__jit_debug_descriptor);
+            (*entry).next_entry = descriptor.first_entry;
+            if !descriptor.first_entry.is_null() {
+                (*descriptor.first_entry).prev_entry = entry;
+            }
+            descriptor.first_entry = entry;
+            notify(descriptor, entry, JIT_REGISTER_FN);
+        }
+        Ok(Self {
+            entry,
+            _symfile: symfile,
+        })
+    }
+}
+
+impl Drop for GdbJitRegistration {
+    fn drop(&mut self) {
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = &mut *ptr::addr_of_mut!(__jit_debug_descriptor);
+            let entry = &mut *self.entry;
+            if entry.prev_entry.is_null() {
+                descriptor.first_entry = entry.next_entry;
+            } else {
+                (*entry.prev_entry).next_entry = entry.next_entry;
+            }
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry.prev_entry;
+            }
+            notify(descriptor, self.entry, JIT_UNREGISTER_FN);
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+unsafe fn notify(descriptor: &mut JitDescriptor, entry: *mut JitCodeEntry, action: u32) {
+    descriptor.relevant_entry = entry;
+    descriptor.action_flag = action;
+    __jit_debug_register_code();
+    descriptor.relevant_entry = ptr::null_mut();
+    descriptor.action_flag = JIT_NOACTION;
+}
+
+/// Builds an ELF object with an absolute symbol for every function, and a
+/// `.debug_line` section if `debug_line` isn't empty.
+fn symbol_file(symbols: &[GdbJitSymbol], debug_line: &[u8]) -> Result<Vec<u8>, String> {
+    let architecture = if cfg!(target_arch = "x86_64") {
+        Architecture::X86_64
+    } else if cfg!(target_arch = "aarch64") {
+        Architecture::Aarch64
+    } else if cfg!(target_arch = "x86") {
+        Architecture::I386
+    } else {
+        return Err("the GDB JIT interface isn't supported on this architecture".to_string());
+    };
+    let endianness = if cfg!(target_endian = "little") {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    };
+    let mut object = Object::new(BinaryFormat::Elf, architecture, endianness);
+    for symbol in symbols {
+        object.add_symbol(Symbol {
+            name: symbol.name.as_bytes().to_vec(),
+            value: symbol.address as u64,
+            size: symbol.len as u64,
+            kind: SymbolKind::Text,
+            scope: SymbolScope::Linkage,
+            weak: false,
+            section: SymbolSection::Absolute,
+            flags: SymbolFlags::None,
+        });
+    }
+    if !debug_line.is_empty() {
+        let section = object.add_section(Vec::new(), b".debug_line".to_vec(), SectionKind::Debug);
+        object.append_section_data(section, debug_line, 1);
+    }
+    object.write().map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Walks the entries known to the debugger, newest first, checking that
+    /// the list is well-formed.
+    fn registered_entries() -> Vec<*mut JitCodeEntry> {
+        let mut entries = vec![];
+        unsafe {
+            let descriptor = &*ptr::addr_of!(__jit_debug_descriptor);
+            assert_eq!(descriptor.version, 1);
+            assert_eq!(descriptor.action_flag, JIT_NOACTION);
+            assert!(descriptor.relevant_entry.is_null());
+            let mut previous = ptr::null_mut();
+            let mut entry = descriptor.first_entry;
+            while !entry.is_null() {
+                assert_eq!((*entry).prev_entry, previous);
+                let symfile =
+                    std::slice::from_raw_parts((*entry).symfile_addr, (*entry).symfile_size as _);
+                assert!(symfile.starts_with(b"\x7fELF"));
+                entries.push(entry);
+                previous = entry;
+                entry = (*entry).next_entry;
+            }
+        }
+        entries
+    }
+
+    fn symbols(name: &str) -> Vec<GdbJitSymbol> {
+        vec![GdbJitSymbol {
+            name: name.to_string(),
+            address: 0x1000,
+            len: 16,
+        }]
+    }
+
+    #[test]
+    fn registrations_form_a_linked_list() {
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        let before = registered_entries();
+        drop(_guard);
+
+        let first = GdbJitRegistration::register(&symbols("first"), &[]).unwrap();
+        let second = GdbJitRegistration::register(&symbols("second"), &[]).unwrap();
+        let third = GdbJitRegistration::register(&symbols("third"), &[]).unwrap();
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        let entries = registered_entries();
+        assert_eq!(entries[..3], [third.entry, second.entry, first.entry]);
+        assert_eq!(entries[3..], before[..]);
+        drop(_guard);
+
+        // Removing an entry from the middle of the list keeps it linked.
+        let (first_entry, third_entry) = (first.entry, third.entry);
+        drop(second);
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        assert_eq!(registered_entries()[..2], [third_entry, first_entry]);
+        drop(_guard);
+
+        drop(third);
+        drop(first);
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        assert_eq!(registered_entries(), before);
+    }
+}
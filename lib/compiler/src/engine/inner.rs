@@ -1,34 +1,199 @@
 //! Universal compilation.
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::activity::EngineActivity;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::archive::{read_archive, write_archive};
 use crate::engine::builder::EngineBuilder;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::Artifact;
+use crate::engine::function_table::FunctionTable;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::link::apply_relocation;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::CodeMemory;
+use crate::engine::mapped_image::MappedCode;
 #[cfg(feature = "compiler")]
-use crate::{Compiler, CompilerConfig};
 #[cfg(not(target_arch = "wasm32"))]
-use crate::{FunctionExtent, Tunables};
+use crate::{libcall_trampoline_len, ArtifactBuild, CompileFuture};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Artifact, ArtifactCreate};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    CodeMemory, CodeMemoryError, CodeMemoryPool, CodeMemoryStats, ExecutionGuard, HugePages,
+    IdleFuture, RegionPermission, SharedTrampolineTable, UnwindEntry,
+};
+#[cfg(feature = "compiler")]
+use crate::{CompileExecutor, Compiler, CompilerConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    FrameInfo, FunctionExtent, RelocationHook, RuntimeError, Tunables, UnreachableHandler,
+};
 #[cfg(not(target_arch = "wasm32"))]
 use memmap2::Mmap;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fmt;
 #[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::{Arc, Mutex};
 #[cfg(not(target_arch = "wasm32"))]
+use std::sync::{RwLock, Weak};
+#[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{
-    entity::PrimaryMap, DeserializeError, FunctionBody, FunctionIndex, FunctionType,
-    LocalFunctionIndex, ModuleInfo, SignatureIndex,
+    entity::PrimaryMap, ArtifactHash, DeserializeError, DeserializeForwardPolicy, FunctionBody,
+    FunctionIndex, FunctionType, LocalFunctionIndex, ModuleInfo, Relocation, SerializeError,
+    SignatureIndex,
 };
-use wasmer_types::{CompileError, Features, Target};
+use wasmer_types::{CallingConvention, CompileError, CompileOptions, Features, Target};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{CustomSection, CustomSectionProtection, SectionIndex};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_vm::{
-    FunctionBodyPtr, SectionBodyPtr, SignatureRegistry, VMFunctionBody, VMSharedSignatureIndex,
-    VMTrampoline,
+    install_trap_handlers, trap_handlers_installed, trap_handlers_overridden, FunctionBodyPtr,
+    RoundingMode, SectionBodyPtr, SignatureRegistry, TableGrowLimits, TableGrowObserver, TrapCode,
+    VMFunctionBody, VMSharedSignatureIndex, VMTrampoline,
 };
 
+/// A function called with every artifact compiled by an `Engine`, once its
+/// code has been published.
+///
+/// See [`Engine::set_code_published_callback`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type CodePublishedCallback = Box<dyn Fn(&Artifact) + Send + Sync>;
+
+/// A function transforming every WebAssembly binary compiled by an
+/// `Engine`, before it is validated and compiled.
+///
+/// See [`Engine::set_pre_compile_hook`].
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+pub type PreCompileHook = Arc<dyn Fn(&[u8]) -> Result<Cow<'_, [u8]>, CompileError> + Send + Sync>;
+
+/// A function called with the index, address and length of every function
+/// made executable by an `Engine`.
+///
+/// See [`Engine::on_function_published`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type FunctionPublishedCallback = Arc<dyn Fn(FunctionIndex, *const u8, usize) + Send + Sync>;
+
+/// The function call trampolines known to an `Engine`, by signature.
+///
+/// See [`Engine::trampoline_table`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type TrampolineTable = HashMap<VMSharedSignatureIndex, VMTrampoline>;
+
+/// Releases one registration of each of the given signatures, forgetting
+/// the function call trampolines of those released for the last time.
+///
+/// # Safety
+///
+/// See [`Engine::unregister_signatures`].
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn release_signatures(
+    signatures: &SignatureRegistry,
+    function_call_trampolines: &RwLock<Arc<TrampolineTable>>,
+    indices: &[VMSharedSignatureIndex],
+) {
+    let mut function_call_trampolines = function_call_trampolines.write().unwrap();
+    let mut table = None;
+    for index in indices {
+        if signatures.unregister(*index) {
+            table
+                .get_or_insert_with(|| (**function_call_trampolines).clone())
+                .remove(index);
+        }
+    }
+    if let Some(table) = table {
+        *function_call_trampolines = Arc::new(table);
+    }
+}
+
+/// The registrations of the signatures of an artifact, released when
+/// the artifact and every instance created from it are dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct SignatureRegistration {
+    signatures: Arc<SignatureRegistry>,
+    function_call_trampolines: Arc<RwLock<Arc<TrampolineTable>>>,
+    indices: Vec<VMSharedSignatureIndex>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SignatureRegistration {
+    /// The indices of the registered signatures, in registration order.
+    pub(crate) fn indices(&self) -> &[VMSharedSignatureIndex] {
+        &self.indices
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for SignatureRegistration {
+    fn drop(&mut self) {
+        // SAFETY: the instances created from the artifact hold the
+        // registration, so none of them is alive anymore.
+        unsafe {
+            release_signatures(
+                &self.signatures,
+                &self.function_call_trampolines,
+                &self.indices,
+            )
+        }
+    }
+}
+
+/// The trampolines imported into an engine with
+/// [`Engine::import_trampoline_table`], whose signatures stay registered
+/// until this is dropped.
+///
+/// Once it is, the imported trampolines are forgotten along with the
+/// signatures nothing else registered.
+#[cfg(not(target_arch = "wasm32"))]
+#[must_use = "the imported trampolines are forgotten when this is dropped"]
+pub struct ImportedTrampolineTable {
+    registration: SignatureRegistration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl fmt::Debug for ImportedTrampolineTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportedTrampolineTable")
+            .field("signatures", &self.registration.indices())
+            .finish()
+    }
+}
+
+/// Bytes of code counted towards the limit of an engine, see
+/// [`Engine::set_max_code_bytes`], given back when the artifact holding
+/// them is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct CodeBytesCharge {
+    charged_code_bytes: Arc<AtomicUsize>,
+    bytes: AtomicUsize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CodeBytesCharge {
+    /// Moves the bytes of `other`, charged by the same engine, into this
+    /// charge, so that they are given back along with it.
+    pub(crate) fn absorb(&self, other: Self) {
+        self.bytes.fetch_add(other.bytes.swap(0, SeqCst), SeqCst);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for CodeBytesCharge {
+    fn drop(&mut self) {
+        self.charged_code_bytes
+            .fetch_sub(*self.bytes.get_mut(), SeqCst);
+    }
+}
+
 /// A WebAssembly `Universal` Engine.
 #[derive(Clone)]
 pub struct Engine {
@@ -36,6 +201,26 @@ pub struct Engine {
     /// The target for the compiler
     target: Arc<Target>,
     engine_id: EngineId,
+    /// The guest executions in flight, used to drain the engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    activity: Arc<EngineActivity>,
+    /// The signature registry, also held by `EngineInner`. It has its own
+    /// lock, so that registering and looking up signatures doesn't wait
+    /// for compilations.
+    #[cfg(not(target_arch = "wasm32"))]
+    signatures: Arc<SignatureRegistry>,
+    /// The function call trampolines known to this engine, by signature,
+    /// behind their own lock for the same reason.
+    ///
+    /// The table itself is immutable once published: adding or removing
+    /// trampolines builds a new table and swaps it in, so that readers only
+    /// hold the lock for as long as it takes to clone the `Arc`.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_call_trampolines: Arc<RwLock<Arc<TrampolineTable>>>,
+    /// The compiled functions by address, also held by `EngineInner`, so
+    /// that looking them up doesn't wait for compilations either.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_table: Arc<RwLock<FunctionTable>>,
 }
 
 impl Engine {
@@ -46,20 +231,113 @@ impl Engine {
         target: Target,
         features: Features,
     ) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let signatures = Arc::new(SignatureRegistry::new());
+        #[cfg(not(target_arch = "wasm32"))]
+        let function_call_trampolines = Arc::new(RwLock::new(Arc::new(HashMap::new())));
+        #[cfg(not(target_arch = "wasm32"))]
+        let function_table = Arc::new(RwLock::new(FunctionTable::default()));
+        let engine_id = EngineId::default();
         Self {
             inner: Arc::new(Mutex::new(EngineInner {
-                compiler: Some(compiler_config.compiler()),
+                #[cfg(not(target_arch = "wasm32"))]
+                engine_id: engine_id.copy(),
+                compiler: Some(Arc::from(compiler_config.compiler())),
+                compile_executor: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                pre_compile_hook: None,
                 features,
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
-                signatures: SignatureRegistry::new(),
+                mapped_code: vec![],
+                #[cfg(not(target_arch = "wasm32"))]
+                code_memory_stats: CodeMemoryStats::default(),
+                #[cfg(feature = "perfmap")]
+                #[cfg(not(target_arch = "wasm32"))]
+                perfmap_entries: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                signatures: signatures.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_call_trampolines: function_call_trampolines.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_table: function_table.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                deserialize_forward_policy: DeserializeForwardPolicy::default(),
+                #[cfg(not(target_arch = "wasm32"))]
+                relocation_hook: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                unreachable_handler: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                code_published_callback: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                function_published_callbacks: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                unpublished_functions: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                published_functions: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                installed_trap_handlers: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                table_grow_limits: Arc::new(TableGrowLimits::default()),
+                #[cfg(not(target_arch = "wasm32"))]
+                default_tunables: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                function_dedup: cfg!(feature = "dedup-code"),
+                #[cfg(not(target_arch = "wasm32"))]
+                code_base_address: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                code_memory_pool: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                huge_pages: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                max_code_bytes: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                charged_code_bytes: Arc::new(AtomicUsize::new(0)),
+                #[cfg(not(target_arch = "wasm32"))]
+                module_cache: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                bounds_check_elimination: true,
+                #[cfg(not(target_arch = "wasm32"))]
+                stack_maps: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                max_locals_per_function: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                host_calling_convention: None,
             })),
             target: Arc::new(target),
-            engine_id: EngineId::default(),
+            engine_id,
+            #[cfg(not(target_arch = "wasm32"))]
+            activity: Arc::new(EngineActivity::default()),
+            #[cfg(not(target_arch = "wasm32"))]
+            signatures,
+            #[cfg(not(target_arch = "wasm32"))]
+            function_call_trampolines,
+            #[cfg(not(target_arch = "wasm32"))]
+            function_table,
         }
     }
 
+    /// Create a new `Engine` with the given config, whose code memory
+    /// comes from `pool` and is returned to it when the engine is dropped.
+    ///
+    /// Sharing a pool between short-lived engines saves mapping and
+    /// unmapping memory for every one of them. Code placed at an address
+    /// requested with [`Engine::request_code_base_address`] doesn't use the
+    /// pool.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_with_pool(
+        compiler_config: Box<dyn CompilerConfig>,
+        target: Target,
+        features: Features,
+        pool: Arc<CodeMemoryPool>,
+    ) -> Self {
+        let engine = Self::new(compiler_config, target, features);
+        engine.inner_mut().code_memory_pool = Some(pool);
+        engine
+    }
+
     /// Create a headless `Engine`
     ///
     /// A headless engine is an engine without any compiler attached.
@@ -74,19 +352,93 @@ impl Engine {
     /// Headless engines can't compile or validate any modules,
     /// they just take already processed Modules (via `Module::serialize`).
     pub fn headless() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let signatures = Arc::new(SignatureRegistry::new());
+        #[cfg(not(target_arch = "wasm32"))]
+        let function_call_trampolines = Arc::new(RwLock::new(Arc::new(HashMap::new())));
+        #[cfg(not(target_arch = "wasm32"))]
+        let function_table = Arc::new(RwLock::new(FunctionTable::default()));
+        let engine_id = EngineId::default();
         Self {
             inner: Arc::new(Mutex::new(EngineInner {
+                #[cfg(not(target_arch = "wasm32"))]
+                engine_id: engine_id.copy(),
                 #[cfg(feature = "compiler")]
                 compiler: None,
                 #[cfg(feature = "compiler")]
+                compile_executor: None,
+                #[cfg(feature = "compiler")]
+                #[cfg(not(target_arch = "wasm32"))]
+                pre_compile_hook: None,
                 features: Features::default(),
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
-                signatures: SignatureRegistry::new(),
+                mapped_code: vec![],
+                #[cfg(not(target_arch = "wasm32"))]
+                code_memory_stats: CodeMemoryStats::default(),
+                #[cfg(feature = "perfmap")]
+                #[cfg(not(target_arch = "wasm32"))]
+                perfmap_entries: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                signatures: signatures.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_call_trampolines: function_call_trampolines.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_table: function_table.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                deserialize_forward_policy: DeserializeForwardPolicy::default(),
+                #[cfg(not(target_arch = "wasm32"))]
+                relocation_hook: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                unreachable_handler: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                code_published_callback: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                function_published_callbacks: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                unpublished_functions: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                published_functions: Vec::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                installed_trap_handlers: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                table_grow_limits: Arc::new(TableGrowLimits::default()),
+                #[cfg(not(target_arch = "wasm32"))]
+                default_tunables: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                function_dedup: cfg!(feature = "dedup-code"),
+                #[cfg(not(target_arch = "wasm32"))]
+                code_base_address: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                code_memory_pool: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                huge_pages: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                max_code_bytes: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                charged_code_bytes: Arc::new(AtomicUsize::new(0)),
+                #[cfg(not(target_arch = "wasm32"))]
+                module_cache: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                bounds_check_elimination: true,
+                #[cfg(not(target_arch = "wasm32"))]
+                stack_maps: false,
+                #[cfg(not(target_arch = "wasm32"))]
+                max_locals_per_function: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                host_calling_convention: None,
             })),
             target: Arc::new(Target::default()),
-            engine_id: EngineId::default(),
+            engine_id,
+            #[cfg(not(target_arch = "wasm32"))]
+            activity: Arc::new(EngineActivity::default()),
+            #[cfg(not(target_arch = "wasm32"))]
+            signatures,
+            #[cfg(not(target_arch = "wasm32"))]
+            function_call_trampolines,
+            #[cfg(not(target_arch = "wasm32"))]
+            function_table,
         }
     }
 
@@ -105,18 +457,305 @@ impl Engine {
         &self.target
     }
 
+    /// The WebAssembly proposals this engine was configured with.
+    ///
+    /// Modules using other proposals are rejected at compilation.
+    pub fn features(&self) -> Features {
+        self.inner().features().clone()
+    }
+
+    /// Whether this engine has no compiler attached, see
+    /// [`Engine::headless`].
+    ///
+    /// Headless engines can only load serialized artifacts, so this tells
+    /// up front whether to give them a binary or not.
+    pub fn is_headless(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        {
+            self.inner().compiler.is_none()
+        }
+        #[cfg(not(feature = "compiler"))]
+        {
+            true
+        }
+    }
+
     /// Register a signature
     #[cfg(not(target_arch = "wasm32"))]
     pub fn register_signature(&self, func_type: &FunctionType) -> VMSharedSignatureIndex {
-        let compiler = self.inner();
-        compiler.signatures().register(func_type)
+        self.signatures.register(func_type)
     }
 
     /// Lookup a signature
     #[cfg(not(target_arch = "wasm32"))]
     pub fn lookup_signature(&self, sig: VMSharedSignatureIndex) -> Option<FunctionType> {
-        let compiler = self.inner();
-        compiler.signatures().lookup(sig)
+        self.signatures.lookup(sig)
+    }
+
+    /// Lookup a signature, sharing it with the engine instead of copying it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn lookup_signature_arc(&self, sig: VMSharedSignatureIndex) -> Option<Arc<FunctionType>> {
+        self.signatures.lookup_arc(sig)
+    }
+
+    /// Returns every signature currently registered with the engine, with
+    /// its index, in index order.
+    ///
+    /// This is a snapshot, for diagnostics: checking which signatures the
+    /// modules compiled so far registered, or that signatures are released.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn registered_signatures(&self) -> Vec<(VMSharedSignatureIndex, FunctionType)> {
+        self.signatures.signatures()
+    }
+
+    /// Releases one registration of each of the given signatures, see
+    /// [`SignatureRegistry::unregister`].
+    ///
+    /// The function call trampolines of the signatures released for the
+    /// last time are forgotten too. Artifacts release the signatures they
+    /// registered on their own, once they and their instances are dropped.
+    ///
+    /// # Safety
+    ///
+    /// Once the last registration of a signature is released, no live
+    /// instance or function may use it anymore.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn unregister_signatures(&self, indices: &[VMSharedSignatureIndex]) {
+        release_signatures(&self.signatures, &self.function_call_trampolines, indices)
+    }
+
+    /// The number of signatures currently registered in this engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn signature_count(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Pre-registers every signature used by `artifact`, along with its
+    /// function call trampoline.
+    ///
+    /// This is useful after deserializing an artifact into a fresh engine,
+    /// to pay the cost of resolving trampolines at load time rather than
+    /// on the first call of each signature. The trampolines known to the
+    /// engine are the ones `Function::call` uses.
+    ///
+    /// The signatures stay registered by the artifact itself, so their
+    /// trampolines are forgotten along with them. Artifacts of other
+    /// engines, or without code to run, are ignored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn warm_signatures_from(&self, artifact: &Arc<Artifact>) {
+        if !self.owns_artifact(artifact) || !artifact.is_runnable() {
+            return;
+        }
+        let trampolines = artifact.finished_function_call_trampolines();
+        let mut function_call_trampolines = self.function_call_trampolines.write().unwrap();
+        let mut table = (**function_call_trampolines).clone();
+        table.extend(
+            artifact
+                .signatures()
+                .values()
+                .copied()
+                .zip(trampolines.values().copied()),
+        );
+        *function_call_trampolines = Arc::new(table);
+    }
+
+    /// Makes the function call trampolines of a table shared with another
+    /// process known to this engine, so that they aren't generated again.
+    ///
+    /// Trampolines already known for the same signatures are replaced. No
+    /// trampoline is imported if any of them doesn't fit in the shared
+    /// region. The signatures of the trampolines stay registered for as
+    /// long as the returned table is alive.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn import_trampoline_table(
+        &self,
+        shared: &SharedTrampolineTable,
+    ) -> Result<ImportedTrampolineTable, CodeMemoryError> {
+        if let Some((_, offset, _)) = shared.trampolines().iter().find(|(_, offset, len)| {
+            offset
+                .checked_add(*len)
+                .map_or(true, |end| end > shared.len())
+        }) {
+            return Err(CodeMemoryError::OutsideSharedRegion(*offset));
+        }
+        let registration = SignatureRegistration {
+            signatures: self.signatures.clone(),
+            function_call_trampolines: self.function_call_trampolines.clone(),
+            indices: self.signatures.register_all(
+                shared
+                    .trampolines()
+                    .iter()
+                    .map(|(signature, _, _)| signature),
+            ),
+        };
+        let mut function_call_trampolines = self.function_call_trampolines.write().unwrap();
+        let mut table = (**function_call_trampolines).clone();
+        for (shared_index, (_, offset, _)) in
+            registration.indices().iter().zip(shared.trampolines())
+        {
+            // Safety: the creator of the table guarantees that the region
+            // holds these trampolines for as long as this engine is alive.
+            let trampoline = unsafe {
+                std::mem::transmute::<*const u8, VMTrampoline>(shared.base().add(*offset))
+            };
+            table.insert(*shared_index, trampoline);
+        }
+        *function_call_trampolines = Arc::new(table);
+        Ok(ImportedTrampolineTable { registration })
+    }
+
+    /// Checks whether a function call trampoline is known for the given
+    /// signature.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn has_trampoline(&self, sig: VMSharedSignatureIndex) -> bool {
+        self.trampoline_table().contains_key(&sig)
+    }
+
+    /// Gets the function call trampoline for the given signature, if known.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn function_call_trampoline(&self, sig: VMSharedSignatureIndex) -> Option<VMTrampoline> {
+        self.trampoline_table().get(&sig).copied()
+    }
+
+    /// A snapshot of the function call trampolines known to this engine.
+    ///
+    /// The snapshot isn't affected by trampolines added or removed later
+    /// on, and looking up trampolines in it takes no lock, which makes it
+    /// the cheapest way to resolve many signatures in a row.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn trampoline_table(&self) -> Arc<TrampolineTable> {
+        self.function_call_trampolines.read().unwrap().clone()
+    }
+
+    /// Finds the function compiled by this engine whose code holds `pc`,
+    /// returning its index in its module and the offset of `pc` in it.
+    ///
+    /// Functions sharing a body because of deduplication resolve to the
+    /// first of them. Trampolines, and functions of static objects, aren't
+    /// found.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn lookup_function(&self, pc: usize) -> Option<(FunctionIndex, usize)> {
+        self.function_table.read().unwrap().lookup(pc)
+    }
+
+    /// Resolves the program counter of a trap to the function compiled by
+    /// this engine it happened in, and where in it.
+    ///
+    /// The offset in the wasm binary is only known with the `debug-info`
+    /// feature, see [`Engine::wasm_offset_at`].
+    ///
+    /// This doesn't allocate, but takes locks of the engine, so it must not
+    /// be called from a signal handler that may have interrupted a thread
+    /// holding them, such as one compiling a module.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn resolve_trap(&self, pc: usize) -> Option<TrapLocation> {
+        let (function, function_offset) = self.lookup_function(pc)?;
+        #[cfg(feature = "debug-info")]
+        let wasm_offset = self.wasm_offset_at(pc);
+        #[cfg(not(feature = "debug-info"))]
+        let wasm_offset = None;
+        Some(TrapLocation {
+            function,
+            function_offset,
+            wasm_offset,
+        })
+    }
+
+    /// Enables or disables the deduplication of identical function bodies.
+    ///
+    /// When enabled, functions of a module whose compiled code and
+    /// relocations are identical share a single allocation in code memory.
+    /// Traps raised in a shared body are attributed to the first function
+    /// with that body.
+    ///
+    /// This is disabled by default, unless the `dedup-code` feature is
+    /// enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_function_dedup(self, enabled: bool) -> Self {
+        self.inner_mut().function_dedup = enabled;
+        self
+    }
+
+    /// Enables or disables the deduplication of identical modules.
+    ///
+    /// When enabled, compiling a binary this engine already compiled with
+    /// the same tunables and target returns the artifact compiled the
+    /// first time, as long as it is alive, rather than compiling it again.
+    /// Artifacts are only weakly referenced, so the engine doesn't keep
+    /// dropped modules alive, but like any code of the engine, their code
+    /// is only freed with the engine. Looking up a previous artifact
+    /// translates the whole binary again to hash it, so even a cache hit
+    /// costs a full translation, which is still cheap compared to
+    /// compiling.
+    ///
+    /// This is disabled by default, so that every compilation has code
+    /// memory of its own.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_module_dedup(self, enabled: bool) -> Self {
+        if enabled != self.inner().module_cache.is_some() {
+            self.inner_mut().module_cache = if enabled { Some(HashMap::new()) } else { None };
+        }
+        self
+    }
+
+    /// Enables or disables the elimination of bounds checks on memory
+    /// accesses. It is enabled by default.
+    ///
+    /// When disabled, memories are compiled without relying on guard pages,
+    /// so every access is explicitly checked. This is meant as an analysis
+    /// aid to audit the checks emitted by a compiler.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_bounds_check_elimination(self, enabled: bool) -> Self {
+        self.inner_mut().bounds_check_elimination = enabled;
+        self
+    }
+
+    /// Records, for the safepoints of compiled functions, which stack slots
+    /// hold live references, as needed by a precise garbage collector. See
+    /// [`Artifact::stack_map`].
+    ///
+    /// Compiling fails with [`CompileError::UnsupportedFeature`] if the
+    /// compiler can't record them, see
+    /// [`Compiler::emits_stack_maps`](crate::Compiler::emits_stack_maps).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_stack_maps(self, enabled: bool) -> Self {
+        self.inner_mut().stack_maps = enabled;
+        self
+    }
+
+    /// Limits the number of locals a function may declare in the modules
+    /// compiled by this engine.
+    ///
+    /// Compiling a module with a function that declares more locals fails
+    /// with [`CompileError::TooManyLocals`]. This bounds the stack frame
+    /// size of functions coming from untrusted modules. There is no limit
+    /// by default, or with `None`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_max_locals_per_function(self, max: Option<u32>) -> Self {
+        self.inner_mut().max_locals_per_function = max;
+        self
+    }
+
+    /// Sets the calling convention used by the dynamic function trampolines
+    /// of the modules compiled from now on to call host functions.
+    ///
+    /// By default, this is the system C calling convention of the target.
+    /// Host functions created with `Function::new` follow this setting, so
+    /// it has to be set before creating them. Not every compiler supports
+    /// calling conventions other than the default one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_host_calling_convention(self, calling_convention: CallingConvention) -> Self {
+        self.inner_mut().host_calling_convention = Some(calling_convention);
+        self
+    }
+
+    /// Returns the calling convention used by the dynamic function
+    /// trampolines to call host functions.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn host_calling_convention(&self) -> Option<CallingConvention> {
+        self.inner()
+            .host_calling_convention
+            .or_else(|| self.target.triple().default_calling_convention().ok())
     }
 
     /// Validates a WebAssembly module
@@ -125,6 +764,18 @@ impl Engine {
         self.inner().validate(binary)
     }
 
+    /// Validates a WebAssembly module with the given features rather than
+    /// those of the engine, to check whether it would be valid in an engine
+    /// configured differently.
+    #[cfg(feature = "compiler")]
+    pub fn validate_with_features(
+        &self,
+        binary: &[u8],
+        features: &Features,
+    ) -> Result<(), CompileError> {
+        self.inner().validate_with_features(binary, features)
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     #[cfg(not(target_arch = "wasm32"))]
@@ -133,7 +784,269 @@ impl Engine {
         binary: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Arc<Artifact>, CompileError> {
-        Ok(Arc::new(Artifact::new(self, binary, tunables)?))
+        self.compile_for(binary, tunables, self.target())
+    }
+
+    /// Compile a WebAssembly binary for `target` rather than for the target
+    /// of the engine, to compile ahead of time for several targets with
+    /// one engine.
+    ///
+    /// The artifact records its target, so deserializing it in an engine
+    /// for another architecture or operating system fails. Unless `target`
+    /// is the host, the code isn't loaded into the engine at all: the
+    /// artifact can only be serialized, and instantiating it fails.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_for(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        target: &Target,
+    ) -> Result<Arc<Artifact>, CompileError> {
+        if self.is_draining() {
+            return Err(CompileError::Resource(
+                "The Engine is draining and doesn't accept new compilations.".to_string(),
+            ));
+        }
+        // Don't hold the lock while running the hook, it may use the engine.
+        let hook = self.inner().pre_compile_hook.clone();
+        // Hosts validate the binaries they give, not the transformed ones.
+        let binary = match &hook {
+            Some(hook) => {
+                let transformed = hook(binary)?;
+                self.validate(&transformed)?;
+                transformed
+            }
+            None => Cow::Borrowed(binary),
+        };
+        let binary = &*binary;
+        if self.inner().module_cache.is_some() {
+            let hash = Artifact::compile_hash(self, binary, tunables, target)?;
+            if let Some(artifact) = self.inner().cached_module(&hash) {
+                return Ok(artifact);
+            }
+        }
+        let artifact = Artifact::new(self, binary, tunables, target);
+        self.notify_published_functions();
+        let artifact = Arc::new(artifact?);
+        self.inner_mut().cache_module(&artifact);
+        // Don't hold the lock while running the callback, it may use the
+        // engine.
+        let callback = self.inner().code_published_callback.clone();
+        if let (Some(callback), true) = (callback, artifact.is_runnable()) {
+            callback(&artifact);
+        }
+        Ok(artifact)
+    }
+
+    /// Compiles a WebAssembly binary on another thread, returning a future
+    /// that resolves to the artifact, so that async hosts don't block while
+    /// compiling.
+    ///
+    /// The future doesn't need any particular executor. The compilations
+    /// run on a pool of at most one thread per core, see [`CompileFuture`].
+    /// Dropping the future early cancels the compilation if it didn't start
+    /// yet. Like [`Engine::compile`], a compilation only locks the engine
+    /// to allocate and publish its code, so compilations on the same engine
+    /// run concurrently.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_async(
+        &self,
+        binary: impl Into<Vec<u8>>,
+        tunables: Arc<dyn Tunables + Send + Sync>,
+    ) -> CompileFuture {
+        CompileFuture::spawn(self.clone(), binary.into(), tunables)
+    }
+
+    /// Compiles `body` as a new body for the local function `index` of
+    /// `artifact`, returning a pointer to it, so that a host can redirect
+    /// calls to it, for example to hot-reload a function.
+    ///
+    /// The function is compiled with the features and memory and table
+    /// styles of the artifact, and only takes the compile hints from
+    /// `tunables`. Since it keeps its signature, it keeps its trampolines
+    /// and the signature it is registered with, and it calls the other
+    /// functions of the artifact at their current address. Like
+    /// [`Engine::compile`], this expects `body` to be valid for the module,
+    /// including its locals declarations.
+    ///
+    /// The artifact is left unchanged, and the previous body stays in
+    /// memory for as long as the engine is alive, so code still calling it
+    /// keeps working. Traps in the new body have no frame information, and
+    /// it isn't described to the unwinder with DWARF.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_function(
+        &self,
+        artifact: &Artifact,
+        index: LocalFunctionIndex,
+        body: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<FunctionBodyPtr, CompileError> {
+        if self.is_draining() {
+            return Err(CompileError::Resource(
+                "The Engine is draining and doesn't accept new compilations.".to_string(),
+            ));
+        }
+        if !self.owns_artifact(artifact) {
+            return Err(CompileError::Codegen(
+                "The artifact was not created by this engine.".to_string(),
+            ));
+        }
+        if !artifact.is_runnable() {
+            return Err(CompileError::Codegen(
+                "The artifact was compiled for another target.".to_string(),
+            ));
+        }
+        let target = self.target();
+        let module = artifact.create_module_info();
+        let settings = self.inner().compile_settings()?;
+        let (function, custom_sections) = ArtifactBuild::compile_function(
+            &settings,
+            artifact,
+            &module,
+            index,
+            body,
+            target,
+            &tunables.compile_hints(),
+        )?;
+        let ptr = {
+            let mut inner = self.inner_mut();
+            let (extent, allocated_sections, charge) =
+                inner.allocate_function(&module, index, &function.body, &custom_sections)?;
+            artifact.charge_code_bytes(charge);
+
+            let mut functions = artifact
+                .finished_functions()
+                .iter()
+                .map(|(function, ptr)| FunctionExtent {
+                    ptr: *ptr,
+                    length: artifact.function_code_size(function),
+                })
+                .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+            functions[index] = FunctionExtent {
+                ptr: extent.ptr,
+                length: extent.length,
+            };
+            // The libcall trampolines are the last section.
+            let libcall_trampolines = custom_sections.keys().last().unwrap();
+            let libcall_trampoline_len = libcall_trampoline_len(target);
+            let relocations = custom_sections
+                .iter()
+                .flat_map(|(section, custom_section)| {
+                    let body = *allocated_sections[section] as usize;
+                    custom_section
+                        .relocations
+                        .iter()
+                        .map(move |relocation| (body, relocation))
+                })
+                .chain(
+                    function
+                        .relocations
+                        .iter()
+                        .map(|relocation| (*extent.ptr as usize, relocation)),
+                );
+            for (body, relocation) in relocations {
+                apply_relocation(
+                    body,
+                    relocation,
+                    &functions,
+                    &allocated_sections,
+                    libcall_trampolines,
+                    libcall_trampoline_len,
+                    inner.relocation_hook(),
+                );
+            }
+
+            inner.publish_compiled_code();
+            inner.publish_eh_frame(None)?;
+            extent.ptr
+        };
+        self.notify_published_functions();
+        Ok(ptr)
+    }
+
+    /// Sets a hook that transforms every WebAssembly binary compiled by
+    /// this engine, for example to instrument it, before it is validated
+    /// and compiled.
+    ///
+    /// The hook returns the binary it was given, borrowed, when it leaves
+    /// it unchanged, and its errors fail the compilation. Whatever binary
+    /// it returns is validated before being compiled. With
+    /// [`Engine::with_module_dedup`], previous artifacts are looked up with
+    /// the transformed binary. [`Engine::validate`] doesn't run the hook.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_pre_compile_hook(&self, hook: PreCompileHook) {
+        self.inner_mut().pre_compile_hook = Some(hook);
+    }
+
+    /// Sets a callback that is invoked with every artifact compiled by this
+    /// engine, once its code has been published.
+    ///
+    /// This allows persisting compiled artifacts as soon as they are
+    /// available, for example by serializing them into a cache. It isn't
+    /// invoked for deserialized artifacts.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_code_published_callback(&self, callback: CodePublishedCallback) {
+        self.inner_mut().code_published_callback = Some(Arc::from(callback));
+    }
+
+    /// Adds a callback that is invoked with the index, address and length
+    /// of every function once it has been made executable, by compiling or
+    /// deserializing an artifact.
+    ///
+    /// This allows maintaining symbol databases or auditing the code of
+    /// the engine, as the `perfmap` and `gdb-jit` features do. The
+    /// callbacks are invoked in the order they were added, and not while
+    /// the engine is locked, so they may use it. Functions published before
+    /// a callback is added aren't reported to it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn on_function_published(&self, callback: FunctionPublishedCallback) {
+        self.inner_mut().function_published_callbacks.push(callback);
+    }
+
+    /// Invokes the callbacks added with [`Engine::on_function_published`]
+    /// with the functions published since they were last invoked.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn notify_published_functions(&self) {
+        let (callbacks, functions) = {
+            let mut inner = self.inner_mut();
+            if inner.published_functions.is_empty() {
+                return;
+            }
+            (
+                inner.function_published_callbacks.clone(),
+                std::mem::take(&mut inner.published_functions),
+            )
+        };
+        for (index, address, len) in functions {
+            for callback in &callbacks {
+                callback(index, address as *const u8, len);
+            }
+        }
+    }
+
+    /// Sets the `Tunables` used by [`Engine::compile_default`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_default_tunables(&self, tunables: Arc<dyn Tunables + Send + Sync>) {
+        self.inner_mut().default_tunables = Some(tunables);
+    }
+
+    /// Compile a WebAssembly binary with the `Tunables` set by
+    /// [`Engine::set_default_tunables`].
+    ///
+    /// Fails with [`CompileError::NoDefaultTunables`] if none were set.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_default(&self, binary: &[u8]) -> Result<Arc<Artifact>, CompileError> {
+        // Don't hold the lock while compiling, since compilation takes it too.
+        let tunables = self
+            .inner()
+            .default_tunables
+            .clone()
+            .ok_or(CompileError::NoDefaultTunables)?;
+        self.compile(binary, tunables.as_ref())
     }
 
     /// Compile a WebAssembly binary
@@ -150,28 +1063,442 @@ impl Engine {
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    /// Deserializes a WebAssembly module
+    /// Deserializes a WebAssembly module
+    ///
+    /// # Safety
+    ///
+    /// The serialized content must represent a serialized WebAssembly module.
+    pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
+        let artifact = Artifact::deserialize(self, bytes);
+        self.notify_published_functions();
+        Ok(Arc::new(artifact?))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Deserializes a WebAssembly module from a path
+    ///
+    /// The file is memory-mapped rather than read into a buffer. The
+    /// artifact copies everything it needs out of the mapping, which is
+    /// unmapped before returning, so the file may be removed afterwards.
+    ///
+    /// Truncated files and files that don't hold an artifact are rejected
+    /// with a [`DeserializeError`].
+    ///
+    /// # Safety
+    ///
+    /// The file's content must represent a serialized WebAssembly module,
+    /// and must not be modified while it is being deserialized.
+    pub unsafe fn deserialize_from_file(
+        &self,
+        file_ref: &Path,
+    ) -> Result<Arc<Artifact>, DeserializeError> {
+        let file = std::fs::File::open(file_ref)?;
+        // Empty files can't be mapped on every platform.
+        if file.metadata()?.len() == 0 {
+            return self.deserialize(&[]);
+        }
+        let mmap = Mmap::map(&file)?;
+        self.deserialize(&mmap)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Deserializes a WebAssembly module from a path, executing its code
+    /// straight from the mapping of the file when possible.
+    ///
+    /// See [`Artifact::deserialize_mmap`].
+    ///
+    /// # Safety
+    ///
+    /// The file's content must represent a serialized WebAssembly module,
+    /// and must not be modified for as long as this engine is alive.
+    pub unsafe fn deserialize_mmap(
+        &self,
+        file_ref: &Path,
+    ) -> Result<Arc<Artifact>, DeserializeError> {
+        let artifact = Artifact::deserialize_mmap(self, file_ref);
+        self.notify_published_functions();
+        Ok(Arc::new(artifact?))
+    }
+
+    /// Serializes several artifacts into a single archive, which can be
+    /// loaded back with [`Engine::deserialize_archive`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn serialize_archive(
+        &self,
+        artifacts: &[Arc<Artifact>],
+    ) -> Result<Vec<u8>, SerializeError> {
+        let serialized = artifacts
+            .iter()
+            .map(|artifact| artifact.serialize())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(write_archive(&serialized))
+    }
+
+    /// Deserializes the artifacts of an archive created with
+    /// [`Engine::serialize_archive`], on up to `parallelism` threads.
+    ///
+    /// The artifacts are returned in the order of the archive once they
+    /// are all deserialized. If any of them fails, no more artifacts are
+    /// deserialized and its error is returned.
+    ///
+    /// # Safety
+    ///
+    /// Every artifact of the archive must be a serialized WebAssembly
+    /// module, see [`Engine::deserialize`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn deserialize_archive(
+        &self,
+        bytes: &[u8],
+        parallelism: usize,
+    ) -> Result<Vec<Arc<Artifact>>, DeserializeError> {
+        self.deserialize_archive_with_progress(bytes, parallelism, |_| {})
+    }
+
+    /// Like [`Engine::deserialize_archive`], calling `progress` with the
+    /// position in the archive of every artifact right before it is
+    /// deserialized, on the thread deserializing it.
+    ///
+    /// # Safety
+    ///
+    /// See [`Engine::deserialize_archive`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn deserialize_archive_with_progress(
+        &self,
+        bytes: &[u8],
+        parallelism: usize,
+        progress: impl Fn(usize) + Send + Sync + 'static,
+    ) -> Result<Vec<Arc<Artifact>>, DeserializeError> {
+        // The threads can't borrow from the caller, so they are given
+        // their own copy of the artifacts.
+        let entries: Arc<Vec<Vec<u8>>> = Arc::new(
+            read_archive(bytes)?
+                .into_iter()
+                .map(<[u8]>::to_vec)
+                .collect(),
+        );
+        let results = Arc::new(Mutex::new(
+            (0..entries.len()).map(|_| None).collect::<Vec<_>>(),
+        ));
+        let next = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicBool::new(false));
+        let progress = Arc::new(progress);
+
+        let workers = (0..parallelism.max(1).min(entries.len()))
+            .map(|_| {
+                let engine = self.clone();
+                let entries = entries.clone();
+                let results = results.clone();
+                let next = next.clone();
+                let failed = failed.clone();
+                let progress = progress.clone();
+                std::thread::spawn(move || {
+                    while !failed.load(SeqCst) {
+                        let index = next.fetch_add(1, SeqCst);
+                        if index >= entries.len() {
+                            break;
+                        }
+                        progress(index);
+                        let result = engine.deserialize(&entries[index]);
+                        if result.is_err() {
+                            failed.store(true, SeqCst);
+                        }
+                        results.lock().unwrap()[index] = Some(result);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            if let Err(panic) = worker.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+
+        let results = std::mem::take(&mut *results.lock().unwrap());
+        let mut artifacts = Vec::with_capacity(results.len());
+        for result in results.into_iter().flatten() {
+            artifacts.push(result?);
+        }
+        Ok(artifacts)
+    }
+
+    /// Returns statistics about the memory used by the code compiled or
+    /// deserialized by this engine so far.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn code_memory_stats(&self) -> CodeMemoryStats {
+        self.inner().code_memory_stats
+    }
+
+    /// Returns the functions compiled or deserialized by this engine that
+    /// are registered with the unwinder of the system, for embedders
+    /// walking the stack themselves.
+    ///
+    /// Functions compiled without unwind information, and the code of
+    /// artifacts mapped with [`Engine::deserialize_mmap`], have no entry.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn unwind_entries(&self) -> Vec<UnwindEntry> {
+        self.inner()
+            .code_memory
+            .iter()
+            .flat_map(|code_memory| code_memory.unwind_entries().iter().copied())
+            .collect()
+    }
+
+    /// Resolves an address in the code compiled by this engine to the
+    /// offset, in its wasm binary, of the instruction it was compiled from.
+    ///
+    /// Returns `None` for addresses outside of the compiled functions, and
+    /// for code the compiler didn't map to instructions. The code of
+    /// artifacts mapped with [`Engine::deserialize_mmap`] isn't covered.
+    #[cfg(feature = "debug-info")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wasm_offset_at(&self, address: usize) -> Option<u32> {
+        self.inner().code_memory.iter().find_map(|code_memory| {
+            crate::engine::debug_line::lookup(code_memory.debug_line(), address)
+        })
+    }
+
+    /// Faults in the pages holding the code compiled or deserialized by
+    /// this engine, and its data, so that the first calls into it don't.
+    ///
+    /// Latency-sensitive hosts can call this once their modules are
+    /// loaded, rather than paying for page faults on the first requests.
+    /// The pages may be evicted again under memory pressure.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn warm_code(&self) {
+        let inner = self.inner();
+        inner.code_memory.iter().for_each(CodeMemory::warm);
+        inner.mapped_code.iter().for_each(MappedCode::warm);
+    }
+
+    /// Checks that no memory holding the code compiled or deserialized by
+    /// this engine is both writable and executable.
+    ///
+    /// Returns the offending regions otherwise. This is meant as a
+    /// hardening self-check.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn verify_code_memory_permissions(&self) -> Result<(), Vec<RegionPermission>> {
+        let writable_and_executable = self
+            .inner()
+            .code_memory
+            .iter()
+            .flat_map(CodeMemory::region_permissions)
+            .filter(|permission| permission.writable && permission.executable)
+            .collect::<Vec<_>>();
+        if writable_and_executable.is_empty() {
+            Ok(())
+        } else {
+            Err(writable_and_executable)
+        }
+    }
+
+    /// Sets how artifacts serialized with a newer format version than the
+    /// one supported by this engine are handled on deserialization.
+    ///
+    /// By default such artifacts are rejected with
+    /// [`DeserializeError::NewerFormat`]. Only allow versions whose layout
+    /// is known to match the current one, see
+    /// [`DeserializeForwardPolicy::AllowVersions`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_deserialize_forward_policy(&self, policy: DeserializeForwardPolicy) {
+        self.inner_mut().deserialize_forward_policy = policy;
+    }
+
+    /// Requests that the code memory allocated from now on is mapped at
+    /// `address`, which must be page-aligned.
+    ///
+    /// The code of every subsequently compiled or deserialized module is
+    /// mapped right after the code of the previous one, starting at
+    /// `address`. This fails if the address is not available, and the
+    /// allocations fail later on if the following pages are taken.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request_code_base_address(&self, address: usize) -> Result<(), CodeMemoryError> {
+        if address % region::page::size() != 0 {
+            return Err(CodeMemoryError::UnalignedAddress(address));
+        }
+        CodeMemory::probe_address(address)
+            .map_err(|message| CodeMemoryError::UnavailableAddress(address, message))?;
+        self.inner_mut().code_base_address = Some(address);
+        Ok(())
+    }
+
+    /// Sets whether the code memory allocated from now on is backed by huge
+    /// pages, which reduces the TLB misses of the code of large modules.
+    ///
+    /// Huge pages are only used on Linux, for the code memory of modules
+    /// larger than [`HugePages::min_size`], not placed at an address
+    /// requested with [`Engine::request_code_base_address`] nor taken from
+    /// a [`CodeMemoryPool`]. Normal pages are used when huge pages aren't
+    /// available.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_huge_pages(&self, huge_pages: Option<HugePages>) {
+        self.inner_mut().huge_pages = huge_pages;
+    }
+
+    /// Sets the executor the compiler runs the work it splits the
+    /// compilation of a module into on, such as compiling every function,
+    /// instead of its own threads. `None`, the default, leaves the
+    /// parallelism to the compiler.
+    ///
+    /// This lets embedders choose the threads compiling modules, for example
+    /// to avoid oversubscribing cores. Singlepass, Cranelift and LLVM
+    /// compile each function as a task of the executor; compilers that don't
+    /// split their work ignore it.
+    #[cfg(feature = "compiler")]
+    pub fn set_compile_executor(&self, executor: Option<Arc<dyn CompileExecutor>>) {
+        self.inner_mut().compile_executor = executor;
+    }
+
+    /// Sets the maximum number of bytes of function bodies, trampolines and
+    /// custom sections the engine allocates, over all the modules it
+    /// compiles or deserializes, including the code it maps from files
+    /// with [`Engine::deserialize_mmap`]. `None`, the default, doesn't
+    /// limit them.
+    ///
+    /// Compiling or deserializing a module whose code would exceed the
+    /// limit fails with a [`CompileError::Resource`] error. The bytes of a
+    /// module, including the functions recompiled for it with
+    /// [`Engine::compile_function`], count until its artifact is dropped,
+    /// even though the code itself stays allocated until the engine is
+    /// dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_max_code_bytes(&self, max_code_bytes: Option<usize>) {
+        self.inner_mut().max_code_bytes = max_code_bytes;
+    }
+
+    /// Sets a hook that is invoked for every relocation right before it is
+    /// applied, when compiling or deserializing a module.
+    ///
+    /// The hook can redirect the relocation, for example to replace a
+    /// runtime function. It runs with the engine locked, so it must not use
+    /// the engine, see [`RelocationHook`].
+    ///
+    /// # Safety
+    ///
+    /// The address the hook leaves is patched into executable memory
+    /// as-is. A redirected address must point to code (or data) that
+    /// matches what the compiled code expects at that location, including
+    /// the calling convention of a called function, it must stay alive for
+    /// as long as the patched code can run, and it must be reachable by the
+    /// relocation kind (for example within ±2GiB for 4-byte PC-relative
+    /// relocations).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn set_relocation_hook(&self, hook: RelocationHook) {
+        self.inner_mut().relocation_hook = Some(hook);
+    }
+
+    /// Sets a handler that is invoked when a guest executes `unreachable`,
+    /// with the frame of the function that executed it, before the trap
+    /// is returned to the caller.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_unreachable_handler(&self, handler: UnreachableHandler) {
+        self.inner_mut().unreachable_handler = Some(Arc::from(handler));
+    }
+
+    /// Invokes the handler set with [`Engine::set_unreachable_handler`] if
+    /// `error` comes from a guest executing `unreachable`.
+    ///
+    /// This is called by the runtime when a call into a guest traps.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn notify_trap(&self, error: &RuntimeError) {
+        if error.clone().to_trap() != Some(TrapCode::UnreachableCodeReached) {
+            return;
+        }
+        // Don't hold the lock while running the handler, it may use the
+        // engine too.
+        let handler = self.inner().unreachable_handler.clone();
+        if let (Some(handler), Some(frame)) = (handler, error.trace().first()) {
+            handler(frame.clone());
+        }
+    }
+
+    /// Sets the maximum number of elements a guest may grow a table to
+    /// with `table.grow`, in every instance of the modules of this engine.
+    ///
+    /// A guest growing a table past it traps with a
+    /// [`TableLimitExceeded`](wasmer_vm::TableLimitExceeded) error. Tables
+    /// grown by the host aren't limited.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_max_table_elements(&self, max: u32) {
+        self.inner().table_grow_limits.set_max_elements(max);
+    }
+
+    /// Sets a function called every time a guest executes `table.grow`, in
+    /// every instance of the modules of this engine, with the size of the
+    /// table and the number of elements requested.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_table_grow_observer(&self, observer: TableGrowObserver) {
+        self.inner().table_grow_limits.set_observer(observer);
+    }
+
+    /// Reports the state of the process-wide handlers turning faults in
+    /// guest code into traps.
     ///
-    /// # Safety
+    /// They are installed when the first `Store` or artifact of the process
+    /// is created. This helps finding out why traps aren't caught when
+    /// several copies of Wasmer, or other code, compete for them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn trap_handler_status(&self) -> TrapHandlerStatus {
+        TrapHandlerStatus {
+            registered: trap_handlers_installed(),
+            installed_by_this_engine: self.inner().installed_trap_handlers,
+            conflict: trap_handlers_overridden(),
+        }
+    }
+
+    /// Starts draining the engine: new compilations and instantiations are
+    /// rejected from now on, while the guest executions in flight carry on.
     ///
-    /// The serialized content must represent a serialized WebAssembly module.
-    pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
-        Ok(Arc::new(Artifact::deserialize(self, bytes)?))
+    /// Use [`Engine::wait_idle`] or [`Engine::await_idle`] to wait for them
+    /// to finish before releasing the engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn begin_drain(&self) {
+        self.activity.begin_drain();
     }
 
+    /// Checks whether [`Engine::begin_drain`] was called.
     #[cfg(not(target_arch = "wasm32"))]
-    /// Deserializes a WebAssembly module from a path
+    pub fn is_draining(&self) -> bool {
+        self.activity.is_draining()
+    }
+
+    /// Sets the IEEE 754 rounding mode used by the floating-point operations
+    /// of the guests running on this engine.
     ///
-    /// # Safety
+    /// The mode is applied to the calling thread around every call into a
+    /// guest, and the previous mode is restored when the call returns. Host
+    /// functions called by the guest run with that mode too.
     ///
-    /// The file's content must represent a serialized WebAssembly module.
-    pub unsafe fn deserialize_from_file(
-        &self,
-        file_ref: &Path,
-    ) -> Result<Arc<Artifact>, DeserializeError> {
-        let file = std::fs::File::open(file_ref)?;
-        let mmap = Mmap::map(&file)?;
-        self.deserialize(&mmap)
+    /// This is supported on x86_64 and aarch64, and ignored on other
+    /// architectures. Note that compilers may still fold constant
+    /// operations assuming the default mode, which is the only one allowed
+    /// by the WebAssembly specification.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_float_rounding_mode(&self, mode: RoundingMode) {
+        self.activity.set_rounding_mode(mode);
+    }
+
+    /// Marks a guest execution as in flight until the returned guard is
+    /// dropped, and applies the rounding mode set with
+    /// [`Engine::set_float_rounding_mode`] meanwhile.
+    ///
+    /// This is called by the runtime around every call into a guest.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn enter_execution(&self) -> ExecutionGuard {
+        self.activity.enter()
+    }
+
+    /// Blocks the current thread until no guest execution is in flight.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait_idle(&self) {
+        self.activity.wait_idle()
+    }
+
+    /// Returns a future that resolves once no guest execution is in flight.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn await_idle(&self) -> impl std::future::Future<Output = ()> {
+        IdleFuture {
+            activity: self.activity.clone(),
+        }
     }
 
     /// A unique identifier for this object.
@@ -183,6 +1510,16 @@ impl Engine {
         &self.engine_id
     }
 
+    /// Returns whether `artifact` was created by this engine or by one of
+    /// its clones.
+    ///
+    /// Running an artifact against an engine other than the one that
+    /// created it mixes up their signature registries, which can crash.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn owns_artifact(&self, artifact: &Artifact) -> bool {
+        artifact.engine_id() == self.inner().engine_id()
+    }
+
     /// Clone the engine
     pub fn cloned(&self) -> Self {
         self.clone()
@@ -191,23 +1528,189 @@ impl Engine {
 
 /// The inner contents of `Engine`
 pub struct EngineInner {
+    /// The id of the engine this was created for, which its clones get a
+    /// new id of their own from. Artifacts record this one, so that they
+    /// belong to every clone.
+    #[cfg(not(target_arch = "wasm32"))]
+    engine_id: EngineId,
     #[cfg(feature = "compiler")]
     /// The compiler and cpu features
-    compiler: Option<Box<dyn Compiler>>,
+    compiler: Option<Arc<dyn Compiler>>,
+    /// The executor the compiler runs its work on, if any.
     #[cfg(feature = "compiler")]
-    /// The compiler and cpu features
+    compile_executor: Option<Arc<dyn CompileExecutor>>,
+    /// The hook transforming the binaries before they are compiled.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pre_compile_hook: Option<PreCompileHook>,
+    /// The Wasm features of the engine. Headless engines only report them.
     features: Features,
     /// The code memory is responsible of publishing the compiled
     /// functions to memory.
     #[cfg(not(target_arch = "wasm32"))]
     code_memory: Vec<CodeMemory>,
+    /// The code executed straight from the files of artifacts.
+    #[cfg(not(target_arch = "wasm32"))]
+    mapped_code: Vec<MappedCode>,
+    /// Per-category accounting of the memory allocated in `code_memory`.
+    #[cfg(not(target_arch = "wasm32"))]
+    code_memory_stats: CodeMemoryStats,
+    /// The perf map entries of the functions allocated since code was last
+    /// published.
+    #[cfg(feature = "perfmap")]
+    #[cfg(not(target_arch = "wasm32"))]
+    perfmap_entries: Vec<crate::engine::perfmap::PerfMapEntry>,
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
-    signatures: SignatureRegistry,
+    signatures: Arc<SignatureRegistry>,
+    /// The function call trampolines known to the engine, also held by
+    /// `Engine`.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_call_trampolines: Arc<RwLock<Arc<TrampolineTable>>>,
+    /// The compiled functions by address.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_table: Arc<RwLock<FunctionTable>>,
+    /// How to handle artifacts with a newer format version on deserialization.
+    #[cfg(not(target_arch = "wasm32"))]
+    deserialize_forward_policy: DeserializeForwardPolicy,
+    /// A hook invoked for every relocation before it is applied.
+    #[cfg(not(target_arch = "wasm32"))]
+    relocation_hook: Option<RelocationHook>,
+    /// A handler invoked when a guest executes `unreachable`.
+    #[cfg(not(target_arch = "wasm32"))]
+    unreachable_handler: Option<Arc<dyn Fn(FrameInfo) + Send + Sync>>,
+    /// A callback invoked with every artifact compiled by this engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    code_published_callback: Option<Arc<dyn Fn(&Artifact) + Send + Sync>>,
+    /// The callbacks invoked with every function once it is executable.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_published_callbacks: Vec<FunctionPublishedCallback>,
+    /// The functions allocated since code was last published, by index,
+    /// address and length, if there are callbacks to invoke with them.
+    #[cfg(not(target_arch = "wasm32"))]
+    unpublished_functions: Vec<(FunctionIndex, usize, usize)>,
+    /// The functions published since the callbacks were last invoked.
+    #[cfg(not(target_arch = "wasm32"))]
+    published_functions: Vec<(FunctionIndex, usize, usize)>,
+    /// Whether the process-wide trap handlers were installed when this
+    /// engine created an artifact.
+    #[cfg(not(target_arch = "wasm32"))]
+    installed_trap_handlers: bool,
+    /// The limits on table growth shared by the instances of the modules
+    /// of this engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    table_grow_limits: Arc<TableGrowLimits>,
+    /// The `Tunables` used when none are given explicitly.
+    #[cfg(not(target_arch = "wasm32"))]
+    default_tunables: Option<Arc<dyn Tunables + Send + Sync>>,
+    /// Whether identical function bodies share a single allocation.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_dedup: bool,
+    /// The address at which the next code memory is mapped, if requested.
+    #[cfg(not(target_arch = "wasm32"))]
+    code_base_address: Option<usize>,
+    /// The pool the code memory comes from, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    code_memory_pool: Option<Arc<CodeMemoryPool>>,
+    /// The preference for backing the code memory with huge pages, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    huge_pages: Option<HugePages>,
+    /// The maximum number of bytes of code and custom sections allocated
+    /// by the engine, if limited.
+    #[cfg(not(target_arch = "wasm32"))]
+    max_code_bytes: Option<usize>,
+    /// The bytes of code and custom sections held by the live artifacts of
+    /// the engine, which count towards `max_code_bytes`.
+    #[cfg(not(target_arch = "wasm32"))]
+    charged_code_bytes: Arc<AtomicUsize>,
+    /// The artifacts compiled so far by hash, if modules are deduplicated.
+    #[cfg(not(target_arch = "wasm32"))]
+    module_cache: Option<HashMap<ArtifactHash, Weak<Artifact>>>,
+    /// The maximum number of locals a compiled function may declare.
+    #[cfg(not(target_arch = "wasm32"))]
+    max_locals_per_function: Option<u32>,
+    /// The calling convention used to call host functions, if not the
+    /// default one of the target.
+    #[cfg(not(target_arch = "wasm32"))]
+    host_calling_convention: Option<CallingConvention>,
+    /// Whether memories may rely on guard pages to elide bounds checks.
+    #[cfg(not(target_arch = "wasm32"))]
+    bounds_check_elimination: bool,
+    /// Whether the stack maps of compiled functions are recorded.
+    #[cfg(not(target_arch = "wasm32"))]
+    stack_maps: bool,
+}
+
+/// The settings of an [`Engine`] that determine how it compiles modules,
+/// see [`EngineInner::compile_settings`].
+#[cfg(feature = "compiler")]
+#[derive(Clone)]
+pub struct CompileSettings {
+    compiler: Arc<dyn Compiler>,
+    compile_executor: Option<Arc<dyn CompileExecutor>>,
+    features: Features,
+    max_locals_per_function: Option<u32>,
+    host_calling_convention: Option<CallingConvention>,
+    bounds_check_elimination: bool,
+    stack_maps: bool,
+}
+
+#[cfg(feature = "compiler")]
+impl CompileSettings {
+    /// The compiler of the engine.
+    pub fn compiler(&self) -> &dyn Compiler {
+        &*self.compiler
+    }
+
+    /// The executor the compiler runs its work on, if any.
+    pub fn compile_executor(&self) -> Option<&Arc<dyn CompileExecutor>> {
+        self.compile_executor.as_ref()
+    }
+
+    /// The Wasm features
+    pub fn features(&self) -> &Features {
+        &self.features
+    }
+
+    /// The maximum number of locals a compiled function may declare.
+    pub fn max_locals_per_function(&self) -> Option<u32> {
+        self.max_locals_per_function
+    }
+
+    /// The calling convention used to call host functions, if not the
+    /// default one of the target.
+    pub fn host_calling_convention(&self) -> Option<CallingConvention> {
+        self.host_calling_convention
+    }
+
+    /// Whether memories may rely on guard pages to elide bounds checks.
+    pub fn bounds_check_elimination(&self) -> bool {
+        self.bounds_check_elimination
+    }
+
+    /// Whether the stack maps of compiled functions are recorded.
+    pub fn stack_maps(&self) -> bool {
+        self.stack_maps
+    }
+
+    /// The options of the engine that the code it runs must be compiled
+    /// with.
+    pub fn compile_options(&self) -> CompileOptions {
+        CompileOptions {
+            bounds_check_elimination: self.bounds_check_elimination,
+        }
+    }
 }
 
 impl EngineInner {
+    /// The id artifacts created by the engine record, see
+    /// [`Artifact::engine_id`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn engine_id(&self) -> &EngineId {
+        &self.engine_id
+    }
+
     /// Gets the compiler associated to this engine.
     #[cfg(feature = "compiler")]
     pub fn compiler(&self) -> Result<&dyn Compiler, CompileError> {
@@ -219,26 +1722,153 @@ impl EngineInner {
         }
     }
 
+    /// The executor the compiler runs its work on, if any.
+    #[cfg(feature = "compiler")]
+    pub fn compile_executor(&self) -> Option<&Arc<dyn CompileExecutor>> {
+        self.compile_executor.as_ref()
+    }
+
+    /// Copies the settings modules are compiled with, so that they can be
+    /// compiled without holding the lock of the engine.
+    #[cfg(feature = "compiler")]
+    pub fn compile_settings(&self) -> Result<CompileSettings, CompileError> {
+        let compiler = match self.compiler.as_ref() {
+            None => {
+                return Err(CompileError::Codegen(
+                    "The Engine is not compiled in.".to_string(),
+                ))
+            }
+            Some(compiler) => compiler.clone(),
+        };
+        Ok(CompileSettings {
+            compiler,
+            compile_executor: self.compile_executor.clone(),
+            features: self.features.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            max_locals_per_function: self.max_locals_per_function,
+            #[cfg(target_arch = "wasm32")]
+            max_locals_per_function: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            host_calling_convention: self.host_calling_convention,
+            #[cfg(target_arch = "wasm32")]
+            host_calling_convention: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            bounds_check_elimination: self.bounds_check_elimination,
+            #[cfg(target_arch = "wasm32")]
+            bounds_check_elimination: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            stack_maps: self.stack_maps,
+            #[cfg(target_arch = "wasm32")]
+            stack_maps: false,
+        })
+    }
+
     /// Validate the module
     #[cfg(feature = "compiler")]
     pub fn validate(&self, data: &[u8]) -> Result<(), CompileError> {
+        self.validate_with_features(data, &self.features)
+    }
+
+    /// Validate the module with the given features
+    #[cfg(feature = "compiler")]
+    pub fn validate_with_features(
+        &self,
+        data: &[u8],
+        features: &Features,
+    ) -> Result<(), CompileError> {
         let compiler = self.compiler()?;
-        compiler.validate_module(&self.features, data)
+        compiler.validate_module(features, data)
     }
 
     /// The Wasm features
-    #[cfg(feature = "compiler")]
     pub fn features(&self) -> &Features {
         &self.features
     }
 
+    /// Sets the Wasm features of a headless engine, see
+    /// [`EngineBuilder::set_features`](crate::EngineBuilder::set_features).
+    pub(crate) fn set_features(&mut self, features: Features) {
+        self.features = features;
+    }
+
+    /// Whether memories may rely on guard pages to elide bounds checks.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn bounds_check_elimination(&self) -> bool {
+        self.bounds_check_elimination
+    }
+
+    /// Whether the stack maps of compiled functions are recorded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stack_maps(&self) -> bool {
+        self.stack_maps
+    }
+
+    /// The options of this engine that the code it runs must be compiled
+    /// with.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_options(&self) -> CompileOptions {
+        CompileOptions {
+            bounds_check_elimination: self.bounds_check_elimination,
+        }
+    }
+
+    /// The artifact previously compiled with `hash`, if modules are
+    /// deduplicated and it is still alive.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cached_module(&self, hash: &ArtifactHash) -> Option<Arc<Artifact>> {
+        self.module_cache.as_ref()?.get(hash)?.upgrade()
+    }
+
+    /// Records a compiled artifact if modules are deduplicated, forgetting
+    /// the dropped ones.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cache_module(&mut self, artifact: &Arc<Artifact>) {
+        if let Some(module_cache) = &mut self.module_cache {
+            module_cache.retain(|_, artifact| artifact.strong_count() > 0);
+            module_cache.insert(artifact.hash(), Arc::downgrade(artifact));
+        }
+    }
+
+    /// The limits on table growth shared by the instances of the modules
+    /// of this engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn table_grow_limits(&self) -> &Arc<TableGrowLimits> {
+        &self.table_grow_limits
+    }
+
+    /// Makes sure the process-wide trap handlers are installed, recording
+    /// whether this engine installed them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn install_trap_handlers(&mut self) {
+        if install_trap_handlers() {
+            self.installed_trap_handlers = true;
+        }
+    }
+
+    /// The maximum number of locals a compiled function may declare, if
+    /// limited.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn max_locals_per_function(&self) -> Option<u32> {
+        self.max_locals_per_function
+    }
+
+    /// The calling convention used to call host functions, if not the
+    /// default one of the target.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn host_calling_convention(&self) -> Option<CallingConvention> {
+        self.host_calling_convention
+    }
+
     /// Allocate compiled functions into memory
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
     pub(crate) fn allocate(
         &mut self,
-        _module: &ModuleInfo,
+        module: &ModuleInfo,
         functions: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
+        function_relocations: &PrimaryMap<LocalFunctionIndex, Vec<Relocation>>,
         function_call_trampolines: &PrimaryMap<SignatureIndex, FunctionBody>,
         dynamic_function_trampolines: &PrimaryMap<FunctionIndex, FunctionBody>,
         custom_sections: &PrimaryMap<SectionIndex, CustomSection>,
@@ -248,18 +1878,39 @@ impl EngineInner {
             PrimaryMap<SignatureIndex, VMTrampoline>,
             PrimaryMap<FunctionIndex, FunctionBodyPtr>,
             PrimaryMap<SectionIndex, SectionBodyPtr>,
+            CodeBytesCharge,
         ),
         CompileError,
     > {
-        let function_bodies = functions
-            .values()
+        // Maps every function to the index of the allocated body it uses.
+        let (function_slots, unique_functions) = if self.function_dedup {
+            dedup_functions(functions, function_relocations)
+        } else {
+            (
+                (0..functions.len()).collect(),
+                functions.values().collect::<Vec<_>>(),
+            )
+        };
+        // Functions are laid out in `LocalFunctionIndex` order, before the
+        // trampolines and sections, which `Artifact::function_layout_order`
+        // relies on.
+        let function_bodies = unique_functions
+            .iter()
+            .copied()
             .chain(function_call_trampolines.values())
             .chain(dynamic_function_trampolines.values())
             .collect::<Vec<_>>();
         let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
             .values()
             .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
-        self.code_memory.push(CodeMemory::new());
+        let charge = self.charge_code_bytes(
+            function_bodies
+                .iter()
+                .map(|function| function.body.len())
+                .sum::<usize>()
+                + section_bytes(custom_sections),
+        )?;
+        self.code_memory.push(self.new_code_memory());
 
         let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
             self.code_memory
@@ -277,27 +1928,47 @@ impl EngineInner {
                     ))
                 })?;
 
-        let allocated_functions_result = allocated_functions
-            .drain(0..functions.len())
-            .map(|slice| FunctionExtent {
-                ptr: FunctionBodyPtr(slice.as_ptr()),
-                length: slice.len(),
+        let stats = &mut self.code_memory_stats;
+        stats.function_bodies += unique_functions.len();
+        stats.function_code_bytes += unique_functions
+            .iter()
+            .map(|function| function.body.len())
+            .sum::<usize>();
+        stats.call_trampoline_bytes += function_call_trampolines
+            .values()
+            .map(|trampoline| trampoline.body.len())
+            .sum::<usize>();
+        stats.dynamic_trampoline_bytes += dynamic_function_trampolines
+            .values()
+            .map(|trampoline| trampoline.body.len())
+            .sum::<usize>();
+        stats.custom_section_bytes += section_bytes(custom_sections);
+
+        let allocated_dynamic_functions =
+            allocated_functions.split_off(unique_functions.len() + function_call_trampolines.len());
+        let allocated_call_trampolines = allocated_functions.split_off(unique_functions.len());
+
+        let allocated_unique_functions = allocated_functions
+            .iter()
+            .map(|slice| (FunctionBodyPtr(slice.as_ptr()), slice.len()))
+            .collect::<Vec<_>>();
+        let allocated_functions_result = function_slots
+            .into_iter()
+            .map(|slot| {
+                let (ptr, length) = allocated_unique_functions[slot];
+                FunctionExtent { ptr, length }
             })
             .collect::<PrimaryMap<LocalFunctionIndex, _>>();
 
-        let mut allocated_function_call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline> =
-            PrimaryMap::new();
-        for ptr in allocated_functions
-            .drain(0..function_call_trampolines.len())
-            .map(|slice| slice.as_ptr())
-        {
-            let trampoline =
-                unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(ptr) };
-            allocated_function_call_trampolines.push(trampoline);
-        }
+        let allocated_function_call_trampolines = allocated_call_trampolines
+            .iter()
+            .map(|slice| unsafe {
+                std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(slice.as_ptr())
+            })
+            .collect::<PrimaryMap<SignatureIndex, _>>();
 
-        let allocated_dynamic_function_trampolines = allocated_functions
-            .drain(..)
+        let allocated_dynamic_function_trampolines = allocated_dynamic_functions
+            .iter()
             .map(|slice| FunctionBodyPtr(slice.as_ptr()))
             .collect::<PrimaryMap<FunctionIndex, _>>();
 
@@ -318,18 +1989,239 @@ impl EngineInner {
             })
             .collect::<PrimaryMap<SectionIndex, _>>();
 
+        self.register_functions(module, &allocated_functions_result);
+
+        #[cfg(feature = "gdb-jit")]
+        self.code_memory.last_mut().unwrap().set_gdb_jit_symbols(
+            crate::engine::gdb_jit::function_symbols(module, &allocated_functions_result),
+        );
+
+        #[cfg(feature = "perfmap")]
+        self.perfmap_entries
+            .extend(crate::engine::perfmap::function_entries(
+                module,
+                &allocated_functions_result,
+            ));
+
+        let mapped_len = self.code_memory.last().unwrap().mapped_len();
+        self.code_memory_stats.reserved_bytes += mapped_len;
+        if let Some(address) = self.code_base_address.as_mut() {
+            *address += mapped_len;
+        }
+
         Ok((
             allocated_functions_result,
             allocated_function_call_trampolines,
             allocated_dynamic_function_trampolines,
             allocated_custom_sections,
+            charge,
         ))
     }
 
+    /// Allocates the code memory for a single function recompiled for
+    /// `module`, and for the custom sections it refers to.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn allocate_function(
+        &mut self,
+        module: &ModuleInfo,
+        index: LocalFunctionIndex,
+        function: &FunctionBody,
+        custom_sections: &PrimaryMap<SectionIndex, CustomSection>,
+    ) -> Result<
+        (
+            FunctionExtent,
+            PrimaryMap<SectionIndex, SectionBodyPtr>,
+            CodeBytesCharge,
+        ),
+        CompileError,
+    > {
+        let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
+            .values()
+            .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
+        let charge =
+            self.charge_code_bytes(function.body.len() + section_bytes(custom_sections))?;
+        self.code_memory.push(self.new_code_memory());
+        let (allocated_functions, allocated_executable_sections, allocated_data_sections) = self
+            .code_memory
+            .last_mut()
+            .unwrap()
+            .allocate(
+                &[function],
+                executable_sections.as_slice(),
+                data_sections.as_slice(),
+            )
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to allocate memory for functions: {}",
+                    message
+                ))
+            })?;
+        let extent = FunctionExtent {
+            ptr: FunctionBodyPtr(allocated_functions[0].as_ptr()),
+            length: allocated_functions[0].len(),
+        };
+
+        let mut exec_iter = allocated_executable_sections.iter();
+        let mut data_iter = allocated_data_sections.iter();
+        let allocated_custom_sections = custom_sections
+            .values()
+            .map(|section| {
+                SectionBodyPtr(
+                    if section.protection == CustomSectionProtection::ReadExecute {
+                        exec_iter.next()
+                    } else {
+                        data_iter.next()
+                    }
+                    .unwrap()
+                    .as_ptr(),
+                )
+            })
+            .collect::<PrimaryMap<SectionIndex, _>>();
+
+        let stats = &mut self.code_memory_stats;
+        stats.function_bodies += 1;
+        stats.function_code_bytes += function.body.len();
+        stats.custom_section_bytes += section_bytes(custom_sections);
+
+        self.register_function_ranges(vec![(
+            module.func_index(index),
+            extent.ptr.0 as usize,
+            extent.length,
+        )]);
+
+        let mapped_len = self.code_memory.last().unwrap().mapped_len();
+        self.code_memory_stats.reserved_bytes += mapped_len;
+        if let Some(address) = self.code_base_address.as_mut() {
+            *address += mapped_len;
+        }
+        Ok((extent, allocated_custom_sections, charge))
+    }
+
+    /// Charges `bytes` more of code and custom sections, about to be
+    /// allocated or mapped, if that stays within the limit of the engine.
+    ///
+    /// Charges are only given back when released, so this must be called
+    /// with the engine locked to check and charge at once.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn charge_code_bytes(&self, bytes: usize) -> Result<CodeBytesCharge, CompileError> {
+        if let Some(max_code_bytes) = self.max_code_bytes {
+            if self.charged_code_bytes.load(SeqCst).saturating_add(bytes) > max_code_bytes {
+                return Err(CompileError::Resource(
+                    "code size limit exceeded".to_string(),
+                ));
+            }
+        }
+        self.charged_code_bytes.fetch_add(bytes, SeqCst);
+        Ok(CodeBytesCharge {
+            charged_code_bytes: self.charged_code_bytes.clone(),
+            bytes: AtomicUsize::new(bytes),
+        })
+    }
+
+    /// A charge of no bytes, for artifacts without code allocated by the
+    /// engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn no_code_bytes_charge(&self) -> CodeBytesCharge {
+        CodeBytesCharge {
+            charged_code_bytes: self.charged_code_bytes.clone(),
+            bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates the code memory for the next module, according to the
+    /// placement and pooling settings of the engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_code_memory(&self) -> CodeMemory {
+        match (self.code_base_address, &self.code_memory_pool) {
+            (Some(address), _) => CodeMemory::new_at(address),
+            (None, Some(pool)) => CodeMemory::with_pool(pool.clone()),
+            (None, None) => match self.huge_pages {
+                Some(huge_pages) => CodeMemory::with_huge_pages(huge_pages),
+                None => CodeMemory::new(),
+            },
+        }
+    }
+
+    /// Records the addresses of the functions of a module, for
+    /// [`Engine::lookup_function`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn register_functions(
+        &mut self,
+        module: &ModuleInfo,
+        functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    ) {
+        self.register_function_ranges(
+            functions
+                .iter()
+                .map(|(index, extent)| {
+                    (
+                        module.func_index(index),
+                        extent.ptr.0 as usize,
+                        extent.length,
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    /// Records the index, address and length of functions, for
+    /// [`Engine::lookup_function`] and the callbacks added with
+    /// [`Engine::on_function_published`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn register_function_ranges(&mut self, functions: Vec<(FunctionIndex, usize, usize)>) {
+        if !self.function_published_callbacks.is_empty() {
+            self.unpublished_functions.extend(functions.iter().copied());
+        }
+        self.function_table.write().unwrap().insert(
+            functions
+                .into_iter()
+                .map(|(index, address, len)| (address, len, index)),
+        );
+    }
+
+    /// Keeps code mapped from the file of an artifact for as long as the
+    /// engine is alive, like the code it allocates itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn adopt_mapped_code(
+        &mut self,
+        module: &ModuleInfo,
+        code: MappedCode,
+        functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    ) {
+        self.register_functions(module, functions);
+        // Mapped code is executable from the start.
+        self.published_functions
+            .append(&mut self.unpublished_functions);
+        self.code_memory_stats.mapped_bytes += code.len();
+        self.mapped_code.push(code);
+    }
+
+    /// Sets the `.debug_line` section of the code allocated last, built from
+    /// the address maps of its functions.
+    #[cfg(feature = "debug-info")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_debug_line(
+        &mut self,
+        module: &ModuleInfo,
+        functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+        frame_info: &PrimaryMap<LocalFunctionIndex, wasmer_types::CompiledFunctionFrameInfo>,
+    ) {
+        self.code_memory.last_mut().unwrap().set_debug_line(
+            crate::engine::debug_line::line_program(module, functions, frame_info),
+        );
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     /// Make memory containing compiled code executable.
     pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
+        let code_memory = self.code_memory.last_mut().unwrap();
+        code_memory.publish();
+        self.code_memory_stats.published_bytes += code_memory.executable_len();
+        self.published_functions
+            .append(&mut self.unpublished_functions);
+        #[cfg(feature = "perfmap")]
+        crate::engine::perfmap::write_entries(&std::mem::take(&mut self.perfmap_entries));
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -351,6 +2243,77 @@ impl EngineInner {
     pub fn signatures(&self) -> &SignatureRegistry {
         &self.signatures
     }
+
+    /// Registers `signatures` on behalf of an artifact, until the returned
+    /// registration is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn register_signatures<'a>(
+        &self,
+        signatures: impl IntoIterator<Item = &'a FunctionType>,
+    ) -> SignatureRegistration {
+        SignatureRegistration {
+            signatures: self.signatures.clone(),
+            function_call_trampolines: self.function_call_trampolines.clone(),
+            indices: self.signatures.register_all(signatures),
+        }
+    }
+
+    /// The hook invoked for every relocation before it is applied, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn relocation_hook(&self) -> Option<&RelocationHook> {
+        self.relocation_hook.as_ref()
+    }
+
+    /// The policy for artifacts with a newer format version.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn deserialize_forward_policy(&self) -> DeserializeForwardPolicy {
+        self.deserialize_forward_policy
+    }
+}
+
+/// The number of bytes of the custom sections.
+#[cfg(not(target_arch = "wasm32"))]
+fn section_bytes(custom_sections: &PrimaryMap<SectionIndex, CustomSection>) -> usize {
+    custom_sections
+        .values()
+        .map(|section| section.bytes.len())
+        .sum()
+}
+
+/// Groups the functions with identical code and relocations.
+///
+/// Returns, for every function, the index of the body it uses in the
+/// returned list of unique bodies.
+#[cfg(not(target_arch = "wasm32"))]
+fn dedup_functions<'a>(
+    functions: &'a PrimaryMap<LocalFunctionIndex, FunctionBody>,
+    function_relocations: &PrimaryMap<LocalFunctionIndex, Vec<Relocation>>,
+) -> (Vec<usize>, Vec<&'a FunctionBody>) {
+    let mut by_code: HashMap<&[u8], Vec<(LocalFunctionIndex, usize)>> = HashMap::new();
+    let mut unique_functions = Vec::new();
+    let function_slots = functions
+        .iter()
+        .map(|(index, function)| {
+            let candidates = by_code.entry(function.body.as_slice()).or_default();
+            let duplicate = candidates
+                .iter()
+                .find(|(other, _)| {
+                    functions[*other] == *function
+                        && function_relocations[*other] == function_relocations[index]
+                })
+                .map(|(_, slot)| *slot);
+            match duplicate {
+                Some(slot) => slot,
+                None => {
+                    let slot = unique_functions.len();
+                    unique_functions.push(function);
+                    candidates.push((index, slot));
+                    slot
+                }
+            }
+        })
+        .collect();
+    (function_slots, unique_functions)
 }
 
 #[cfg(feature = "compiler")]
@@ -372,6 +2335,37 @@ impl From<&Self> for Engine {
     }
 }
 
+/// The state of the process-wide trap handlers, as seen by an `Engine`.
+///
+/// See [`Engine::trap_handler_status`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapHandlerStatus {
+    /// Whether the trap handlers are installed.
+    pub registered: bool,
+    /// Whether this engine is the one that installed them.
+    pub installed_by_this_engine: bool,
+    /// Whether other code replaced them since, so that traps may not be
+    /// caught anymore. This is only detected on Unix.
+    pub conflict: bool,
+}
+
+/// Where a trap happened in the code compiled by an `Engine`.
+///
+/// See [`Engine::resolve_trap`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapLocation {
+    /// The index of the function in its module.
+    pub function: FunctionIndex,
+    /// The offset of the trapping instruction in the compiled code of the
+    /// function.
+    pub function_offset: usize,
+    /// The offset in the wasm binary of the instruction compiled to the
+    /// trapping one, if known.
+    pub wasm_offset: Option<u32>,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 /// A unique identifier for an Engine.
@@ -384,6 +2378,12 @@ impl EngineId {
     pub fn id(&self) -> String {
         format!("{}", &self.id)
     }
+
+    /// Copies this identifier. Unlike `clone`, which creates a new
+    /// identifier, the copy compares equal to the original.
+    pub(crate) fn copy(&self) -> Self {
+        Self { id: self.id }
+    }
 }
 
 impl Clone for EngineId {
@@ -9,13 +9,48 @@ use wasmer_types::{Relocation, RelocationKind, RelocationTarget, Relocations, Se
 use wasmer_vm::libcalls::function_pointer;
 use wasmer_vm::SectionBodyPtr;
 
-fn apply_relocation(
+/// A hook that can observe every relocation before it is applied by
+/// [`link_module`], and redirect it.
+///
+/// The hook is given the relocation, the address of the function body or
+/// custom section being patched, and the address the relocation target
+/// resolved to, which it can change. Only that address can be changed:
+/// what the compiled code expects at it is described by
+/// [`Engine::set_relocation_hook`].
+///
+/// # Deadlocks
+///
+/// The hook runs while the engine is locked, as the code being patched
+/// isn't published yet. It must not call into the [`Engine`] it is set on,
+/// or into the modules and stores using it, which would deadlock.
+///
+/// [`Engine`]: crate::Engine
+/// [`Engine::set_relocation_hook`]: crate::Engine::set_relocation_hook
+pub type RelocationHook = Box<dyn Fn(&Relocation, usize, &mut usize) + Send + Sync>;
+
+/// Whether a relocation stays valid when the code holding it is moved
+/// along with its target, so that it can be applied ahead of time.
+///
+/// Relocations to libcalls are only position-independent if they go
+/// through the libcall trampolines of the module, see `apply_relocation`.
+pub(crate) fn is_position_independent(r: &Relocation) -> bool {
+    match r.kind {
+        RelocationKind::X86PCRel4 | RelocationKind::X86CallPCRel4 | RelocationKind::Arm64Call => {
+            true
+        }
+        RelocationKind::X86PCRel8 => !matches!(r.reloc_target, RelocationTarget::LibCall(_)),
+        _ => false,
+    }
+}
+
+pub(crate) fn apply_relocation(
     body: usize,
     r: &Relocation,
     allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
     allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
     libcall_trampolines: SectionIndex,
     libcall_trampoline_len: usize,
+    relocation_hook: Option<&RelocationHook>,
 ) {
     let target_func_address: usize = match r.reloc_target {
         RelocationTarget::LocalFunc(index) => *allocated_functions[index].ptr as usize,
@@ -37,6 +72,14 @@ fn apply_relocation(
         }
     };
 
+    let mut target_func_address = target_func_address;
+    if let Some(hook) = relocation_hook {
+        hook(r, body, &mut target_func_address);
+    }
+    patch_relocation(body, r, target_func_address);
+}
+
+fn patch_relocation(body: usize, r: &Relocation, target_func_address: usize) {
     match r.kind {
         RelocationKind::Abs8 => unsafe {
             let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
@@ -102,6 +145,10 @@ fn apply_relocation(
 
 /// Links a module, patching the allocated functions with the
 /// required relocations and jump tables.
+///
+/// If a `relocation_hook` is given, it is invoked for every relocation
+/// right before it is applied.
+#[allow(clippy::too_many_arguments)]
 pub fn link_module(
     _module: &ModuleInfo,
     allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
@@ -110,6 +157,7 @@ pub fn link_module(
     section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
     libcall_trampolines: SectionIndex,
     trampoline_len: usize,
+    relocation_hook: Option<&RelocationHook>,
 ) {
     for (i, section_relocs) in section_relocations.iter() {
         let body = *allocated_sections[i] as usize;
@@ -121,6 +169,7 @@ pub fn link_module(
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
+                relocation_hook,
             );
         }
     }
@@ -134,6 +183,7 @@ pub fn link_module(
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
+                relocation_hook,
             );
         }
     }
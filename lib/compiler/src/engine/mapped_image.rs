@@ -0,0 +1,474 @@
+//! Code executed straight from the memory-mapped file of an artifact.
+//!
+//! A mappable artifact is a serialized artifact followed by an image of its
+//! code, laid out and linked the way it runs, and by a footer locating the
+//! image. The image starts at an [`IMAGE_ALIGNMENT`] boundary of the file,
+//! so that it can be mapped on its own.
+//!
+//! The image has two parts, each starting at an `IMAGE_ALIGNMENT`
+//! boundary: the code, whose relocations are all position-independent and
+//! applied when the image is built, and the sections with relocations that
+//! must be applied by every process loading it, such as the libcall
+//! trampolines. Only the pages of the latter are written to when the image
+//! is mapped, so the code stays shared with the page cache.
+
+use crate::engine::code_memory::touch_pages;
+use crate::engine::link::{apply_relocation, is_position_independent};
+use crate::engine::unwind::UnwindRegistry;
+use crate::{ArtifactBuild, FunctionExtent};
+use memmap2::{Mmap, MmapOptions};
+use std::convert::TryInto;
+use std::fs::File;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    CompiledFunctionUnwindInfo, CustomSectionProtection, DeserializeError, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, SectionIndex, SignatureIndex,
+};
+use wasmer_vm::{FunctionBodyPtr, SectionBodyPtr, VMFunctionBody, VMTrampoline};
+
+/// The alignment of the image in the file, and of its parts.
+///
+/// This is a multiple of the page size of every supported platform.
+pub(crate) const IMAGE_ALIGNMENT: usize = 0x10000;
+
+/// The alignment of the functions and trampolines in the image, as in
+/// `CodeMemory`.
+const FUNCTION_ALIGNMENT: usize = 16;
+
+/// The alignment of the sections patched when the image is mapped.
+const PATCHED_SECTION_ALIGNMENT: usize = 64;
+
+/// The bytes ending a mappable artifact.
+const FOOTER_MAGIC: &[u8; 16] = b"wasmer-mappable\0";
+
+/// The offsets of the image and of its layout, followed by the magic.
+const FOOTER_LEN: usize = 8 + 8 + FOOTER_MAGIC.len();
+
+/// Where everything is in an image, relative to its start.
+pub(crate) struct ImageLayout {
+    len: usize,
+    patched_start: usize,
+    functions: Vec<(usize, usize)>,
+    call_trampolines: Vec<usize>,
+    dynamic_trampolines: Vec<usize>,
+    /// The offset of every section, and whether it is patched on mapping.
+    sections: Vec<(usize, bool)>,
+}
+
+/// The image of a mappable artifact, in its file.
+pub(crate) struct ImageLocation {
+    offset: usize,
+    layout: ImageLayout,
+}
+
+impl ImageLocation {
+    /// The offset of the image in the file, which is also the length of
+    /// the serialized artifact before it.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The length of the image, which is mapped as a whole.
+    pub(crate) fn len(&self) -> usize {
+        self.layout.len
+    }
+}
+
+/// Code mapped from the file of an artifact.
+pub(crate) struct MappedCode {
+    // Declared first, so that the unwinder forgets about the code before
+    // it is unmapped.
+    unwind_registry: UnwindRegistry,
+    image: Mmap,
+}
+
+impl MappedCode {
+    /// The number of bytes mapped.
+    pub(crate) fn len(&self) -> usize {
+        self.image.len()
+    }
+
+    /// Faults in every page of the image, reading it from the file if
+    /// needed.
+    pub(crate) fn warm(&self) {
+        touch_pages(self.image.as_ptr(), self.image.len());
+    }
+
+    /// Registers the DWARF unwind information of the code.
+    pub(crate) fn publish_eh_frame(&mut self, eh_frame: Option<&[u8]>) -> Result<(), String> {
+        self.unwind_registry.publish(eh_frame)
+    }
+}
+
+/// The code of an artifact, mapped from its file.
+pub(crate) struct MappedImage {
+    pub(crate) code: MappedCode,
+    pub(crate) functions: PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    pub(crate) call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline>,
+    pub(crate) dynamic_trampolines: PrimaryMap<FunctionIndex, FunctionBodyPtr>,
+    pub(crate) sections: PrimaryMap<SectionIndex, SectionBodyPtr>,
+}
+
+fn round_up(size: usize, multiple: usize) -> usize {
+    debug_assert!(multiple.is_power_of_two());
+    (size + (multiple - 1)) & !(multiple - 1)
+}
+
+/// Reserves `size` bytes at the end of an image of `len` bytes.
+fn place(len: &mut usize, size: usize, alignment: usize) -> usize {
+    let offset = round_up(*len, alignment);
+    *len = offset + size;
+    offset
+}
+
+fn has_windows_unwind_info(body: &FunctionBody) -> bool {
+    matches!(
+        body.unwind_info,
+        Some(CompiledFunctionUnwindInfo::WindowsX64(_))
+    )
+}
+
+/// Builds the image of the code of `artifact`.
+///
+/// Returns `None` if the code can't run from a read-only mapping, because
+/// some of its relocations aren't position-independent, or because its
+/// unwind information must be registered function by function.
+pub(crate) fn build_image(artifact: &ArtifactBuild) -> Option<(Vec<u8>, ImageLayout)> {
+    let functions = artifact.get_function_bodies_ref();
+    let call_trampolines = artifact.get_function_call_trampolines_ref();
+    let dynamic_trampolines = artifact.get_dynamic_function_trampolines_ref();
+    let sections = artifact.get_custom_sections_ref();
+    let function_relocations = artifact.get_function_relocations();
+    let section_relocations = artifact.get_custom_section_relocations_ref();
+
+    if functions
+        .values()
+        .chain(call_trampolines.values())
+        .chain(dynamic_trampolines.values())
+        .any(has_windows_unwind_info)
+        || function_relocations
+            .values()
+            .flatten()
+            .any(|r| !is_position_independent(r))
+    {
+        return None;
+    }
+    // The libcall trampolines hold the absolute address of every libcall,
+    // so they are always patched.
+    let patched = |index: SectionIndex| {
+        sections[index].protection != CustomSectionProtection::ReadExecute
+            || !section_relocations[index]
+                .iter()
+                .all(is_position_independent)
+    };
+
+    let mut len = 0;
+    let function_offsets = functions
+        .values()
+        .map(|function| {
+            let size = function.body.len();
+            (place(&mut len, size, FUNCTION_ALIGNMENT), size)
+        })
+        .collect::<Vec<_>>();
+    let call_trampoline_offsets = call_trampolines
+        .values()
+        .map(|trampoline| place(&mut len, trampoline.body.len(), FUNCTION_ALIGNMENT))
+        .collect::<Vec<_>>();
+    let dynamic_trampoline_offsets = dynamic_trampolines
+        .values()
+        .map(|trampoline| place(&mut len, trampoline.body.len(), FUNCTION_ALIGNMENT))
+        .collect::<Vec<_>>();
+    let mut section_offsets = vec![(0, false); sections.len()];
+    for (index, section) in sections.iter().filter(|(index, _)| !patched(*index)) {
+        section_offsets[index.index()] = (
+            place(&mut len, section.bytes.len(), FUNCTION_ALIGNMENT),
+            false,
+        );
+    }
+    let patched_start = place(&mut len, 0, IMAGE_ALIGNMENT);
+    for (index, section) in sections.iter().filter(|(index, _)| patched(*index)) {
+        section_offsets[index.index()] = (
+            place(&mut len, section.bytes.len(), PATCHED_SECTION_ALIGNMENT),
+            true,
+        );
+    }
+    let layout = ImageLayout {
+        len,
+        patched_start,
+        functions: function_offsets,
+        call_trampolines: call_trampoline_offsets,
+        dynamic_trampolines: dynamic_trampoline_offsets,
+        sections: section_offsets,
+    };
+
+    let mut image = vec![0; len];
+    let bodies = functions
+        .values()
+        .zip(layout.functions.iter().map(|(offset, _)| *offset))
+        .chain(
+            call_trampolines
+                .values()
+                .zip(layout.call_trampolines.iter().copied()),
+        )
+        .chain(
+            dynamic_trampolines
+                .values()
+                .zip(layout.dynamic_trampolines.iter().copied()),
+        );
+    for (body, offset) in bodies {
+        image[offset..offset + body.body.len()].copy_from_slice(&body.body);
+    }
+    for (section, (offset, _)) in sections.values().zip(layout.sections.iter()) {
+        let bytes = section.bytes.as_slice();
+        image[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // Position-independent relocations only depend on where the code and
+    // its targets are relative to each other, so they can be applied to
+    // the image wherever it is.
+    let base = image.as_mut_ptr() as usize;
+    let allocated_functions = layout.functions(base);
+    let allocated_sections = layout.sections(base);
+    let unpatched_section_relocations = section_relocations
+        .iter()
+        .filter(|(index, _)| !patched(*index))
+        .map(|(index, relocations)| (*allocated_sections[index] as usize, relocations));
+    let relocations = function_relocations
+        .iter()
+        .map(|(index, relocations)| (*allocated_functions[index].ptr as usize, relocations))
+        .chain(unpatched_section_relocations);
+    for (body, relocations) in relocations {
+        for r in relocations {
+            apply_relocation(
+                body,
+                r,
+                &allocated_functions,
+                &allocated_sections,
+                artifact.get_libcall_trampolines(),
+                artifact.get_libcall_trampoline_len(),
+                None,
+            );
+        }
+    }
+    Some((image, layout))
+}
+
+fn push_u64(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
+/// Appends an image, and its footer, to a serialized artifact.
+pub(crate) fn append_image(serialized: &mut Vec<u8>, image: &[u8], layout: &ImageLayout) {
+    serialized.resize(round_up(serialized.len(), IMAGE_ALIGNMENT), 0);
+    let image_offset = serialized.len();
+    serialized.extend_from_slice(image);
+    let layout_offset = serialized.len();
+    layout.write(serialized);
+    push_u64(serialized, image_offset);
+    push_u64(serialized, layout_offset);
+    serialized.extend_from_slice(FOOTER_MAGIC);
+}
+
+/// Finds the image of a mappable artifact, if `bytes` holds one.
+pub(crate) fn find_image(bytes: &[u8]) -> Result<Option<ImageLocation>, DeserializeError> {
+    if bytes.len() < FOOTER_LEN || !bytes.ends_with(FOOTER_MAGIC) {
+        return Ok(None);
+    }
+    let corrupted = || DeserializeError::CorruptedBinary("Invalid mappable artifact".to_string());
+    let footer_start = bytes.len() - FOOTER_LEN;
+    let mut footer = Reader(&bytes[footer_start..]);
+    let image_offset = footer.read().ok_or_else(corrupted)?;
+    let layout_offset = footer.read().ok_or_else(corrupted)?;
+    if image_offset % IMAGE_ALIGNMENT != 0
+        || image_offset > layout_offset
+        || layout_offset > footer_start
+    {
+        return Err(corrupted());
+    }
+    let layout = ImageLayout::read(&bytes[layout_offset..footer_start]).ok_or_else(corrupted)?;
+    if layout.len != layout_offset - image_offset {
+        return Err(corrupted());
+    }
+    Ok(Some(ImageLocation {
+        offset: image_offset,
+        layout,
+    }))
+}
+
+/// Maps the image of `artifact` found at `location` in `file`, applying
+/// the relocations of its patched sections.
+///
+/// # Safety
+///
+/// The image must have been built by [`build_image`] from the same
+/// artifact, and the file must not be modified while the image is mapped.
+pub(crate) unsafe fn map_image(
+    file: &File,
+    location: &ImageLocation,
+    artifact: &ArtifactBuild,
+) -> Result<MappedImage, DeserializeError> {
+    let layout = &location.layout;
+    if layout.functions.len() != artifact.get_function_bodies_ref().len()
+        || layout.call_trampolines.len() != artifact.get_function_call_trampolines_ref().len()
+        || layout.dynamic_trampolines.len() != artifact.get_dynamic_function_trampolines_ref().len()
+        || layout.sections.len() != artifact.get_custom_sections_ref().len()
+    {
+        return Err(DeserializeError::CorruptedBinary(
+            "The code image doesn't match the artifact".to_string(),
+        ));
+    }
+
+    // The mapping is private, so that patching it only copies the pages
+    // being patched.
+    let mut image = MmapOptions::new()
+        .offset(location.offset as u64)
+        .len(layout.len)
+        .map_copy(file)?;
+    let base = image.as_mut_ptr() as usize;
+    let functions = layout.functions(base);
+    let sections = layout.sections(base);
+    for (index, relocations) in artifact.get_custom_section_relocations_ref().iter() {
+        if !layout.sections[index.index()].1 {
+            continue;
+        }
+        let body = *sections[index] as usize;
+        for r in relocations {
+            apply_relocation(
+                body,
+                r,
+                &functions,
+                &sections,
+                artifact.get_libcall_trampolines(),
+                artifact.get_libcall_trampoline_len(),
+                None,
+            );
+        }
+    }
+    let image = image.make_exec()?;
+
+    Ok(MappedImage {
+        code: MappedCode {
+            unwind_registry: UnwindRegistry::new(),
+            image,
+        },
+        functions,
+        call_trampolines: layout.call_trampolines(base),
+        dynamic_trampolines: layout.dynamic_trampolines(base),
+        sections,
+    })
+}
+
+impl ImageLayout {
+    fn functions(&self, base: usize) -> PrimaryMap<LocalFunctionIndex, FunctionExtent> {
+        self.functions
+            .iter()
+            .map(|(offset, length)| FunctionExtent {
+                ptr: FunctionBodyPtr((base + offset) as *const VMFunctionBody),
+                length: *length,
+            })
+            .collect()
+    }
+
+    fn call_trampolines(&self, base: usize) -> PrimaryMap<SignatureIndex, VMTrampoline> {
+        self.call_trampolines
+            .iter()
+            .map(|offset| unsafe { std::mem::transmute::<usize, VMTrampoline>(base + offset) })
+            .collect()
+    }
+
+    fn dynamic_trampolines(&self, base: usize) -> PrimaryMap<FunctionIndex, FunctionBodyPtr> {
+        self.dynamic_trampolines
+            .iter()
+            .map(|offset| FunctionBodyPtr((base + offset) as *const VMFunctionBody))
+            .collect()
+    }
+
+    fn sections(&self, base: usize) -> PrimaryMap<SectionIndex, SectionBodyPtr> {
+        self.sections
+            .iter()
+            .map(|(offset, _)| SectionBodyPtr((base + offset) as *const u8))
+            .collect()
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        push_u64(bytes, self.len);
+        push_u64(bytes, self.patched_start);
+        push_u64(bytes, self.functions.len());
+        for (offset, length) in &self.functions {
+            push_u64(bytes, *offset);
+            push_u64(bytes, *length);
+        }
+        for offsets in [&self.call_trampolines, &self.dynamic_trampolines] {
+            push_u64(bytes, offsets.len());
+            for offset in offsets {
+                push_u64(bytes, *offset);
+            }
+        }
+        push_u64(bytes, self.sections.len());
+        for (offset, patched) in &self.sections {
+            push_u64(bytes, *offset);
+            push_u64(bytes, *patched as usize);
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        let len = reader.read()?;
+        let patched_start = reader.read()?;
+        let functions = reader.read_list(|reader| Some((reader.read()?, reader.read()?)))?;
+        let call_trampolines = reader.read_list(Reader::read)?;
+        let dynamic_trampolines = reader.read_list(Reader::read)?;
+        let sections = reader.read_list(|reader| Some((reader.read()?, reader.read()? != 0)))?;
+        if !reader.0.is_empty() || patched_start > len {
+            return None;
+        }
+        // Everything must be within the image, to not map anything else.
+        let in_image = |offset: usize, length: usize| {
+            offset.checked_add(length).map_or(false, |end| end <= len)
+        };
+        if !functions
+            .iter()
+            .all(|(offset, length)| in_image(*offset, *length))
+            || !call_trampolines
+                .iter()
+                .chain(&dynamic_trampolines)
+                .chain(sections.iter().map(|(offset, _)| offset))
+                .all(|offset| in_image(*offset, 0))
+        {
+            return None;
+        }
+        Some(Self {
+            len,
+            patched_start,
+            functions,
+            call_trampolines,
+            dynamic_trampolines,
+            sections,
+        })
+    }
+}
+
+/// Reads the little-endian integers of a layout.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read(&mut self) -> Option<usize> {
+        if self.0.len() < 8 {
+            return None;
+        }
+        let (value, rest) = self.0.split_at(8);
+        self.0 = rest;
+        u64::from_le_bytes(value.try_into().unwrap())
+            .try_into()
+            .ok()
+    }
+
+    fn read_list<T>(&mut self, read: impl Fn(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let count = self.read()?;
+        // Every entry takes at least 8 bytes.
+        if count > self.0.len() / 8 {
+            return None;
+        }
+        (0..count).map(|_| read(self)).collect()
+    }
+}
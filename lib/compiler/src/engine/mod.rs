@@ -1,6 +1,12 @@
 //! The Wasmer Engine.
 
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod activity;
 mod error;
+#[cfg(feature = "gdb-jit")]
+#[cfg(not(target_arch = "wasm32"))]
+mod gdb_jit;
 #[cfg(not(target_arch = "wasm32"))]
 mod resolver;
 #[cfg(not(target_arch = "wasm32"))]
@@ -8,6 +14,9 @@ mod trap;
 #[cfg(not(target_arch = "wasm32"))]
 mod tunables;
 
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod archive;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod artifact;
@@ -16,6 +25,16 @@ mod builder;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod code_memory;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+mod compile_future;
+#[cfg(feature = "debug-info")]
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod debug_line;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod function_table;
 #[cfg(feature = "translator")]
 mod inner;
 #[cfg(feature = "translator")]
@@ -23,9 +42,19 @@ mod inner;
 mod link;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
+mod mapped_image;
+#[cfg(feature = "perfmap")]
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod perfmap;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
 mod unwind;
 
-pub use self::error::{InstantiationError, LinkError};
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::activity::{ExecutionGuard, IdleFuture};
+pub use self::error::{CodeMemoryError, EngineBuilderError, InstantiationError, LinkError};
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::resolver::resolve_imports;
 #[cfg(not(target_arch = "wasm32"))]
@@ -40,9 +69,34 @@ pub use self::artifact::Artifact;
 pub use self::builder::EngineBuilder;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::code_memory::CodeMemory;
+pub use self::code_memory::{
+    CodeMemory, CodeMemoryPool, CodeMemoryStats, HugePages, RegionPermission, SharedTrampolineTable,
+};
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::compile_future::CompileFuture;
+#[cfg(feature = "compiler")]
+pub use self::inner::CompileSettings;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::inner::PreCompileHook;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::inner::{
+    CodePublishedCallback, FunctionPublishedCallback, ImportedTrampolineTable, TrampolineTable,
+};
 #[cfg(feature = "translator")]
-pub use self::inner::{Engine, EngineInner};
+pub use self::inner::{Engine, EngineId, EngineInner};
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::inner::{TrapHandlerStatus, TrapLocation};
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::link::{link_module, RelocationHook};
+#[cfg(feature = "perfmap")]
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::perfmap::{perfmap_path, PERFMAP_PATH_ENV};
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::link::link_module;
+pub use self::unwind::UnwindEntry;
@@ -0,0 +1,88 @@
+//! Writing of a [perf map] naming the compiled functions, so that `perf`
+//! can attribute samples to them.
+//!
+//! [perf map]: https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jit-interface.txt
+
+use super::trap::FunctionExtent;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{LocalFunctionIndex, ModuleInfo};
+
+/// The environment variable setting the path of the perf map, which is
+/// `/tmp/perf-<pid>.map` by default, where `perf` looks for it.
+pub const PERFMAP_PATH_ENV: &str = "WASMER_PERFMAP_PATH";
+
+lazy_static::lazy_static! {
+    static ref PERFMAP_PATH: PathBuf = std::env::var_os(PERFMAP_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| format!("/tmp/perf-{}.map", std::process::id()).into());
+    /// The perf map, opened on the first write.
+    static ref PERFMAP: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// The path of the perf map of this process, read from
+/// [`PERFMAP_PATH_ENV`] the first time it is needed.
+pub fn perfmap_path() -> &'static Path {
+    &PERFMAP_PATH
+}
+
+/// A line of the perf map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PerfMapEntry {
+    address: usize,
+    len: usize,
+    name: String,
+}
+
+/// The entries for every function of a module, named from its name
+/// section, or `wasm_func[<index>]` for the functions it doesn't name.
+pub(crate) fn function_entries(
+    module: &ModuleInfo,
+    functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+) -> Vec<PerfMapEntry> {
+    functions
+        .iter()
+        .map(|(local_index, extent)| {
+            let index = module.func_index(local_index);
+            let name = match module.function_names.get(&index) {
+                // A line break would end the line of the entry.
+                Some(name) => name.replace('\n', " "),
+                None => format!("wasm_func[{}]", index.index()),
+            };
+            PerfMapEntry {
+                address: extent.ptr.0 as usize,
+                len: extent.length,
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Appends the entries to the perf map.
+///
+/// This is only a profiling aid, so failing to write the perf map doesn't
+/// prevent the code from running.
+pub(crate) fn write_entries(entries: &[PerfMapEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+    let mut perfmap = PERFMAP.lock().unwrap();
+    if perfmap.is_none() {
+        *perfmap = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(perfmap_path())
+            .ok();
+    }
+    if let Some(file) = perfmap.as_mut() {
+        let lines = entries
+            .iter()
+            .map(|entry| format!("{:x} {:x} {}\n", entry.address, entry.len, entry.name))
+            .collect::<String>();
+        // Write every line at once, so that `perf` never sees part of one.
+        let _ = file.write_all(lines.as_bytes());
+    }
+}
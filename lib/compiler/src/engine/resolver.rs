@@ -38,7 +38,7 @@ fn get_extern_from_import(module: &ModuleInfo, import_index: &ImportIndex) -> Ex
 /// Get an `ExternType` given an export (and Engine signatures in case is a function).
 fn get_extern_type(context: &StoreObjects, extern_: &VMExtern) -> ExternType {
     match extern_ {
-        VMExtern::Function(f) => ExternType::Function(f.get(context).signature.clone()),
+        VMExtern::Function(f) => ExternType::Function((*f.get(context).signature).clone()),
         VMExtern::Table(t) => ExternType::Table(*t.get(context).ty()),
         VMExtern::Memory(m) => ExternType::Memory(m.get(context).ty()),
         VMExtern::Global(g) => {
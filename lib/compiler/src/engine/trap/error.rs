@@ -41,6 +41,10 @@ struct RuntimeErrorInner {
     native_trace: Backtrace,
 }
 
+/// A handler invoked with the frame of a guest that executed
+/// `unreachable`, before the resulting trap is returned to the caller.
+pub type UnreachableHandler = Box<dyn Fn(FrameInfo) + Send + Sync>;
+
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
     (t, t)
 }
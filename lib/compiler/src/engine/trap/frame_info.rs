@@ -211,7 +211,8 @@ pub fn register(
             start,
             local_index: i,
         };
-        assert!(functions.insert(end, func).is_none());
+        // Deduplicated functions share their extent; the first one wins.
+        functions.entry(end).or_insert(func);
     }
     if functions.is_empty() {
         return None;
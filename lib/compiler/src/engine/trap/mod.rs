@@ -1,6 +1,6 @@
 mod error;
 mod frame_info;
-pub use error::RuntimeError;
+pub use error::{RuntimeError, UnreachableHandler};
 pub use frame_info::{
     register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
     FRAME_INFO,
@@ -2,8 +2,8 @@ use crate::engine::error::LinkError;
 use std::ptr::NonNull;
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
-    GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
-    ModuleInfo, TableIndex, TableType,
+    CompileHints, GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
+    MemoryType, ModuleInfo, Pages, TableIndex, TableType,
 };
 use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects};
 use wasmer_vm::{MemoryStyle, TableStyle};
@@ -51,6 +51,21 @@ pub trait Tunables {
         vm_definition_location: NonNull<VMTableDefinition>,
     ) -> Result<VMTable, String>;
 
+    /// The number of pages of every memory created at instantiation to
+    /// commit up front, rather than when they are first accessed.
+    ///
+    /// This makes instantiation slower, but avoids page faults when the
+    /// instance starts using its memory. No pages are committed by default.
+    fn initial_committed_pages(&self) -> u32 {
+        0
+    }
+
+    /// Hints on how to compile modules, overriding the configuration of the
+    /// compiler of the engine. No hints are given by default.
+    fn compile_hints(&self) -> CompileHints {
+        CompileHints::default()
+    }
+
     /// Create a global with an unset value.
     fn create_global(&self, ty: GlobalType) -> Result<VMGlobal, String> {
         Ok(VMGlobal::new(ty))
@@ -79,11 +94,11 @@ pub trait Tunables {
             let mi = MemoryIndex::new(index);
             let ty = &module.memories[mi];
             let style = &memory_styles[mi];
-            memories.push(InternalStoreHandle::new(
-                context,
-                self.create_vm_memory(ty, style, *mdl)
-                    .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?,
-            ));
+            let memory = self
+                .create_vm_memory(ty, style, *mdl)
+                .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?;
+            memory.commit(Pages(self.initial_committed_pages()));
+            memories.push(InternalStoreHandle::new(context, memory));
         }
         Ok(memories)
     }
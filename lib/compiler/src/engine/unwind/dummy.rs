@@ -1,5 +1,6 @@
 //! Module for Dummy unwind registry.
 
+use super::UnwindEntry;
 use wasmer_types::CompiledFunctionUnwindInfo;
 
 /// Represents a registry of function unwind information when the host system
@@ -24,9 +25,19 @@ impl DummyUnwindRegistry {
         Ok(())
     }
 
+    /// The functions registered so far, which is always none.
+    pub fn entries(&self) -> &[UnwindEntry] {
+        &[]
+    }
+
     /// Publishes all registered functions.
     pub fn publish(&mut self, eh_frame: Option<&[u8]>) -> Result<(), String> {
         // Do nothing
         Ok(())
     }
+
+    /// Deregisters all published functions.
+    pub fn deregister(&mut self) {
+        // Do nothing
+    }
 }
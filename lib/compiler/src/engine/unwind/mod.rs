@@ -11,3 +11,19 @@ cfg_if::cfg_if! {
         pub use self::dummy::DummyUnwindRegistry as UnwindRegistry;
     }
 }
+
+/// A function registered with the unwinder of the system.
+///
+/// See [`Engine::unwind_entries`](crate::Engine::unwind_entries).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnwindEntry {
+    /// The address of the start of the function.
+    pub function_start: usize,
+    /// The length of the function, in bytes.
+    pub function_len: usize,
+    /// The offset of the unwind information of the function from its
+    /// start, when it is stored in code memory after the function, as on
+    /// Windows x64. DWARF unwind information is instead found in the
+    /// `.eh_frame` section published with the module.
+    pub unwind_info_offset: Option<usize>,
+}
@@ -3,11 +3,13 @@
 
 //! Module for System V ABI unwind registry.
 
+use super::UnwindEntry;
 use wasmer_types::CompiledFunctionUnwindInfo;
 
 /// Represents a registry of function unwind information for System V ABI.
 pub struct UnwindRegistry {
     registrations: Vec<usize>,
+    entries: Vec<UnwindEntry>,
     published: bool,
 }
 
@@ -22,6 +24,7 @@ impl UnwindRegistry {
     pub fn new() -> Self {
         Self {
             registrations: Vec::new(),
+            entries: Vec::new(),
             published: false,
         }
     }
@@ -29,18 +32,30 @@ impl UnwindRegistry {
     /// Registers a function given the start offset, length, and unwind information.
     pub fn register(
         &mut self,
-        _base_address: usize,
-        _func_start: u32,
-        _func_len: u32,
+        base_address: usize,
+        func_start: u32,
+        func_len: u32,
         info: &CompiledFunctionUnwindInfo,
     ) -> Result<(), String> {
         match info {
             CompiledFunctionUnwindInfo::Dwarf => {}
             _ => return Err("unsupported unwind information".to_string()),
         };
+        // The frames themselves are registered from the `eh_frame` section
+        // when publishing.
+        self.entries.push(UnwindEntry {
+            function_start: base_address + func_start as usize,
+            function_len: func_len as usize,
+            unwind_info_offset: None,
+        });
         Ok(())
     }
 
+    /// The functions registered so far.
+    pub fn entries(&self) -> &[UnwindEntry] {
+        &self.entries
+    }
+
     /// Publishes all registered functions.
     pub fn publish(&mut self, eh_frame: Option<&[u8]>) -> Result<(), String> {
         if self.published {
@@ -100,8 +115,11 @@ impl UnwindRegistry {
     }
 }
 
-impl Drop for UnwindRegistry {
-    fn drop(&mut self) {
+impl UnwindRegistry {
+    /// Deregisters all the published functions from the unwinder, which
+    /// must happen before the memory holding their unwind information is
+    /// released. This is done when dropping the registry at the latest.
+    pub fn deregister(&mut self) {
         if self.published {
             unsafe {
                 // libgcc stores the frame entries as a linked list in decreasing sort order
@@ -116,6 +134,15 @@ impl Drop for UnwindRegistry {
                     __deregister_frame(*fde as *const _);
                 }
             }
+            self.registrations.clear();
+            self.entries.clear();
+            self.published = false;
         }
     }
 }
+
+impl Drop for UnwindRegistry {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
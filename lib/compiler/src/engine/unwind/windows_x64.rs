@@ -2,6 +2,7 @@
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
 //! Module for Windows x64 ABI unwind registry.
+use super::UnwindEntry;
 use std::collections::HashMap;
 use wasmer_types::CompiledFunctionUnwindInfo;
 use winapi::um::winnt;
@@ -10,6 +11,7 @@ use winapi::um::winnt;
 pub struct UnwindRegistry {
     // A hashmap mapping the baseaddress with the registered runtime functions
     functions: HashMap<usize, Vec<winnt::RUNTIME_FUNCTION>>,
+    entries: Vec<UnwindEntry>,
     published: bool,
 }
 
@@ -18,6 +20,7 @@ impl UnwindRegistry {
     pub fn new() -> Self {
         Self {
             functions: HashMap::new(),
+            entries: Vec::new(),
             published: false,
         }
     }
@@ -56,9 +59,21 @@ impl UnwindRegistry {
 
         entries.push(entry);
 
+        let unwind_info_address = unsafe { *entry.u.UnwindInfoAddress() };
+        self.entries.push(UnwindEntry {
+            function_start: base_address + func_start as usize,
+            function_len: func_len as usize,
+            unwind_info_offset: Some((unwind_info_address - func_start) as usize),
+        });
+
         Ok(())
     }
 
+    /// The functions registered so far.
+    pub fn entries(&self) -> &[UnwindEntry] {
+        &self.entries
+    }
+
     /// Publishes all registered functions.
     pub fn publish(&mut self, _eh_frame: Option<&[u8]>) -> Result<(), String> {
         if self.published {
@@ -92,14 +107,26 @@ impl UnwindRegistry {
     }
 }
 
-impl Drop for UnwindRegistry {
-    fn drop(&mut self) {
+impl UnwindRegistry {
+    /// Deregisters all the published function tables from the system, which
+    /// must happen before the memory holding the functions is released.
+    /// This is done when dropping the registry at the latest.
+    pub fn deregister(&mut self) {
         if self.published {
             unsafe {
                 for functions in self.functions.values_mut() {
                     winnt::RtlDeleteFunctionTable(functions.as_mut_ptr());
                 }
             }
+            self.functions.clear();
+            self.entries.clear();
+            self.published = false;
         }
     }
 }
+
+impl Drop for UnwindRegistry {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
@@ -70,15 +70,16 @@ mod compiler;
 #[macro_use]
 mod translator;
 #[cfg(feature = "translator")]
-pub use crate::compiler::{Compiler, CompilerConfig};
+pub use crate::compiler::{map_on_executor, CompileExecutor, Compiler, CompilerConfig};
 #[cfg(feature = "translator")]
 pub use crate::translator::{
-    from_binaryreadererror_wasmerror, translate_module, wptype_to_type, FunctionBinaryReader,
-    FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState,
-    ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    from_binaryreadererror_wasmerror, translate_module, wptype_to_type, DylinkInfo,
+    FunctionBinaryReader, FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader,
+    MiddlewareReaderState, ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain,
+    ModuleTranslationState,
 };
 
-pub use wasmer_types::{Addend, CodeOffset, Features};
+pub use wasmer_types::{Addend, CodeOffset, CompileHints, Features, OptLevel};
 
 #[cfg(feature = "translator")]
 /// wasmparser is exported as a module to slim compiler dependencies
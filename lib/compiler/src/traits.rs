@@ -3,7 +3,8 @@
 use crate::Features;
 use enumset::EnumSet;
 use std::any::Any;
-use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::SerializeError;
@@ -39,10 +40,16 @@ pub trait ArtifactCreate: Send + Sync + Upcastable {
     /// Serializes an artifact into bytes
     fn serialize(&self) -> Result<Vec<u8>, SerializeError>;
 
+    /// Serializes an artifact into `writer`, in the same format as
+    /// [`ArtifactCreate::serialize`], without holding the serialized
+    /// artifact in memory.
+    fn serialize_to(&self, writer: &mut dyn Write) -> Result<(), SerializeError>;
+
     /// Serializes an artifact into a file path
     fn serialize_to_file(&self, path: &Path) -> Result<(), SerializeError> {
-        let serialized = self.serialize()?;
-        fs::write(&path, serialized)?;
+        let mut file = BufWriter::new(File::create(path)?);
+        self.serialize_to(&mut file)?;
+        file.flush()?;
         Ok(())
     }
 }
@@ -0,0 +1,82 @@
+//! Parsing of the `dylink.0` custom section of modules built as dynamic
+//! libraries, as described by the [tool conventions].
+//!
+//! [tool conventions]: https://github.com/WebAssembly/tool-conventions/blob/main/DynamicLinking.md
+
+use wasmparser::{BinaryReader, BinaryReaderError};
+
+/// The name of the custom section describing a dynamic library.
+pub const DYLINK_SECTION_NAME: &str = "dylink.0";
+
+/// The name of the section used before `dylink.0`, which has the same
+/// contents without being split into subsections.
+pub const LEGACY_DYLINK_SECTION_NAME: &str = "dylink";
+
+/// The subsection of `dylink.0` describing the memory and table of the
+/// library.
+const WASM_DYLINK_MEM_INFO: u8 = 1;
+
+/// The subsection of `dylink.0` listing the libraries this one depends on.
+const WASM_DYLINK_NEEDED: u8 = 2;
+
+/// What a module built as a dynamic library requires from the module it
+/// gets linked into.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DylinkInfo {
+    /// The size of the data of the library in memory, in bytes.
+    pub memory_size: u32,
+    /// The alignment of the data of the library in memory, as a power of 2.
+    pub memory_alignment: u32,
+    /// The number of table elements used by the library.
+    pub table_size: u32,
+    /// The alignment of the table elements of the library, as a power of 2.
+    pub table_alignment: u32,
+    /// The names of the libraries this one depends on.
+    pub needed: Vec<String>,
+}
+
+impl DylinkInfo {
+    /// Parses the contents of a `dylink.0` section.
+    pub fn parse(data: &[u8]) -> Result<Self, BinaryReaderError> {
+        let mut info = Self::default();
+        let mut reader = BinaryReader::new(data);
+        while !reader.eof() {
+            let id = reader.read_u8()?;
+            let size = reader.read_var_u32()?;
+            let mut subsection = BinaryReader::new(reader.read_bytes(size as usize)?);
+            // Other subsections, such as the export and import info, are
+            // skipped.
+            if id == WASM_DYLINK_MEM_INFO.into() {
+                info.read_mem_info(&mut subsection)?;
+            } else if id == WASM_DYLINK_NEEDED.into() {
+                info.read_needed(&mut subsection)?;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Parses the contents of a legacy `dylink` section.
+    pub fn parse_legacy(data: &[u8]) -> Result<Self, BinaryReaderError> {
+        let mut info = Self::default();
+        let mut reader = BinaryReader::new(data);
+        info.read_mem_info(&mut reader)?;
+        info.read_needed(&mut reader)?;
+        Ok(info)
+    }
+
+    fn read_mem_info(&mut self, reader: &mut BinaryReader) -> Result<(), BinaryReaderError> {
+        self.memory_size = reader.read_var_u32()?;
+        self.memory_alignment = reader.read_var_u32()?;
+        self.table_size = reader.read_var_u32()?;
+        self.table_alignment = reader.read_var_u32()?;
+        Ok(())
+    }
+
+    fn read_needed(&mut self, reader: &mut BinaryReader) -> Result<(), BinaryReaderError> {
+        let count = reader.read_var_u32()?;
+        self.needed = (0..count)
+            .map(|_| reader.read_string().map(str::to_string))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+}
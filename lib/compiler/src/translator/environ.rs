@@ -234,6 +234,18 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn reserve_tags(&mut self, num: u32) -> WasmResult<()> {
+        self.module
+            .tags
+            .reserve_exact(usize::try_from(num).unwrap());
+        Ok(())
+    }
+
+    pub(crate) fn declare_tag(&mut self, sig_index: SignatureIndex) -> WasmResult<()> {
+        self.module.tags.push(sig_index);
+        Ok(())
+    }
+
     pub(crate) fn reserve_tables(&mut self, num: u32) -> WasmResult<()> {
         self.module
             .tables
@@ -398,6 +410,7 @@ impl<'data> ModuleEnvironment<'data> {
     }
 
     pub(crate) fn reserve_passive_data(&mut self, count: u32) -> WasmResult<()> {
+        self.module.data_count = Some(count);
         let count = usize::try_from(count).unwrap();
         self.module.passive_data.reserve(count);
         Ok(())
@@ -452,6 +465,7 @@ impl<'data> ModuleEnvironment<'data> {
             .custom_sections
             .insert(String::from(name), custom_section);
         self.module.custom_sections_data.push(Box::from(data));
+        self.module.custom_section_names.push(String::from(name));
         Ok(())
     }
 }
@@ -5,20 +5,24 @@
 //! compilers rather than just Cranelift.
 //!
 //! [cranelift-wasm]: https://crates.io/crates/cranelift-wasm/
+mod dylink;
 mod environ;
 mod middleware;
 mod module;
+mod proposals;
 mod state;
 #[macro_use]
 mod error;
 mod sections;
 
+pub use self::dylink::{DylinkInfo, DYLINK_SECTION_NAME, LEGACY_DYLINK_SECTION_NAME};
 pub use self::environ::{FunctionBinaryReader, FunctionBodyData, ModuleEnvironment};
 pub use self::middleware::{
     FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState, ModuleMiddleware,
     ModuleMiddlewareChain,
 };
 pub use self::module::translate_module;
+pub use self::proposals::{missing_proposals, scan_proposals, used_proposals};
 pub use self::sections::wptype_to_type;
 pub use self::state::ModuleTranslationState;
 pub use error::from_binaryreadererror_wasmerror;
@@ -8,10 +8,10 @@ use super::error::from_binaryreadererror_wasmerror;
 use super::sections::{
     parse_data_section, parse_element_section, parse_export_section, parse_function_section,
     parse_global_section, parse_import_section, parse_memory_section, parse_name_section,
-    parse_start_section, parse_table_section, parse_type_section,
+    parse_start_section, parse_table_section, parse_tag_section, parse_type_section,
 };
 use super::state::ModuleTranslationState;
-use wasmer_types::WasmResult;
+use wasmer_types::{WasmError, WasmResult};
 use wasmparser::{NameSectionReader, Parser, Payload};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a
@@ -21,6 +21,8 @@ pub fn translate_module<'data>(
     environ: &mut ModuleEnvironment<'data>,
 ) -> WasmResult<ModuleTranslationState> {
     let mut module_translation_state = ModuleTranslationState::new();
+    let mut data_segments = 0;
+    let mut data_count_offset = 0;
 
     for payload in Parser::new(0).parse_all(data) {
         match payload.map_err(from_binaryreadererror_wasmerror)? {
@@ -76,10 +78,12 @@ pub fn translate_module<'data>(
             }
 
             Payload::DataSection(data) => {
+                data_segments = data.get_count();
                 parse_data_section(data, environ)?;
             }
 
-            Payload::DataCountSection { count, .. } => {
+            Payload::DataCountSection { count, range } => {
+                data_count_offset = range.start;
                 environ.reserve_passive_data(count)?;
             }
 
@@ -90,8 +94,8 @@ pub fn translate_module<'data>(
                 unimplemented!("module linking not implemented yet")
             }
 
-            Payload::TagSection(_) => {
-                unimplemented!("exception handling not implemented yet")
+            Payload::TagSection(tags) => {
+                parse_tag_section(tags, environ)?;
             }
 
             Payload::CustomSection {
@@ -111,5 +115,19 @@ pub fn translate_module<'data>(
         }
     }
 
+    // The data count section lets `memory.init` and `data.drop` be checked
+    // before the data section is read, so it must agree with it.
+    if let Some(count) = environ.module.data_count {
+        if count != data_segments {
+            return Err(WasmError::InvalidWebAssembly {
+                message: format!(
+                    "the data count section declares {} segments, but there are {}",
+                    count, data_segments
+                ),
+                offset: data_count_offset,
+            });
+        }
+    }
+
     Ok(module_translation_state)
 }
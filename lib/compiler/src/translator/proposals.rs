@@ -0,0 +1,335 @@
+//! Detection of the WebAssembly proposals a module uses, by reading its
+//! sections and the operators of its functions once.
+
+use super::error::from_binaryreadererror_wasmerror;
+use std::vec::Vec;
+use wasmer_types::{Features, WasmResult};
+use wasmparser::{
+    BinaryReader, DataKind, ElementKind, ExternalKind, ImportSectionEntryType, InitExpr, Operator,
+    Parser, Payload, TableType, Type, TypeDef, TypeOrFuncType,
+};
+
+/// The number of proposal flags in [`Features`].
+const PROPOSALS_COUNT: usize = 12;
+
+/// The names of the proposals, in the order of [`proposal_flags`].
+const PROPOSAL_NAMES: [&str; PROPOSALS_COUNT] = [
+    "threads",
+    "reference types",
+    "SIMD",
+    "bulk memory",
+    "multi-value",
+    "tail call",
+    "module linking",
+    "multi-memory",
+    "memory64",
+    "exceptions",
+    "relaxed SIMD",
+    "extended constant expressions",
+];
+
+/// Returns mutable references to all the proposal flags of `features`.
+fn proposal_flags(features: &mut Features) -> [&mut bool; PROPOSALS_COUNT] {
+    [
+        &mut features.threads,
+        &mut features.reference_types,
+        &mut features.simd,
+        &mut features.bulk_memory,
+        &mut features.multi_value,
+        &mut features.tail_call,
+        &mut features.module_linking,
+        &mut features.multi_memory,
+        &mut features.memory64,
+        &mut features.exceptions,
+        &mut features.relaxed_simd,
+        &mut features.extended_const,
+    ]
+}
+
+/// Returns the values of all the proposal flags of `features`.
+fn proposal_values(features: &Features) -> [bool; PROPOSALS_COUNT] {
+    proposal_flags(&mut features.clone()).map(|flag| *flag)
+}
+
+/// The proposals of `needed` that are enabled in `features`.
+pub fn used_proposals(needed: &Features, features: &Features) -> Features {
+    let mut used = features.clone();
+    for (flag, needed) in proposal_flags(&mut used)
+        .iter_mut()
+        .zip(proposal_values(needed))
+    {
+        **flag &= needed;
+    }
+    used
+}
+
+/// The names of the proposals of `needed` that are disabled in
+/// `features`.
+pub fn missing_proposals(needed: &Features, features: &Features) -> Vec<&'static str> {
+    let needed = proposal_values(needed);
+    let enabled = proposal_values(features);
+    (0..PROPOSALS_COUNT)
+        .filter(|index| needed[*index] && !enabled[*index])
+        .map(|index| PROPOSAL_NAMES[index])
+        .collect()
+}
+
+/// Reads the module in `data` once, returning the proposals it needs to
+/// be valid, with every other flag disabled.
+///
+/// The module isn't validated: the constructs of a proposal are reported
+/// wherever they appear.
+pub fn scan_proposals(data: &[u8]) -> WasmResult<Features> {
+    let mut needed = Features::default();
+    for flag in proposal_flags(&mut needed) {
+        *flag = false;
+    }
+    let mut memories = 0;
+    let mut tables = 0;
+
+    for payload in Parser::new(0).parse_all(data) {
+        match payload.map_err(from_binaryreadererror_wasmerror)? {
+            Payload::TypeSection(types) => {
+                for entry in types {
+                    match entry.map_err(from_binaryreadererror_wasmerror)? {
+                        TypeDef::Func(func_type) => {
+                            needed.multi_value |= func_type.returns.len() > 1;
+                            for ty in func_type.params.iter().chain(func_type.returns.iter()) {
+                                scan_type(*ty, &mut needed);
+                            }
+                        }
+                        _ => needed.module_linking = true,
+                    }
+                }
+            }
+            Payload::ImportSection(imports) => {
+                for entry in imports {
+                    let import = entry.map_err(from_binaryreadererror_wasmerror)?;
+                    needed.module_linking |= import.field.is_none();
+                    match import.ty {
+                        ImportSectionEntryType::Function(_) => {}
+                        ImportSectionEntryType::Table(ref table) => {
+                            tables += 1;
+                            scan_table(table, &mut needed);
+                        }
+                        ImportSectionEntryType::Memory(memory) => {
+                            memories += 1;
+                            needed.threads |= memory.shared;
+                            needed.memory64 |= memory.memory64;
+                        }
+                        ImportSectionEntryType::Tag(_) => needed.exceptions = true,
+                        ImportSectionEntryType::Global(global) => {
+                            scan_type(global.content_type, &mut needed)
+                        }
+                        ImportSectionEntryType::Module(_) | ImportSectionEntryType::Instance(_) => {
+                            needed.module_linking = true
+                        }
+                    }
+                }
+            }
+            Payload::TableSection(section) => {
+                for entry in section {
+                    tables += 1;
+                    scan_table(
+                        &entry.map_err(from_binaryreadererror_wasmerror)?,
+                        &mut needed,
+                    );
+                }
+            }
+            Payload::MemorySection(section) => {
+                for entry in section {
+                    let memory = entry.map_err(from_binaryreadererror_wasmerror)?;
+                    memories += 1;
+                    needed.threads |= memory.shared;
+                    needed.memory64 |= memory.memory64;
+                }
+            }
+            Payload::TagSection(_) => needed.exceptions = true,
+            Payload::GlobalSection(globals) => {
+                for entry in globals {
+                    let global = entry.map_err(from_binaryreadererror_wasmerror)?;
+                    scan_type(global.ty.content_type, &mut needed);
+                    scan_init_expr(&global.init_expr, &mut needed)?;
+                }
+            }
+            Payload::ExportSection(exports) => {
+                for entry in exports {
+                    match entry.map_err(from_binaryreadererror_wasmerror)?.kind {
+                        ExternalKind::Tag => needed.exceptions = true,
+                        ExternalKind::Type | ExternalKind::Module | ExternalKind::Instance => {
+                            needed.module_linking = true
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Payload::ElementSection(elements) => {
+                for entry in elements {
+                    let element = entry.map_err(from_binaryreadererror_wasmerror)?;
+                    needed.reference_types |= element.ty != Type::FuncRef;
+                    match element.kind {
+                        ElementKind::Active {
+                            table_index,
+                            init_expr,
+                        } => {
+                            needed.reference_types |= table_index != 0;
+                            scan_init_expr(&init_expr, &mut needed)?;
+                        }
+                        ElementKind::Passive => needed.bulk_memory = true,
+                        ElementKind::Declared => needed.reference_types = true,
+                    }
+                }
+            }
+            Payload::DataCountSection { .. } => needed.bulk_memory = true,
+            Payload::DataSection(data) => {
+                for entry in data {
+                    match entry.map_err(from_binaryreadererror_wasmerror)?.kind {
+                        DataKind::Active {
+                            memory_index,
+                            init_expr,
+                        } => {
+                            needed.multi_memory |= memory_index != 0;
+                            scan_init_expr(&init_expr, &mut needed)?;
+                        }
+                        DataKind::Passive => needed.bulk_memory = true,
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let mut code = body.get_binary_reader();
+                let size = code.bytes_remaining();
+                let code = code
+                    .read_bytes(size)
+                    .map_err(from_binaryreadererror_wasmerror)?;
+                scan_function_body(code, &mut needed)?;
+            }
+            Payload::InstanceSection(_)
+            | Payload::AliasSection(_)
+            | Payload::ModuleSectionStart { .. }
+            | Payload::ModuleSectionEntry { .. } => needed.module_linking = true,
+            _ => {}
+        }
+    }
+
+    needed.multi_memory |= memories > 1;
+    needed.reference_types |= tables > 1;
+    Ok(needed)
+}
+
+/// Records the proposals needed by a value type.
+fn scan_type(ty: Type, needed: &mut Features) {
+    match ty {
+        Type::V128 => needed.simd = true,
+        Type::FuncRef | Type::ExternRef => needed.reference_types = true,
+        _ => {}
+    }
+}
+
+/// Records the proposals needed by a table type.
+fn scan_table(table: &TableType, needed: &mut Features) {
+    needed.reference_types |= table.element_type != Type::FuncRef;
+}
+
+/// Records the proposals needed by a constant expression.
+fn scan_init_expr(init_expr: &InitExpr, needed: &mut Features) -> WasmResult<()> {
+    let mut reader = init_expr.get_operators_reader();
+    loop {
+        match reader.read().map_err(from_binaryreadererror_wasmerror)? {
+            Operator::End => return Ok(()),
+            Operator::I32Add
+            | Operator::I32Sub
+            | Operator::I32Mul
+            | Operator::I64Add
+            | Operator::I64Sub
+            | Operator::I64Mul => needed.extended_const = true,
+            Operator::RefNull { .. } | Operator::RefFunc { .. } => needed.reference_types = true,
+            Operator::V128Const { .. } => needed.simd = true,
+            _ => {}
+        }
+    }
+}
+
+/// Records the proposals needed by the locals and operators of a function
+/// body, given without its size.
+fn scan_function_body(code: &[u8], needed: &mut Features) -> WasmResult<()> {
+    let mut reader = BinaryReader::new(code);
+    let local_decls = reader
+        .read_var_u32()
+        .map_err(from_binaryreadererror_wasmerror)?;
+    for _ in 0..local_decls {
+        reader
+            .read_var_u32()
+            .map_err(from_binaryreadererror_wasmerror)?;
+        let ty = reader
+            .read_type()
+            .map_err(from_binaryreadererror_wasmerror)?;
+        scan_type(ty, needed);
+    }
+    while !reader.eof() {
+        // The operators of SIMD and of atomics are recognized by their
+        // prefix, there are too many of them to list.
+        match code[reader.current_position()] {
+            0xfd => needed.simd = true,
+            0xfe => needed.threads = true,
+            _ => {}
+        }
+        match reader
+            .read_operator()
+            .map_err(from_binaryreadererror_wasmerror)?
+        {
+            Operator::Block { ty } | Operator::Loop { ty } | Operator::If { ty } => {
+                needed.multi_value |= matches!(ty, TypeOrFuncType::FuncType(_))
+            }
+            Operator::Try { ty } => {
+                needed.exceptions = true;
+                needed.multi_value |= matches!(ty, TypeOrFuncType::FuncType(_));
+            }
+            Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+            | Operator::Delegate { .. } => needed.exceptions = true,
+            Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
+                needed.tail_call = true
+            }
+            Operator::TypedSelect { ty } => {
+                needed.reference_types = true;
+                scan_type(ty, needed);
+            }
+            Operator::RefNull { .. }
+            | Operator::RefIsNull
+            | Operator::RefFunc { .. }
+            | Operator::TableGet { .. }
+            | Operator::TableSet { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableSize { .. }
+            | Operator::TableFill { .. } => needed.reference_types = true,
+            Operator::MemoryInit { .. }
+            | Operator::DataDrop { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::TableInit { .. }
+            | Operator::ElemDrop { .. }
+            | Operator::TableCopy { .. } => needed.bulk_memory = true,
+            Operator::I8x16RelaxedSwizzle
+            | Operator::I32x4RelaxedTruncSatF32x4S
+            | Operator::I32x4RelaxedTruncSatF32x4U
+            | Operator::I32x4RelaxedTruncSatF64x2SZero
+            | Operator::I32x4RelaxedTruncSatF64x2UZero
+            | Operator::F32x4Fma
+            | Operator::F32x4Fms
+            | Operator::F64x2Fma
+            | Operator::F64x2Fms
+            | Operator::I8x16LaneSelect
+            | Operator::I16x8LaneSelect
+            | Operator::I32x4LaneSelect
+            | Operator::I64x2LaneSelect
+            | Operator::F32x4RelaxedMin
+            | Operator::F32x4RelaxedMax
+            | Operator::F64x2RelaxedMin
+            | Operator::F64x2RelaxedMax => needed.relaxed_simd = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -30,7 +30,8 @@ use wasmparser::{
     ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType as WPFunctionType,
     FunctionSectionReader, GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionEntryType,
     ImportSectionReader, MemorySectionReader, MemoryType as WPMemoryType, NameSectionReader,
-    Naming, NamingReader, Operator, TableSectionReader, TypeDef, TypeSectionReader,
+    Naming, NamingReader, Operator, TableSectionReader, TagSectionReader, TypeDef,
+    TypeSectionReader,
 };
 
 /// Helper function translating wasmparser types to Wasm Type.
@@ -180,6 +181,21 @@ pub fn parse_function_section(
     Ok(())
 }
 
+/// Parses the Tag section of the wasm module.
+pub fn parse_tag_section(
+    tags: TagSectionReader,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    environ.reserve_tags(tags.get_count())?;
+
+    for entry in tags {
+        let tag = entry.map_err(from_binaryreadererror_wasmerror)?;
+        environ.declare_tag(SignatureIndex::from_u32(tag.type_index))?;
+    }
+
+    Ok(())
+}
+
 /// Parses the Table section of the wasm module.
 pub fn parse_table_section(
     tables: TableSectionReader,
@@ -4,7 +4,7 @@
 use crate::wasm_unsupported;
 use std::boxed::Box;
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::{SignatureIndex, WasmResult};
+use wasmer_types::{ModuleInfo, SignatureIndex, Type, WasmResult};
 
 /// Map of signatures to a function's parameter and return types.
 pub(crate) type WasmTypes =
@@ -33,6 +33,32 @@ impl ModuleTranslationState {
         }
     }
 
+    /// Recreates the state of the translation of the module described by
+    /// `module`, to compile more functions for it.
+    pub fn from_module_info(module: &ModuleInfo) -> Self {
+        let wptypes = |types: &[Type]| {
+            types
+                .iter()
+                .map(|ty| match ty {
+                    Type::I32 => wasmparser::Type::I32,
+                    Type::I64 => wasmparser::Type::I64,
+                    Type::F32 => wasmparser::Type::F32,
+                    Type::F64 => wasmparser::Type::F64,
+                    Type::V128 => wasmparser::Type::V128,
+                    Type::ExternRef => wasmparser::Type::ExternRef,
+                    Type::FuncRef => wasmparser::Type::FuncRef,
+                })
+                .collect::<Box<[_]>>()
+        };
+        Self {
+            wasm_types: module
+                .signatures
+                .values()
+                .map(|signature| (wptypes(signature.params()), wptypes(signature.results())))
+                .collect(),
+        }
+    }
+
     /// Get the parameter and result types for the given Wasm blocktype.
     pub fn blocktype_params_results(
         &self,
@@ -0,0 +1,384 @@
+//! `instruction_budget` is a middleware for putting a limit on the
+//! number of instructions executed by a WebAssembly instance.
+//!
+//! Unlike [`metering`](crate::metering), which checks the accumulated
+//! cost of a whole basic block before running it, every instruction is
+//! counted and checked when it runs. The execution is stopped at exactly
+//! the instruction exceeding the budget, which makes the limit
+//! reproducible across machines and compilers, at the price of slower
+//! code.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+#[derive(Clone)]
+struct BudgetGlobalIndexes(GlobalIndex, GlobalIndex);
+
+impl BudgetGlobalIndexes {
+    /// The global index in the current module for the remaining
+    /// instructions.
+    fn remaining_instructions(&self) -> GlobalIndex {
+        self.0
+    }
+
+    /// The global index in the current module for a boolean indicating
+    /// whether the budget is exhausted or not.
+    /// This boolean is represented as a i32 global:
+    ///   * 0: there are remaining instructions
+    ///   * 1: the budget has been exhausted
+    fn budget_exhausted(&self) -> GlobalIndex {
+        self.1
+    }
+}
+
+impl fmt::Debug for BudgetGlobalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetGlobalIndexes")
+            .field("remaining_instructions", &self.remaining_instructions())
+            .field("budget_exhausted", &self.budget_exhausted())
+            .finish()
+    }
+}
+
+/// The module-level instruction budget middleware.
+///
+/// Every operator of the function bodies, including `end`, counts as one
+/// instruction.
+///
+/// # Panic
+///
+/// An instance of `InstructionBudget` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the global index to store the budget. Attempts to use an
+/// `InstructionBudget` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::InstructionBudget;
+///
+/// fn create_instruction_budget_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Let's allow 1000 instructions until the budget is changed.
+///     let budget = Arc::new(InstructionBudget::new(1000));
+///
+///     compiler_config.push_middleware(budget);
+/// }
+/// ```
+pub struct InstructionBudget {
+    /// Initial number of instructions allowed.
+    initial_budget: u64,
+
+    /// The global indexes for the budget.
+    global_indexes: Mutex<Option<BudgetGlobalIndexes>>,
+}
+
+/// The function-level instruction budget middleware.
+pub struct FunctionInstructionBudget {
+    /// The global indexes for the budget.
+    global_indexes: BudgetGlobalIndexes,
+}
+
+/// Represents the state of the instruction budget, either `Remaining` or
+/// `Exhausted`.
+///
+/// # Example
+///
+/// See the [`get_remaining_instructions`] function to get an example.
+#[derive(Debug, Eq, PartialEq)]
+pub enum RemainingInstructions {
+    /// The given number of instructions can still be executed.
+    Remaining(u64),
+
+    /// The execution was terminated because it exceeded the budget. You
+    /// can recover from this state by setting a new budget via
+    /// [`set_instruction_budget`] and restart the execution.
+    Exhausted,
+}
+
+impl InstructionBudget {
+    /// Creates an `InstructionBudget` middleware.
+    pub fn new(initial_budget: u64) -> Self {
+        Self {
+            initial_budget,
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl fmt::Debug for InstructionBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstructionBudget")
+            .field("initial_budget", &self.initial_budget)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for InstructionBudget {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionInstructionBudget {
+            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("InstructionBudget::transform_module_info: Attempting to use an `InstructionBudget` middleware from multiple modules.");
+        }
+
+        // Append a global for the remaining instructions and initialize it.
+        let remaining_instructions_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(self.initial_budget as i64));
+
+        module_info.exports.insert(
+            "wasmer_instruction_budget_remaining".to_string(),
+            ExportIndex::Global(remaining_instructions_global_index),
+        );
+
+        // Append a global for the exhausted budget boolean and initialize it.
+        let budget_exhausted_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_instruction_budget_exhausted".to_string(),
+            ExportIndex::Global(budget_exhausted_global_index),
+        );
+
+        *global_indexes = Some(BudgetGlobalIndexes(
+            remaining_instructions_global_index,
+            budget_exhausted_global_index,
+        ))
+    }
+}
+
+impl fmt::Debug for FunctionInstructionBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionInstructionBudget")
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionMiddleware for FunctionInstructionBudget {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let remaining = self.global_indexes.remaining_instructions().as_u32();
+        let exhausted = self.global_indexes.budget_exhausted().as_u32();
+        // The check leaves the operand stack untouched, so it can be put
+        // right before any operator.
+        state.extend(&[
+            // if globals[remaining_instructions_index] == 0 { throw(); }
+            Operator::GlobalGet {
+                global_index: remaining,
+            },
+            Operator::I64Eqz,
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet {
+                global_index: exhausted,
+            },
+            Operator::Unreachable,
+            Operator::End,
+            // globals[remaining_instructions_index] -= 1;
+            Operator::GlobalGet {
+                global_index: remaining,
+            },
+            Operator::I64Const { value: 1 },
+            Operator::I64Sub,
+            Operator::GlobalSet {
+                global_index: remaining,
+            },
+        ]);
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the remaining instructions of an [`Instance`][wasmer::Instance].
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`InstructionBudget`] middleware at compile time, otherwise this
+/// will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::instruction_budget::{get_remaining_instructions, RemainingInstructions};
+///
+/// /// Check whether the instance stopped because it ran out of budget.
+/// fn ran_out_of_budget(store: &mut impl AsStoreMut, instance: &Instance) -> bool {
+///     get_remaining_instructions(store, instance) == RemainingInstructions::Exhausted
+/// }
+/// ```
+pub fn get_remaining_instructions(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+) -> RemainingInstructions {
+    let exhausted: i32 = instance
+        .exports
+        .get_global("wasmer_instruction_budget_exhausted")
+        .expect("Can't get `wasmer_instruction_budget_exhausted` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_instruction_budget_exhausted` from Instance has wrong type");
+
+    if exhausted > 0 {
+        return RemainingInstructions::Exhausted;
+    }
+
+    let remaining = instance
+        .exports
+        .get_global("wasmer_instruction_budget_remaining")
+        .expect("Can't get `wasmer_instruction_budget_remaining` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_instruction_budget_remaining` from Instance has wrong type");
+
+    RemainingInstructions::Remaining(remaining)
+}
+
+/// Set the number of instructions an [`Instance`][wasmer::Instance] may
+/// still execute.
+///
+/// # Panic
+///
+/// The given [`Instance`][wasmer::Instance] must have been processed
+/// with the [`InstructionBudget`] middleware at compile time, otherwise
+/// this will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::instruction_budget::set_instruction_budget;
+///
+/// fn allow_one_million_instructions(store: &mut impl AsStoreMut, instance: &Instance) {
+///     set_instruction_budget(store, instance, 1_000_000);
+/// }
+/// ```
+pub fn set_instruction_budget(ctx: &mut impl AsStoreMut, instance: &Instance, budget: u64) {
+    instance
+        .exports
+        .get_global("wasmer_instruction_budget_remaining")
+        .expect("Can't get `wasmer_instruction_budget_remaining` from Instance")
+        .set(ctx, budget.into())
+        .expect("Can't set `wasmer_instruction_budget_remaining` in Instance");
+
+    instance
+        .exports
+        .get_global("wasmer_instruction_budget_exhausted")
+        .expect("Can't get `wasmer_instruction_budget_exhausted` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_instruction_budget_exhausted` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $sum (export "sum") (param $n i32) (result i32)
+                (local $acc i32)
+                (block
+                    (loop
+                        local.get $n
+                        i32.eqz
+                        br_if 1
+                        local.get $acc
+                        local.get $n
+                        i32.add
+                        local.set $acc
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        local.set $n
+                        br 0))
+                local.get $acc))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    /// Runs `sum(10)` with the given budget, returning whether it completed
+    /// and the number of instructions executed.
+    fn run_sum(budget: u64) -> (bool, u64) {
+        let instruction_budget = Arc::new(InstructionBudget::new(0));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(instruction_budget);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let sum: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("sum")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        set_instruction_budget(&mut store, &instance, budget);
+        let completed = sum.call(&mut store, 10).is_ok();
+        let executed = match get_remaining_instructions(&mut store, &instance) {
+            RemainingInstructions::Remaining(remaining) => budget - remaining,
+            RemainingInstructions::Exhausted => budget,
+        };
+        (completed, executed)
+    }
+
+    #[test]
+    fn budget_is_reproducible() {
+        let (completed, executed) = run_sum(10_000);
+        assert!(completed);
+        assert_eq!(run_sum(10_000), (true, executed));
+
+        // The budget is exactly enough.
+        assert_eq!(run_sum(executed), (true, executed));
+
+        // Running out of budget stops at the same instruction every time.
+        assert_eq!(run_sum(executed - 1), (false, executed - 1));
+        assert_eq!(run_sum(executed - 1), (false, executed - 1));
+        assert_eq!(run_sum(executed / 2), (false, executed / 2));
+        assert_eq!(run_sum(executed / 2), (false, executed / 2));
+    }
+}
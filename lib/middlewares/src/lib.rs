@@ -1,6 +1,8 @@
+pub mod instruction_budget;
 pub mod metering;
 
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use instruction_budget::InstructionBudget;
 pub use metering::Metering;
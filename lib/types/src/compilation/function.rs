@@ -4,8 +4,10 @@
 //! A `Compilation` contains the compiled function bodies for a WebAssembly
 //! module (`CompiledFunction`).
 
+use super::stack_map::StackMap;
 use super::trap::TrapInformation;
 use crate::entity::PrimaryMap;
+use crate::lib::std::mem;
 use crate::lib::std::vec::Vec;
 use crate::{CompiledFunctionUnwindInfo, FunctionAddressMap};
 use crate::{
@@ -29,6 +31,11 @@ pub struct CompiledFunctionFrameInfo {
 
     /// The address map.
     pub address_map: FunctionAddressMap,
+
+    /// The stack maps at the safepoints of the function, if requested.
+    ///
+    /// Code offsets of the stack maps MUST be in ascending order.
+    pub stack_maps: Vec<StackMap>,
 }
 
 /// The function body.
@@ -171,6 +178,15 @@ impl Compilation {
         self.functions.is_empty()
     }
 
+    /// Takes the compiled functions out of the compilation, leaving it
+    /// with none.
+    ///
+    /// Unlike the getters, this doesn't copy the function bodies, which
+    /// make up most of the memory used by the compilation of large modules.
+    pub fn take_functions(&mut self) -> Functions {
+        mem::take(&mut self.functions)
+    }
+
     /// Gets functions relocations.
     pub fn get_relocations(&self) -> PrimaryMap<LocalFunctionIndex, Vec<Relocation>> {
         self.functions
@@ -0,0 +1,24 @@
+//! Hints given to the compiler for compiling a module.
+
+/// How much effort the compiler should spend optimizing the generated code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No optimizations performed, minimizes compilation time.
+    None,
+    /// Generates the fastest possible code, but may take longer.
+    Speed,
+    /// Similar to `Speed`, but also performs transformations aimed at
+    /// reducing code size.
+    SpeedAndSize,
+}
+
+/// Hints from the tunables of an engine on how to compile a module,
+/// overriding the configuration of the compiler.
+///
+/// Compilers are free to ignore hints they don't support.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompileHints {
+    /// The optimization level to use instead of the one the compiler was
+    /// configured with.
+    pub opt_level: Option<OptLevel>,
+}
@@ -2,10 +2,12 @@
 
 pub mod address_map;
 pub mod function;
+pub mod hints;
 pub mod module;
 pub mod relocation;
 pub mod section;
 pub mod sourceloc;
+pub mod stack_map;
 pub mod symbols;
 pub mod target;
 pub mod trap;
@@ -74,6 +74,14 @@ pub struct CustomSection {
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq, Default)]
 pub struct SectionBody(#[cfg_attr(feature = "enable-serde", serde(with = "serde_bytes"))] Vec<u8>);
 
+impl ArchivedSectionBody {
+    /// Returns the length of this section in bytes, without deserializing
+    /// it.
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
 impl SectionBody {
     /// Create a new section body with the given contents.
     pub fn new_with_vec(contents: Vec<u8>) -> Self {
@@ -0,0 +1,22 @@
+//! Types for stack maps.
+use crate::lib::std::vec::Vec;
+use crate::CodeOffset;
+use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// The stack slots holding live references at a safepoint of a function,
+/// which a precise garbage collector must visit.
+#[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
+#[derive(RkyvSerialize, RkyvDeserialize, Archive, Clone, Debug, PartialEq, Eq)]
+pub struct StackMap {
+    /// The offset of the safepoint in native code, relative to the beginning
+    /// of the function. For calls, this is the return address.
+    pub code_offset: CodeOffset,
+    /// The number of words of the frame described by the map, starting
+    /// from the stack pointer at the safepoint.
+    pub mapped_words: u32,
+    /// The words holding live references, in ascending order, counted
+    /// from the stack pointer at the safepoint.
+    pub live_slots: Vec<u32>,
+}
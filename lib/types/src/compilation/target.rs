@@ -172,6 +172,7 @@ impl ToString for CpuFeature {
 pub struct Target {
     triple: Triple,
     cpu_features: EnumSet<CpuFeature>,
+    host_calling_convention: Option<CallingConvention>,
 }
 
 impl Target {
@@ -180,9 +181,27 @@ impl Target {
         Self {
             triple,
             cpu_features,
+            host_calling_convention: None,
         }
     }
 
+    /// Sets the calling convention used by the dynamic function trampolines
+    /// to call host functions, instead of the default one of the triple.
+    pub fn with_host_calling_convention(mut self, calling_convention: CallingConvention) -> Self {
+        self.host_calling_convention = Some(calling_convention);
+        self
+    }
+
+    /// The calling convention used by the dynamic function trampolines to
+    /// call host functions.
+    ///
+    /// This is the default calling convention of the triple unless another
+    /// one was set with [`Target::with_host_calling_convention`].
+    pub fn host_calling_convention(&self) -> Option<CallingConvention> {
+        self.host_calling_convention
+            .or_else(|| self.triple.default_calling_convention().ok())
+    }
+
     /// The triple associated for the target.
     pub fn triple(&self) -> &Triple {
         &self.triple
@@ -200,6 +219,7 @@ impl Default for Target {
         Self {
             triple: Triple::host(),
             cpu_features: CpuFeature::for_host(),
+            host_calling_convention: None,
         }
     }
 }
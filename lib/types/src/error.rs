@@ -1,5 +1,5 @@
 //! The WebAssembly possible errors
-use crate::{ExternType, Pages};
+use crate::{ExternType, FunctionIndex, LocalFunctionIndex, Pages};
 use std::io;
 use thiserror::Error;
 
@@ -43,6 +43,25 @@ pub enum DeserializeError {
         /// How many bytes the artifact contained
         got: usize,
     },
+    /// The artifact was serialized with a newer format version than
+    /// the one supported by this version of Wasmer
+    #[error("the artifact format version {found} is newer than the supported version {supported}")]
+    NewerFormat {
+        /// The format version found in the artifact
+        found: u32,
+        /// The newest format version supported
+        supported: u32,
+    },
+    /// The artifact can only be read by a newer version of Wasmer
+    #[error(
+        "the artifact requires format version {required}, but only version {current} is supported"
+    )]
+    RuntimeTooOld {
+        /// The oldest format version able to read the artifact
+        required: u32,
+        /// The format version supported
+        current: u32,
+    },
 }
 
 /// Error type describing things that can go wrong when operating on Wasm Memories.
@@ -142,6 +161,21 @@ pub enum CompileError {
     #[cfg_attr(feature = "std", error("Compilation error: {0}"))]
     Codegen(String),
 
+    /// Compiling a function failed.
+    #[cfg_attr(
+        feature = "std",
+        error("Compilation error in function {func:?}: {message}")
+    )]
+    FunctionCodegen {
+        /// The function that failed to compile.
+        func: LocalFunctionIndex,
+        /// The offset in the module of the instruction that failed to
+        /// compile, if known.
+        wasm_offset: Option<u32>,
+        /// A description of the error.
+        message: String,
+    },
+
     /// The module did not pass validation.
     #[cfg_attr(feature = "std", error("Validation error: {0}"))]
     Validate(String),
@@ -150,6 +184,17 @@ pub enum CompileError {
     #[cfg_attr(feature = "std", error("Feature {0} is not yet supported"))]
     UnsupportedFeature(String),
 
+    /// The module uses a Wasm proposal that isn't enabled in the features
+    /// it is compiled with.
+    #[cfg_attr(
+        feature = "std",
+        error("The module uses the {feature} proposal, which is not enabled")
+    )]
+    DisabledFeature {
+        /// The name of the proposal.
+        feature: &'static str,
+    },
+
     /// The compiler cannot compile for the given target.
     /// This can refer to the OS, the chipset or any other aspect of the target system.
     #[cfg_attr(feature = "std", error("The target {0} is not yet supported (see https://docs.wasmer.io/ecosystem/wasmer/wasmer-features)"))]
@@ -158,6 +203,48 @@ pub enum CompileError {
     /// Insufficient resources available for execution.
     #[cfg_attr(feature = "std", error("Insufficient resources: {0}"))]
     Resource(String),
+
+    /// A function declares more locals than the engine allows.
+    #[cfg_attr(
+        feature = "std",
+        error("Function {function:?} declares {count} locals, which exceeds the limit")
+    )]
+    TooManyLocals {
+        /// The function declaring the locals.
+        function: FunctionIndex,
+        /// The number of locals declared by the function.
+        count: u32,
+    },
+
+    /// A module was compiled with the default `Tunables` of an engine that
+    /// has none set.
+    #[cfg_attr(feature = "std", error("The Engine has no default Tunables set"))]
+    NoDefaultTunables,
+}
+
+impl CompileError {
+    /// Ties an error to the function `func` it occurred in, if it is a
+    /// code generation error or an invalid instruction.
+    ///
+    /// Other errors, and errors already tied to a function, are returned
+    /// unchanged.
+    pub fn in_function(self, func: LocalFunctionIndex) -> Self {
+        match self {
+            Self::Codegen(message) => Self::FunctionCodegen {
+                func,
+                wasm_offset: None,
+                message,
+            },
+            Self::Wasm(WasmError::InvalidWebAssembly { message, offset }) => {
+                Self::FunctionCodegen {
+                    func,
+                    wasm_offset: Some(offset as u32),
+                    message,
+                }
+            }
+            other => other,
+        }
+    }
 }
 
 impl From<WasmError> for CompileError {
@@ -74,7 +74,10 @@ pub use crate::compilation::target::{
     Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness,
     Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
 };
-pub use crate::serialize::{MetadataHeader, SerializableCompilation, SerializableModule};
+pub use crate::serialize::{
+    ArtifactHash, ArtifactMetadata, CompileOptions, CustomSectionInfo, DeserializeForwardPolicy,
+    DeterminismReport, MetadataHeader, SerializableCompilation, SerializableModule,
+};
 pub use error::{
     CompileError, DeserializeError, ImportError, MemoryError, MiddlewareError,
     ParseCpuFeatureError, PreInstantiationError, SerializeError, WasmError, WasmResult,
@@ -98,7 +101,7 @@ pub use crate::units::{
 };
 pub use types::{
     ExportType, ExternType, FunctionType, GlobalInit, GlobalType, ImportType, MemoryType,
-    Mutability, TableType, Type, V128,
+    Mutability, TableType, TagType, Type, V128,
 };
 pub use value::{RawValue, ValueType};
 
@@ -122,8 +125,10 @@ pub use crate::compilation::function::{
     Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
     Functions,
 };
+pub use crate::compilation::hints::{CompileHints, OptLevel};
 pub use crate::compilation::module::CompileModuleInfo;
 pub use crate::compilation::sourceloc::SourceLoc;
+pub use crate::compilation::stack_map::StackMap;
 pub use crate::compilation::symbols::{Symbol, SymbolRegistry};
 pub use crate::compilation::trap::TrapInformation;
 pub use crate::compilation::unwind::CompiledFunctionUnwindInfo;
@@ -106,6 +106,10 @@ pub struct ModuleInfo {
     /// WebAssembly passive data segments.
     pub passive_data: HashMap<DataIndex, Box<[u8]>>,
 
+    /// The number of data segments declared by the data count section, if
+    /// present.
+    pub data_count: Option<u32>,
+
     /// WebAssembly global initializers.
     pub global_initializers: PrimaryMap<LocalGlobalIndex, GlobalInit>,
 
@@ -127,12 +131,20 @@ pub struct ModuleInfo {
     /// WebAssembly global variables (imported and local).
     pub globals: PrimaryMap<GlobalIndex, GlobalType>,
 
+    /// The signatures of the exception tags declared by the module.
+    pub tags: Vec<SignatureIndex>,
+
     /// Custom sections in the module.
     pub custom_sections: IndexMap<String, CustomSectionIndex>,
 
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
 
+    /// The name of each CustomSection in the module, including the
+    /// sections shadowed in `custom_sections` by a later one with the same
+    /// name.
+    pub custom_section_names: PrimaryMap<CustomSectionIndex, String>,
+
     /// Number of imported functions in the module.
     pub num_imported_functions: usize,
 
@@ -156,6 +168,7 @@ pub struct ArchivableModuleInfo {
     table_initializers: Vec<TableInitializer>,
     passive_elements: BTreeMap<ElemIndex, Box<[FunctionIndex]>>,
     passive_data: BTreeMap<DataIndex, Box<[u8]>>,
+    data_count: Option<u32>,
     global_initializers: PrimaryMap<LocalGlobalIndex, GlobalInit>,
     function_names: BTreeMap<FunctionIndex, String>,
     signatures: PrimaryMap<SignatureIndex, FunctionType>,
@@ -163,8 +176,10 @@ pub struct ArchivableModuleInfo {
     tables: PrimaryMap<TableIndex, TableType>,
     memories: PrimaryMap<MemoryIndex, MemoryType>,
     globals: PrimaryMap<GlobalIndex, GlobalType>,
+    tags: Vec<SignatureIndex>,
     custom_sections: IndexMap<String, CustomSectionIndex>,
     custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
+    custom_section_names: PrimaryMap<CustomSectionIndex, String>,
     num_imported_functions: usize,
     num_imported_tables: usize,
     num_imported_memories: usize,
@@ -181,6 +196,7 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             table_initializers: it.table_initializers,
             passive_elements: it.passive_elements.into_iter().collect(),
             passive_data: it.passive_data.into_iter().collect(),
+            data_count: it.data_count,
             global_initializers: it.global_initializers,
             function_names: it.function_names.into_iter().collect(),
             signatures: it.signatures,
@@ -188,8 +204,10 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             tables: it.tables,
             memories: it.memories,
             globals: it.globals,
+            tags: it.tags,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            custom_section_names: it.custom_section_names,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -209,6 +227,7 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             table_initializers: it.table_initializers,
             passive_elements: it.passive_elements.into_iter().collect(),
             passive_data: it.passive_data.into_iter().collect(),
+            data_count: it.data_count,
             global_initializers: it.global_initializers,
             function_names: it.function_names.into_iter().collect(),
             signatures: it.signatures,
@@ -216,8 +235,10 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             tables: it.tables,
             memories: it.memories,
             globals: it.globals,
+            tags: it.tags,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            custom_section_names: it.custom_section_names,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -278,6 +299,7 @@ impl PartialEq for ModuleInfo {
             && self.globals == other.globals
             && self.custom_sections == other.custom_sections
             && self.custom_sections_data == other.custom_sections_data
+            && self.custom_section_names == other.custom_section_names
             && self.num_imported_functions == other.num_imported_functions
             && self.num_imported_tables == other.num_imported_tables
             && self.num_imported_memories == other.num_imported_memories
@@ -370,15 +392,16 @@ impl ModuleInfo {
         ImportsIterator::new(iter, self.imports.len())
     }
 
-    /// Get the custom sections of the module given a `name`.
+    /// Get the custom sections of the module given a `name`, in the order
+    /// they are declared in.
     pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
-        self.custom_sections
+        self.custom_section_names
             .iter()
-            .filter_map(move |(section_name, section_index)| {
+            .filter_map(move |(section_index, section_name)| {
                 if name != section_name {
                     return None;
                 }
-                Some(self.custom_sections_data[*section_index].clone())
+                Some(self.custom_sections_data[section_index].clone())
             })
     }
 
@@ -1,22 +1,29 @@
 use crate::entity::PrimaryMap;
 use crate::{
     compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
-    DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex,
-    SerializeError, SignatureIndex, TableIndex, TableStyle,
+    CustomSectionProtection, DeserializeError, Dwarf, ExportType, Features, FunctionBody,
+    FunctionIndex, ImportType, LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo,
+    OwnedDataInitializer, Relocation, SectionIndex, SerializeError, SignatureIndex, TableIndex,
+    TableStyle,
 };
 use enumset::EnumSet;
 use rkyv::{
-    archived_value, de::deserializers::SharedDeserializeMap, ser::serializers::AllocSerializer,
-    ser::Serializer as RkyvSerializer, Archive, Deserialize as RkyvDeserialize,
-    Serialize as RkyvSerialize,
+    archived_value,
+    de::deserializers::SharedDeserializeMap,
+    ser::serializers::{
+        AllocScratch, AllocSerializer, CompositeSerializer, FallbackScratch, HeapScratch,
+        SharedSerializeMap, WriteSerializer,
+    },
+    ser::Serializer as RkyvSerializer,
+    Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize,
 };
 use std::convert::TryInto;
+use std::io::{self, Write};
 use std::path::Path;
-use std::{fs, mem};
+use std::{fmt, fs, mem};
 
 /// The compilation related data for a serialized modules
-#[derive(Archive, Default, RkyvDeserialize, RkyvSerialize)]
+#[derive(Archive, Default, Clone, RkyvDeserialize, RkyvSerialize)]
 #[allow(missing_docs)]
 pub struct SerializableCompilation {
     pub function_bodies: PrimaryMap<LocalFunctionIndex, FunctionBody>,
@@ -49,8 +56,88 @@ impl SerializableCompilation {
     }
 }
 
+/// The options of the engine an artifact was compiled with, which must
+/// agree with the engine running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Archive, RkyvDeserialize, RkyvSerialize)]
+pub struct CompileOptions {
+    /// Whether memories may rely on guard pages to elide bounds checks.
+    pub bounds_check_elimination: bool,
+}
+
+impl CompileOptions {
+    /// Returns why code compiled with these options can't run in an
+    /// engine with the `engine` options, if it can't.
+    ///
+    /// Code with more checks than required by the engine can run in it,
+    /// but not code with less.
+    pub fn conflict_with(&self, engine: &Self) -> Option<&'static str> {
+        if self.bounds_check_elimination && !engine.bounds_check_elimination {
+            return Some("the artifact elides bounds checks, but the engine requires them");
+        }
+        None
+    }
+}
+
+/// The sources of nondeterminism a module uses, which may make its
+/// execution differ between hosts or between runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Archive, RkyvDeserialize, RkyvSerialize)]
+pub struct DeterminismReport {
+    /// Whether the module uses floating-point values, whose NaN bit
+    /// patterns differ between hosts unless the compiler canonicalizes
+    /// NaNs.
+    pub floats: bool,
+    /// Whether the module declares or imports a shared memory, which
+    /// threads may access concurrently.
+    pub threads: bool,
+    /// Whether the module uses SIMD vectors, whose floating-point lanes
+    /// have the same NaN issue as scalar floats.
+    pub simd: bool,
+}
+
+impl DeterminismReport {
+    /// Whether the module uses none of the sources of nondeterminism.
+    pub fn is_deterministic(&self) -> bool {
+        !self.floats && !self.threads && !self.simd
+    }
+}
+
+/// A digest of what determines the code compiled for a module: its binary,
+/// the target, the enabled features, the options of the engine and the
+/// compiler.
+///
+/// Engines configured the same way produce the same hash for the same
+/// binary, in any process, so it can be used as a key for caching
+/// artifacts.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Archive, RkyvDeserialize, RkyvSerialize)]
+pub struct ArtifactHash([u8; 32]);
+
+impl ArtifactHash {
+    /// Creates a hash from the bytes of a digest, without hashing them.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ArtifactHash {
+    /// Writes the digest in hexadecimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl fmt::Debug for ArtifactHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ArtifactHash({})", self)
+    }
+}
+
 /// Serializable struct that is able to serialize from and to a `ArtifactInfo`.
-#[derive(Archive, RkyvDeserialize, RkyvSerialize)]
+#[derive(Archive, Clone, RkyvDeserialize, RkyvSerialize)]
 #[allow(missing_docs)]
 pub struct SerializableModule {
     /// The main serializable compilation object
@@ -61,12 +148,114 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// The enabled proposals actually used by the module
+    pub used_proposals: Features,
+    /// The options of the engine the module was compiled with
+    pub compile_options: CompileOptions,
+    /// The sources of nondeterminism used by the module
+    pub determinism: DeterminismReport,
+    /// The triple of the target the module was compiled for
+    pub target_triple: String,
+    /// The hash of what determined the compiled code
+    pub hash: ArtifactHash,
+}
+
+/// The description of a serialized module, read by
+/// [`SerializableModule::deserialize_metadata`] without deserializing its
+/// code.
+#[derive(Debug, Clone)]
+pub struct ArtifactMetadata {
+    compile_info: CompileModuleInfo,
+    cpu_features: u64,
+    used_proposals: Features,
+    target_triple: String,
+    hash: ArtifactHash,
+    num_functions: usize,
+    custom_sections: Vec<CustomSectionInfo>,
+}
+
+/// The layout of a custom section of a serialized module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomSectionInfo {
+    /// The memory protection the section is mapped with.
+    pub protection: CustomSectionProtection,
+    /// The length of the section in bytes.
+    pub len: usize,
+}
+
+impl ArtifactMetadata {
+    /// Returns the information of the module
+    pub fn module(&self) -> &ModuleInfo {
+        &self.compile_info.module
+    }
+
+    /// Returns the features the module was compiled with
+    pub fn features(&self) -> &Features {
+        &self.compile_info.features
+    }
+
+    /// Returns the CPU features the module was compiled for
+    pub fn cpu_features(&self) -> EnumSet<CpuFeature> {
+        EnumSet::from_u64(self.cpu_features)
+    }
+
+    /// Returns the enabled proposals actually used by the module
+    pub fn used_proposals(&self) -> &Features {
+        &self.used_proposals
+    }
+
+    /// Returns the triple of the target the module was compiled for
+    pub fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+
+    /// Returns the hash of what determined the code of the module
+    pub fn hash(&self) -> ArtifactHash {
+        self.hash
+    }
+
+    /// Returns the number of functions defined by the module, which have
+    /// compiled code
+    pub fn num_functions(&self) -> usize {
+        self.num_functions
+    }
+
+    /// Returns the imports of the module
+    pub fn imports(&self) -> Vec<ImportType> {
+        self.module().imports().collect()
+    }
+
+    /// Returns the exports of the module
+    pub fn exports(&self) -> Vec<ExportType> {
+        self.module().exports().collect()
+    }
+
+    /// Returns the custom sections of the module, in order
+    pub fn custom_sections(&self) -> &[CustomSectionInfo] {
+        &self.custom_sections
+    }
 }
 
 fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
     SerializeError::Generic(format!("{}", err))
 }
 
+/// A serializer writing the archive straight into `W`, producing the same
+/// bytes as `AllocSerializer`.
+type StreamSerializer<W> = CompositeSerializer<
+    WriteSerializer<W>,
+    FallbackScratch<HeapScratch<4096>, AllocScratch>,
+    SharedSerializeMap,
+>;
+
+fn stream_serializer<W: Write>(writer: W) -> StreamSerializer<W> {
+    CompositeSerializer::new(
+        WriteSerializer::new(writer),
+        Default::default(),
+        Default::default(),
+    )
+}
+
 impl SerializableModule {
     /// Serialize a Module into bytes
     /// The bytes will have the following format:
@@ -81,6 +270,29 @@ impl SerializableModule {
         Ok(serialized_data.to_vec())
     }
 
+    /// The number of bytes [`SerializableModule::serialize`] produces for
+    /// this module, computed without keeping them.
+    pub fn serialized_len(&self) -> Result<usize, SerializeError> {
+        let mut serializer = stream_serializer(io::sink());
+        serializer
+            .serialize_value(self)
+            .map_err(to_serialize_error)?;
+        Ok(serializer.pos() + mem::size_of::<u64>())
+    }
+
+    /// Serializes the module into `writer`, in the same format as
+    /// [`SerializableModule::serialize`] but without holding the
+    /// serialized bytes in memory.
+    pub fn serialize_to<W: Write>(&self, writer: W) -> Result<(), SerializeError> {
+        let mut serializer = stream_serializer(writer);
+        let pos = serializer
+            .serialize_value(self)
+            .map_err(to_serialize_error)? as u64;
+        let (serializer, _, _) = serializer.into_components();
+        serializer.into_inner().write_all(&pos.to_le_bytes())?;
+        Ok(())
+    }
+
     /// Deserialize a Module from a slice.
     /// The slice must have the following format:
     /// RKYV serialization (any length) + POS (8 bytes)
@@ -118,6 +330,47 @@ impl SerializableModule {
         ))
     }
 
+    /// Deserializes the description of a module from a slice in the format
+    /// of [`SerializableModule::deserialize`], without deserializing its
+    /// code, data initializers and frame information.
+    ///
+    /// # Safety
+    ///
+    /// See [`SerializableModule::deserialize`].
+    pub unsafe fn deserialize_metadata(
+        metadata_slice: &[u8],
+    ) -> Result<ArtifactMetadata, DeserializeError> {
+        fn corrupted(error: impl fmt::Debug) -> DeserializeError {
+            DeserializeError::CorruptedBinary(format!("{:?}", error))
+        }
+        let archived = Self::archive_from_slice(metadata_slice)?;
+        let mut deserializer = SharedDeserializeMap::new();
+        Ok(ArtifactMetadata {
+            compile_info: RkyvDeserialize::deserialize(&archived.compile_info, &mut deserializer)
+                .map_err(corrupted)?,
+            cpu_features: archived.cpu_features,
+            used_proposals: RkyvDeserialize::deserialize(
+                &archived.used_proposals,
+                &mut deserializer,
+            )
+            .map_err(corrupted)?,
+            target_triple: archived.target_triple.as_str().to_string(),
+            hash: RkyvDeserialize::deserialize(&archived.hash, &mut deserializer)
+                .map_err(corrupted)?,
+            num_functions: archived.compilation.function_bodies.elems.len(),
+            custom_sections: archived
+                .compilation
+                .custom_sections
+                .elems
+                .iter()
+                .map(|section| CustomSectionInfo {
+                    protection: section.protection.clone(),
+                    len: section.bytes.len(),
+                })
+                .collect(),
+        })
+    }
+
     /// Deserialize a compilation module from an archive
     pub fn deserialize_from_archive(
         archived: &ArchivedSerializableModule,
@@ -142,6 +395,31 @@ impl SerializableModule {
         EnumSet::from_u64(self.cpu_features)
     }
 
+    /// Returns the enabled proposals actually used by this Artifact
+    pub fn used_proposals(&self) -> &Features {
+        &self.used_proposals
+    }
+
+    /// Returns the options of the engine this Artifact was compiled with
+    pub fn compile_options(&self) -> CompileOptions {
+        self.compile_options
+    }
+
+    /// Returns the sources of nondeterminism used by this Artifact
+    pub fn determinism_report(&self) -> DeterminismReport {
+        self.determinism
+    }
+
+    /// Returns the triple of the target this Artifact was compiled for
+    pub fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+
+    /// Returns the hash of what determined the code of this Artifact
+    pub fn hash(&self) -> ArtifactHash {
+        self.hash
+    }
+
     /// Returns data initializers to pass to `InstanceHandle::initialize`
     pub fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.data_initializers
@@ -165,26 +443,59 @@ impl SerializableModule {
     }
 }
 
-/// Metadata header which holds an ABI version and the length of the remaining
-/// metadata.
+/// The policy to follow when deserializing an artifact that was serialized
+/// with a newer format version than the one supported by this version of
+/// Wasmer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeserializeForwardPolicy {
+    /// Reject the artifact with a [`DeserializeError::NewerFormat`] error.
+    Reject,
+    /// Read artifacts whose format version is in the list as if they had
+    /// been serialized with the current version, and reject the others.
+    ///
+    /// Artifacts are read without validation, so only list versions known
+    /// to keep the exact archived layout of the current version: reading
+    /// any other layout is undefined behavior.
+    AllowVersions(&'static [u32]),
+}
+
+impl Default for DeserializeForwardPolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+/// Metadata header which holds an ABI version, the oldest ABI version able to
+/// read the metadata, and the length of the remaining metadata.
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct MetadataHeader {
     magic: [u8; 8],
     version: u32,
+    min_runtime_version: u32,
     len: u32,
+    reserved: [u8; 12],
 }
 
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 13;
+
+    /// Oldest ABI version able to read the data serialized by this version.
+    ///
+    /// Only raise this for changes that older versions can't read even
+    /// when told the layout is compatible, such as a new layout of this
+    /// header. Bumping `CURRENT_VERSION` alone is enough for every other
+    /// change: readers of older versions reject newer artifacts unless
+    /// they list the version in [`DeserializeForwardPolicy::AllowVersions`].
+    const MIN_RUNTIME_VERSION: u32 = 7;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
 
     /// Length of the metadata header.
-    pub const LEN: usize = 16;
+    pub const LEN: usize = 32;
 
     /// Alignment of the metadata.
     pub const ALIGN: usize = 16;
@@ -194,24 +505,53 @@ impl MetadataHeader {
         Self {
             magic: Self::MAGIC,
             version: Self::CURRENT_VERSION,
+            min_runtime_version: Self::MIN_RUNTIME_VERSION,
             len: len.try_into().expect("metadata exceeds maximum length"),
+            reserved: [0; 12],
         }
     }
 
+    /// Sets the oldest ABI version able to read the metadata.
+    pub fn with_min_runtime_version(mut self, version: u32) -> Self {
+        self.min_runtime_version = version;
+        self
+    }
+
+    /// The ABI version the metadata was serialized with.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The oldest ABI version able to read the metadata.
+    pub fn min_runtime_version(&self) -> u32 {
+        self.min_runtime_version
+    }
+
+    /// The length of the metadata following the header.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns whether no metadata follows the header.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// Convert the header into its bytes representation.
-    pub fn into_bytes(self) -> [u8; 16] {
+    pub fn into_bytes(self) -> [u8; 32] {
         unsafe { mem::transmute(self) }
     }
 
-    /// Parses the header and returns the length of the metadata following it.
-    pub fn parse(bytes: &[u8]) -> Result<usize, DeserializeError> {
+    /// Reads the header at the start of `bytes`, without checking whether
+    /// this version of Wasmer can read the metadata.
+    pub fn peek(bytes: &[u8]) -> Result<Self, DeserializeError> {
         if bytes.as_ptr() as usize % 8 != 0 {
             return Err(DeserializeError::CorruptedBinary(
                 "misaligned metadata".to_string(),
             ));
         }
-        let bytes: [u8; 16] = bytes
-            .get(..16)
+        let bytes: [u8; 32] = bytes
+            .get(..Self::LEN)
             .ok_or_else(|| {
                 DeserializeError::CorruptedBinary("invalid metadata header".to_string())
             })?
@@ -223,7 +563,47 @@ impl MetadataHeader {
                 "The provided bytes were not serialized by Wasmer".to_string(),
             ));
         }
-        if header.version != Self::CURRENT_VERSION {
+        Ok(header)
+    }
+
+    /// Parses the header and returns the length of the metadata following it.
+    ///
+    /// Artifacts with a newer format version, or that require a newer
+    /// version to be read, are rejected.
+    pub fn parse(bytes: &[u8]) -> Result<usize, DeserializeError> {
+        Self::parse_with_policy(bytes, DeserializeForwardPolicy::Reject)
+    }
+
+    /// Parses the header and returns the length of the metadata following it,
+    /// handling artifacts with a newer format version according to `policy`.
+    ///
+    /// Artifacts that require a newer version to be read are rejected
+    /// regardless of `policy`.
+    pub fn parse_with_policy(
+        bytes: &[u8],
+        policy: DeserializeForwardPolicy,
+    ) -> Result<usize, DeserializeError> {
+        let header = Self::peek(bytes)?;
+        if header.min_runtime_version > Self::CURRENT_VERSION {
+            return Err(DeserializeError::RuntimeTooOld {
+                required: header.min_runtime_version,
+                current: Self::CURRENT_VERSION,
+            });
+        }
+        if header.version > Self::CURRENT_VERSION {
+            let allowed = match policy {
+                DeserializeForwardPolicy::Reject => false,
+                DeserializeForwardPolicy::AllowVersions(versions) => {
+                    versions.contains(&header.version)
+                }
+            };
+            if !allowed {
+                return Err(DeserializeError::NewerFormat {
+                    found: header.version,
+                    supported: Self::CURRENT_VERSION,
+                });
+            }
+        } else if header.version != Self::CURRENT_VERSION {
             return Err(DeserializeError::Incompatible(
                 "The provided bytes were serialized by an incompatible version of Wasmer"
                     .to_string(),
@@ -397,6 +397,44 @@ impl fmt::Display for GlobalType {
     }
 }
 
+/// The type of an exception tag, declared by the exception handling
+/// proposal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct TagType {
+    /// The types of the values carried by exceptions with the tag.
+    params: Box<[Type]>,
+}
+
+impl TagType {
+    /// Creates a new tag type carrying values of the given types.
+    pub fn new<Params>(params: Params) -> Self
+    where
+        Params: Into<Box<[Type]>>,
+    {
+        Self {
+            params: params.into(),
+        }
+    }
+
+    /// The types of the values carried by exceptions with the tag.
+    pub fn params(&self) -> &[Type] {
+        &self.params
+    }
+}
+
+impl fmt::Display for TagType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{}]", params)
+    }
+}
+
 /// Globals are initialized via the `const` operators or by referring to another import.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
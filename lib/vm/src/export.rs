@@ -8,6 +8,7 @@ use crate::table::VMTable;
 use crate::vmcontext::VMFunctionKind;
 use crate::{MaybeInstanceOwned, VMCallerCheckedAnyfunc};
 use std::any::Any;
+use std::sync::Arc;
 use wasmer_types::FunctionType;
 
 /// The value of an export passed from one instance to another.
@@ -31,8 +32,9 @@ pub struct VMFunction {
     /// call the function and check its signature.
     pub anyfunc: MaybeInstanceOwned<VMCallerCheckedAnyfunc>,
 
-    /// The function type, used for compatibility checking.
-    pub signature: FunctionType,
+    /// The function type, used for compatibility checking, shared with the
+    /// registry of the engine when the function comes from a table.
+    pub signature: Arc<FunctionType>,
 
     /// The function kind (specifies the calling convention for the
     /// function).
@@ -1,4 +1,5 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::thread::{self, ThreadId};
 
 use wasmer_types::RawValue;
 
@@ -7,14 +8,113 @@ use crate::store::InternalStoreHandle;
 /// Underlying object referenced by a `VMExternRef`.
 pub struct VMExternObj {
     contents: Box<dyn Any + Send + Sync + 'static>,
+    type_id: TypeId,
+}
+
+/// A value that isn't necessarily `Send + Sync`, which is only accessed
+/// and dropped on the thread owning it.
+struct LocalValue {
+    value: Box<dyn Any + 'static>,
+    owner: ThreadId,
+}
+
+impl LocalValue {
+    fn is_owned(&self) -> bool {
+        self.owner == thread::current().id()
+    }
+}
+
+// The value is only handed out on the thread owning it, and never dropped
+// on another one.
+unsafe impl Send for LocalValue {}
+unsafe impl Sync for LocalValue {}
+
+impl Drop for LocalValue {
+    fn drop(&mut self) {
+        if !self.is_owned() {
+            // Dropping the value here could race with its owner thread, so
+            // it is leaked instead.
+            std::mem::forget(std::mem::replace(&mut self.value, Box::new(())));
+        }
+    }
 }
 
 impl VMExternObj {
     /// Wraps the given value to expose it to Wasm code as an externref.
-    pub fn new(val: impl Any + Send + Sync + 'static) -> Self {
+    pub fn new<T: Any + Send + Sync + 'static>(val: T) -> Self {
         Self {
             contents: Box::new(val),
+            type_id: TypeId::of::<T>(),
+        }
+    }
+
+    /// Wraps a value that may not be `Send + Sync`, owned by the current
+    /// thread, which is the only one
+    /// [`downcast_local_ref`](VMExternObj::downcast_local_ref) hands it to.
+    ///
+    /// Dropping the object on another thread leaks the value.
+    pub fn new_local<T: Any + 'static>(val: T) -> Self {
+        Self {
+            contents: Box::new(LocalValue {
+                value: Box::new(val),
+                owner: thread::current().id(),
+            }),
+            type_id: TypeId::of::<T>(),
+        }
+    }
+
+    /// Returns the `TypeId` of the underlying value.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Checks whether the underlying value is a `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// Returns a reference to the underlying value if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        self.contents.downcast_ref()
+    }
+
+    /// Returns a mutable reference to the underlying value if it is a `T`.
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        self.contents.downcast_mut()
+    }
+
+    /// Returns a reference to the underlying value of an object made with
+    /// [`VMExternObj::new_local`] if it is a `T` and the current thread
+    /// owns it.
+    pub fn downcast_local_ref<T: Any>(&self) -> Option<&T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        let local = self.contents.downcast_ref::<LocalValue>()?;
+        if !local.is_owned() {
+            return None;
         }
+        local.value.downcast_ref()
+    }
+
+    /// Returns a mutable reference to the underlying value of an object
+    /// made with [`VMExternObj::new_local`] if it is a `T` and the current
+    /// thread owns it.
+    pub fn downcast_local_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        let local = self.contents.downcast_mut::<LocalValue>()?;
+        if !local.is_owned() {
+            return None;
+        }
+        local.value.downcast_mut()
     }
 
     #[allow(clippy::should_implement_trait)]
@@ -22,6 +122,12 @@ impl VMExternObj {
     pub fn as_ref(&self) -> &(dyn Any + Send + Sync + 'static) {
         &*self.contents
     }
+
+    #[allow(clippy::should_implement_trait)]
+    /// Returns a mutable reference to the underlying value.
+    pub fn as_mut(&mut self) -> &mut (dyn Any + Send + Sync + 'static) {
+        &mut *self.contents
+    }
 }
 
 /// Represents an opaque reference to any data within WebAssembly.
@@ -12,6 +12,7 @@ use crate::export::VMExtern;
 use crate::imports::Imports;
 use crate::store::{InternalStoreHandle, StoreObjects};
 use crate::table::TableElement;
+use crate::table_limits::TableGrowLimits;
 use crate::trap::{catch_traps, Trap, TrapCode};
 use crate::vmcontext::{
     memory_copy, memory_fill, VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext,
@@ -25,6 +26,7 @@ pub use allocator::InstanceAllocator;
 use memoffset::offset_of;
 use more_asserts::assert_lt;
 use std::alloc::Layout;
+use std::any::Any;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -88,6 +90,14 @@ pub(crate) struct Instance {
     /// will point to elements here for functions imported by this instance.
     imported_funcrefs: BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 
+    /// The limits checked when this instance grows a table with
+    /// `table.grow`, if any.
+    table_grow_limits: Option<Arc<TableGrowLimits>>,
+
+    /// A value kept alive for as long as the instance, such as the
+    /// registrations of the signatures it uses.
+    keep_alive: Option<Arc<dyn Any + Send + Sync>>,
+
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -430,6 +440,15 @@ impl Instance {
         table.get(self.context()).size()
     }
 
+    /// Checks growing a table of `size` elements by `delta` elements with
+    /// `table.grow` against the limits of this instance, if any.
+    pub(crate) fn check_table_grow(&self, size: u32, delta: u32) -> Result<(), Trap> {
+        match &self.table_grow_limits {
+            Some(limits) => limits.check_grow(size, delta),
+            None => Ok(()),
+        }
+    }
+
     /// Grow table by the specified amount of elements.
     ///
     /// Returns `None` if table can't be grown by the specified amount
@@ -858,6 +877,8 @@ impl InstanceHandle {
                 passive_data,
                 funcrefs,
                 imported_funcrefs,
+                table_grow_limits: None,
+                keep_alive: None,
                 vmctx: VMContext {},
             };
 
@@ -938,6 +959,18 @@ impl InstanceHandle {
         unsafe { self.instance.as_mut() }
     }
 
+    /// Sets the limits checked when the instance grows a table with
+    /// `table.grow`.
+    pub fn set_table_grow_limits(&mut self, limits: Arc<TableGrowLimits>) {
+        self.instance_mut().table_grow_limits = Some(limits);
+    }
+
+    /// Keeps `value` alive for as long as the instance, for example the
+    /// registrations of the signatures its functions are checked against.
+    pub fn set_keep_alive(&mut self, value: Arc<dyn Any + Send + Sync>) {
+        self.instance_mut().keep_alive = Some(value);
+    }
+
     /// Finishes the instantiation process started by `Instance::new`.
     ///
     /// # Safety
@@ -1004,7 +1037,7 @@ impl InstanceHandle {
                 let handle = if let Some(def_index) = instance.module.local_func_index(index) {
                     // A VMFunction is lazily created only for functions that are
                     // exported.
-                    let signature = instance.module.signatures[*sig_index].clone();
+                    let signature = Arc::new(instance.module.signatures[*sig_index].clone());
                     let vm_function = VMFunction {
                         anyfunc: MaybeInstanceOwned::Instance(NonNull::from(
                             &instance.funcrefs[def_index],
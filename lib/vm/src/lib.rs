@@ -29,9 +29,11 @@ mod instance;
 mod memory;
 mod mmap;
 mod probestack;
+mod rounding;
 mod sig_registry;
 mod store;
 mod table;
+mod table_limits;
 mod trap;
 mod vmcontext;
 
@@ -46,13 +48,17 @@ pub use crate::global::*;
 pub use crate::imports::Imports;
 pub use crate::instance::{InstanceAllocator, InstanceHandle};
 pub use crate::memory::{LinearMemory, VMMemory};
-pub use crate::mmap::Mmap;
+pub use crate::mmap::{Mmap, HUGE_PAGE_SIZE};
 pub use crate::probestack::PROBESTACK;
+pub use crate::rounding::{
+    restore_float_control, set_rounding_mode, RoundingMode, SavedFloatControl,
+};
 pub use crate::sig_registry::SignatureRegistry;
 pub use crate::store::{
     InternalStoreHandle, MaybeInstanceOwned, StoreHandle, StoreId, StoreObjects,
 };
 pub use crate::table::{TableElement, VMTable};
+pub use crate::table_limits::{TableGrowLimits, TableGrowObserver, TableLimitExceeded};
 pub use crate::trap::*;
 pub use crate::vmcontext::{
     VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext, VMFunctionContext,
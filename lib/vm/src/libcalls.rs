@@ -442,20 +442,27 @@ pub unsafe extern "C" fn wasmer_vm_table_grow(
     delta: u32,
     table_index: u32,
 ) -> u32 {
-    on_host_stack(|| {
+    let result: Result<u32, Trap> = on_host_stack(|| {
         let instance = (*vmctx).instance_mut();
         let table_index = LocalTableIndex::from_u32(table_index);
 
-        let init_value = match instance.get_local_table(table_index).ty().ty {
+        let table = instance.get_local_table(table_index);
+        let size = table.size();
+        let init_value = match table.ty().ty {
             Type::ExternRef => TableElement::ExternRef(init_value.extern_ref),
             Type::FuncRef => TableElement::FuncRef(init_value.func_ref),
             _ => panic!("Unrecognized table type: does not contain references"),
         };
 
-        instance
+        instance.check_table_grow(size, delta)?;
+        Ok(instance
             .table_grow(table_index, delta, init_value)
-            .unwrap_or(u32::max_value())
-    })
+            .unwrap_or(u32::max_value()))
+    });
+    match result {
+        Ok(previous_size) => previous_size,
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
 /// Implementation of `table.grow` for imported tables.
@@ -470,19 +477,26 @@ pub unsafe extern "C" fn wasmer_vm_imported_table_grow(
     delta: u32,
     table_index: u32,
 ) -> u32 {
-    on_host_stack(|| {
+    let result: Result<u32, Trap> = on_host_stack(|| {
         let instance = (*vmctx).instance_mut();
         let table_index = TableIndex::from_u32(table_index);
-        let init_value = match instance.get_table(table_index).ty().ty {
+        let table = instance.get_table(table_index);
+        let size = table.size();
+        let init_value = match table.ty().ty {
             Type::ExternRef => TableElement::ExternRef(init_value.extern_ref),
             Type::FuncRef => TableElement::FuncRef(init_value.func_ref),
             _ => panic!("Unrecognized table type: does not contain references"),
         };
 
-        instance
+        instance.check_table_grow(size, delta)?;
+        Ok(instance
             .imported_table_grow(table_index, delta, init_value)
-            .unwrap_or(u32::max_value())
-    })
+            .unwrap_or(u32::max_value()))
+    });
+    match result {
+        Ok(previous_size) => previous_size,
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
 /// Implementation of `func.ref`.
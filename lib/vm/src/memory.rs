@@ -373,6 +373,22 @@ impl VMMemory {
         )?)))
     }
 
+    /// Commits the first `pages` pages of the memory, or the whole memory if
+    /// it is smaller, so that the first accesses to them don't fault.
+    ///
+    /// # Safety
+    /// - The memory must not be accessed concurrently.
+    pub unsafe fn commit(&self, pages: Pages) {
+        let definition = self.vmmemory().as_ref();
+        let len = std::cmp::min(pages.bytes().0, definition.current_length);
+        // Writing a byte back to every page makes the kernel back it, which
+        // merely reading it wouldn't do.
+        for offset in (0..len).step_by(region::page::size()) {
+            let byte = definition.base.add(offset);
+            std::ptr::write_volatile(byte, std::ptr::read_volatile(byte));
+        }
+    }
+
     /// Creates VMMemory from a custom implementation - the following into implementations
     /// are natively supported
     /// - VMOwnedMemory -> VMMemory
@@ -10,6 +10,10 @@ use std::io;
 use std::ptr;
 use std::slice;
 
+/// The size of the huge pages of [`Mmap::with_at_least_huge`], which is the
+/// default size of huge pages on x86_64 and aarch64 Linux.
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
 /// Round `size` up to the nearest multiple of `page_size`.
 fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
     (size + (page_size - 1)) & !(page_size - 1)
@@ -47,6 +51,156 @@ impl Mmap {
         Self::accessible_reserved(rounded_size, rounded_size)
     }
 
+    /// Create a new `Mmap` pointing to at least `size` bytes of page-aligned accessible memory
+    /// starting exactly at `address`, which must be a native page-size multiple.
+    ///
+    /// This fails instead of replacing any existing mapping in that range.
+    #[cfg(not(target_os = "windows"))]
+    pub fn with_at_least_at(address: usize, size: usize) -> Result<Self, String> {
+        let page_size = region::page::size();
+        assert_eq!(address & (page_size - 1), 0);
+        let rounded_size = round_up_to_page_size(size, page_size);
+        if rounded_size == 0 {
+            return Ok(Self::new());
+        }
+
+        #[cfg(target_os = "linux")]
+        let fixed_flags = libc::MAP_FIXED_NOREPLACE;
+        #[cfg(not(target_os = "linux"))]
+        let fixed_flags = 0;
+        let ptr = unsafe {
+            libc::mmap(
+                address as *mut libc::c_void,
+                rounded_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | fixed_flags,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+
+        let result = Self {
+            ptr: ptr as usize,
+            len: rounded_size,
+        };
+        // Without `MAP_FIXED_NOREPLACE` the address is only a hint, and
+        // kernels that predate it ignore the flag.
+        if result.ptr != address {
+            return Err(format!("the address {:#x} is not available", address));
+        }
+        Ok(result)
+    }
+
+    /// Create a new `Mmap` pointing to at least `size` bytes of page-aligned accessible memory
+    /// starting exactly at `address`, which must be a native page-size multiple.
+    ///
+    /// This fails instead of replacing any existing mapping in that range.
+    #[cfg(target_os = "windows")]
+    pub fn with_at_least_at(address: usize, size: usize) -> Result<Self, String> {
+        use winapi::um::memoryapi::VirtualAlloc;
+        use winapi::um::winnt::{MEM_COMMIT, MEM_RESERVE, PAGE_READWRITE};
+
+        let page_size = region::page::size();
+        assert_eq!(address & (page_size - 1), 0);
+        let rounded_size = round_up_to_page_size(size, page_size);
+        if rounded_size == 0 {
+            return Ok(Self::new());
+        }
+
+        let ptr = unsafe {
+            VirtualAlloc(
+                address as *mut _,
+                rounded_size,
+                MEM_RESERVE | MEM_COMMIT,
+                PAGE_READWRITE,
+            )
+        };
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error().to_string());
+        }
+
+        Ok(Self {
+            ptr: ptr as usize,
+            len: rounded_size,
+        })
+    }
+
+    /// Create a new `Mmap` pointing to at least `size` bytes of accessible memory backed by
+    /// huge pages, aligned to and a multiple of [`HUGE_PAGE_SIZE`].
+    ///
+    /// With `explicit`, the memory is mapped from the huge pages reserved by the system
+    /// (`MAP_HUGETLB`), which fails if not enough of them are free. Otherwise the kernel is
+    /// advised to back the memory with transparent huge pages, which it may not do.
+    #[cfg(target_os = "linux")]
+    pub fn with_at_least_huge(size: usize, explicit: bool) -> Result<Self, String> {
+        let rounded_size = round_up_to_page_size(size, HUGE_PAGE_SIZE);
+        if rounded_size == 0 {
+            return Ok(Self::new());
+        }
+
+        if explicit {
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    rounded_size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_HUGETLB,
+                    -1,
+                    0,
+                )
+            };
+            if ptr as isize == -1_isize {
+                return Err(io::Error::last_os_error().to_string());
+            }
+            return Ok(Self {
+                ptr: ptr as usize,
+                len: rounded_size,
+            });
+        }
+
+        // Transparent huge pages are only used for aligned ranges, so map
+        // one more huge page and trim the mapping to an aligned range.
+        let mapping_size = rounded_size + HUGE_PAGE_SIZE;
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                mapping_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let start = ptr as usize;
+        let aligned = round_up_to_page_size(start, HUGE_PAGE_SIZE);
+        let end = aligned + rounded_size;
+        unsafe {
+            if aligned > start {
+                libc::munmap(start as *mut libc::c_void, aligned - start);
+            }
+            if start + mapping_size > end {
+                libc::munmap(end as *mut libc::c_void, start + mapping_size - end);
+            }
+            // This is only advice: without transparent huge pages, the
+            // memory is backed by normal pages.
+            libc::madvise(
+                aligned as *mut libc::c_void,
+                rounded_size,
+                libc::MADV_HUGEPAGE,
+            );
+        }
+        Ok(Self {
+            ptr: aligned,
+            len: rounded_size,
+        })
+    }
+
     /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
     /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
     /// must be native page-size multiples.
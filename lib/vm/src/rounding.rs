@@ -0,0 +1,121 @@
+//! Control of the rounding mode used by floating-point operations.
+//!
+//! The rounding mode can be changed on x86_64 (through `MXCSR`) and on
+//! aarch64 (through `FPCR`). On other architectures it can't be changed,
+//! and [`set_rounding_mode`] does nothing.
+
+/// An IEEE 754 rounding mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RoundingMode {
+    /// Round to the nearest value, ties to even. This is the default, and
+    /// the only mode allowed by the WebAssembly specification.
+    ToNearest,
+    /// Round toward zero.
+    TowardZero,
+    /// Round toward positive infinity.
+    Upward,
+    /// Round toward negative infinity.
+    Downward,
+}
+
+/// The floating-point control state of the current thread, as it was
+/// before calling [`set_rounding_mode`].
+#[derive(Debug, Clone, Copy)]
+pub struct SavedFloatControl(u64);
+
+/// Sets the rounding mode of the current thread, returning the previous
+/// control state so that it can be restored with [`restore_float_control`].
+///
+/// Returns `None` if the rounding mode can't be changed on this
+/// architecture.
+pub fn set_rounding_mode(mode: RoundingMode) -> Option<SavedFloatControl> {
+    imp::set_rounding_mode(mode)
+}
+
+/// Restores the floating-point control state saved by
+/// [`set_rounding_mode`].
+pub fn restore_float_control(saved: SavedFloatControl) {
+    imp::restore_float_control(saved)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod imp {
+    use super::{RoundingMode, SavedFloatControl};
+    use std::arch::asm;
+
+    /// The rounding control bits of `MXCSR`.
+    const ROUNDING_CONTROL_MASK: u32 = 0b11 << 13;
+
+    fn read_mxcsr() -> u32 {
+        let mut mxcsr: u32 = 0;
+        unsafe { asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack)) };
+        mxcsr
+    }
+
+    fn write_mxcsr(mxcsr: u32) {
+        unsafe { asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, readonly)) };
+    }
+
+    pub(super) fn set_rounding_mode(mode: RoundingMode) -> Option<SavedFloatControl> {
+        let mxcsr = read_mxcsr();
+        let rounding_control = match mode {
+            RoundingMode::ToNearest => 0b00,
+            RoundingMode::Downward => 0b01,
+            RoundingMode::Upward => 0b10,
+            RoundingMode::TowardZero => 0b11,
+        } << 13;
+        write_mxcsr((mxcsr & !ROUNDING_CONTROL_MASK) | rounding_control);
+        Some(SavedFloatControl(mxcsr as u64))
+    }
+
+    pub(super) fn restore_float_control(saved: SavedFloatControl) {
+        write_mxcsr(saved.0 as u32);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod imp {
+    use super::{RoundingMode, SavedFloatControl};
+    use std::arch::asm;
+
+    /// The rounding mode bits of `FPCR`.
+    const ROUNDING_MODE_MASK: u64 = 0b11 << 22;
+
+    fn read_fpcr() -> u64 {
+        let fpcr: u64;
+        unsafe { asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack)) };
+        fpcr
+    }
+
+    fn write_fpcr(fpcr: u64) {
+        unsafe { asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack)) };
+    }
+
+    pub(super) fn set_rounding_mode(mode: RoundingMode) -> Option<SavedFloatControl> {
+        let fpcr = read_fpcr();
+        let rounding_mode = match mode {
+            RoundingMode::ToNearest => 0b00,
+            RoundingMode::Upward => 0b01,
+            RoundingMode::Downward => 0b10,
+            RoundingMode::TowardZero => 0b11,
+        } << 22;
+        write_fpcr((fpcr & !ROUNDING_MODE_MASK) | rounding_mode);
+        Some(SavedFloatControl(fpcr))
+    }
+
+    pub(super) fn restore_float_control(saved: SavedFloatControl) {
+        write_fpcr(saved.0);
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod imp {
+    use super::{RoundingMode, SavedFloatControl};
+
+    pub(super) fn set_rounding_mode(_mode: RoundingMode) -> Option<SavedFloatControl> {
+        None
+    }
+
+    pub(super) fn restore_float_control(_saved: SavedFloatControl) {}
+}
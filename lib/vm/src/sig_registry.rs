@@ -6,9 +6,9 @@
 
 use crate::vmcontext::VMSharedSignatureIndex;
 use more_asserts::{assert_lt, debug_assert_lt};
-use std::collections::{hash_map, HashMap};
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use wasmer_types::FunctionType;
 
 /// WebAssembly requires that the caller and callee signatures in an indirect
@@ -27,8 +27,18 @@ pub struct SignatureRegistry {
 
 #[derive(Debug, Default)]
 struct Inner {
-    signature2index: HashMap<FunctionType, VMSharedSignatureIndex>,
-    index2signature: HashMap<VMSharedSignatureIndex, FunctionType>,
+    signature2index: HashMap<Arc<FunctionType>, VMSharedSignatureIndex>,
+    index2signature: HashMap<VMSharedSignatureIndex, Entry>,
+    /// The indices of the signatures that were unregistered, to be reused.
+    free: Vec<VMSharedSignatureIndex>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    signature: Arc<FunctionType>,
+    /// The number of times the signature was registered and not
+    /// unregistered yet.
+    references: usize,
 }
 
 impl SignatureRegistry {
@@ -38,26 +48,81 @@ impl SignatureRegistry {
     }
 
     /// Register a signature and return its unique index.
+    ///
+    /// Every registration counts as a reference to the signature, which
+    /// stays registered until each of them is released with
+    /// [`SignatureRegistry::unregister`].
     pub fn register(&self, sig: &FunctionType) -> VMSharedSignatureIndex {
+        self.inner.write().unwrap().register(sig)
+    }
+
+    /// Registers several signatures at once, returning their indices in
+    /// the same order.
+    ///
+    /// This gives the same indices as registering the signatures one by
+    /// one with [`SignatureRegistry::register`], but only takes the lock
+    /// of the registry once.
+    pub fn register_all<'a>(
+        &self,
+        sigs: impl IntoIterator<Item = &'a FunctionType>,
+    ) -> Vec<VMSharedSignatureIndex> {
         let mut inner = self.inner.write().unwrap();
-        let len = inner.signature2index.len();
-        let entry = inner.signature2index.entry(sig.clone());
-        match entry {
-            hash_map::Entry::Occupied(entry) => *entry.get(),
-            hash_map::Entry::Vacant(entry) => {
-                // Keep `signature_hash` len under 2**32 -- VMSharedSignatureIndex::new(std::u32::MAX)
-                // is reserved for VMSharedSignatureIndex::default().
-                debug_assert_lt!(
-                    len,
-                    std::u32::MAX as usize,
-                    "Invariant check: signature_hash.len() < std::u32::MAX"
-                );
-                let sig_id = VMSharedSignatureIndex::new(u32::try_from(len).unwrap());
-                entry.insert(sig_id);
-                inner.index2signature.insert(sig_id, sig.clone());
-                sig_id
-            }
+        sigs.into_iter().map(|sig| inner.register(sig)).collect()
+    }
+
+    /// Releases one registration of a signature, and returns whether it
+    /// was the last one, in which case the signature is removed from the
+    /// registry and its index may be given to another signature.
+    ///
+    /// Indices that aren't registered are ignored.
+    ///
+    /// # Safety
+    ///
+    /// Once the last registration is released, nothing may use the index
+    /// anymore: no live instance or function may have been created with
+    /// it, as indirect calls would check signatures against the index of
+    /// another signature.
+    pub unsafe fn unregister(&self, idx: VMSharedSignatureIndex) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        let entry = match inner.index2signature.get_mut(&idx) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        entry.references -= 1;
+        if entry.references > 0 {
+            return false;
         }
+        let entry = inner.index2signature.remove(&idx).unwrap();
+        inner.signature2index.remove(&entry.signature);
+        inner.free.push(idx);
+        true
+    }
+
+    /// The number of signatures currently registered.
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().index2signature.len()
+    }
+
+    /// Whether no signature is currently registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every signature currently registered with its index, in
+    /// index order.
+    ///
+    /// The signatures are read at once, so that signatures registered or
+    /// released concurrently are either all seen or not at all.
+    pub fn signatures(&self) -> Vec<(VMSharedSignatureIndex, FunctionType)> {
+        let inner = self.inner.read().unwrap();
+        let mut signatures = inner
+            .index2signature
+            .iter()
+            .map(|(index, entry)| (*index, (*entry.signature).clone()))
+            .collect::<Vec<_>>();
+        drop(inner);
+        signatures.sort_unstable_by_key(|(index, _)| *index);
+        signatures
     }
 
     /// Looks up a shared signature index within this registry.
@@ -65,11 +130,137 @@ impl SignatureRegistry {
     /// Note that for this operation to be semantically correct the `idx` must
     /// have previously come from a call to `register` of this same object.
     pub fn lookup(&self, idx: VMSharedSignatureIndex) -> Option<FunctionType> {
+        self.lookup_arc(idx).map(|sig| (*sig).clone())
+    }
+
+    /// Looks up a shared signature index within this registry, returning
+    /// the signature shared by the registry instead of a copy of it.
+    ///
+    /// The same requirements as for [`SignatureRegistry::lookup`] apply.
+    pub fn lookup_arc(&self, idx: VMSharedSignatureIndex) -> Option<Arc<FunctionType>> {
         self.inner
             .read()
             .unwrap()
             .index2signature
             .get(&idx)
-            .cloned()
+            .map(|entry| entry.signature.clone())
+    }
+}
+
+impl Inner {
+    fn register(&mut self, sig: &FunctionType) -> VMSharedSignatureIndex {
+        if let Some(sig_id) = self.signature2index.get(sig).copied() {
+            self.index2signature.get_mut(&sig_id).unwrap().references += 1;
+            return sig_id;
+        }
+        let sig_id = match self.free.pop() {
+            Some(sig_id) => sig_id,
+            None => {
+                let len = self.signature2index.len();
+                // Keep `signature_hash` len under 2**32 -- VMSharedSignatureIndex::new(std::u32::MAX)
+                // is reserved for VMSharedSignatureIndex::default().
+                debug_assert_lt!(
+                    len,
+                    std::u32::MAX as usize,
+                    "Invariant check: signature_hash.len() < std::u32::MAX"
+                );
+                VMSharedSignatureIndex::new(u32::try_from(len).unwrap())
+            }
+        };
+        let sig = Arc::new(sig.clone());
+        self.signature2index.insert(sig.clone(), sig_id);
+        self.index2signature.insert(
+            sig_id,
+            Entry {
+                signature: sig,
+                references: 1,
+            },
+        );
+        sig_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::Type;
+
+    #[test]
+    fn lookup_arc_shares_the_signature() {
+        let registry = SignatureRegistry::new();
+        let sig = FunctionType::new([Type::I32, Type::F64], [Type::I64]);
+        let index = registry.register(&sig);
+        assert_eq!(registry.register(&sig.clone()), index);
+
+        let first = registry.lookup_arc(index).unwrap();
+        let second = registry.lookup_arc(index).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(*first, sig);
+        assert_eq!(registry.lookup(index), Some(sig));
+    }
+
+    #[test]
+    fn unregister_releases_the_last_reference() {
+        let registry = SignatureRegistry::new();
+        let sig = FunctionType::new([Type::I32], []);
+        let index = registry.register(&sig);
+        assert_eq!(registry.register(&sig), index);
+        assert_eq!(registry.len(), 1);
+
+        unsafe {
+            assert!(!registry.unregister(index));
+            assert_eq!(registry.lookup(index), Some(sig.clone()));
+            assert!(registry.unregister(index));
+            assert!(!registry.unregister(index));
+        }
+        assert!(registry.is_empty());
+        assert_eq!(registry.lookup(index), None);
+
+        // The index of the released signature is reused.
+        let other = FunctionType::new([], [Type::F32]);
+        assert_eq!(registry.register(&other), index);
+        assert_eq!(registry.lookup(index), Some(other));
+    }
+
+    #[test]
+    fn signatures_lists_the_registered_signatures() {
+        let registry = SignatureRegistry::new();
+        let first = FunctionType::new([Type::I32], []);
+        let second = FunctionType::new([], [Type::F32]);
+        let released = FunctionType::new([Type::V128], [Type::V128]);
+        let first_index = registry.register(&first);
+        let second_index = registry.register(&second);
+        registry.register(&first);
+        unsafe { registry.unregister(registry.register(&released)) };
+
+        assert_eq!(
+            registry.signatures(),
+            vec![(first_index, first), (second_index, second)]
+        );
+    }
+
+    #[test]
+    fn register_all_matches_registering_one_by_one() {
+        let sigs = (0..1000)
+            .map(|i| FunctionType::new(vec![Type::I32; i % 37], vec![Type::F64; i % 3]))
+            .collect::<Vec<_>>();
+        let serial = SignatureRegistry::new();
+        let batched = SignatureRegistry::new();
+        // Free an index in both, to check it is reused the same way.
+        let released = FunctionType::new([Type::V128], []);
+        unsafe {
+            serial.unregister(serial.register(&released));
+            batched.unregister(batched.register(&released));
+        }
+
+        let expected = sigs
+            .iter()
+            .map(|sig| serial.register(sig))
+            .collect::<Vec<_>>();
+        assert_eq!(batched.register_all(&sigs), expected);
+        assert_eq!(batched.len(), serial.len());
+        for index in expected {
+            assert_eq!(batched.lookup(index), serial.lookup(index));
+        }
     }
 }
@@ -0,0 +1,76 @@
+//! Limits on the growth of tables by guests, shared by all the instances
+//! of an engine.
+
+use crate::Trap;
+use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+/// A function called every time a guest executes `table.grow`, with the
+/// size of the table before growing and the number of elements requested.
+pub type TableGrowObserver = Box<dyn Fn(u32, u32) + Send + Sync>;
+
+/// The error a guest traps with when `table.grow` would make a table
+/// larger than allowed by [`TableGrowLimits`].
+#[derive(Error, Debug)]
+#[error("growing a table to {requested} elements exceeds the limit of {max} elements")]
+pub struct TableLimitExceeded {
+    /// The number of elements the table would have had.
+    pub requested: u64,
+    /// The maximum number of elements of a table.
+    pub max: u32,
+}
+
+/// The maximum number of elements of the tables of a set of instances,
+/// and an observer of their growth.
+///
+/// Only growth requested by guests with `table.grow` is checked; the host
+/// can still grow tables past the limit.
+pub struct TableGrowLimits {
+    max_elements: AtomicU32,
+    observer: RwLock<Option<Arc<dyn Fn(u32, u32) + Send + Sync>>>,
+}
+
+impl Default for TableGrowLimits {
+    fn default() -> Self {
+        Self {
+            max_elements: AtomicU32::new(u32::MAX),
+            observer: RwLock::new(None),
+        }
+    }
+}
+
+impl TableGrowLimits {
+    /// Sets the maximum number of elements a table may be grown to.
+    pub fn set_max_elements(&self, max: u32) {
+        self.max_elements.store(max, SeqCst);
+    }
+
+    /// The maximum number of elements a table may be grown to.
+    pub fn max_elements(&self) -> u32 {
+        self.max_elements.load(SeqCst)
+    }
+
+    /// Sets the function called every time a guest executes `table.grow`,
+    /// including when it exceeds the limit.
+    pub fn set_observer(&self, observer: TableGrowObserver) {
+        *self.observer.write().unwrap() = Some(Arc::from(observer));
+    }
+
+    /// Reports a guest growing a table of `size` elements by `delta`
+    /// elements, returning the trap to raise if this exceeds the limit.
+    pub(crate) fn check_grow(&self, size: u32, delta: u32) -> Result<(), Trap> {
+        // Don't hold the lock while running the observer, it may set a new
+        // one.
+        let observer = self.observer.read().unwrap().clone();
+        if let Some(observer) = observer {
+            observer(size, delta);
+        }
+        let requested = u64::from(size) + u64::from(delta);
+        let max = self.max_elements();
+        if requested > u64::from(max) {
+            return Err(Trap::User(Box::new(TableLimitExceeded { requested, max })));
+        }
+        Ok(())
+    }
+}
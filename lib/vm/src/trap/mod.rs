@@ -13,5 +13,8 @@ pub use traphandlers::{
     catch_traps, on_host_stack, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
     TrapHandler, TrapHandlerFn,
 };
-pub use traphandlers::{init_traps, resume_panic};
+pub use traphandlers::{
+    init_traps, install_trap_handlers, resume_panic, trap_handlers_installed,
+    trap_handlers_overridden,
+};
 pub use wasmer_types::TrapCode;
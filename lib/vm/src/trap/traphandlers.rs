@@ -109,6 +109,23 @@ cfg_if::cfg_if! {
         static mut PREV_SIGILL: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
         static mut PREV_SIGFPE: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
 
+        /// Returns whether the handler of one of the signals turned into
+        /// traps isn't ours anymore.
+        unsafe fn platform_handlers_overridden() -> bool {
+            let mut signals = vec![libc::SIGSEGV, libc::SIGILL];
+            if cfg!(target_arch = "x86") || cfg!(target_arch = "x86_64") {
+                signals.push(libc::SIGFPE);
+            }
+            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
+                signals.push(libc::SIGBUS);
+            }
+            signals.into_iter().any(|signal| {
+                let mut current: libc::sigaction = mem::zeroed();
+                libc::sigaction(signal, ptr::null(), &mut current) == 0
+                    && current.sa_sigaction != trap_handler as usize
+            })
+        }
+
         unsafe fn platform_init() {
             let register = |slot: &mut MaybeUninit<libc::sigaction>, signal: i32| {
                 let mut handler: libc::sigaction = mem::zeroed();
@@ -426,6 +443,12 @@ cfg_if::cfg_if! {
         use winapi::um::minwinbase::*;
         use winapi::vc::excpt::*;
 
+        /// Vectored exception handlers can't be queried, so they are never
+        /// reported as overridden.
+        unsafe fn platform_handlers_overridden() -> bool {
+            false
+        }
+
         unsafe fn platform_init() {
             // our trap handler needs to go first, so that we can recover from
             // wasm faults and continue execution, so pass `1` as a true value
@@ -546,10 +569,34 @@ cfg_if::cfg_if! {
 /// WebAssembly. Currently in wasmer's integration this function is called on
 /// creation of a `Store`.
 pub fn init_traps() {
-    static INIT: Once = Once::new();
-    INIT.call_once(|| unsafe {
+    install_trap_handlers();
+}
+
+static INIT_TRAPS: Once = Once::new();
+
+/// Same as [`init_traps`], but returns whether this call is the one that
+/// installed the process-wide trap handlers.
+pub fn install_trap_handlers() -> bool {
+    let mut installed = false;
+    INIT_TRAPS.call_once(|| unsafe {
         platform_init();
+        installed = true;
     });
+    installed
+}
+
+/// Returns whether the process-wide trap handlers have been installed.
+pub fn trap_handlers_installed() -> bool {
+    INIT_TRAPS.is_completed()
+}
+
+/// Returns whether the process-wide trap handlers were replaced by other
+/// code after being installed, in which case traps may not be caught.
+///
+/// This only detects it on Unix, where the current signal handlers can be
+/// queried.
+pub fn trap_handlers_overridden() -> bool {
+    trap_handlers_installed() && unsafe { platform_handlers_overridden() }
 }
 
 /// Raises a user-defined trap immediately.
@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+#
+# Checks by hand that gdb names the functions compiled by wasmer when the
+# `gdb-jit` feature of `wasmer-compiler` is enabled.
+#
+# The guest traps in `$answer`, so the backtrace printed by gdb should start
+# with `answer::answer` rather than a raw address.
+
+set -euo pipefail
+
+cargo build --manifest-path lib/cli/Cargo.toml --features cranelift,wasmer-compiler/gdb-jit
+
+wat=$(mktemp --suffix .wat)
+trap 'rm -f "$wat"' EXIT
+cat > "$wat" <<'WAT'
+(module $answer
+  (func $answer (export "answer") (result i32)
+    unreachable))
+WAT
+
+gdb -batch -ex run -ex bt --args target/debug/wasmer run "$wat" --invoke answer
@@ -0,0 +1,536 @@
+//! Tests for the introspection APIs exposed by compiled `Artifact`s.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_compiler::ArtifactCreate;
+
+#[compiler_test(artifact)]
+fn used_proposals_reports_unused_simd(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.simd(true);
+    config.set_features(features);
+    let store = config.store();
+
+    let scalar = wat2wasm(
+        br#"(module (func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add))"#,
+    )?;
+    let artifact = store.engine().compile(&scalar, store.tunables())?;
+    assert!(artifact.features().simd);
+    assert!(!artifact.used_proposals().simd);
+
+    let vector = wat2wasm(
+        br#"(module (func (export "splat") (param i32) (result v128)
+                local.get 0
+                i32x4.splat))"#,
+    )?;
+    let artifact = store.engine().compile(&vector, store.tunables())?;
+    assert!(artifact.used_proposals().simd);
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn export_index_by_name(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{FunctionIndex, MemoryIndex, TableIndex};
+
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (import "host" "f" (func))
+                (func (export "first"))
+                (func (export "second"))
+                (memory (export "memory") 1)
+                (table (export "table") 1 funcref))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+
+    assert_eq!(
+        artifact.export_index("first"),
+        Some(ExportIndex::Function(FunctionIndex::new(1)))
+    );
+    assert_eq!(
+        artifact.export_index("second"),
+        Some(ExportIndex::Function(FunctionIndex::new(2)))
+    );
+    assert_eq!(
+        artifact.export_index("memory"),
+        Some(ExportIndex::Memory(MemoryIndex::new(0)))
+    );
+    assert_eq!(
+        artifact.export_index("table"),
+        Some(ExportIndex::Table(TableIndex::new(0)))
+    );
+    assert_eq!(artifact.export_index("missing"), None);
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn shared_code_refcount_of_deduplicated_functions(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    let wasm = wat2wasm(
+        br#"(module
+                (func (result i32) i32.const 7)
+                (func (result i32) i32.const 7)
+                (func (result i32) i32.const 8))"#,
+    )?;
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(true);
+    let store = Store::new(engine);
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+
+    assert_eq!(artifact.shared_code_refcount(LocalFunctionIndex::new(0)), 2);
+    assert_eq!(artifact.shared_code_refcount(LocalFunctionIndex::new(1)), 2);
+    assert_eq!(artifact.shared_code_refcount(LocalFunctionIndex::new(2)), 1);
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn direct_calls_match_generic_calls(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{LocalFunctionIndex, TrapCode};
+
+    let mut store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "double") (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul)
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add)
+                (func (export "negate") (param i64) (result i64)
+                    i64.const 0
+                    local.get 0
+                    i64.sub)
+                (func (export "half") (param f64) (result f64)
+                    local.get 0
+                    f64.const 0.5
+                    f64.mul))"#,
+    )?;
+    let module = Module::new(&store, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let double: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&mut store, "double")?;
+    let add: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "add")?;
+    let negate: TypedFunction<i64, i64> =
+        instance.exports.get_typed_function(&mut store, "negate")?;
+    let half: TypedFunction<f64, f64> = instance.exports.get_typed_function(&mut store, "half")?;
+
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    let mut objects = vm::StoreObjects::default();
+    let mut handle = unsafe { artifact.instantiate(store.tunables(), &[], &mut objects)? };
+    unsafe { artifact.finish_instantiation(None, &mut handle)? };
+
+    for arg in [0, 1, -21, i32::MAX] {
+        let direct =
+            unsafe { artifact.call_i32_i32(None, &handle, LocalFunctionIndex::new(0), arg) };
+        assert_eq!(direct.ok(), Some(double.call(&mut store, arg)?));
+        let direct =
+            unsafe { artifact.call_i32_i32_i32(None, &handle, LocalFunctionIndex::new(1), arg, 5) };
+        assert_eq!(direct.ok(), Some(add.call(&mut store, arg, 5)?));
+        let direct =
+            unsafe { artifact.call_i64_i64(None, &handle, LocalFunctionIndex::new(2), arg as i64) };
+        assert_eq!(direct.ok(), Some(negate.call(&mut store, arg as i64)?));
+        let direct =
+            unsafe { artifact.call_f64_f64(None, &handle, LocalFunctionIndex::new(3), arg as f64) };
+        assert_eq!(direct.ok(), Some(half.call(&mut store, arg as f64)?));
+    }
+
+    // Functions are checked against the signature of the entry point.
+    let mismatch = unsafe { artifact.call_i32_i32(None, &handle, LocalFunctionIndex::new(1), 1) };
+    let error = RuntimeError::from_trap(mismatch.unwrap_err());
+    assert_eq!(error.to_trap(), Some(TrapCode::BadSignature));
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn dylink_info_of_side_module(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    // The section of a side module with 16 bytes of data aligned to 4
+    // bytes, 3 table elements, and depending on `libfoo.wasm`.
+    let wasm = wat2wasm(
+        br#"(module
+                (@custom "dylink.0" (before first)
+                    "\01\04\10\02\03\00"
+                    "\02\0d\01\0blibfoo.wasm")
+                (import "env" "memory" (memory 1))
+                (import "env" "__memory_base" (global i32))
+                (func (export "side")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+
+    assert_eq!(
+        artifact.dylink_info(),
+        Some(wasmer_compiler::DylinkInfo {
+            memory_size: 16,
+            memory_alignment: 2,
+            table_size: 3,
+            table_alignment: 0,
+            needed: vec!["libfoo.wasm".to_string()],
+        })
+    );
+
+    let wasm = wat2wasm(br#"(module (func (export "main")))"#)?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.dylink_info(), None);
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn interface_signatures_of_imports_and_exports(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (import "host" "log" (func (param i32)))
+                (import "host" "print" (func (param i32)))
+                (import "host" "now" (func (result i64)))
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add)
+                (func (export "notify") (param i32))
+                (func (param f64) (result f64)
+                    local.get 0))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+
+    assert_eq!(
+        artifact.interface_signatures(),
+        vec![
+            FunctionType::new(vec![Type::I32], vec![]),
+            FunctionType::new(vec![], vec![Type::I64]),
+            FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]),
+        ]
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn function_name_from_name_section(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::FunctionIndex;
+
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (func $named)
+                (func))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+
+    assert_eq!(
+        artifact.function_name(FunctionIndex::new(0)),
+        Some("named".to_string())
+    );
+    assert_eq!(artifact.function_name(FunctionIndex::new(1)), None);
+    Ok(())
+}
+
+#[cfg(all(feature = "cranelift", target_arch = "x86_64"))]
+#[test]
+fn required_libcalls_from_relocations() -> Result<()> {
+    use wasmer_compiler::EngineBuilder;
+    use wasmer_types::LibCall;
+
+    // Without SSE4.1, Cranelift lowers `f32.ceil` to a libcall.
+    let target = Target::new(Triple::host(), CpuFeature::set());
+    let engine = EngineBuilder::new(wasmer_compiler_cranelift::Cranelift::new())
+        .set_target(Some(target))
+        .engine();
+    let store = Store::new(engine);
+
+    let wasm = wat2wasm(
+        br#"(module
+                (memory 1)
+                (func (export "grow") (param i32) (result i32)
+                    local.get 0
+                    memory.grow)
+                (func (export "ceil") (param f32) (result f32)
+                    local.get 0
+                    f32.ceil))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.required_libcalls(), vec![LibCall::CeilF32]);
+
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert!(artifact.required_libcalls().is_empty());
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn function_code_size_per_function(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    let engine = config.engine(config.compiler_config(false));
+    let wasm = wat2wasm(
+        br#"(module
+                (memory 1)
+                (func (export "nothing"))
+                (func (export "checksum") (param i32 i32) (result i32)
+                    (local i32)
+                    (block
+                        (loop
+                            local.get 1
+                            i32.eqz
+                            br_if 1
+                            local.get 2
+                            i32.const 31
+                            i32.mul
+                            local.get 0
+                            i32.load8_u
+                            i32.add
+                            local.set 2
+                            local.get 0
+                            i32.const 1
+                            i32.add
+                            local.set 0
+                            local.get 1
+                            i32.const 1
+                            i32.sub
+                            local.set 1
+                            br 0))
+                    local.get 2))"#,
+    )?;
+
+    let before = engine.code_memory_stats();
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let after = engine.code_memory_stats();
+
+    let nothing = artifact.function_code_size(LocalFunctionIndex::new(0));
+    let checksum = artifact.function_code_size(LocalFunctionIndex::new(1));
+    assert!(nothing > 0);
+    assert!(nothing < checksum);
+    assert_eq!(
+        nothing + checksum,
+        after.function_code_bytes - before.function_code_bytes
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn tags_of_exception_handling_module(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (tag $error (param i32))
+                (tag $pair (param f64 i64))
+                (func (export "run")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(
+        artifact.tags(),
+        vec![
+            TagType::new(vec![Type::I32]),
+            TagType::new(vec![Type::F64, Type::I64]),
+        ]
+    );
+
+    let wasm = wat2wasm(br#"(module (func (export "run")))"#)?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert!(artifact.tags().is_empty());
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn source_map_url_survives_serialization(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (@custom "sourceMappingURL" "\0dmain.wasm.map")
+                (func (export "main")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.source_map_url().as_deref(), Some("main.wasm.map"));
+
+    let wasm = wat2wasm(br#"(module (func (export "main")))"#)?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.source_map_url(), None);
+
+    artifact.set_source_map_url("https://example.com/main.wasm.map");
+    let serialized = artifact.serialize()?;
+    let deserialized = unsafe { store.engine().deserialize(&serialized)? };
+    assert_eq!(
+        deserialized.source_map_url().as_deref(),
+        Some("https://example.com/main.wasm.map")
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn custom_sections_in_declaration_order(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (@custom "vendor" "first")
+                (@custom "other" "middle")
+                (@custom "vendor" "second")
+                (func (export "main")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(
+        artifact.custom_sections(),
+        vec![
+            ("vendor".to_string(), &b"first"[..]),
+            ("other".to_string(), &b"middle"[..]),
+            ("vendor".to_string(), &b"second"[..]),
+        ]
+    );
+
+    // Looking sections up by name also yields every one of them.
+    let module = Module::new(&store, &wasm)?;
+    assert_eq!(
+        module.custom_sections("vendor").collect::<Vec<_>>(),
+        vec![Box::from(&b"first"[..]), Box::from(&b"second"[..])]
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn functions_are_laid_out_in_index_order(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "a") (result i32) i32.const 1)
+                (func (export "b") (param i64) (result i64)
+                    local.get 0
+                    i64.const 3
+                    i64.mul)
+                (func (export "c") (param f32) (result f32)
+                    local.get 0
+                    f32.neg)
+                (func (export "d")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    let order = artifact.function_layout_order();
+    assert_eq!(
+        order,
+        (0..4).map(LocalFunctionIndex::new).collect::<Vec<_>>()
+    );
+
+    let addresses = order
+        .iter()
+        .map(|index| artifact.finished_functions()[*index].0 as usize)
+        .collect::<Vec<_>>();
+    assert!(addresses.windows(2).all(|pair| pair[0] < pair[1]));
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn exported_globals_report_mutability(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (global (export "counter") (mut i32) (i32.const 0))
+                (global (export "pi") f64 (f64.const 3.14))
+                (func (export "main")))"#,
+    )?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(
+        artifact.exported_globals(),
+        vec![
+            (
+                "counter".to_string(),
+                GlobalType::new(Type::I32, Mutability::Var)
+            ),
+            (
+                "pi".to_string(),
+                GlobalType::new(Type::F64, Mutability::Const)
+            ),
+        ]
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn determinism_report_of_float_and_threads_module(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.threads(true);
+    config.set_features(features);
+    let store = config.store();
+
+    // The float only appears as an intermediate value.
+    let nondeterministic = wat2wasm(
+        br#"(module
+                (memory 1 1 shared)
+                (func (export "round") (param i32) (result i32)
+                    local.get 0
+                    f32.convert_i32_s
+                    i32.trunc_f32_s))"#,
+    )?;
+    let artifact = store
+        .engine()
+        .compile(&nondeterministic, store.tunables())?;
+    assert_eq!(
+        artifact.determinism_report(),
+        DeterminismReport {
+            floats: true,
+            threads: true,
+            simd: false,
+        }
+    );
+    assert!(!artifact.determinism_report().is_deterministic());
+
+    let integer = wat2wasm(
+        br#"(module
+                (memory 1)
+                (func (export "add") (param i32 i32) (result i32)
+                    (local i64)
+                    local.get 0
+                    local.get 1
+                    i32.add))"#,
+    )?;
+    let artifact = store.engine().compile(&integer, store.tunables())?;
+    assert_eq!(artifact.determinism_report(), DeterminismReport::default());
+    assert!(artifact.determinism_report().is_deterministic());
+    Ok(())
+}
+
+#[compiler_test(artifact)]
+fn hash_is_stable_across_engines(mut config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(br#"(module (func (export "run") (result i32) i32.const 7))"#)?;
+    let compile = |config: &crate::Config| -> Result<ArtifactHash> {
+        let engine = config.engine(config.compiler_config(false));
+        let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+        Ok(artifact.hash())
+    };
+
+    let hash = compile(&config)?;
+    assert_eq!(compile(&config)?, hash);
+    assert_eq!(hash.to_string().len(), 64);
+
+    // The hash is kept in serialized artifacts.
+    let engine = config.engine(config.compiler_config(false));
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let deserialized = unsafe { Artifact::deserialize(&engine, &artifact.serialize()?)? };
+    assert_eq!(deserialized.hash(), hash);
+
+    let other_wasm = wat2wasm(br#"(module (func (export "run") (result i32) i32.const 8))"#)?;
+    let other_artifact = engine.compile(&other_wasm, &BaseTunables::for_target(engine.target()))?;
+    assert_ne!(other_artifact.hash(), hash);
+
+    let mut features = Features::default();
+    features.multi_memory(!features.multi_memory);
+    config.set_features(features);
+    assert_ne!(compile(&config)?, hash);
+    Ok(())
+}
@@ -0,0 +1,2318 @@
+//! Tests for the introspection and configuration APIs exposed by the
+//! `Engine`.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_compiler::ArtifactCreate;
+
+#[compiler_test(engine)]
+fn code_memory_stats_per_category(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"
+        (module
+            (import "host" "callback" (func $callback (param i32) (result i32)))
+            (func (export "run") (param i32) (result i32)
+                local.get 0
+                call $callback)
+        )
+    "#;
+
+    let before = store.engine().code_memory_stats();
+    let _module = Module::new(&store, wat)?;
+    let after = store.engine().code_memory_stats();
+
+    assert!(after.function_code_bytes > before.function_code_bytes);
+    assert!(after.call_trampoline_bytes > before.call_trampoline_bytes);
+    // The imported function gets a dynamic function trampoline.
+    assert!(after.dynamic_trampoline_bytes > before.dynamic_trampoline_bytes);
+    // The libcall trampolines are always emitted as a custom section.
+    assert!(after.custom_section_bytes > before.custom_section_bytes);
+    Ok(())
+}
+
+#[cfg(all(feature = "cranelift", target_arch = "x86_64"))]
+#[test]
+fn relocation_hook_redirects_libcall() -> Result<()> {
+    use wasmer_compiler::EngineBuilder;
+    use wasmer_types::{LibCall, Relocation, RelocationTarget};
+
+    extern "C" fn ceil_stub(_value: f32) -> f32 {
+        42.0
+    }
+
+    // Without SSE4.1, Cranelift lowers `f32.ceil` to a libcall.
+    let target = Target::new(Triple::host(), CpuFeature::set());
+    let engine = EngineBuilder::new(wasmer_compiler_cranelift::Cranelift::new())
+        .set_target(Some(target))
+        .engine();
+    // The stub has the signature of the libcall and outlives the code
+    // calling it.
+    unsafe {
+        engine.set_relocation_hook(Box::new(
+            |relocation: &Relocation, _body: usize, target: &mut usize| {
+                if relocation.reloc_target == RelocationTarget::LibCall(LibCall::CeilF32) {
+                    *target = ceil_stub as usize;
+                }
+            },
+        ));
+    }
+
+    let mut store = Store::new(engine);
+    let wat = r#"
+        (module
+            (func (export "ceil") (param f32) (result f32)
+                local.get 0
+                f32.ceil)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let ceil = instance.exports.get_function("ceil")?;
+    let result = ceil.call(&mut store, &[Value::F32(1.5)])?;
+    assert_eq!(result.to_vec(), vec![Value::F32(42.0)]);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compile_default_uses_default_tunables(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let engine = store.engine();
+    let wasm = wat2wasm(
+        br#"(module
+                (memory (export "memory") 1)
+                (table (export "table") 1 funcref))"#,
+    )?;
+
+    assert!(matches!(
+        engine.compile_default(&wasm),
+        Err(CompileError::NoDefaultTunables)
+    ));
+
+    let tunables = BaseTunables::for_target(engine.target());
+    engine.set_default_tunables(Arc::new(tunables.clone()));
+    let default_artifact = engine.compile_default(&wasm)?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+
+    assert_eq!(default_artifact.memory_styles(), artifact.memory_styles());
+    assert_eq!(default_artifact.table_styles(), artifact.table_styles());
+    assert_eq!(
+        default_artifact.create_module_info().exports,
+        artifact.create_module_info().exports
+    );
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn warm_signatures_from_artifact(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (import "host" "log" (func (param i32)))
+                (func (export "add") (param i64 i64) (result i64)
+                    local.get 0
+                    local.get 1
+                    i64.add))"#,
+    )?;
+    let serialized = store
+        .engine()
+        .compile(&wasm, store.tunables())?
+        .serialize()?;
+
+    let headless_store = config.headless_store();
+    let engine = headless_store.engine();
+    let artifact = unsafe { engine.deserialize(&serialized)? };
+    let signatures = artifact
+        .create_module_info()
+        .signatures
+        .values()
+        .map(|func_type| engine.register_signature(func_type))
+        .collect::<Vec<_>>();
+    assert!(!signatures.is_empty());
+    assert!(signatures.iter().all(|sig| !engine.has_trampoline(*sig)));
+
+    // Warming only looks the signatures of the artifact up.
+    let signature_count = engine.signature_count();
+    engine.warm_signatures_from(&artifact);
+    assert!(signatures.iter().all(|sig| engine.has_trampoline(*sig)));
+    assert_eq!(engine.signature_count(), signature_count);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn trampoline_lookups_see_concurrently_added_trampolines(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+              (func (export "add") (param i64 i64) (result i64)
+                local.get 0
+                local.get 1
+                i64.add))"#,
+    )?;
+    let serialized = store
+        .engine()
+        .compile(&wasm, store.tunables())?
+        .serialize()?;
+
+    let headless_store = config.headless_store();
+    let engine = headless_store.engine().clone();
+    let artifact = unsafe { engine.deserialize(&serialized)? };
+    let signatures = artifact
+        .create_module_info()
+        .signatures
+        .values()
+        .map(|func_type| engine.register_signature(func_type))
+        .collect::<Vec<_>>();
+    let snapshot = engine.trampoline_table();
+    assert!(signatures.iter().all(|sig| !snapshot.contains_key(sig)));
+
+    let reader = {
+        let engine = engine.clone();
+        let signatures = signatures.clone();
+        std::thread::spawn(move || loop {
+            if signatures
+                .iter()
+                .all(|sig| engine.function_call_trampoline(*sig).is_some())
+            {
+                break;
+            }
+            std::thread::yield_now();
+        })
+    };
+    let writer = std::thread::spawn(move || engine.warm_signatures_from(&artifact));
+    writer.join().unwrap();
+    reader.join().unwrap();
+
+    // Snapshots taken before the trampolines were added aren't changed.
+    assert!(signatures.iter().all(|sig| !snapshot.contains_key(sig)));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn max_locals_per_function_is_enforced(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::FunctionIndex;
+
+    let wasm = wat2wasm(
+        br#"(module
+                (func (local i32 i32 i64))
+                (func (local i32 i64 f32 f64 i32)))"#,
+    )?;
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_max_locals_per_function(Some(3));
+    let tunables = BaseTunables::for_target(engine.target());
+    match engine.compile(&wasm, &tunables) {
+        Err(CompileError::TooManyLocals { function, count }) => {
+            assert_eq!(function, FunctionIndex::new(1));
+            assert_eq!(count, 5);
+        }
+        Err(e) => panic!("unexpected compile error: {}", e),
+        Ok(_) => panic!("compilation should fail"),
+    }
+
+    let engine = engine.with_max_locals_per_function(Some(5));
+    engine.compile(&wasm, &tunables)?;
+    let engine = engine.with_max_locals_per_function(None);
+    engine.compile(&wasm, &tunables)?;
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn function_dedup_shares_identical_bodies(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "first") (param i32) (result i32)
+                    local.get 0
+                    i32.const 7
+                    i32.mul)
+                (func (export "second") (param i32) (result i32)
+                    local.get 0
+                    i32.const 7
+                    i32.mul)
+                (func (export "other") (param i32) (result i32)
+                    local.get 0
+                    i32.const 3
+                    i32.add))"#,
+    )?;
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(false);
+    let tunables = BaseTunables::for_target(engine.target());
+    engine.compile(&wasm, &tunables)?;
+    let full_size = engine.code_memory_stats().function_code_bytes;
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(true);
+    let mut store = Store::new(engine);
+    let module = Module::new(&store, &wasm)?;
+    let deduped_size = store.engine().code_memory_stats().function_code_bytes;
+    assert!(deduped_size < full_size);
+
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    for (name, expected) in [("first", 42), ("second", 42), ("other", 9)] {
+        let function = instance.exports.get_function(name)?;
+        let result = function.call(&mut store, &[Value::I32(6)])?;
+        assert_eq!(result.to_vec(), vec![Value::I32(expected)]);
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn module_dedup_shares_identical_modules(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "double") (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul))"#,
+    )?;
+    fn first_function(artifact: &Artifact) -> usize {
+        artifact.finished_functions().values().next().unwrap().0 as usize
+    }
+
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let first = engine.compile(&wasm, &tunables)?;
+    let second = engine.compile(&wasm, &tunables)?;
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_ne!(first_function(&first), first_function(&second));
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_module_dedup(true);
+    let first = engine.compile(&wasm, &tunables)?;
+    let second = engine.compile(&wasm, &tunables)?;
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(first_function(&first), first_function(&second));
+    let other = engine.compile(&wat2wasm(b"(module (func (export \"nop\")))")?, &tunables)?;
+    assert!(!Arc::ptr_eq(&first, &other));
+
+    // The engine doesn't keep dropped modules alive.
+    let weak = Arc::downgrade(&first);
+    drop((first, second));
+    assert!(weak.upgrade().is_none());
+    engine.compile(&wasm, &tunables)?;
+    Ok(())
+}
+
+#[cfg(all(
+    feature = "cranelift",
+    target_os = "linux",
+    target_pointer_width = "64"
+))]
+#[test]
+fn code_base_address_places_code_memory() -> Result<()> {
+    use wasmer_compiler::{CodeMemoryError, EngineBuilder};
+
+    // An address far away from where the kernel places mappings by default.
+    let base_address = 0x3c00_0000_0000;
+    let engine = EngineBuilder::new(wasmer_compiler_cranelift::Cranelift::new()).engine();
+    assert_eq!(
+        engine.request_code_base_address(base_address + 1),
+        Err(CodeMemoryError::UnalignedAddress(base_address + 1))
+    );
+    engine.request_code_base_address(base_address)?;
+
+    let store = Store::new(engine);
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "one") (result i32) i32.const 1)
+                (func (export "two") (result i32) i32.const 2))"#,
+    )?;
+    let first = store.engine().compile(&wasm, store.tunables())?;
+    let second = store.engine().compile(&wasm, store.tunables())?;
+    let addresses = first
+        .finished_functions()
+        .values()
+        .chain(second.finished_functions().values())
+        .map(|ptr| ptr.0 as usize)
+        .collect::<Vec<_>>();
+    assert!(addresses.iter().all(|address| *address >= base_address));
+    assert!(addresses
+        .iter()
+        .all(|address| *address < base_address + (1 << 30)));
+
+    // The address is now taken by the code of the modules above.
+    let other = EngineBuilder::new(wasmer_compiler_cranelift::Cranelift::new()).engine();
+    assert!(matches!(
+        other.request_code_base_address(base_address),
+        Err(CodeMemoryError::UnavailableAddress(..))
+    ));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn import_trampoline_table_from_shared_region(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::{CodeMemoryError, SharedTrampolineTable};
+
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param i64 i64) (result i64)
+                    local.get 0
+                    local.get 1
+                    i64.add)
+                (func (export "neg") (param f32) (result f32)
+                    local.get 0
+                    f32.neg))"#,
+    )?;
+    // The code memory of another engine stands in for the region shared
+    // with another process; it outlives the engine importing from it.
+    let producer = config.engine(config.compiler_config(false));
+    let artifact = producer.compile(&wasm, &BaseTunables::for_target(producer.target()))?;
+    let module_info = artifact.create_module_info();
+    let addresses = artifact
+        .finished_function_call_trampolines()
+        .values()
+        .map(|trampoline| *trampoline as usize)
+        .collect::<Vec<_>>();
+    let base = *addresses.iter().min().unwrap();
+    let len = *addresses.iter().max().unwrap() - base + 1;
+    let mut shared = unsafe { SharedTrampolineTable::new(base as *const u8, len) };
+    for (signature, address) in module_info.signatures.values().zip(&addresses) {
+        shared.add(signature.clone(), address - base, 1);
+    }
+
+    let engine = config.engine(config.compiler_config(false));
+    let imported = engine.import_trampoline_table(&shared)?;
+    let mut indices = vec![];
+    for (signature, address) in module_info.signatures.values().zip(&addresses) {
+        let index = engine.register_signature(signature);
+        let trampoline = engine.function_call_trampoline(index).unwrap();
+        assert_eq!(trampoline as usize, *address);
+        indices.push(index);
+    }
+    // Nothing uses the signatures registered above.
+    unsafe { engine.unregister_signatures(&indices) };
+    assert_eq!(engine.signature_count(), indices.len());
+
+    // Dropping the imported table releases its signatures, and so forgets
+    // their trampolines.
+    drop(imported);
+    assert_eq!(engine.signature_count(), 0);
+    assert!(indices
+        .iter()
+        .all(|index| engine.function_call_trampoline(*index).is_none()));
+
+    let mut outside = unsafe { SharedTrampolineTable::new(base as *const u8, len) };
+    outside.add(FunctionType::new(vec![], vec![]), len, 1);
+    assert!(matches!(
+        engine.import_trampoline_table(&outside),
+        Err(CodeMemoryError::OutsideSharedRegion(offset)) if offset == len
+    ));
+    // A trampoline starting in the region must end in it too.
+    let mut overflowing = unsafe { SharedTrampolineTable::new(base as *const u8, len) };
+    overflowing.add(FunctionType::new(vec![], vec![]), len - 1, 2);
+    assert!(matches!(
+        engine.import_trampoline_table(&overflowing),
+        Err(CodeMemoryError::OutsideSharedRegion(offset)) if offset == len - 1
+    ));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn function_call_uses_the_trampolines_of_the_engine(config: crate::Config) -> Result<()> {
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use wasmer_compiler::SharedTrampolineTable;
+
+    type Trampoline = unsafe extern "C" fn(*mut c_void, *const c_void, *mut c_void);
+    static ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    // Counts its calls, and forwards them to a trampoline of the compiler.
+    unsafe extern "C" fn counting(vmctx: *mut c_void, body: *const c_void, values: *mut c_void) {
+        CALLS.fetch_add(1, SeqCst);
+        let original = std::mem::transmute::<usize, Trampoline>(ORIGINAL.load(SeqCst));
+        original(vmctx, body, values)
+    }
+
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param i64 i64) (result i64)
+                    local.get 0
+                    local.get 1
+                    i64.add))"#,
+    )?;
+    let mut store = config.store();
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    let signature = artifact
+        .create_module_info()
+        .signatures
+        .values()
+        .next()
+        .unwrap()
+        .clone();
+    let original = *artifact
+        .finished_function_call_trampolines()
+        .values()
+        .next()
+        .unwrap();
+    ORIGINAL.store(original as usize, SeqCst);
+
+    let module = Module::new(&store, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let add = instance.exports.get_function("add")?;
+    let args = [Value::I64(2), Value::I64(40)];
+    assert_eq!(add.call(&mut store, &args)?.to_vec(), vec![Value::I64(42)]);
+    assert_eq!(CALLS.load(SeqCst), 0);
+
+    let counting = counting as Trampoline as *const u8;
+    let mut shared = unsafe { SharedTrampolineTable::new(counting, 1) };
+    shared.add(signature, 0, 1);
+    let _imported = store.engine().import_trampoline_table(&shared)?;
+    assert_eq!(add.call(&mut store, &args)?.to_vec(), vec![Value::I64(42)]);
+    assert_eq!(CALLS.load(SeqCst), 1);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn data_count_must_match_data_segments(config: crate::Config) -> Result<()> {
+    // A module with one memory, a data count section declaring
+    // `data_count` segments, and a data section with a single segment.
+    let module = |data_count: u8| -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+        wasm.extend_from_slice(&[0x0c, 0x01, data_count]);
+        wasm.extend_from_slice(&[0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, b'a']);
+        wasm
+    };
+
+    let store = config.store();
+    let wasm = module(1);
+    store.engine().validate(&wasm)?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.data_count(), Some(1));
+
+    let wasm = module(2);
+    assert!(matches!(
+        store.engine().validate(&wasm),
+        Err(CompileError::Validate(_))
+    ));
+    assert!(matches!(
+        store.engine().compile(&wasm, store.tunables()),
+        Err(CompileError::Wasm(WasmError::InvalidWebAssembly { .. }))
+    ));
+
+    let wasm = wat2wasm(br#"(module (memory 1) (data (i32.const 0) "a"))"#)?;
+    let artifact = store.engine().compile(&wasm, store.tunables())?;
+    assert_eq!(artifact.data_count(), None);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn bounds_check_elimination_can_be_disabled(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    let wasm = wat2wasm(
+        br#"(module
+                (memory 1)
+                (func (export "sum") (param i32) (result i32)
+                    local.get 0
+                    i32.load
+                    local.get 0
+                    i32.load offset=4
+                    i32.add
+                    local.get 0
+                    i32.load offset=8
+                    i32.add))"#,
+    )?;
+    let function = LocalFunctionIndex::new(0);
+
+    let store = config.store();
+    let optimized = store.engine().compile(&wasm, store.tunables())?;
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_bounds_check_elimination(false);
+    let store = Store::new(engine);
+    let audited = store.engine().compile(&wasm, store.tunables())?;
+
+    assert!(audited.trap_sites(function).len() > optimized.trap_sites(function).len());
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn drain_waits_for_in_flight_executions(config: crate::Config) -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+
+    struct Env {
+        started: Mutex<mpsc::Sender<()>>,
+        finished: Arc<AtomicBool>,
+    }
+
+    let wasm = wat2wasm(
+        br#"(module
+                (import "host" "started" (func $started))
+                (import "host" "finished" (func $finished))
+                (func (export "run") (param i32)
+                    call $started
+                    (block
+                        (loop
+                            local.get 0
+                            i32.eqz
+                            br_if 1
+                            local.get 0
+                            i32.const 1
+                            i32.sub
+                            local.set 0
+                            br 0))
+                    call $finished))"#,
+    )?;
+
+    let mut store = config.store();
+    let engine = store.engine().cloned();
+    let module = Module::new(&store, &wasm)?;
+    let (started_sender, started) = mpsc::channel();
+    let finished = Arc::new(AtomicBool::new(false));
+    let env = FunctionEnv::new(
+        &mut store,
+        Env {
+            started: Mutex::new(started_sender),
+            finished: finished.clone(),
+        },
+    );
+    let imports = imports! {
+        "host" => {
+            "started" => Function::new_typed_with_env(&mut store, &env, |env: FunctionEnvMut<Env>| {
+                env.data().started.lock().unwrap().send(()).unwrap();
+            }),
+            "finished" => Function::new_typed_with_env(&mut store, &env, |env: FunctionEnvMut<Env>| {
+                env.data().finished.store(true, SeqCst);
+            }),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let run = instance.exports.get_function("run")?.clone();
+
+    let guest =
+        std::thread::spawn(move || run.call(&mut store, &[Value::I32(100_000_000)]).map(|_| ()));
+    started.recv()?;
+
+    engine.begin_drain();
+    assert!(engine
+        .compile(&wasm, &BaseTunables::for_target(engine.target()))
+        .is_err());
+    engine.wait_idle();
+    assert!(finished.load(SeqCst));
+
+    guest.join().unwrap()?;
+    Ok(())
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[compiler_test(engine)]
+fn float_rounding_mode_applies_to_guests(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param f32 f32) (result f32)
+                    local.get 0
+                    local.get 1
+                    f32.add))"#,
+    )?;
+    // Three quarters of the distance between 1.0 and the next `f32`.
+    let addend = 3.0 * 2f32.powi(-25);
+    let next_after_one = f32::from_bits(1f32.to_bits() + 1);
+
+    for (mode, expected) in [
+        (RoundingMode::ToNearest, next_after_one),
+        (RoundingMode::TowardZero, 1.0),
+    ] {
+        let mut store = config.store();
+        store.engine().set_float_rounding_mode(mode);
+        let module = Module::new(&store, &wasm)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let add = instance.exports.get_function("add")?;
+        let result = add.call(&mut store, &[Value::F32(1.0), Value::F32(addend)])?;
+        assert_eq!(result.to_vec(), vec![Value::F32(expected)]);
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn unreachable_handler_sees_trapping_frame(config: crate::Config) -> Result<()> {
+    use std::sync::Mutex;
+    use wasmer_types::TrapCode;
+
+    let mut store = config.store();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let recorded = frames.clone();
+    store
+        .engine()
+        .set_unreachable_handler(Box::new(move |frame: FrameInfo| {
+            recorded.lock().unwrap().push(frame.func_index());
+        }));
+
+    let wat = r#"
+        (module
+            (func (export "run")
+                call $fail)
+            (func $fail
+                unreachable)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run = instance.exports.get_function("run")?;
+
+    let error = run.call(&mut store, &[]).unwrap_err();
+    assert_eq!(error.to_trap(), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(*frames.lock().unwrap(), vec![1]);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn table_grow_past_the_engine_limit_traps(config: crate::Config) -> Result<()> {
+    use std::sync::Mutex;
+    use wasmer::vm::TableLimitExceeded;
+
+    let mut store = config.store();
+    let grows = Arc::new(Mutex::new(Vec::new()));
+    let recorded = grows.clone();
+    store.engine().set_max_table_elements(4);
+    store
+        .engine()
+        .set_table_grow_observer(Box::new(move |size, delta| {
+            recorded.lock().unwrap().push((size, delta));
+        }));
+
+    let wat = r#"
+        (module
+            (table 1 funcref)
+            (func (export "grow") (param i32) (result i32)
+                ref.null func
+                local.get 0
+                table.grow 0)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let grow = instance.exports.get_function("grow")?;
+
+    assert_eq!(
+        grow.call(&mut store, &[Value::I32(3)])?.to_vec(),
+        vec![Value::I32(1)]
+    );
+    let error = grow.call(&mut store, &[Value::I32(1)]).unwrap_err();
+    let error = error.downcast::<TableLimitExceeded>().unwrap();
+    assert_eq!(error.requested, 5);
+    assert_eq!(error.max, 4);
+    assert_eq!(*grows.lock().unwrap(), vec![(1, 3), (4, 1)]);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn owns_artifact_tells_engines_apart(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(br#"(module (func (export "run")))"#)?;
+    let engine = config.engine(config.compiler_config(false));
+    let other_engine = config.engine(config.compiler_config(false));
+
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let foreign_artifact =
+        other_engine.compile(&wasm, &BaseTunables::for_target(other_engine.target()))?;
+
+    assert!(engine.owns_artifact(&artifact));
+    assert!(!engine.owns_artifact(&foreign_artifact));
+    assert!(other_engine.owns_artifact(&foreign_artifact));
+
+    // Clones get an id of their own, but share the artifacts.
+    let clone = engine.clone();
+    assert_ne!(clone.id(), engine.id());
+    let tunables = Arc::new(BaseTunables::for_target(engine.target()));
+    let cloned_artifact = clone.compile(&wasm, &*tunables)?;
+    assert!(engine.owns_artifact(&cloned_artifact));
+    assert!(clone.owns_artifact(&artifact));
+    assert!(!other_engine.owns_artifact(&cloned_artifact));
+    let async_artifact = block_on(engine.compile_async(wasm.to_vec(), tunables))?;
+    assert!(engine.owns_artifact(&async_artifact));
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[compiler_test(engine)]
+fn published_code_memory_is_not_writable(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (memory 1)
+                (data (i32.const 0) "data")
+                (func (export "run") (result i32) i32.const 1))"#,
+    )?;
+    let module = Module::new(&store, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    instance
+        .exports
+        .get_typed_function::<(), i32>(&mut store, "run")?
+        .call(&mut store)?;
+
+    assert_eq!(store.engine().verify_code_memory_permissions(), Ok(()));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn unwind_entries_cover_compiled_functions(config: crate::Config) -> Result<()> {
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(false);
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add)
+                (func (export "sub") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.sub))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+    let entries = engine.unwind_entries();
+
+    for entry in &entries {
+        assert!(entry.function_len > 0);
+        if let Some(offset) = entry.unwind_info_offset {
+            assert!(offset >= entry.function_len);
+        }
+    }
+    // LLVM describes the frames of its functions in the `.eh_frame` section
+    // only.
+    if config.compiler == crate::Compiler::LLVM {
+        return Ok(());
+    }
+    for body in artifact.finished_functions().values() {
+        let start = body.0 as usize;
+        let function_entries = entries
+            .iter()
+            .filter(|entry| entry.function_start == start)
+            .collect::<Vec<_>>();
+        assert_eq!(function_entries.len(), 1);
+        let end = start + function_entries[0].function_len;
+        let (index, offset) = engine.lookup_function(end - 1).unwrap();
+        assert_eq!(engine.lookup_function(start), Some((index, 0)));
+        assert_eq!(offset, end - 1 - start);
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compile_function_replaces_one_body(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{FunctionIndex, LocalFunctionIndex};
+
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "scale") (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+    let index = LocalFunctionIndex::new(0);
+    // No locals, then `local.get 0`, `i32.const 3`, `i32.mul` and `end`.
+    let body = [0x00, 0x20, 0x00, 0x41, 0x03, 0x6c, 0x0b];
+    let new = engine.compile_function(&artifact, index, &body, &tunables)?;
+    let old = artifact.finished_functions()[index];
+    assert_ne!(new.0, old.0);
+    assert_eq!(
+        engine.lookup_function(new.0 as usize),
+        Some((FunctionIndex::new(0), 0))
+    );
+
+    // Neither body uses its `VMContext`, so they can be called without an
+    // instance.
+    type Scale = unsafe extern "C" fn(*mut u8, i32) -> i32;
+    let (old, new) = unsafe {
+        (
+            std::mem::transmute::<_, Scale>(old.0),
+            std::mem::transmute::<_, Scale>(new.0),
+        )
+    };
+    assert_eq!(unsafe { old(std::ptr::null_mut(), 7) }, 14);
+    assert_eq!(unsafe { new(std::ptr::null_mut(), 7) }, 21);
+
+    let missing = LocalFunctionIndex::new(1);
+    assert!(engine
+        .compile_function(&artifact, missing, &body, &tunables)
+        .is_err());
+    Ok(())
+}
+
+#[cfg(all(feature = "cranelift", target_arch = "x86_64", target_os = "linux"))]
+#[test]
+fn host_functions_follow_the_host_calling_convention() -> Result<()> {
+    use wasmer_compiler::EngineBuilder;
+
+    let engine = EngineBuilder::new(wasmer_compiler_cranelift::Cranelift::new()).engine();
+    assert_eq!(
+        engine.host_calling_convention(),
+        Some(CallingConvention::SystemV)
+    );
+    let engine = engine.with_host_calling_convention(CallingConvention::WindowsFastcall);
+    assert_eq!(
+        engine.host_calling_convention(),
+        Some(CallingConvention::WindowsFastcall)
+    );
+
+    let mut store = Store::new(engine);
+    let wasm = wat2wasm(
+        br#"(module
+                (import "host" "sub" (func $sub (param i32 i64 f32 f64) (result f64)))
+                (func (export "run") (result f64)
+                    i32.const 100
+                    i64.const 20
+                    f32.const 3
+                    f64.const 0.5
+                    call $sub))"#,
+    )?;
+    let module = Module::new(&store, &wasm)?;
+    let sub = Function::new(
+        &mut store,
+        FunctionType::new(
+            vec![Type::I32, Type::I64, Type::F32, Type::F64],
+            vec![Type::F64],
+        ),
+        |args| {
+            let result = args[0].unwrap_i32() as f64
+                - args[1].unwrap_i64() as f64
+                - args[2].unwrap_f32() as f64
+                - args[3].unwrap_f64();
+            Ok(vec![Value::F64(result)])
+        },
+    );
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! { "host" => { "sub" => sub } },
+    )?;
+    let run = instance
+        .exports
+        .get_typed_function::<(), f64>(&mut store, "run")?;
+
+    assert_eq!(run.call(&mut store)?, 76.5);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn trap_handlers_are_registered_with_the_first_artifact(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let wasm = wat2wasm(br#"(module (func (export "run")))"#)?;
+    engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+
+    let status = engine.trap_handler_status();
+    assert!(status.registered);
+    assert!(!status.conflict);
+    // Other tests running in the same process may have installed them
+    // first, but another engine can't have installed them on top.
+    let other_engine = config.engine(config.compiler_config(false));
+    other_engine.compile(&wasm, &BaseTunables::for_target(other_engine.target()))?;
+    assert!(!other_engine.trap_handler_status().installed_by_this_engine);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn deserialize_archive_in_parallel(config: crate::Config) -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::time::Duration;
+
+    let store = config.store();
+    let engine = store.engine();
+    let artifacts = (0..10)
+        .map(|i| {
+            let wasm = wat2wasm(
+                format!(
+                    r#"(module (func (export "get") (result i32) i32.const {}))"#,
+                    i
+                )
+                .as_bytes(),
+            )?;
+            Ok(engine.compile(&wasm, store.tunables())?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let archive = engine.serialize_archive(&artifacts)?;
+
+    // Every artifact holds up its thread for a while, so that the threads
+    // overlap if they run concurrently.
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    let (hook_active, hook_max_active) = (active.clone(), max_active.clone());
+    let deserialized = unsafe {
+        engine.deserialize_archive_with_progress(&archive, 4, move |_| {
+            let now_active = hook_active.fetch_add(1, SeqCst) + 1;
+            hook_max_active.fetch_max(now_active, SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            hook_active.fetch_sub(1, SeqCst);
+        })?
+    };
+    assert!(max_active.load(SeqCst) > 1);
+    assert!(max_active.load(SeqCst) <= 4);
+
+    // The artifacts come back in order.
+    assert_eq!(deserialized.len(), 10);
+    for (artifact, original) in deserialized.iter().zip(&artifacts) {
+        assert!(engine.owns_artifact(artifact));
+        assert_eq!(artifact.serialize()?, original.serialize()?);
+    }
+
+    let mut corrupted = archive.clone();
+    corrupted.truncate(archive.len() - 1);
+    assert!(unsafe { engine.deserialize_archive(&corrupted, 4) }.is_err());
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn deserialize_archive_artifacts_report_engine_id(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(br#"(module (func (export "f")))"#)?;
+    let artifacts = vec![
+        engine.compile(&wasm, &tunables)?,
+        engine.compile(&wasm, &tunables)?,
+    ];
+    let archive = engine.serialize_archive(&artifacts)?;
+
+    let deserialized = unsafe { engine.deserialize_archive(&archive, 2)? };
+    assert_eq!(deserialized.len(), 2);
+    for artifact in &deserialized {
+        assert_eq!(artifact.engine_id(), engine.id());
+        assert_eq!(artifact.engine_id(), artifacts[0].engine_id());
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn deserialize_rejects_conflicting_compile_options(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(br#"(module (func (export "f")))"#)?;
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let artifact = engine.compile(&wasm, &tunables)?;
+    let serialized = artifact.serialize()?;
+    assert_eq!(
+        artifact.compile_options(),
+        CompileOptions {
+            bounds_check_elimination: true,
+        }
+    );
+
+    let checked = config
+        .engine(config.compiler_config(false))
+        .with_bounds_check_elimination(false);
+    assert!(matches!(
+        unsafe { checked.deserialize(&serialized) },
+        Err(DeserializeError::Incompatible(_))
+    ));
+
+    let compatible = config.engine(config.compiler_config(false));
+    let deserialized = unsafe { compatible.deserialize(&serialized)? };
+    assert_eq!(deserialized.compile_options(), artifact.compile_options());
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn code_published_callback_fires_once_per_compile(config: crate::Config) -> Result<()> {
+    use std::sync::Mutex;
+
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let published = Arc::new(Mutex::new(Vec::new()));
+    let callback_published = published.clone();
+    engine.set_code_published_callback(Box::new(move |artifact| {
+        callback_published
+            .lock()
+            .unwrap()
+            .push(artifact.serialize().unwrap());
+    }));
+
+    let first = engine.compile(&wat2wasm(br#"(module (func (export "a")))"#)?, &tunables)?;
+    let second = engine.compile(
+        &wat2wasm(br#"(module (func (export "b") (result i32) i32.const 1))"#)?,
+        &tunables,
+    )?;
+    let persisted = published.lock().unwrap().clone();
+    assert_eq!(persisted.len(), 2);
+    assert_eq!(persisted[0], first.serialize()?);
+    assert_eq!(persisted[1], second.serialize()?);
+
+    // The persisted bytes can be loaded back, and loading them doesn't
+    // invoke the callback.
+    let loaded = unsafe { engine.deserialize(&persisted[1])? };
+    assert_eq!(loaded.export_index("b"), second.export_index("b"));
+    assert_eq!(published.lock().unwrap().len(), 2);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn stack_map_at_call_with_live_reference(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    // The reference is live across the call to `f`, so the call is a
+    // safepoint with a stack slot holding it.
+    let wasm = wat2wasm(
+        br#"(module
+            (import "host" "f" (func $f))
+            (func (export "keep") (param externref) (result externref)
+                call $f
+                local.get 0))"#,
+    )?;
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_stack_maps(true);
+    let result = engine.compile(&wasm, &BaseTunables::for_target(engine.target()));
+    if config.compiler != crate::Compiler::Cranelift {
+        assert!(matches!(result, Err(CompileError::UnsupportedFeature(_))));
+        return Ok(());
+    }
+    let artifact = result?;
+
+    let keep = LocalFunctionIndex::new(0);
+    let start = artifact.finished_functions()[keep].0 as usize;
+    let end = start + artifact.function_code_size(keep);
+    let stack_maps = (start..=end)
+        .filter_map(|pc| artifact.stack_map(pc))
+        .collect::<Vec<_>>();
+    assert!(!stack_maps.is_empty());
+    assert!(stack_maps
+        .iter()
+        .any(|stack_map| !stack_map.live_slots.is_empty()));
+
+    // Without the option, no stack maps are recorded.
+    let engine = config.engine(config.compiler_config(false));
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let start = artifact.finished_functions()[keep].0 as usize;
+    let end = start + artifact.function_code_size(keep);
+    assert!((start..=end).all(|pc| artifact.stack_map(pc).is_none()));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn engines_report_whether_they_are_headless(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    assert!(!engine.is_headless());
+    assert!(config.engine_headless().is_headless());
+    assert!(Engine::headless().is_headless());
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn concurrent_signature_registration(config: crate::Config) -> Result<()> {
+    const THREADS: usize = 8;
+    const SIGNATURES: usize = 64;
+
+    let engine = config.engine(config.compiler_config(false));
+    let signature = |i: usize| FunctionType::new(vec![Type::I32; i], [Type::I64]);
+    let wasm = wat2wasm(br#"(module (func (export "f") (param i32) (result i64) i64.const 0))"#)?;
+
+    // Compile meanwhile, as registering signatures no longer waits for
+    // compilations.
+    let compiling = {
+        let engine = engine.clone();
+        std::thread::spawn(move || {
+            for _ in 0..4 {
+                engine
+                    .compile(&wasm, &BaseTunables::for_target(engine.target()))
+                    .unwrap();
+            }
+        })
+    };
+    let registering = (0..THREADS)
+        .map(|thread| {
+            let engine = engine.clone();
+            std::thread::spawn(move || {
+                (0..SIGNATURES)
+                    .map(|i| {
+                        // Every thread registers the signatures in a
+                        // different order.
+                        let i = (i + thread * 7) % SIGNATURES;
+                        let index = engine.register_signature(&signature(i));
+                        assert_eq!(engine.lookup_signature(index), Some(signature(i)));
+                        (i, index)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    compiling.join().unwrap();
+    let mut indices = vec![None; SIGNATURES];
+    for thread in registering {
+        for (i, index) in thread.join().unwrap() {
+            // All threads agree on the index of a signature.
+            assert_eq!(*indices[i].get_or_insert(index), index);
+        }
+    }
+    for (i, index) in indices.into_iter().enumerate() {
+        assert_eq!(engine.register_signature(&signature(i)), index.unwrap());
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn unregistered_signatures_are_reclaimed(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let baseline = engine.signature_count();
+
+    let indices = (0..100)
+        .map(|i| engine.register_signature(&FunctionType::new(vec![Type::F64; i], [])))
+        .collect::<Vec<_>>();
+    assert_eq!(engine.signature_count(), baseline + 100);
+
+    // Signatures registered twice need to be released twice.
+    engine.register_signature(&FunctionType::new([], []));
+    unsafe { engine.unregister_signatures(&indices) };
+    assert_eq!(engine.signature_count(), baseline + 1);
+    assert_eq!(
+        engine.lookup_signature(indices[0]),
+        Some(FunctionType::new([], []))
+    );
+    unsafe { engine.unregister_signatures(&indices[..1]) };
+    assert_eq!(engine.signature_count(), baseline);
+    assert_eq!(engine.lookup_signature(indices[0]), None);
+    assert_eq!(engine.lookup_signature(indices[99]), None);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn dropped_artifacts_release_their_signatures(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "f") (param i64 f32 i64) (result f64)
+                    f64.const 0))"#,
+    )?;
+    let baseline = engine.signature_count();
+
+    let artifact = engine.compile(&wasm, &tunables)?;
+    assert_eq!(engine.signature_count(), baseline + 1);
+    drop(artifact);
+    assert_eq!(engine.signature_count(), baseline);
+
+    // Instances keep the signatures of their artifact registered.
+    let artifact = engine.compile(&wasm, &tunables)?;
+    let mut objects = vm::StoreObjects::default();
+    let _handle = unsafe { artifact.instantiate(&tunables, &[], &mut objects)? };
+    drop(artifact);
+    assert_eq!(engine.signature_count(), baseline + 1);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn code_memory_stats_grow_with_published_code(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    assert_eq!(
+        engine.code_memory_stats(),
+        wasmer_compiler::CodeMemoryStats::default()
+    );
+
+    let first =
+        wat2wasm(br#"(module (func (export "f")) (func (export "g") (result i32) i32.const 1))"#)?;
+    let _first = engine.compile(&first, &tunables)?;
+    let after_first = engine.code_memory_stats();
+    assert!(after_first.published_bytes > 0);
+    assert!(after_first.reserved_bytes >= after_first.published_bytes);
+    assert_eq!(after_first.function_bodies, 2);
+
+    let second = wat2wasm(br#"(module (func (export "h") (param i32) (result i32) local.get 0))"#)?;
+    let _second = engine.compile(&second, &tunables)?;
+    let after_second = engine.code_memory_stats();
+    assert!(after_second.published_bytes > after_first.published_bytes);
+    assert!(after_second.reserved_bytes > after_first.reserved_bytes);
+    assert_eq!(after_second.function_bodies, 3);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compile_hints_reach_the_compiler(config: crate::Config) -> Result<()> {
+    use std::ptr::NonNull;
+    use std::sync::Mutex;
+    use wasmer::vm::{
+        MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition, VMTable,
+        VMTableDefinition,
+    };
+    use wasmer_compiler::{
+        Compiler, CompilerConfig, FunctionBodyData, ModuleMiddleware, ModuleTranslationState,
+    };
+    use wasmer_types::entity::PrimaryMap;
+    use wasmer_types::{Compilation, CompileModuleInfo, LocalFunctionIndex};
+
+    type Recorded = Arc<Mutex<Vec<CompileHints>>>;
+
+    // A compiler recording the hints it gets before compiling with the
+    // configured compiler.
+    struct RecordingConfig(Box<dyn CompilerConfig>, Recorded);
+    struct RecordingCompiler(Box<dyn Compiler>, Recorded);
+
+    impl CompilerConfig for RecordingConfig {
+        fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
+            Box::new(RecordingCompiler(self.0.compiler(), self.1))
+        }
+
+        fn push_middleware(&mut self, middleware: Arc<dyn ModuleMiddleware>) {
+            self.0.push_middleware(middleware)
+        }
+    }
+
+    impl Compiler for RecordingCompiler {
+        fn compile_module<'data, 'module>(
+            &self,
+            target: &Target,
+            module: &'module CompileModuleInfo,
+            module_translation: &ModuleTranslationState,
+            function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        ) -> Result<Compilation, CompileError> {
+            self.0
+                .compile_module(target, module, module_translation, function_body_inputs)
+        }
+
+        fn compile_module_with_hints<'data, 'module>(
+            &self,
+            target: &Target,
+            module: &'module CompileModuleInfo,
+            module_translation: &ModuleTranslationState,
+            function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+            hints: &CompileHints,
+        ) -> Result<Compilation, CompileError> {
+            self.1.lock().unwrap().push(hints.clone());
+            self.0.compile_module_with_hints(
+                target,
+                module,
+                module_translation,
+                function_body_inputs,
+                hints,
+            )
+        }
+
+        fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+            self.0.get_middlewares()
+        }
+    }
+
+    // The base tunables, asking for unoptimized code.
+    struct UnoptimizedTunables(BaseTunables);
+
+    impl Tunables for UnoptimizedTunables {
+        fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+            self.0.memory_style(memory)
+        }
+
+        fn table_style(&self, table: &TableType) -> TableStyle {
+            self.0.table_style(table)
+        }
+
+        fn create_host_memory(
+            &self,
+            ty: &MemoryType,
+            style: &MemoryStyle,
+        ) -> Result<VMMemory, MemoryError> {
+            self.0.create_host_memory(ty, style)
+        }
+
+        unsafe fn create_vm_memory(
+            &self,
+            ty: &MemoryType,
+            style: &MemoryStyle,
+            vm_definition_location: NonNull<VMMemoryDefinition>,
+        ) -> Result<VMMemory, MemoryError> {
+            self.0.create_vm_memory(ty, style, vm_definition_location)
+        }
+
+        fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+            self.0.create_host_table(ty, style)
+        }
+
+        unsafe fn create_vm_table(
+            &self,
+            ty: &TableType,
+            style: &TableStyle,
+            vm_definition_location: NonNull<VMTableDefinition>,
+        ) -> Result<VMTable, String> {
+            self.0.create_vm_table(ty, style, vm_definition_location)
+        }
+
+        fn compile_hints(&self) -> CompileHints {
+            CompileHints {
+                opt_level: Some(OptLevel::None),
+            }
+        }
+    }
+
+    let recorded = Recorded::default();
+    let engine = config.engine(Box::new(RecordingConfig(
+        config.compiler_config(false),
+        recorded.clone(),
+    )));
+    let base = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(br#"(module (func (export "f") (result i32) i32.const 1))"#)?;
+
+    engine.compile(&wasm, &base)?;
+    engine.compile(&wasm, &UnoptimizedTunables(base.clone()))?;
+    assert_eq!(
+        *recorded.lock().unwrap(),
+        vec![
+            CompileHints::default(),
+            CompileHints {
+                opt_level: Some(OptLevel::None)
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn large_module_layout_and_signatures_keep_their_order(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let mut wat = String::from("(module\n");
+    for i in 0..2000 {
+        wat.push_str(&format!(
+            "(func (export \"f{}\") (param{}) (result i64) i64.const {})\n",
+            i,
+            " i32".repeat(i % 13),
+            i
+        ));
+    }
+    wat.push(')');
+    let artifact = engine.compile(&wat2wasm(wat.as_bytes())?, &tunables)?;
+
+    // Functions are laid out in index order.
+    let addresses = artifact
+        .finished_functions()
+        .values()
+        .map(|ptr| ptr.0 as usize)
+        .collect::<Vec<_>>();
+    assert!(addresses.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // Registering the signatures one by one gives the same indices.
+    let module_info = artifact.create_module_info();
+    let serial = module_info
+        .signatures
+        .values()
+        .map(|sig| engine.register_signature(sig))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        artifact.signatures().values().copied().collect::<Vec<_>>(),
+        serial
+    );
+
+    engine.warm_signatures_from(&artifact);
+    for (sig_index, shared_index) in artifact.signatures().iter() {
+        assert_eq!(
+            engine
+                .function_call_trampoline(*shared_index)
+                .map(|trampoline| trampoline as usize),
+            Some(artifact.finished_function_call_trampolines()[sig_index] as usize)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[compiler_test(engine)]
+fn dropping_engines_releases_their_code_memory(config: crate::Config) -> Result<()> {
+    // The number of bytes of anonymous executable memory mapped in this
+    // process.
+    fn executable_bytes() -> usize {
+        std::fs::read_to_string("/proc/self/maps")
+            .unwrap()
+            .lines()
+            .filter_map(|line| {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                // Anonymous mappings have no path.
+                if fields.len() != 5 || !fields[1].contains('x') {
+                    return None;
+                }
+                let (start, end) = fields[0].split_once('-')?;
+                Some(usize::from_str_radix(end, 16).ok()? - usize::from_str_radix(start, 16).ok()?)
+            })
+            .sum()
+    }
+
+    let wasm = wat2wasm(br#"(module (func (export "f") (result i32) i32.const 1))"#)?;
+    let compile_and_drop = || -> Result<()> {
+        let engine = config.engine(config.compiler_config(false));
+        let tunables = BaseTunables::for_target(engine.target());
+        let artifact = engine.compile(&wasm, &tunables)?;
+        assert!(engine.code_memory_stats().published_bytes > 0);
+        drop(artifact);
+        drop(engine);
+        Ok(())
+    };
+
+    for _ in 0..20 {
+        compile_and_drop()?;
+    }
+    let before = executable_bytes();
+    for _ in 0..200 {
+        compile_and_drop()?;
+    }
+    // Leaking the code of every engine would map at least a page of 4KiB
+    // each time. Leave some slack for the tests running concurrently.
+    assert!(executable_bytes() < before + 100 * 4096);
+    Ok(())
+}
+
+#[cfg(feature = "perfmap")]
+#[compiler_test(engine)]
+fn perfmap_names_every_compiled_function(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(
+        br#"(module
+                (func $double (export "double") (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul)
+                (func (export "one") (result i32) i32.const 1))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+
+    let perfmap = std::fs::read_to_string(wasmer_compiler::perfmap_path())?;
+    let entries = perfmap
+        .lines()
+        .map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let address = usize::from_str_radix(fields.next().unwrap(), 16).unwrap();
+            let len = usize::from_str_radix(fields.next().unwrap(), 16).unwrap();
+            (address, (len, fields.next().unwrap().to_string()))
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+    let names = artifact
+        .finished_functions()
+        .values()
+        .map(|ptr| {
+            let (len, name) = &entries[&(ptr.0 as usize)];
+            assert!(*len > 0 && *len < 4096);
+            name.clone()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["double", "wasm_func[1]"]);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn code_memory_pool_recycles_mappings(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::CodeMemoryPool;
+
+    let pool = Arc::new(CodeMemoryPool::new(4));
+    let wasm = wat2wasm(br#"(module (func (export "f") (result i32) i32.const 1))"#)?;
+    for _ in 0..100 {
+        let engine = Engine::new_with_pool(
+            config.compiler_config(false),
+            Target::default(),
+            Features::default(),
+            pool.clone(),
+        );
+        let mut store = Store::new(engine);
+        let module = Module::new(&store, &wasm)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let f = instance
+            .exports
+            .get_typed_function::<(), i32>(&store, "f")?;
+        assert_eq!(f.call(&mut store)?, 1);
+    }
+    // The engines reuse the mappings of the previous ones.
+    assert!(pool.mappings() <= 2);
+    assert!(pool.cached() >= 1 && pool.cached() <= 2);
+    Ok(())
+}
+
+#[cfg(feature = "debug-info")]
+#[compiler_test(engine)]
+fn debug_lines_resolve_addresses_to_wasm_offsets(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    // Without names, so that the module has no name section.
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+
+    assert_eq!(engine.wasm_offset_at(0), None);
+    // LLVM doesn't map its code to instructions.
+    if config.compiler == crate::Compiler::LLVM {
+        return Ok(());
+    }
+    let start = artifact.finished_functions().values().next().unwrap().0 as usize;
+    let mut address = start;
+    while engine.lookup_function(address).is_some() {
+        let offset = engine.wasm_offset_at(address).unwrap() as usize;
+        assert!(offset > 0 && offset < wasm.len());
+        address += 1;
+    }
+    assert!(address > start);
+    Ok(())
+}
+
+#[cfg(feature = "dedup-code")]
+#[compiler_test(engine)]
+fn dedup_code_shares_identical_bodies_by_default(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "first") (result i64) i64.const 1234)
+                (func (export "second") (result i64) i64.const 1234)
+                (func (export "third") (result i64) i64.const 1234))"#,
+    )?;
+    let deduped = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(deduped.target());
+    deduped.compile(&wasm, &tunables)?;
+    let full = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(false);
+    full.compile(&wasm, &tunables)?;
+
+    assert_eq!(deduped.code_memory_stats().function_bodies, 1);
+    assert_eq!(full.code_memory_stats().function_bodies, 3);
+    assert!(
+        deduped.code_memory_stats().function_code_bytes
+            < full.code_memory_stats().function_code_bytes
+    );
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn engine_reports_its_features(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.threads(true).simd(false).memory64(true);
+    config.set_features(features.clone());
+    let engine = config.engine(config.compiler_config(false));
+    assert_eq!(engine.features(), features);
+    assert_ne!(engine.features(), Features::default());
+
+    let headless = wasmer_compiler::EngineBuilder::headless()
+        .set_features(Some(features.clone()))
+        .headless_engine()?;
+    assert_eq!(headless.features(), features);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compiling_reports_disabled_proposals(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.threads(false).reference_types(false);
+    config.set_features(features);
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+
+    let threads = wat2wasm(br#"(module (memory 1 1 shared))"#)?;
+    match engine.compile(&threads, &tunables) {
+        Err(CompileError::DisabledFeature { feature }) => assert_eq!(feature, "threads"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let reference_types = wat2wasm(
+        br#"(module
+                (func (export "null") (result externref)
+                    ref.null extern))"#,
+    )?;
+    match engine.compile(&reference_types, &tunables) {
+        Err(CompileError::DisabledFeature { feature }) => assert_eq!(feature, "reference types"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+/// Drives a future to completion on the current thread.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(output) => return output,
+            std::task::Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[compiler_test(engine)]
+fn compile_async_compiles_concurrently_requested_modules(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = Arc::new(BaseTunables::for_target(engine.target()));
+    let futures = (0..4)
+        .map(|i| {
+            let wasm = wat2wasm(
+                format!(
+                    r#"(module (func (export "get") (result i32) i32.const {}))"#,
+                    i
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+            engine.compile_async(wasm.into_owned(), tunables.clone())
+        })
+        .collect::<Vec<_>>();
+
+    for future in futures {
+        let artifact = block_on(future)?;
+        assert_eq!(artifact.create_module_info().exports.len(), 1);
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compiling_only_locks_the_engine_to_publish_code(config: crate::Config) -> Result<()> {
+    use std::sync::Mutex;
+    use wasmer_compiler::CompileExecutor;
+
+    // An executor using the engine while a module compiles, which
+    // deadlocks if compiling holds the lock of the engine.
+    struct EngineUsingExecutor(Mutex<Option<Engine>>);
+
+    impl CompileExecutor for EngineUsingExecutor {
+        fn scope<'a>(&self, tasks: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+            if let Some(engine) = &*self.0.lock().unwrap() {
+                engine.code_memory_stats();
+            }
+            for task in tasks {
+                task();
+            }
+        }
+    }
+
+    let engine = config.engine(config.compiler_config(false));
+    let executor = Arc::new(EngineUsingExecutor(Mutex::new(Some(engine.clone()))));
+    engine.set_compile_executor(Some(executor.clone()));
+    let tunables = BaseTunables::for_target(engine.target());
+    let wasm = wat2wasm(br#"(module (func (export "get") (result i32) i32.const 1))"#)?;
+    let artifact = engine.compile(&wasm, &tunables);
+    // The executor keeps the engine alive.
+    executor.0.lock().unwrap().take();
+    assert_eq!(artifact?.create_module_info().exports.len(), 1);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn dropping_compile_async_futures_leaves_the_engine_usable(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = Arc::new(BaseTunables::for_target(engine.target()));
+    let wasm = wat2wasm(br#"(module (func (export "run")))"#)?;
+    for _ in 0..4 {
+        drop(engine.compile_async(wasm.to_vec(), tunables.clone()));
+    }
+
+    // A cancelled compilation doesn't poison the engine for the ones after.
+    let artifact = block_on(engine.compile_async(wasm.to_vec(), tunables))?;
+    assert_eq!(artifact.create_module_info().exports.len(), 1);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn engine_builder_defaults_to_the_host_and_the_compiler_features(
+    config: crate::Config,
+) -> Result<()> {
+    use wasmer_compiler::EngineBuilder;
+
+    let compiler_config = config.compiler_config(false);
+    let default_features = compiler_config.default_features_for_target(&Target::default());
+    let builder = EngineBuilder::default().set_compiler_config(compiler_config);
+    assert!(builder.compiler_config().is_some());
+    assert!(builder.target().is_none());
+    assert!(builder.features().is_none());
+
+    let engine = builder.engine();
+    assert_eq!(engine.target(), &Target::default());
+    assert_eq!(engine.features(), default_features);
+    engine.compile(
+        &wat2wasm(b"(module)")?,
+        &BaseTunables::for_target(engine.target()),
+    )?;
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn engine_builder_uses_the_given_target_and_features(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::EngineBuilder;
+
+    let target = Target::new(Triple::host(), CpuFeature::set());
+    let mut features = Features::default();
+    features.multi_memory(true);
+    let engine = EngineBuilder::new(config.compiler_config(false))
+        .set_target(Some(target.clone()))
+        .set_features(Some(features.clone()))
+        .engine();
+    assert_eq!(engine.target(), &target);
+    assert_eq!(engine.features(), features);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn engine_builder_builds_headless_engines_without_a_compiler(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::{EngineBuilder, EngineBuilderError};
+
+    let engine = EngineBuilder::headless().headless_engine()?;
+    assert!(engine
+        .compile(
+            &wat2wasm(b"(module)")?,
+            &BaseTunables::for_target(engine.target())
+        )
+        .is_err());
+    assert!(EngineBuilder::default().headless_engine().is_ok());
+
+    let error = EngineBuilder::new(config.compiler_config(false))
+        .headless_engine()
+        .unwrap_err();
+    assert_eq!(error, EngineBuilderError::CompilerInHeadlessEngine);
+    assert_eq!(error.to_string(), "A headless engine can't have a compiler");
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn lookup_function_resolves_addresses_in_compiled_functions(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(false);
+    let wasm = wat2wasm(
+        br#"(module
+            (import "host" "f" (func))
+            (func (export "first") (result i32) i32.const 1)
+            (func (export "second") (param i32) (result i32)
+                local.get 0
+                i32.const 2
+                i32.add))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let module_info = artifact.create_module_info();
+
+    for (local_index, body) in artifact.finished_functions().iter() {
+        let start = body.0 as usize;
+        let len = artifact.function_code_size(local_index);
+        let index = module_info.func_index(local_index);
+        // The imported function comes first.
+        assert_eq!(index.index(), local_index.index() + 1);
+        assert_eq!(engine.lookup_function(start), Some((index, 0)));
+        assert_eq!(
+            engine.lookup_function(start + len / 2),
+            Some((index, len / 2))
+        );
+        assert_eq!(
+            engine.lookup_function(start + len - 1),
+            Some((index, len - 1))
+        );
+    }
+    // Addresses outside of compiled code belong to no function.
+    fn host_function() {}
+    assert_eq!(engine.lookup_function(0), None);
+    assert_eq!(engine.lookup_function(host_function as usize), None);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn function_published_callbacks_fire_once_per_function(config: crate::Config) -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::sync::Mutex;
+
+    let engine = config
+        .engine(config.compiler_config(false))
+        .with_function_dedup(false);
+    let published = Arc::new(Mutex::new(Vec::new()));
+    let count = Arc::new(AtomicUsize::new(0));
+    engine.on_function_published({
+        let published = published.clone();
+        Arc::new(move |index, address, len| {
+            published
+                .lock()
+                .unwrap()
+                .push((index, address as usize, len))
+        })
+    });
+    engine.on_function_published({
+        let count = count.clone();
+        Arc::new(move |_, _, _| {
+            count.fetch_add(1, SeqCst);
+        })
+    });
+
+    let wasm = wat2wasm(
+        br#"(module
+            (func (export "first") (result i32) i32.const 1)
+            (func (export "second") (result i32) i32.const 2)
+            (func (export "third") (result i32) i32.const 3))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    let module_info = artifact.create_module_info();
+    let expected = artifact
+        .finished_functions()
+        .iter()
+        .map(|(local_index, body)| {
+            (
+                module_info.func_index(local_index),
+                body.0 as usize,
+                artifact.function_code_size(local_index),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(expected.len(), 3);
+    assert_eq!(*published.lock().unwrap(), expected);
+    assert_eq!(count.load(SeqCst), 3);
+
+    // Deserialized functions are published too.
+    unsafe { engine.deserialize(&artifact.serialize()?)? };
+    assert_eq!(published.lock().unwrap().len(), 6);
+    assert_eq!(count.load(SeqCst), 6);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compile_errors_point_at_the_failing_function(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+
+    #[rustfmt::skip]
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // One `[] -> []` type.
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        // Three functions of that type.
+        0x03, 0x04, 0x03, 0x00, 0x00, 0x00,
+        0x0a, 0x0c, 0x03,
+        0x02, 0x00, 0x0b,
+        0x02, 0x00, 0x0b,
+        // A `nop` followed by an illegal opcode.
+        0x04, 0x00, 0x01, 0xff, 0x0b,
+    ];
+    let engine = config.engine(config.compiler_config(false));
+    // The module isn't valid, so compile it without validating it first.
+    match engine.compile(&wasm, &BaseTunables::for_target(engine.target())) {
+        Err(CompileError::FunctionCodegen {
+            func, wasm_offset, ..
+        }) => {
+            assert_eq!(func, LocalFunctionIndex::new(2));
+            let wasm_offset = wasm_offset.unwrap() as usize;
+            assert!((31..wasm.len()).contains(&wasm_offset), "{}", wasm_offset);
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("the malformed function should fail to compile"),
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn validate_with_features_overrides_the_engine_features(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(
+        br#"(module
+            (func (export "splat") (param i32) (result v128)
+                local.get 0
+                i32x4.splat))"#,
+    )?;
+    let engine = config.engine(config.compiler_config(false));
+    let mut with_simd = engine.features();
+    with_simd.simd(true);
+    let mut without_simd = with_simd.clone();
+    without_simd.simd(false);
+
+    engine.validate_with_features(&wasm, &with_simd)?;
+    assert!(matches!(
+        engine.validate_with_features(&wasm, &without_simd),
+        Err(CompileError::Validate(_))
+    ));
+    // The features of the engine are left alone.
+    assert_eq!(engine.validate(&wasm).is_ok(), engine.features().simd);
+
+    let headless = Engine::headless();
+    assert!(matches!(
+        headless.validate_with_features(&wasm, &with_simd),
+        Err(CompileError::Codegen(_))
+    ));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn max_code_bytes_rejects_modules_over_the_limit(config: crate::Config) -> Result<()> {
+    fn allocated_bytes(engine: &Engine) -> usize {
+        let stats = engine.code_memory_stats();
+        stats.function_code_bytes
+            + stats.call_trampoline_bytes
+            + stats.dynamic_trampoline_bytes
+            + stats.custom_section_bytes
+    }
+
+    let small = wat2wasm(br#"(module (func (export "one") (result i32) i32.const 1))"#)?;
+    let mut large = String::from("(module");
+    for _ in 0..200 {
+        large.push_str("(func (param i32) (result i32) local.get 0");
+        large.push_str(&" i32.const 3 i32.mul".repeat(50));
+        large.push(')');
+    }
+    large.push(')');
+    let large = wat2wasm(large.as_bytes())?;
+
+    let unlimited = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(unlimited.target());
+    unlimited.compile(&small, &tunables)?;
+    let small_bytes = allocated_bytes(&unlimited);
+
+    // Room for the small module twice, but not for the large one.
+    let engine = wasmer_compiler::EngineBuilder::new(config.compiler_config(false))
+        .set_max_code_bytes(Some(2 * small_bytes))
+        .engine();
+    let first = engine.compile(&small, &tunables)?;
+    assert_eq!(allocated_bytes(&engine), small_bytes);
+    match engine.compile(&large, &tunables) {
+        Err(CompileError::Resource(message)) => assert_eq!(message, "code size limit exceeded"),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("the large module should exceed the limit"),
+    }
+    // Rejected modules don't count towards the limit.
+    assert_eq!(allocated_bytes(&engine), small_bytes);
+
+    let second = engine.compile(&small, &tunables)?;
+    assert!(matches!(
+        engine.compile(&small, &tunables),
+        Err(CompileError::Resource(_))
+    ));
+
+    // Dropping an artifact gives its bytes back to the budget, though its
+    // code stays allocated with the engine.
+    drop(first);
+    let third = engine.compile(&small, &tunables)?;
+    assert_eq!(allocated_bytes(&engine), 3 * small_bytes);
+    assert!(matches!(
+        engine.compile(&small, &tunables),
+        Err(CompileError::Resource(_))
+    ));
+    drop((second, third));
+    engine.compile(&small, &tunables)?;
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn registered_signatures_include_every_compiled_type(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    let first = wat2wasm(
+        br#"(module
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add)
+                (func (export "nop")))"#,
+    )?;
+    let second = wat2wasm(
+        br#"(module
+                (func (export "nop"))
+                (func (export "half") (param f64) (result f64)
+                    local.get 0
+                    f64.const 2
+                    f64.div))"#,
+    )?;
+    let _first = engine.compile(&first, &tunables)?;
+    let _second = engine.compile(&second, &tunables)?;
+
+    let signatures = engine.registered_signatures();
+    let expected = [
+        FunctionType::new([Type::I32, Type::I32], [Type::I32]),
+        FunctionType::new([], []),
+        FunctionType::new([Type::F64], [Type::F64]),
+    ];
+    for signature in &expected {
+        let matching = signatures
+            .iter()
+            .filter(|(_, registered)| registered == signature)
+            .collect::<Vec<_>>();
+        assert_eq!(matching.len(), 1, "{}", signature);
+        assert_eq!(
+            engine.lookup_signature(matching[0].0).as_ref(),
+            Some(signature)
+        );
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn resolve_trap_locates_the_trapping_function(config: crate::Config) -> Result<()> {
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{FunctionIndex, LocalFunctionIndex};
+
+    let store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "identity") (param i32) (result i32)
+                    local.get 0)
+                (func (export "boom") (param i32) (result i32)
+                    local.get 0
+                    drop
+                    unreachable))"#,
+    )?;
+    let engine = store.engine();
+    let artifact = engine.compile(&wasm, store.tunables())?;
+    let mut objects = vm::StoreObjects::default();
+    let mut handle = unsafe { artifact.instantiate(store.tunables(), &[], &mut objects)? };
+    unsafe { artifact.finish_instantiation(None, &mut handle)? };
+
+    let boom = LocalFunctionIndex::new(1);
+    let pc = match unsafe { artifact.call_i32_i32(None, &handle, boom, 7) } {
+        Err(vm::Trap::Wasm { pc, .. }) => pc,
+        Err(trap) => panic!("unexpected trap: {:?}", trap),
+        Ok(_) => panic!("the function should trap"),
+    };
+    let location = engine.resolve_trap(pc).unwrap();
+    assert_eq!(location.function, FunctionIndex::new(1));
+    let start = artifact.finished_functions()[boom].0 as usize;
+    assert_eq!(location.function_offset, pc - start);
+
+    #[cfg(feature = "debug-info")]
+    if config.compiler != crate::Compiler::LLVM {
+        // The trap is attributed to the `unreachable` instruction.
+        let wasm_offset = location.wasm_offset.unwrap() as usize;
+        assert_eq!(wasm[wasm_offset], 0x00);
+    }
+    #[cfg(not(feature = "debug-info"))]
+    assert_eq!(location.wasm_offset, None);
+
+    assert_eq!(engine.resolve_trap(0), None);
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn compile_executor_runs_the_compilation_tasks(config: crate::Config) -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use wasmer_compiler::{CompileExecutor, EngineBuilder};
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    #[derive(Default)]
+    struct SerialExecutor {
+        tasks: AtomicUsize,
+    }
+
+    impl CompileExecutor for SerialExecutor {
+        fn scope<'a>(&self, tasks: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+            self.tasks.fetch_add(tasks.len(), Ordering::SeqCst);
+            for task in tasks {
+                task();
+            }
+        }
+    }
+
+    let executor = Arc::new(SerialExecutor::default());
+    let engine = EngineBuilder::new(config.compiler_config(false))
+        .set_compile_executor(Some(executor.clone()))
+        .engine();
+    let wasm = wat2wasm(
+        br#"(module
+                (func (param i32) (result i32)
+                    local.get 0)
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add)
+                (func (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul))"#,
+    )?;
+    let tunables = BaseTunables::for_target(engine.target());
+    let artifact = engine.compile(&wasm, &tunables)?;
+    assert_eq!(executor.tasks.load(Ordering::SeqCst), 3);
+
+    let mut objects = vm::StoreObjects::default();
+    let mut handle = unsafe { artifact.instantiate(&tunables, &[], &mut objects)? };
+    unsafe { artifact.finish_instantiation(None, &mut handle)? };
+    let double = LocalFunctionIndex::new(2);
+    assert_eq!(
+        unsafe { artifact.call_i32_i32(None, &handle, double, 21) }.unwrap(),
+        42
+    );
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[compiler_test(engine)]
+fn warm_code_makes_the_code_pages_resident(config: crate::Config) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module
+               (func (export "double") (param i32) (result i32)
+                   local.get 0
+                   i32.const 2
+                   i32.mul))"#,
+    )?;
+    let dir = tempfile::tempdir_in(env!("CARGO_TARGET_TMPDIR"))?;
+    let path = dir.path().join("module.wasmu");
+    let mut file = std::fs::File::create(&path)?;
+    file.write_all(&module.serialize_mappable()?)?;
+    // Written back, so that its pages can be dropped from the page cache.
+    file.sync_all()?;
+
+    let engine = config.engine_headless();
+    let artifact = unsafe { engine.deserialize_mmap(&path)? };
+    if !artifact.is_code_mapped() {
+        // The code was copied to anonymous memory, which is resident.
+        return Ok(());
+    }
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let pages = artifact
+        .finished_functions()
+        .values()
+        .map(|function| (function.0 as usize) & !(page_size - 1))
+        .collect::<Vec<_>>();
+    let resident = |page: usize| {
+        let mut resident = 0u8;
+        let result = unsafe { libc::mincore(page as *mut libc::c_void, page_size, &mut resident) };
+        assert_eq!(result, 0);
+        resident & 1 == 1
+    };
+
+    // The pages of the functions are clean pages of the file: unmapping
+    // them and dropping them from the page cache evicts them.
+    for page in &pages {
+        let result =
+            unsafe { libc::madvise(*page as *mut libc::c_void, page_size, libc::MADV_DONTNEED) };
+        assert_eq!(result, 0);
+    }
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    assert_eq!(result, 0);
+    if pages.iter().all(|page| resident(*page)) {
+        // The file system keeps its files in memory, as tmpfs does.
+        return Ok(());
+    }
+
+    engine.warm_code();
+    assert!(pages.iter().all(|page| resident(*page)));
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn pre_compile_hook_can_reject_modules(config: crate::Config) -> Result<()> {
+    use std::borrow::Cow;
+
+    fn limit_size(binary: &[u8]) -> Result<Cow<'_, [u8]>, CompileError> {
+        if binary.len() > 64 {
+            return Err(CompileError::Resource(
+                "the module is too large".to_string(),
+            ));
+        }
+        Ok(Cow::Borrowed(binary))
+    }
+
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    engine.set_pre_compile_hook(Arc::new(limit_size));
+
+    let small = wat2wasm(br#"(module (func (export "f")))"#)?;
+    assert!(small.len() <= 64);
+    engine.compile(&small, &tunables)?;
+
+    let large = wat2wasm(
+        br#"(module
+                (func (export "f") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add)
+                (func (export "g") (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul)
+                (func (export "h") (param i32) (result i32)
+                    local.get 0
+                    i32.const 3
+                    i32.sub))"#,
+    )?;
+    assert!(large.len() > 64);
+    match engine.compile(&large, &tunables) {
+        Err(CompileError::Resource(message)) => assert_eq!(message, "the module is too large"),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("the hook should reject the module"),
+    }
+    Ok(())
+}
+
+#[compiler_test(engine)]
+fn pre_compile_hook_transforms_the_compiled_binary(config: crate::Config) -> Result<()> {
+    use std::borrow::Cow;
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::LocalFunctionIndex;
+
+    // Rewrites `i32.const 7` into `i32.const 42`.
+    fn rewrite(binary: &[u8]) -> Result<Cow<'_, [u8]>, CompileError> {
+        match binary.windows(2).position(|bytes| bytes == [0x41, 0x07]) {
+            Some(position) => {
+                let mut binary = binary.to_vec();
+                binary[position + 1] = 0x2a;
+                Ok(Cow::Owned(binary))
+            }
+            None => Ok(Cow::Borrowed(binary)),
+        }
+    }
+
+    // Rewrites `i32.const 7` into `i64.const 7`, which parses but doesn't
+    // validate.
+    fn mistype(binary: &[u8]) -> Result<Cow<'_, [u8]>, CompileError> {
+        let mut binary = binary.to_vec();
+        let position = binary
+            .windows(2)
+            .position(|bytes| bytes == [0x41, 0x07])
+            .unwrap();
+        binary[position] = 0x42;
+        Ok(Cow::Owned(binary))
+    }
+
+    // Substitutes a function returning `i64.const 7` as an `i32`, without
+    // copying it.
+    fn substitute(_binary: &[u8]) -> Result<Cow<'_, [u8]>, CompileError> {
+        const MISTYPED: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type: [] -> [i32]
+            0x03, 0x02, 0x01, 0x00, // function: type 0
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x42, 0x07, 0x0b, // code: i64.const 7
+        ];
+        Ok(Cow::Borrowed(MISTYPED))
+    }
+
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables::for_target(engine.target());
+    engine.set_pre_compile_hook(Arc::new(rewrite));
+    let wasm = wat2wasm(
+        br#"(module
+                (func (export "seven") (param i32) (result i32)
+                    i32.const 7))"#,
+    )?;
+    let artifact = engine.compile(&wasm, &tunables)?;
+    let mut objects = vm::StoreObjects::default();
+    let mut handle = unsafe { artifact.instantiate(&tunables, &[], &mut objects)? };
+    unsafe { artifact.finish_instantiation(None, &mut handle)? };
+    let seven = LocalFunctionIndex::new(0);
+    assert_eq!(
+        unsafe { artifact.call_i32_i32(None, &handle, seven, 0) }.unwrap(),
+        42
+    );
+
+    // The transformed binary is the one validated.
+    engine.set_pre_compile_hook(Arc::new(mistype));
+    assert!(matches!(
+        engine.compile(&wasm, &tunables),
+        Err(CompileError::Validate(_))
+    ));
+    // Even when it is borrowed.
+    engine.set_pre_compile_hook(Arc::new(substitute));
+    assert!(matches!(
+        engine.compile(&wasm, &tunables),
+        Err(CompileError::Validate(_))
+    ));
+    Ok(())
+}
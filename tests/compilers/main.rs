@@ -5,8 +5,10 @@
 #[macro_use]
 extern crate compiler_test_derive;
 
+mod artifact;
 mod config;
 mod deterministic;
+mod engine;
 mod imports;
 mod issues;
 mod metering;
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use std::convert::TryInto;
 use wasmer::*;
 
 #[test]
@@ -85,3 +86,480 @@ fn test_deserialize(config: crate::Config) -> Result<()> {
     assert_eq!(result.to_vec(), vec![Value::I64(1500)]);
     Ok(())
 }
+
+/// Bumps the format version stored in the metadata header of a
+/// serialized artifact.
+fn bump_format_version(serialized_bytes: &mut [u8]) -> u32 {
+    // The metadata header follows the 16-byte artifact magic and starts
+    // with an 8-byte magic of its own, followed by the version.
+    let version_offset = 16 + 8;
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&serialized_bytes[version_offset..version_offset + 4]);
+    let version = u32::from_ne_bytes(version) + 1;
+    serialized_bytes[version_offset..version_offset + 4].copy_from_slice(&version.to_ne_bytes());
+    version
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_newer_format_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, r#"(module (func (export "run")))"#)?;
+    let mut serialized_bytes = module.serialize()?;
+    let bumped_version = bump_format_version(&mut serialized_bytes);
+
+    let headless_store = config.headless_store();
+    let result = unsafe { Module::deserialize(&headless_store, &serialized_bytes) };
+    match result {
+        Err(DeserializeError::NewerFormat { found, supported }) => {
+            assert_eq!(found, bumped_version);
+            assert_eq!(supported, bumped_version - 1);
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a newer format version should be rejected"),
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_newer_format_allowed_versions(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "answer") (result i32) i32.const 42))"#,
+    )?;
+    let mut serialized_bytes = module.serialize()?;
+    let bumped_version = bump_format_version(&mut serialized_bytes);
+
+    let mut headless_store = config.headless_store();
+    headless_store
+        .engine()
+        .set_deserialize_forward_policy(DeserializeForwardPolicy::AllowVersions(&[]));
+    let result = unsafe { Module::deserialize(&headless_store, &serialized_bytes) };
+    assert!(matches!(
+        result,
+        Err(DeserializeError::NewerFormat { found, .. }) if found == bumped_version
+    ));
+
+    let allowed: &'static [u32] = Box::leak(vec![bumped_version].into_boxed_slice());
+    headless_store
+        .engine()
+        .set_deserialize_forward_policy(DeserializeForwardPolicy::AllowVersions(allowed));
+    let module = unsafe { Module::deserialize(&headless_store, &serialized_bytes)? };
+    let instance = Instance::new(&mut headless_store, &module, &imports! {})?;
+    let answer = instance.exports.get_function("answer")?;
+    let result = answer.call(&mut headless_store, &[])?;
+    assert_eq!(result.to_vec(), vec![Value::I32(42)]);
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_metadata_matches_full_deserialize(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::ArtifactCreate;
+
+    let store = config.store();
+    let wat = r#"
+        (module
+        (func $hello (import "" "hello") (param i32))
+        (memory (export "memory") 1)
+        (func (export "run") (param i32) (result i32)
+            local.get 0
+            call $hello
+            local.get 0)
+        (func (export "two") (result i32) i32.const 2)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let serialized_bytes = module.serialize()?;
+
+    let metadata = unsafe { wasmer_compiler::Artifact::deserialize_metadata(&serialized_bytes)? };
+    let artifact = unsafe { Artifact::deserialize(store.engine(), &serialized_bytes)? };
+    let module_info = artifact.create_module_info();
+    assert_eq!(metadata.module(), &module_info);
+    assert_eq!(metadata.num_functions(), 2);
+    assert_eq!(
+        metadata.num_functions(),
+        artifact.finished_functions().len()
+    );
+    assert_eq!(
+        metadata.imports(),
+        module_info.imports().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        metadata.exports(),
+        module_info.exports().collect::<Vec<_>>()
+    );
+    assert_eq!(metadata.features(), artifact.features());
+    assert_eq!(metadata.cpu_features(), artifact.cpu_features());
+    assert_eq!(metadata.used_proposals(), artifact.used_proposals());
+    assert_eq!(metadata.target_triple(), artifact.target_triple());
+    assert_eq!(metadata.hash(), artifact.hash());
+    assert!(!metadata.custom_sections().is_empty());
+
+    assert!(unsafe { wasmer_compiler::Artifact::deserialize_metadata(&[]) }.is_err());
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_future_min_runtime_version_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, r#"(module (func (export "run")))"#)?;
+    let mut serialized_bytes = module.serialize()?;
+    let header = wasmer_compiler::Artifact::peek_artifact_header(&serialized_bytes)?;
+    assert!(header.min_runtime_version() <= header.version());
+
+    // The oldest version able to read the artifact follows the version in
+    // the metadata header.
+    let required = header.version() + 1;
+    let min_version_offset = 16 + 8 + 4;
+    serialized_bytes[min_version_offset..min_version_offset + 4]
+        .copy_from_slice(&required.to_ne_bytes());
+    let header = wasmer_compiler::Artifact::peek_artifact_header(&serialized_bytes)?;
+    assert_eq!(header.min_runtime_version(), required);
+
+    let headless_store = config.headless_store();
+    let result = unsafe { Module::deserialize(&headless_store, &serialized_bytes) };
+    match result {
+        Err(DeserializeError::RuntimeTooOld {
+            required: r,
+            current,
+        }) => {
+            assert_eq!(r, required);
+            assert_eq!(current, header.version());
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("an artifact requiring a newer runtime should be rejected"),
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_from_file(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "answer") (result i32) i32.const 42))"#,
+    )?;
+    let serialized_bytes = module.serialize()?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    std::fs::write(&path, &serialized_bytes)?;
+
+    let mut headless_store = config.headless_store();
+    let module = unsafe { Module::deserialize_from_file(&headless_store, &path)? };
+    // The artifact doesn't need the file once deserialized.
+    std::fs::remove_file(&path)?;
+    let instance = Instance::new(&mut headless_store, &module, &imports! {})?;
+    let answer = instance.exports.get_function("answer")?;
+    assert_eq!(
+        answer.call(&mut headless_store, &[])?.to_vec(),
+        vec![Value::I32(42)]
+    );
+
+    std::fs::write(&path, &serialized_bytes[..serialized_bytes.len() / 2])?;
+    let result = unsafe { Module::deserialize_from_file(&headless_store, &path) };
+    assert!(matches!(
+        result,
+        Err(DeserializeError::InvalidByteLength { .. })
+    ));
+
+    for bytes in [&b""[..], b"\0asm\x01\0\0\0"] {
+        std::fs::write(&path, bytes)?;
+        let result = unsafe { Module::deserialize_from_file(&headless_store, &path) };
+        assert!(matches!(result, Err(DeserializeError::Incompatible(_))));
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_rejects_invalid_headers(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(br#"(module (func (export "run")))"#)?;
+    let module = Module::new(&store, &wasm)?;
+    let serialized_bytes = module.serialize()?;
+    let headless_store = config.headless_store();
+
+    // A WebAssembly binary is not an artifact.
+    let result = unsafe { Module::deserialize(&headless_store, &wasm) };
+    match result {
+        Err(DeserializeError::Incompatible(message)) => {
+            assert!(message.contains("WebAssembly binary"), "{}", message)
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a WebAssembly binary should be rejected"),
+    }
+
+    // Truncated in the artifact magic, the metadata header, or the
+    // metadata.
+    for len in [8, 16 + 8, 16 + 40, serialized_bytes.len() - 1] {
+        let result = unsafe { Module::deserialize(&headless_store, &serialized_bytes[..len]) };
+        match result {
+            Err(DeserializeError::Incompatible(_))
+            | Err(DeserializeError::CorruptedBinary(_))
+            | Err(DeserializeError::InvalidByteLength { .. }) => {}
+            Err(e) => panic!("unexpected error for {} bytes: {}", len, e),
+            Ok(_) => panic!("an artifact truncated to {} bytes should be rejected", len),
+        }
+    }
+
+    // An artifact from an older version of the format.
+    let mut older = serialized_bytes.clone();
+    let version_offset = 16 + 8;
+    let version = u32::from_ne_bytes(older[version_offset..version_offset + 4].try_into()?);
+    older[version_offset..version_offset + 4].copy_from_slice(&(version - 1).to_ne_bytes());
+    let result = unsafe { Module::deserialize(&headless_store, &older) };
+    assert!(matches!(result, Err(DeserializeError::Incompatible(_))));
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_serialize_to_writer(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "double") (param i32) (result i32)
+                     local.get 0
+                     i32.const 2
+                     i32.mul))"#,
+    )?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    let mut file = std::fs::File::create(&path)?;
+    module.serialize_to(&mut file)?;
+    drop(file);
+    // Streaming produces the same bytes as serializing into memory.
+    assert_eq!(std::fs::read(&path)?, module.serialize()?);
+
+    let mut headless_store = config.headless_store();
+    let deserialized = unsafe { Module::deserialize_from_file(&headless_store, &path)? };
+    let instance = Instance::new(&mut headless_store, &deserialized, &imports! {})?;
+    let double = instance.exports.get_function("double")?;
+    assert_eq!(
+        double
+            .call(&mut headless_store, &[Value::I32(21)])?
+            .to_vec(),
+        vec![Value::I32(42)]
+    );
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_mmap(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func $double (param i32) (result i32)
+               local.get 0
+               i32.const 2
+               i32.mul)
+             (func (export "run") (param i32) (result i32)
+               local.get 0
+               call $double))"#,
+    )?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    std::fs::write(&path, module.serialize_mappable()?)?;
+
+    let mut headless_store = config.headless_store();
+    let artifact = unsafe { headless_store.engine().deserialize_mmap(&path)? };
+    // LLVM uses absolute relocations, unless it generates PIC code.
+    assert_eq!(
+        artifact.is_code_mapped(),
+        config.compiler != crate::Compiler::LLVM
+    );
+    let module = unsafe { Module::deserialize_mmap(&headless_store, &path)? };
+    let instance = Instance::new(&mut headless_store, &module, &imports! {})?;
+    let run = instance.exports.get_function("run")?;
+    assert_eq!(
+        run.call(&mut headless_store, &[Value::I32(21)])?.to_vec(),
+        vec![Value::I32(42)]
+    );
+
+    // A relocation hook must see every relocation, so the code is copied.
+    let engine = config.engine_headless();
+    let hook = |_: &wasmer_types::Relocation, _: usize, _: &mut usize| {};
+    unsafe { engine.set_relocation_hook(Box::new(hook)) };
+    let artifact = unsafe { engine.deserialize_mmap(&path)? };
+    assert!(!artifact.is_code_mapped());
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_mmap_counts_towards_max_code_bytes(config: crate::Config) -> Result<()> {
+    fn accounted_bytes(engine: &Engine) -> usize {
+        let stats = engine.code_memory_stats();
+        stats.function_code_bytes
+            + stats.call_trampoline_bytes
+            + stats.dynamic_trampoline_bytes
+            + stats.custom_section_bytes
+            + stats.mapped_bytes
+    }
+
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "run") (result i32) i32.const 1))"#,
+    )?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    std::fs::write(&path, module.serialize_mappable()?)?;
+
+    let unlimited = config.engine_headless();
+    unsafe { unlimited.deserialize_mmap(&path)? };
+    let bytes = accounted_bytes(&unlimited);
+
+    // Room for the module once, whether its code is mapped or copied.
+    let engine = config.engine_headless();
+    engine.set_max_code_bytes(Some(bytes));
+    let _artifact = unsafe { engine.deserialize_mmap(&path)? };
+    match unsafe { engine.deserialize_mmap(&path) } {
+        Err(DeserializeError::Compiler(CompileError::Resource(message))) => {
+            assert_eq!(message, "code size limit exceeded")
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("the module should exceed the limit"),
+    }
+    // The rejected module wasn't mapped.
+    assert_eq!(accounted_bytes(&engine), bytes);
+    Ok(())
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_deserialize_mmap_copies_code_with_absolute_relocations() -> Result<()> {
+    // Without PIC, LLVM uses the large code model, whose calls are
+    // patched with absolute addresses.
+    let store = Store::new(wasmer_compiler_llvm::LLVM::new());
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func $fac (export "fac") (param i64) (result i64)
+               local.get 0
+               i64.eqz
+               if (result i64)
+                 i64.const 1
+               else
+                 local.get 0
+                 local.get 0
+                 i64.const 1
+                 i64.sub
+                 call $fac
+                 i64.mul
+               end))"#,
+    )?;
+    // No image of the code is written.
+    assert_eq!(module.serialize_mappable()?, module.serialize()?);
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    std::fs::write(&path, module.serialize_mappable()?)?;
+
+    let mut headless_store = Store::new(Engine::headless());
+    let artifact = unsafe { headless_store.engine().deserialize_mmap(&path)? };
+    assert!(!artifact.is_code_mapped());
+    let module = unsafe { Module::deserialize_mmap(&headless_store, &path)? };
+    let instance = Instance::new(&mut headless_store, &module, &imports! {})?;
+    let fac = instance.exports.get_function("fac")?;
+    assert_eq!(
+        fac.call(&mut headless_store, &[Value::I64(5)])?.to_vec(),
+        vec![Value::I64(120)]
+    );
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_compile_for_other_target(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::ArtifactCreate;
+
+    let other_triple: Triple = if cfg!(target_arch = "aarch64") {
+        "x86_64-unknown-linux-gnu"
+    } else {
+        "aarch64-unknown-linux-gnu"
+    }
+    .parse()
+    .unwrap();
+    let other_target = Target::new(other_triple.clone(), CpuFeature::set());
+    let engine = config.engine(config.compiler_config(false));
+    let wasm = wat2wasm(br#"(module (func (export "run") (result i32) i32.const 1))"#)?;
+
+    let host_artifact = engine.compile(&wasm, &BaseTunables::for_target(engine.target()))?;
+    assert_eq!(host_artifact.target_triple(), Triple::host().to_string());
+    assert!(host_artifact.is_runnable());
+    let stats_before = engine.code_memory_stats();
+    let unwind_entries_before = engine.unwind_entries().len();
+    let other_artifact = engine.compile_for(
+        &wasm,
+        &BaseTunables::for_target(&other_target),
+        &other_target,
+    )?;
+    assert_eq!(other_artifact.target_triple(), other_triple.to_string());
+    // The foreign code is neither allocated, published nor registered.
+    assert!(!other_artifact.is_runnable());
+    assert_eq!(engine.code_memory_stats(), stats_before);
+    assert_eq!(engine.unwind_entries().len(), unwind_entries_before);
+    let tunables = BaseTunables::for_target(engine.target());
+    let mut objects = vm::StoreObjects::default();
+    match unsafe { other_artifact.instantiate(&tunables, &[], &mut objects) } {
+        Err(wasmer_compiler::InstantiationError::ForeignTarget(triple)) => {
+            assert_eq!(triple, other_triple.to_string())
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("an artifact for another target should not be instantiated"),
+    }
+
+    let headless = Engine::headless();
+    unsafe { Artifact::deserialize(&headless, &host_artifact.serialize()?)? };
+    match unsafe { Artifact::deserialize(&headless, &other_artifact.serialize()?) } {
+        Err(DeserializeError::Incompatible(message)) => {
+            assert!(message.contains(&other_triple.to_string()), "{}", message)
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("an artifact for another target should be rejected"),
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_reserialize_deserialized_artifact(config: crate::Config) -> Result<()> {
+    use wasmer_compiler::ArtifactCreate;
+
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func $double (param i32) (result i32)
+               local.get 0
+               i32.const 2
+               i32.mul)
+             (func (export "run") (param i32) (result i32)
+               local.get 0
+               call $double))"#,
+    )?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    std::fs::write(&path, module.serialize_mappable()?)?;
+
+    let mut headless_store = config.headless_store();
+    let artifact = unsafe { headless_store.engine().deserialize(&module.serialize()?)? };
+    artifact.set_source_map_url("module.wasm.map");
+    let reserialized = artifact.serialize()?;
+    // Artifacts running straight from their file can be serialized again too.
+    let mapped = unsafe { headless_store.engine().deserialize_mmap(&path)? };
+    let reserialized_mapped = mapped.serialize_mappable()?;
+
+    let reloaded = unsafe { headless_store.engine().deserialize(&reserialized)? };
+    assert_eq!(
+        reloaded.source_map_url().as_deref(),
+        Some("module.wasm.map")
+    );
+
+    for bytes in [reserialized, reserialized_mapped] {
+        let module = unsafe { Module::deserialize(&headless_store, &bytes)? };
+        let instance = Instance::new(&mut headless_store, &module, &imports! {})?;
+        let run = instance.exports.get_function("run")?;
+        assert_eq!(
+            run.call(&mut headless_store, &[Value::I32(21)])?.to_vec(),
+            vec![Value::I32(42)]
+        );
+    }
+    Ok(())
+}
@@ -279,7 +279,9 @@ fn trap_start_function_import(config: crate::Config) -> Result<()> {
     match err {
         InstantiationError::Link(_)
         | InstantiationError::DifferentStores
-        | InstantiationError::CpuFeature(_) => {
+        | InstantiationError::CpuFeature(_)
+        | InstantiationError::ForeignTarget(_)
+        | InstantiationError::Compile(_) => {
             panic!("It should be a start error")
         }
         InstantiationError::Start(err) => {